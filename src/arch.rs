@@ -0,0 +1,158 @@
+use crate::{*, error::*, registers::*};
+
+// Architecture of the debuggee.
+// The debugger itself only runs on x86-64 and can only run/attach to x86 processes (64-bit, x32, or i386). Other architectures are supported only for
+// core dumps: we parse registers from the core dump, unwind the stack using .eh_frame/.debug_frame or frame pointers, and evaluate variables.
+// Everything that needs a live process is x86-only and doesn't look at Arch: the int3 breakpoint instruction, single-stepping via the trap flag,
+// debug registers (hardware breakpoints and data breakpoints), syscall injection, function calls. The disassembler (iced_x86) is x86-only too,
+// x86_bitness() returns None for other architectures, and the disassembly window shows an error for them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Hash)]
+pub enum Arch {
+    #[default]
+    X86_64,
+    // x86-64 instruction set and registers, 32-bit pointers (ELF32 with EM_X86_64). Indistinguishable from X86_64 at the register level.
+    X32,
+    // 32-bit x86. Uses the same RegisterIdx-s as x86-64 (Rax is eax, etc), but the registers are 4 bytes wide when saved on the stack.
+    I386,
+    Aarch64,
+}
+
+const X86_64_REGISTERS: [RegisterIdx; 25] = [RegisterIdx::Rax, RegisterIdx::Rdx, RegisterIdx::Rcx, RegisterIdx::Rbx, RegisterIdx::Rsi, RegisterIdx::Rdi, RegisterIdx::Rbp, RegisterIdx::Rsp, RegisterIdx::R8, RegisterIdx::R9, RegisterIdx::R10, RegisterIdx::R11, RegisterIdx::R12, RegisterIdx::R13, RegisterIdx::R14, RegisterIdx::R15, RegisterIdx::Rip, RegisterIdx::Cs, RegisterIdx::Ss, RegisterIdx::Fs, RegisterIdx::Gs, RegisterIdx::FsBase, RegisterIdx::GsBase, RegisterIdx::Flags, RegisterIdx::OrigRax];
+const I386_REGISTERS: [RegisterIdx; 15] = [RegisterIdx::Rax, RegisterIdx::Rdx, RegisterIdx::Rcx, RegisterIdx::Rbx, RegisterIdx::Rsi, RegisterIdx::Rdi, RegisterIdx::Rbp, RegisterIdx::Rsp, RegisterIdx::Rip, RegisterIdx::Cs, RegisterIdx::Ss, RegisterIdx::Fs, RegisterIdx::Gs, RegisterIdx::Flags, RegisterIdx::OrigRax];
+const I386_REGISTER_NAMES: [(RegisterIdx, &str); 10] = [(RegisterIdx::Rax, "eax"), (RegisterIdx::Rdx, "edx"), (RegisterIdx::Rcx, "ecx"), (RegisterIdx::Rbx, "ebx"), (RegisterIdx::Rsi, "esi"), (RegisterIdx::Rdi, "edi"), (RegisterIdx::Rbp, "ebp"), (RegisterIdx::Rsp, "esp"), (RegisterIdx::Rip, "eip"), (RegisterIdx::OrigRax, "orig_eax")];
+const AARCH64_REGISTERS: [RegisterIdx; 34] = [RegisterIdx::X0, RegisterIdx::X1, RegisterIdx::X2, RegisterIdx::X3, RegisterIdx::X4, RegisterIdx::X5, RegisterIdx::X6, RegisterIdx::X7, RegisterIdx::X8, RegisterIdx::X9, RegisterIdx::X10, RegisterIdx::X11, RegisterIdx::X12, RegisterIdx::X13, RegisterIdx::X14, RegisterIdx::X15, RegisterIdx::X16, RegisterIdx::X17, RegisterIdx::X18, RegisterIdx::X19, RegisterIdx::X20, RegisterIdx::X21, RegisterIdx::X22, RegisterIdx::X23, RegisterIdx::X24, RegisterIdx::X25, RegisterIdx::X26, RegisterIdx::X27, RegisterIdx::X28, RegisterIdx::X29, RegisterIdx::X30, RegisterIdx::Sp, RegisterIdx::Pc, RegisterIdx::Pstate];
+
+impl Arch {
+    // `is_32_bit` is ELF class (EI_CLASS == ELFCLASS32).
+    pub fn from_elf_header(e_machine: u16, is_32_bit: bool) -> Result<Self> {
+        match (e_machine, is_32_bit) {
+            (0x3e, false) => Ok(Arch::X86_64),
+            (0x3e, true) => Ok(Arch::X32),
+            (0x03, true) => Ok(Arch::I386),
+            (0xb7, false) => Ok(Arch::Aarch64),
+            _ => err!(UnsupportedExecutable, "only x86-64, x32, i386, and aarch64 executables are supported (got: e_machine = {}, {}-bit)", e_machine, if is_32_bit {32} else {64}),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86-64",
+            Arch::X32 => "x32",
+            Arch::I386 => "i386",
+            Arch::Aarch64 => "aarch64",
+        }
+    }
+
+    // If this is an x86 architecture that we can run, attach to, and disassemble (as opposed to only looking at core dumps), returns the instruction decoder bitness.
+    pub fn x86_bitness(self) -> Option<u32> {
+        match self {
+            Arch::X86_64 | Arch::X32 => Some(64),
+            Arch::I386 => Some(32),
+            Arch::Aarch64 => None,
+        }
+    }
+
+    pub fn pointer_size(self) -> usize {
+        match self {
+            Arch::X86_64 | Arch::Aarch64 => 8,
+            Arch::X32 | Arch::I386 => 4,
+        }
+    }
+
+    // Size of a general-purpose register, e.g. when it's pushed to the stack. Not the same as pointer_size() on x32.
+    pub fn register_size(self) -> usize {
+        match self {
+            Arch::X86_64 | Arch::X32 | Arch::Aarch64 => 8,
+            Arch::I386 => 4,
+        }
+    }
+
+    // Instruction pointer.
+    pub fn pc(self) -> RegisterIdx {
+        match self {
+            Arch::X86_64 | Arch::X32 | Arch::I386 => RegisterIdx::Rip,
+            Arch::Aarch64 => RegisterIdx::Pc,
+        }
+    }
+
+    pub fn sp(self) -> RegisterIdx {
+        match self {
+            Arch::X86_64 | Arch::X32 | Arch::I386 => RegisterIdx::Rsp,
+            Arch::Aarch64 => RegisterIdx::Sp,
+        }
+    }
+
+    pub fn frame_pointer(self) -> RegisterIdx {
+        match self {
+            Arch::X86_64 | Arch::X32 | Arch::I386 => RegisterIdx::Rbp,
+            Arch::Aarch64 => RegisterIdx::X29,
+        }
+    }
+
+    // Real registers of this architecture, in the order in which we show them in the UI. Doesn't include Cfa and Ret.
+    pub fn registers(self) -> &'static [RegisterIdx] {
+        match self {
+            Arch::X86_64 | Arch::X32 => &X86_64_REGISTERS,
+            Arch::I386 => &I386_REGISTERS,
+            Arch::Aarch64 => &AARCH64_REGISTERS,
+        }
+    }
+
+    // Whether the register can be used in expressions for this architecture. Includes our pseudo-registers cfa and ret.
+    // (E.g. a variable named `sp` or `x1` shouldn't become inaccessible on x86.)
+    pub fn has_register(self, reg: RegisterIdx) -> bool {
+        reg == RegisterIdx::Cfa || reg == RegisterIdx::Ret || self.registers().contains(&reg)
+    }
+
+    pub fn register_name(self, reg: RegisterIdx) -> &'static str {
+        if self == Arch::I386 {
+            if let Some(&(_, name)) = I386_REGISTER_NAMES.iter().find(|(r, _)| *r == reg) {
+                return name;
+            }
+        }
+        reg.name()
+    }
+
+    // Register by name, as written in expressions. On i386 accepts both eax and rax.
+    pub fn parse_register(self, name: &str) -> Option<RegisterIdx> {
+        let reg = match self {
+            Arch::I386 => I386_REGISTER_NAMES.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)).map(|(r, _)| *r).or_else(|| RegisterIdx::parse_ignore_case(name)),
+            _ => RegisterIdx::parse_ignore_case(name),
+        };
+        reg.filter(|r| self.has_register(*r))
+    }
+
+    // Registers that functions must preserve, according to the ABI (except the stack pointer). Used as a fallback when unwind information doesn't say where they were saved.
+    pub fn callee_saved_registers(self) -> &'static [RegisterIdx] {
+        match self {
+            Arch::X86_64 | Arch::X32 => &[RegisterIdx::Rbp, RegisterIdx::Rbx, RegisterIdx::R12, RegisterIdx::R13, RegisterIdx::R14, RegisterIdx::R15],
+            Arch::I386 => &[RegisterIdx::Rbp, RegisterIdx::Rbx, RegisterIdx::Rsi, RegisterIdx::Rdi],
+            Arch::Aarch64 => &[RegisterIdx::X29, RegisterIdx::X19, RegisterIdx::X20, RegisterIdx::X21, RegisterIdx::X22, RegisterIdx::X23, RegisterIdx::X24, RegisterIdx::X25, RegisterIdx::X26, RegisterIdx::X27, RegisterIdx::X28],
+        }
+    }
+
+    pub fn register_from_dwarf(self, r: gimli::Register) -> Option<RegisterIdx> {
+        match self {
+            Arch::X86_64 | Arch::X32 => RegisterIdx::from_dwarf(r),
+            Arch::I386 => RegisterIdx::from_dwarf_i386(r),
+            Arch::Aarch64 => RegisterIdx::from_dwarf_aarch64(r),
+        }
+    }
+
+    pub fn extra_register_from_dwarf(self, r: gimli::Register) -> Option<ExtraRegisterIdx> {
+        match self {
+            Arch::X86_64 | Arch::X32 => ExtraRegisterIdx::from_dwarf(r),
+            Arch::I386 => ExtraRegisterIdx::from_dwarf_i386(r),
+            // We don't have aarch64 vector registers anyway.
+            Arch::Aarch64 => None,
+        }
+    }
+
+    // On aarch64, return addresses may be signed (pointer authentication), with the signature stored in the unused high bits. Strip it.
+    pub fn strip_return_address(self, addr: u64) -> u64 {
+        match self {
+            Arch::X86_64 | Arch::X32 | Arch::I386 => addr,
+            Arch::Aarch64 => addr & ((1u64 << 48) - 1),
+        }
+    }
+}
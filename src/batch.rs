@@ -0,0 +1,448 @@
+use crate::{*, debugger::*, error::*, expr::*, interp::*, json::*, os::*, unwind::*};
+use std::{collections::HashMap, fs, io, mem, path::PathBuf, time::{Instant, Duration}};
+use libc::pid_t;
+
+// Non-interactive mode (--batch): reads commands from a script file or stdin, prints results to stdout (as text or as JSON lines), exits at the end of the script.
+// Intended for things like printing stack traces of crashed programs in CI.
+// The main loop is in main.rs (run_batch()). Commands that resume the program (run, continue, step...) make us wait until the program stops or exits.
+pub struct BatchRunner {
+    // Remaining lines of the script, in reverse order.
+    script: Vec<String>,
+    line_number: usize,
+    json: bool,
+
+    // How long to wait for symbols to load before running commands without them. Started when we first find symbols not loaded.
+    symbols_timeout: Duration,
+    symbols_wait_start: Option<Instant>,
+    gave_up_waiting_for_symbols: bool,
+
+    waiting_for_stop: bool,
+    // With --non-stop: stop_count of each thread when we started waiting. A thread that stopped since then ends the wait even though other threads keep running.
+    stop_counts_at_wait: HashMap<pid_t, usize>,
+    selected_tid: pid_t,
+    selected_subframe: usize,
+    eval_state: EvalState,
+    stacks: HashMap<pid_t, StackTrace>,
+
+    pub exit_code: Option<i32>,
+    had_errors: bool,
+}
+
+impl BatchRunner {
+    // `script_path` "-" means stdin. It's read to the end right away, before the debugging session starts: reading it line by line from the main loop would block the loop
+    // (including handling of the debuggee's events) whenever the next line is not available yet.
+    pub fn new(script_path: Option<&str>, json: bool, symbols_timeout: Duration) -> Result<Self> {
+        let script = match script_path {
+            Some(path) => {
+                let text = if path == "-" {io::read_to_string(io::stdin())} else {fs::read_to_string(path)};
+                let text = match text {
+                    Ok(x) => x,
+                    Err(e) => return err!(Environment, "failed to read batch script {}: {}", if path == "-" {"from stdin"} else {path}, e),
+                };
+                text.lines().rev().map(|s| s.to_string()).collect()
+            }
+            // Without a script, print all stack traces, as a crash report.
+            None => vec!["bt all".to_string()],
+        };
+        Ok(Self {script, line_number: 0, json, symbols_timeout, symbols_wait_start: None, gave_up_waiting_for_symbols: false, waiting_for_stop: false, stop_counts_at_wait: HashMap::new(), selected_tid: 0, selected_subframe: 0, eval_state: EvalState::new(), stacks: HashMap::new(), exit_code: None, had_errors: false})
+    }
+
+    // For the no-script case when the program is started by us: "run" before "bt all".
+    pub fn prepend_run_command(&mut self) {
+        self.script.push("run".to_string());
+    }
+
+    // Called from the main loop after the Debugger processed events. Executes commands until one of them needs to wait for the program.
+    // Sets exit_code when done.
+    pub fn run_commands(&mut self, debugger: &mut Debugger) {
+        self.print_log(debugger);
+        if self.waiting_for_stop {
+            match debugger.target_state {
+                // Threads are interrupted asynchronously after target_state becomes Suspended.
+                ProcessState::Suspended if debugger.threads.values().any(|t| t.state != ThreadState::Suspended) => return,
+                ProcessState::Suspended | ProcessState::NoProcess | ProcessState::CoreDump => {
+                    self.waiting_for_stop = false;
+                    self.report_stop(debugger);
+                }
+                // With --non-stop, a stopped thread doesn't suspend the others. Wait for steps to complete, then report any thread that stopped.
+                ProcessState::Running if debugger.context.settings.non_stop && self.newly_stopped_thread(debugger).is_some() => {
+                    self.waiting_for_stop = false;
+                    self.report_stop(debugger);
+                }
+                _ => return,
+            }
+        }
+        while self.exit_code.is_none() {
+            // Stack traces and expressions need symbols of all binaries. In the TUI, things just show up when loaded, but here we have to wait.
+            // (The main loop calls us periodically, to check the timeout.)
+            if debugger.target_state != ProcessState::NoProcess && !self.gave_up_waiting_for_symbols && !debugger.symbols.iter().all(|b| b.symbols_loaded()) {
+                let start = *self.symbols_wait_start.get_or_insert_with(Instant::now);
+                if start.elapsed() < self.symbols_timeout {
+                    return;
+                }
+                let loading: Vec<&str> = debugger.symbols.iter().filter(|b| !b.symbols_loaded()).map(|b| b.locator.path.as_str()).collect();
+                let message = format!("symbols are still loading after {:.0}s, continuing without them (see --symbols-timeout): {}", self.symbols_timeout.as_secs_f64(), loading.join(", "));
+                if self.json {
+                    self.print_json(vec![("event", "warning".into()), ("message", message.into())]);
+                } else {
+                    println!("warning: {}", message);
+                }
+                self.gave_up_waiting_for_symbols = true;
+            }
+            self.symbols_wait_start = None;
+            if !debugger.threads.contains_key(&self.selected_tid) {
+                // E.g. core dump or attached process, or the selected thread exited.
+                if let Some((tid, _)) = debugger.most_interesting_stopped_thread() {
+                    self.selected_tid = tid;
+                    self.selected_subframe = 0;
+                }
+            }
+            let line = match self.next_line() {
+                None => {
+                    self.exit_code = Some(if self.had_errors {1} else {0});
+                    break;
+                }
+                Some(l) => l };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = self.execute(line, debugger) {
+                self.had_errors = true;
+                if self.json {
+                    self.print_json(vec![("command", line.into()), ("line", self.line_number.into()), ("error", format!("{}", e).into())]);
+                } else {
+                    println!("error (line {}: {}): {}", self.line_number, line, e);
+                }
+            }
+            self.print_log(debugger);
+            if self.waiting_for_stop {
+                return;
+            }
+        }
+    }
+
+    fn next_line(&mut self) -> Option<String> {
+        self.line_number += 1;
+        self.script.pop()
+    }
+
+    fn print_json(&self, fields: Vec<(&str, JsonValue)>) {
+        println!("{}", JsonValue::object(fields));
+    }
+
+    // Messages like "process 123 exited with status 0".
+    fn print_log(&mut self, debugger: &mut Debugger) {
+        for line in debugger.log.lines.drain(..) {
+            if self.json {
+                self.print_json(vec![("event", "log".into()), ("message", line.into())]);
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.stacks.clear();
+        self.eval_state.clear();
+    }
+
+    fn start_waiting(&mut self, debugger: &Debugger) {
+        self.invalidate();
+        self.waiting_for_stop = true;
+        self.stop_counts_at_wait = debugger.threads.iter().map(|(tid, t)| (*tid, t.stop_count)).collect();
+    }
+
+    // The thread with the most interesting stop reason among the threads that stopped since start_waiting().
+    fn newly_stopped_thread(&self, debugger: &Debugger) -> Option<(pid_t, Option<StopReason>)> {
+        let mut best: Option<(isize, pid_t, Option<StopReason>)> = None;
+        for (tid, t) in &debugger.threads {
+            if !t.held || t.state != ThreadState::Suspended || t.stop_count <= self.stop_counts_at_wait.get(tid).copied().unwrap_or(0) {
+                continue;
+            }
+            let reason = t.stop_reasons.iter().max_by_key(|r| r.priority()).cloned();
+            let priority = reason.as_ref().map_or(isize::MIN, |r| r.priority());
+            if best.as_ref().is_none_or(|b| priority > b.0 || (priority == b.0 && *tid < b.1)) {
+                best = Some((priority, *tid, reason));
+            }
+        }
+        best.map(|(_, tid, reason)| (tid, reason))
+    }
+
+    fn report_stop(&mut self, debugger: &mut Debugger) {
+        self.invalidate();
+        self.selected_subframe = 0;
+        if debugger.target_state == ProcessState::NoProcess {
+            if self.json {
+                self.print_json(vec![("event", "exited".into())]);
+            }
+            return;
+        }
+        let stopped = if debugger.context.settings.non_stop && debugger.target_state == ProcessState::Running {self.newly_stopped_thread(debugger)} else {debugger.most_interesting_stopped_thread()};
+        let (tid, reason) = match stopped {
+            None => return,
+            Some(x) => x };
+        self.selected_tid = tid;
+        if let Some(sf) = debugger.threads.get(&tid).and_then(|t| t.subframe_to_select) {
+            self.selected_subframe = sf;
+        }
+        let reason = stop_reason_str(reason.as_ref());
+        let frame = self.with_stack(debugger, tid, |stack| stack.subframes.first().map(|sf| (frame_to_text(stack, sf), frame_to_json(stack, sf)))).ok().flatten();
+        if self.json {
+            self.print_json(vec![("event", "stopped".into()), ("tid", tid.into()), ("reason", reason.into()), ("frame", frame.map_or(JsonValue::Null, |f| f.1))]);
+        } else {
+            println!("thread {} stopped: {}{}", tid, reason, frame.map_or(String::new(), |f| format!(" in {}", f.0)));
+        }
+    }
+
+    fn with_stack<R>(&mut self, debugger: &mut Debugger, tid: pid_t, f: impl FnOnce(&StackTrace) -> R) -> Result<R> {
+        if !debugger.threads.contains_key(&tid) {
+            return err!(Usage, "no thread {}", tid);
+        }
+        Ok(f(self.stacks.entry(tid).or_insert_with(|| debugger.get_stack_trace(tid, /*partial*/ false))))
+    }
+
+    fn execute(&mut self, line: &str, debugger: &mut Debugger) -> Result<()> {
+        let (command, arg) = match line.split_once(char::is_whitespace) {
+            Some((c, a)) => (c, a.trim()),
+            None => (line, "") };
+        match command {
+            "run" | "r" => {
+                if debugger.mode != RunMode::Run {
+                    return err!(Usage, "can't run in {} mode", debugger.mode.human_string());
+                }
+                debugger.start_child(BreakpointOn::initial_step_from_settings(&debugger.context.settings))?;
+                self.start_waiting(debugger);
+            }
+            "continue" | "c" => {
+                debugger.resume()?;
+                self.start_waiting(debugger);
+            }
+            "step" | "s" | "next" | "n" | "finish" | "fin" | "stepi" | "si" | "nexti" | "ni" => {
+                let (kind, by_instructions) = match command {
+                    "step" | "s" => (StepKind::Into, false),
+                    "next" | "n" => (StepKind::Over, false),
+                    "finish" | "fin" => (StepKind::Out, false),
+                    "stepi" | "si" => (StepKind::Into, true),
+                    _ => (StepKind::Over, true) };
+                debugger.step(self.selected_tid, self.selected_subframe, kind, by_instructions, /*use_line_number_with_column*/ false)?;
+                self.start_waiting(debugger);
+            }
+            "pause" => {
+                debugger.suspend()?;
+                self.start_waiting(debugger);
+            }
+            "kill" => {
+                debugger.murder(libc::SIGKILL)?;
+                self.start_waiting(debugger);
+            }
+            "break" | "b" => {
+                let (location, condition) = match arg.split_once(" if ") {
+                    Some((l, c)) => (l.trim(), Some(c.trim().to_string())),
+                    None => (arg, None) };
+                let (on, json_fields): (BreakpointOn, Vec<(&str, JsonValue)>) = match LineBreakpoint::parse(location) {
+                    Some(mut bp) => {
+                        let json_fields = vec![("file", bp.path.to_string_lossy().into_owned().into()), ("line", bp.line.into())];
+                        // Debug info usually has absolute paths, so resolve relative paths of existing files relative to current directory.
+                        // (Other relative paths are matched as path suffixes, e.g. "foo.cpp" in a library that's not loaded yet.)
+                        if bp.path.is_relative() && bp.path.exists() {
+                            if let Ok(p) = std::path::absolute(&bp.path) {
+                                bp.path = p;
+                            }
+                        }
+                        (BreakpointOn::Line(bp), json_fields)
+                    }
+                    // Anything that doesn't end with :<line> is a function name or pattern.
+                    None => (BreakpointOn::Function(FunctionBreakpoint::parse(location)?), vec![("function", location.into())]),
+                };
+                let id = debugger.add_breakpoint(on)?;
+                if condition.is_some() {
+                    debugger.set_breakpoint_condition(id, condition);
+                }
+                if self.json {
+                    let mut fields = vec![("command", "break".into())];
+                    fields.extend(json_fields);
+                    self.print_json(fields);
+                }
+            }
+            "threads" => {
+                let mut threads: Vec<&Thread> = debugger.threads.values().collect();
+                threads.sort_by_key(|t| t.idx);
+                let mut list: Vec<JsonValue> = Vec::new();
+                for t in threads {
+                    let reasons: Vec<String> = t.stop_reasons.iter().map(|r| stop_reason_str(Some(r))).collect();
+                    let name = t.info.resource_stats.latest.comm();
+                    if self.json {
+                        list.push(JsonValue::object(vec![("tid", t.tid.into()), ("pid", t.pid.into()), ("name", name.into()), ("stop_reasons", reasons.into_iter().map(|r| r.into()).collect::<Vec<JsonValue>>().into())]));
+                    } else if !debugger.forked_processes.is_empty() {
+                        println!("{}{} (pid {}) {} {}", if t.tid == self.selected_tid {"* "} else {"  "}, t.tid, t.pid, name, reasons.join(", "));
+                    } else {
+                        println!("{}{} {} {}", if t.tid == self.selected_tid {"* "} else {"  "}, t.tid, name, reasons.join(", "));
+                    }
+                }
+                if self.json {
+                    self.print_json(vec![("command", "threads".into()), ("threads", list.into())]);
+                }
+            }
+            "thread" => {
+                let tid = match arg.parse::<pid_t>() {
+                    Ok(t) if debugger.threads.contains_key(&t) => t,
+                    _ => return err!(Usage, "no thread '{}'", arg) };
+                self.selected_tid = tid;
+                self.selected_subframe = 0;
+            }
+            "frame" | "f" => {
+                let idx = match arg.parse::<usize>() {
+                    Ok(i) => i,
+                    Err(_) => return err!(Usage, "expected frame number") };
+                let n = self.with_stack(debugger, self.selected_tid, |s| s.subframes.len())?;
+                if idx >= n {
+                    return err!(Usage, "frame {} is out of range (stack has {} frames)", idx, n);
+                }
+                self.selected_subframe = idx;
+            }
+            "bt" | "backtrace" => {
+                let tids: Vec<pid_t> = match arg {
+                    "" => vec![self.selected_tid],
+                    "all" => {
+                        let mut threads: Vec<&Thread> = debugger.threads.values().collect();
+                        threads.sort_by_key(|t| t.idx);
+                        threads.iter().map(|t| t.tid).collect()
+                    }
+                    _ => return err!(Usage, "expected 'bt' or 'bt all'") };
+                let mut list: Vec<JsonValue> = Vec::new();
+                for tid in tids {
+                    let name = debugger.threads.get(&tid).map_or(String::new(), |t| t.info.resource_stats.latest.comm().to_string());
+                    let json = self.json;
+                    let frames = self.with_stack(debugger, tid, |stack| {
+                        if !json {
+                            println!("thread {}{}:", tid, if name.is_empty() {String::new()} else {format!(" {}", name)});
+                        }
+                        let mut frames: Vec<JsonValue> = Vec::new();
+                        for (i, sf) in stack.subframes.iter().enumerate() {
+                            if json {
+                                frames.push(frame_to_json(stack, sf));
+                            } else {
+                                println!("  #{:<3} {}", i, frame_to_text(stack, sf));
+                            }
+                        }
+                        if let Some(e) = &stack.truncated {
+                            if !json {
+                                println!("  ({})", e);
+                            }
+                        }
+                        (frames, stack.truncated.as_ref().map(|e| format!("{}", e)))
+                    })?;
+                    if self.json {
+                        list.push(JsonValue::object(vec![("tid", tid.into()), ("name", name.into()), ("frames", frames.0.into()), ("error", frames.1.map_or(JsonValue::Null, |e| e.into()))]));
+                    }
+                }
+                if self.json {
+                    self.print_json(vec![("command", "bt".into()), ("threads", list.into())]);
+                }
+            }
+            "print" | "p" => {
+                let text = self.with_eval_context(debugger, |state, context| -> Result<String> {
+                    let (value, _dubious) = eval_watch_expression(arg, state, context)?;
+                    Ok(format_value_plain(&value, state, context).0)
+                })??;
+                if self.json {
+                    self.print_json(vec![("command", "print".into()), ("expression", arg.into()), ("value", text.into())]);
+                } else {
+                    println!("{} = {}", arg, text);
+                }
+            }
+            "locals" => {
+                let subframe = self.selected_subframe;
+                let vars = self.with_eval_context(debugger, |state, context| -> Result<Vec<(String, String)>> {
+                    let mut res: Vec<(String, String)> = Vec::new();
+                    for (name, value) in list_local_variables(context, subframe)? {
+                        res.push((name, match value {
+                            Ok(v) => format_value_plain(&v, state, context).0,
+                            Err(e) => format!("<{}>", e) }));
+                    }
+                    Ok(res)
+                })??;
+                if self.json {
+                    let vars: Vec<JsonValue> = vars.into_iter().map(|(n, v)| JsonValue::object(vec![("name", n.into()), ("value", v.into())])).collect();
+                    self.print_json(vec![("command", "locals".into()), ("variables", vars.into())]);
+                } else {
+                    for (name, value) in vars {
+                        println!("{} = {}", name, value);
+                    }
+                }
+            }
+            "echo" => {
+                if self.json {
+                    self.print_json(vec![("command", "echo".into()), ("text", arg.into())]);
+                } else {
+                    println!("{}", arg);
+                }
+            }
+            "quit" | "q" => {
+                self.exit_code = Some(match arg {
+                    "" => if self.had_errors {1} else {0},
+                    s => match s.parse::<i32>() {
+                        Ok(c) => c,
+                        Err(_) => return err!(Usage, "invalid exit code: '{}'", s) } });
+            }
+            _ => return err!(Usage, "unknown command: '{}' (see nnd --help-batch)", command),
+        }
+        Ok(())
+    }
+
+    fn with_eval_context<R>(&mut self, debugger: &mut Debugger, f: impl FnOnce(&mut EvalState, &mut EvalContext) -> R) -> Result<R> {
+        let tid = self.selected_tid;
+        self.with_stack(debugger, tid, |_| ())?;
+        let stack = &self.stacks[&tid];
+        if self.selected_subframe >= stack.subframes.len() {
+            return match &stack.truncated {
+                Some(e) => Err(e.clone()),
+                None => err!(Usage, "no stack frame") };
+        }
+        let mut context = debugger.make_eval_context(stack, self.selected_subframe, tid);
+        let r = f(&mut self.eval_state, &mut context);
+        let modified = context.modified_debuggee;
+        let events = mem::take(&mut context.function_call_events);
+        if modified {
+            debugger.refresh_after_modifying_debuggee(events);
+            self.stacks.clear();
+        }
+        Ok(r)
+    }
+}
+
+fn stop_reason_str(reason: Option<&StopReason>) -> String {
+    match reason {
+        None => "suspended".to_string(),
+        Some(StopReason::Breakpoint(_)) => "breakpoint".to_string(),
+        Some(StopReason::DataChange(_, old, new)) => format!("value changed: {}", format_data_change(old, new)),
+        Some(StopReason::DebugTrap) => "debug trap".to_string(),
+        Some(StopReason::Step) => "step".to_string(),
+        Some(StopReason::Exception) => "exception".to_string(),
+        Some(StopReason::Signal(s)) => format!("signal {}", signal_name(*s)),
+    }
+}
+
+fn frame_to_text(stack: &StackTrace, sf: &StackSubframe) -> String {
+    let frame = &stack.frames[sf.frame_idx];
+    let mut s = format!("0x{:x} {}", frame.addr, if sf.function_name.is_empty() {"??"} else {&sf.function_name});
+    if let Some(line) = &sf.line {
+        s += &format!(" at {}:{}", line.path.display(), line.line.line());
+        if line.line.column() != 0 {
+            s += &format!(":{}", line.line.column());
+        }
+    }
+    s
+}
+
+fn frame_to_json(stack: &StackTrace, sf: &StackSubframe) -> JsonValue {
+    let frame = &stack.frames[sf.frame_idx];
+    let mut fields = vec![("addr", format!("0x{:x}", frame.addr).into()), ("function", sf.function_name.clone().into())];
+    if let Some(line) = &sf.line {
+        fields.push(("file", line.path.to_string_lossy().into_owned().into()));
+        fields.push(("line", line.line.line().into()));
+        fields.push(("column", line.line.column().into()));
+    }
+    JsonValue::object(fields)
+}
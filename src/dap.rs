@@ -0,0 +1,542 @@
+use crate::{*, debugger::*, error::*, expr::*, interp::*, json::*, persistent::*, settings::*, context::*, symbols::*, symbols_registry::*, common_ui::*, registers::*, os::*, unwind::*};
+use std::{collections::HashMap, mem, pin::Pin, sync::Arc, str, io};
+use libc::pid_t;
+
+// Debug Adapter Protocol server (https://microsoft.github.io/debug-adapter-protocol/), an alternative frontend to the TUI.
+// Maps DAP requests onto the same Debugger API that the UI uses. The main loop is in main.rs (run_dap()), this struct handles the messages.
+//
+// Object references we hand out to the client (frame ids, variablesReference-s) are indices into vectors that are cleared whenever the
+// process is resumed, as the protocol allows. Values are re-read from the debuggee lazily, when the client asks for them.
+pub struct DapServer {
+    context: Arc<Context>,
+    // Consumed when a launch or attach request creates the Debugger.
+    persistent: Option<PersistentState>,
+    supplementary_binaries: Option<SupplementaryBinaries>,
+    // If the command line was passed to nnd, the launch request doesn't need to specify it.
+    default_command_line: Option<Vec<String>>,
+
+    pub debugger: Option<Pin<Box<Debugger>>>,
+    pub should_quit: bool,
+
+    out_fd: i32,
+    input: Vec<u8>,
+    seq: i64,
+
+    // The process is started on configurationDone request (after the client sent breakpoints), with this initial step.
+    pending_start: Option<Option<BreakpointOn>>,
+    source_breakpoints: HashMap<String, Vec<BreakpointId>>,
+    // Configuration requests (setBreakpoints etc) that came before launch or attach request. We send 'initialized' right away, so the client may do that.
+    // They're handled, and responded to, right after the Debugger is created.
+    queued_requests: Vec<JsonValue>,
+
+    // What the client thinks the process state is.
+    reported_state: ProcessState,
+    stop_on_entry: bool,
+
+    // Valid while the process is suspended.
+    eval_state: EvalState,
+    stacks: HashMap<pid_t, StackTrace>,
+    frames: Vec<(pid_t, /*subframe_idx*/ usize)>, // frameId - 1
+    variables: Vec<DapVariables>, // variablesReference - 1
+}
+
+enum DapVariables {
+    Locals {tid: pid_t, subframe_idx: usize},
+    Registers {tid: pid_t, subframe_idx: usize},
+    Children {tid: pid_t, subframe_idx: usize, value: Value},
+}
+
+impl DapServer {
+    pub fn new(context: Arc<Context>, persistent: PersistentState, supplementary_binaries: SupplementaryBinaries, default_command_line: Option<Vec<String>>, out_fd: i32) -> Self {
+        Self {context, persistent: Some(persistent), supplementary_binaries: Some(supplementary_binaries), default_command_line, debugger: None, should_quit: false, out_fd, input: Vec::new(), seq: 1, pending_start: None, source_breakpoints: HashMap::new(), queued_requests: Vec::new(), reported_state: ProcessState::NoProcess, stop_on_entry: false, eval_state: EvalState::new(), stacks: HashMap::new(), frames: Vec::new(), variables: Vec::new()}
+    }
+
+    // Reads whatever is available on the fd (it should be readable according to epoll) and handles complete messages.
+    pub fn read_input(&mut self, fd: i32) -> Result<()> {
+        let mut buf = [0u8; 1 << 16];
+        let n = unsafe {libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len())};
+        if n < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                return Ok(());
+            }
+            return errno_err!("read() from DAP client failed");
+        }
+        if n == 0 {
+            eprintln!("info: DAP client closed the connection");
+            self.should_quit = true;
+            return Ok(());
+        }
+        self.input.extend_from_slice(&buf[..n as usize]);
+
+        // Messages look like HTTP: "Content-Length: <n>\r\n\r\n<n bytes of json>".
+        loop {
+            let header_end = match self.input.windows(4).position(|w| w == b"\r\n\r\n") {
+                None => break,
+                Some(i) => i };
+            let header = String::from_utf8_lossy(&self.input[..header_end]).into_owned();
+            let mut content_length: Option<usize> = None;
+            for line in header.split("\r\n") {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().ok();
+                    }
+                }
+            }
+            let content_length = match content_length {
+                None => return err!(Format, "DAP message without Content-Length: {}", header),
+                Some(x) => x };
+            let body_start = header_end + 4;
+            if self.input.len() < body_start + content_length {
+                break;
+            }
+            let body: Vec<u8> = self.input.drain(..body_start + content_length).skip(body_start).collect();
+            let message = match str::from_utf8(&body) {
+                Ok(s) => JsonValue::parse(s),
+                Err(_) => err!(Format, "DAP message is not utf8") };
+            match message {
+                Ok(m) if m.get("type").as_str() == Some("request") => self.handle_request(&m),
+                Ok(m) => eprintln!("warning: ignoring DAP message of type {:?}", m.get("type")),
+                Err(e) => eprintln!("warning: bad DAP message: {}", e),
+            }
+            if self.should_quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Called after the Debugger processed events. Tells the client about state changes.
+    pub fn report_state_changes(&mut self) {
+        let debugger = match &mut self.debugger {
+            None => return,
+            Some(d) => d };
+
+        let lines: Vec<String> = debugger.log.lines.drain(..).collect();
+        for line in lines {
+            self.send_event("output", JsonValue::object(vec![("category", "console".into()), ("output", format!("{}\n", line).into())]));
+        }
+
+        let debugger = self.debugger.as_ref().unwrap();
+        let state = match debugger.target_state {
+            // Threads are interrupted asynchronously after target_state becomes Suspended. Report the stop when they're all stopped.
+            ProcessState::Suspended if debugger.threads.values().any(|t| t.state != ThreadState::Suspended) => ProcessState::Running,
+            s => s };
+        let suspended = matches!(state, ProcessState::Suspended | ProcessState::CoreDump);
+        let was_suspended = matches!(self.reported_state, ProcessState::Suspended | ProcessState::CoreDump);
+        if suspended && !was_suspended {
+            let (tid, stop_reason) = debugger.most_interesting_stopped_thread().unwrap_or((debugger.pid, None));
+            let (reason, description) = match &stop_reason {
+                None if state == ProcessState::CoreDump => ("exception", "core dump".to_string()),
+                None if self.stop_on_entry => ("entry", "stopped on entry".to_string()),
+                None => ("pause", "paused".to_string()),
+                Some(StopReason::Breakpoint(_)) => ("breakpoint", "hit breakpoint".to_string()),
+                Some(StopReason::DataChange(_, old, new)) => ("data breakpoint", format!("value changed: {}", format_data_change(old, new))),
+                Some(StopReason::DebugTrap) => ("breakpoint", "debug trap".to_string()),
+                Some(StopReason::Step) => ("step", "stepped".to_string()),
+                Some(StopReason::Exception) => ("exception", "exception during step".to_string()),
+                Some(StopReason::Signal(s)) => ("exception", format!("signal {}", signal_name(*s))),
+            };
+            let all_threads_stopped = debugger.threads.values().all(|t| t.state == ThreadState::Suspended);
+            self.stop_on_entry = false;
+            self.invalidate_references();
+            self.send_event("stopped", JsonValue::object(vec![("reason", reason.into()), ("description", description.into()), ("threadId", tid.into()), ("allThreadsStopped", all_threads_stopped.into())]));
+        } else if !suspended && was_suspended && state != ProcessState::NoProcess {
+            let pid = debugger.pid;
+            self.invalidate_references();
+            self.send_event("continued", JsonValue::object(vec![("threadId", pid.into()), ("allThreadsContinued", true.into())]));
+        }
+        if state == ProcessState::NoProcess && self.reported_state != ProcessState::NoProcess {
+            self.invalidate_references();
+            self.send_event("terminated", JsonValue::object(vec![]));
+        }
+        self.reported_state = state;
+    }
+
+    fn invalidate_references(&mut self) {
+        self.variables.clear();
+        self.frames.clear();
+        self.stacks.clear();
+        self.eval_state.clear();
+    }
+
+    fn handle_request(&mut self, request: &JsonValue) {
+        let command = request.get("command").as_str().unwrap_or("").to_string();
+        let args = request.get("arguments");
+        if self.context.settings.trace_logging { eprintln!("trace: DAP request: {}", request); }
+        if self.debugger.is_none() && matches!(&command[..], "setBreakpoints" | "setExceptionBreakpoints" | "configurationDone") {
+            self.queued_requests.push(request.clone());
+            return;
+        }
+        let result = match &command[..] {
+            "initialize" => Ok(JsonValue::object(vec![
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsConditionalBreakpoints", true.into()),
+                ("supportsEvaluateForHovers", true.into()),
+                ("supportsSetVariable", false.into()),
+                ("supportsTerminateRequest", true.into())])),
+            "launch" => self.launch(args),
+            "attach" => self.attach(args),
+            "configurationDone" => self.configuration_done(),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(JsonValue::object(vec![])),
+            "threads" => self.threads(),
+            "stackTrace" => self.stack_trace(args),
+            "scopes" => self.scopes(args),
+            "variables" => self.variables(args),
+            "evaluate" => self.evaluate(args),
+            "continue" => self.debugger_mut().and_then(|d| d.resume()).map(|()| JsonValue::object(vec![("allThreadsContinued", true.into())])),
+            "pause" => self.debugger_mut().and_then(|d| d.suspend()).map(|()| JsonValue::object(vec![])),
+            "next" => self.step(args, StepKind::Over),
+            "stepIn" => self.step(args, StepKind::Into),
+            "stepOut" => self.step(args, StepKind::Out),
+            "terminate" => self.debugger_mut().and_then(|d| d.murder(libc::SIGKILL)).map(|()| JsonValue::object(vec![])),
+            "disconnect" => {
+                self.should_quit = true;
+                Ok(JsonValue::object(vec![]))
+            }
+            _ => err!(NotImplemented, "unsupported request: {}", command),
+        };
+        // Resuming invalidates all references, and the client knows it without being told.
+        if let ("continue" | "next" | "stepIn" | "stepOut", Ok(_)) = (&command[..], &result) {
+            self.invalidate_references();
+            self.reported_state = self.debugger.as_ref().unwrap().target_state;
+        }
+
+        let created_debugger = matches!(&command[..], "launch" | "attach") && result.is_ok();
+        let mut response = vec![("type", "response".into()), ("request_seq", request.get("seq").clone()), ("command", command.clone().into())];
+        match result {
+            Ok(body) => {
+                response.push(("success", true.into()));
+                response.push(("body", body));
+            }
+            Err(e) => {
+                response.push(("success", false.into()));
+                response.push(("message", format!("{}", e).into()));
+            }
+        }
+        self.send(JsonValue::object(response));
+
+        if created_debugger {
+            for r in mem::take(&mut self.queued_requests) {
+                self.handle_request(&r);
+            }
+        }
+        if command == "initialize" {
+            // We're ready to accept breakpoints before the process is started. They'll be activated on start.
+            self.send_event("initialized", JsonValue::object(vec![]));
+        }
+    }
+
+    fn send_event(&mut self, event: &str, body: JsonValue) {
+        self.send(JsonValue::object(vec![("type", "event".into()), ("event", event.into()), ("body", body)]));
+    }
+
+    fn send(&mut self, message: JsonValue) {
+        let mut message = message;
+        if let JsonValue::Object(fields) = &mut message {
+            fields.insert(0, ("seq".to_string(), self.seq.into()));
+        }
+        self.seq += 1;
+        let body = message.to_string();
+        if self.context.settings.trace_logging { eprintln!("trace: DAP send: {}", body); }
+        let data = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut data = data.as_bytes();
+        while !data.is_empty() {
+            let n = unsafe {libc::write(self.out_fd, data.as_ptr() as *const libc::c_void, data.len())};
+            if n < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                eprintln!("error: write to DAP client failed: {}", e);
+                self.should_quit = true;
+                return;
+            }
+            data = &data[n as usize..];
+        }
+    }
+
+    fn debugger_mut(&mut self) -> Result<&mut Debugger> {
+        match &mut self.debugger {
+            None => err!(Usage, "no launch or attach request yet"),
+            Some(d) => Ok(&mut *d),
+        }
+    }
+
+    fn take_init_state(&mut self) -> Result<(PersistentState, SupplementaryBinaries)> {
+        match (self.persistent.take(), self.supplementary_binaries.take()) {
+            (Some(p), Some(s)) => Ok((p, s)),
+            _ => err!(Usage, "already launched or attached (one process per session)"),
+        }
+    }
+
+    fn finish_creating_debugger(&mut self, mut debugger: Debugger) {
+        PersistentState::load_pretty_printers(&mut debugger);
+        self.debugger = Some(Pin::new(Box::new(debugger)));
+    }
+
+    fn launch(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let command_line: Vec<String> = match args.get("program").as_str() {
+            Some(program) => [program.to_string()].into_iter().chain(args.get("args").as_array().iter().filter_map(|a| a.as_str().map(|s| s.to_string()))).collect(),
+            None => match &self.default_command_line {
+                Some(c) => c.clone(),
+                None => return err!(Usage, "launch request must have 'program' argument"),
+            }
+        };
+        let (persistent, supplementary_binaries) = self.take_init_state()?;
+        let debugger = Debugger::from_command_line(&command_line, self.context.clone(), persistent, supplementary_binaries);
+        self.finish_creating_debugger(debugger);
+        let settings = &self.context.settings;
+        self.stop_on_entry = args.get("stopOnEntry").as_bool().unwrap_or(settings.stop_on_main || settings.stop_on_initial_exec);
+        self.pending_start = Some(if !self.stop_on_entry {
+            None
+        } else if settings.stop_on_initial_exec {
+            Some(BreakpointOn::InitialExec)
+        } else {
+            Some(BreakpointOn::PointOfInterest(PointOfInterest::MainFunction))
+        });
+        Ok(JsonValue::object(vec![]))
+    }
+
+    fn attach(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let pid = match args.get("pid").as_i64().or_else(|| args.get("processId").as_i64()) {
+            None => return err!(Usage, "attach request must have 'pid' argument"),
+            Some(p) => p as pid_t };
+        let (persistent, supplementary_binaries) = self.take_init_state()?;
+        let debugger = Debugger::attach(pid, self.context.clone(), persistent, supplementary_binaries)?;
+        self.finish_creating_debugger(debugger);
+        Ok(JsonValue::object(vec![]))
+    }
+
+    fn configuration_done(&mut self) -> Result<JsonValue> {
+        if let Some(initial_step) = self.pending_start.take() {
+            self.debugger_mut()?.start_child(initial_step)?;
+        }
+        Ok(JsonValue::object(vec![]))
+    }
+
+    fn set_breakpoints(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let path = match args.get("source").get("path").as_str() {
+            None => return err!(Usage, "setBreakpoints without source path"),
+            Some(p) => p.to_string() };
+        let old = self.source_breakpoints.remove(&path).unwrap_or_default();
+        let debugger = self.debugger_mut()?;
+        for id in old {
+            debugger.remove_breakpoint(id);
+        }
+        let mut ids: Vec<BreakpointId> = Vec::new();
+        let mut response: Vec<JsonValue> = Vec::new();
+        for b in args.get("breakpoints").as_array() {
+            let line = b.get("line").as_i64().unwrap_or(0).max(0) as usize;
+            let id = match debugger.add_breakpoint(BreakpointOn::Line(LineBreakpoint {path: path.clone().into(), line, adjusted_line: None})) {
+                Ok(id) => id,
+                Err(e) => {
+                    response.push(JsonValue::object(vec![("verified", false.into()), ("line", line.into()), ("message", format!("{}", e).into())]));
+                    continue;
+                }
+            };
+            if let Some(condition) = b.get("condition").as_str() {
+                if !condition.is_empty() {
+                    debugger.set_breakpoint_condition(id, Some(condition.to_string()));
+                }
+            }
+            ids.push(id);
+            let bp = debugger.breakpoints.get(id);
+            let actual_line = match &bp.on {
+                BreakpointOn::Line(l) => l.adjusted_line.unwrap_or(line),
+                _ => line };
+            // Before the process is started the addresses are not known yet, so we report the breakpoint as verified unless resolving it definitely failed.
+            let mut fields = vec![("verified", bp.addrs.as_ref().map_or_else(|e| e.is_not_calculated() || e.is_loading(), |_| true).into()), ("line", actual_line.into())];
+            if let Err(e) = &bp.addrs {
+                if !e.is_not_calculated() {
+                    fields.push(("message", format!("{}", e).into()));
+                }
+            }
+            response.push(JsonValue::object(fields));
+        }
+        self.source_breakpoints.insert(path, ids);
+        Ok(JsonValue::object(vec![("breakpoints", response.into())]))
+    }
+
+    fn threads(&mut self) -> Result<JsonValue> {
+        let mut threads: Vec<JsonValue> = Vec::new();
+        if let Some(debugger) = &self.debugger {
+            let mut list: Vec<&Thread> = debugger.threads.values().collect();
+            list.sort_by_key(|t| t.idx);
+            for t in list {
+                threads.push(JsonValue::object(vec![("id", t.tid.into()), ("name", format!("{} {}", t.tid, t.info.resource_stats.latest.comm()).into())]));
+            }
+        }
+        Ok(JsonValue::object(vec![("threads", threads.into())]))
+    }
+
+    fn ensure_stack(&mut self, tid: pid_t) -> Result<&StackTrace> {
+        let debugger = match &mut self.debugger {
+            None => return err!(Usage, "no process"),
+            Some(d) => d };
+        if !debugger.threads.contains_key(&tid) {
+            return err!(Usage, "no thread {}", tid);
+        }
+        Ok(self.stacks.entry(tid).or_insert_with(|| debugger.get_stack_trace(tid, /*partial*/ false)))
+    }
+
+    fn stack_trace(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let tid = args.get("threadId").as_i64().unwrap_or(0) as pid_t;
+        let start = args.get("startFrame").as_i64().unwrap_or(0).max(0) as usize;
+        let levels = args.get("levels").as_i64().unwrap_or(0).max(0) as usize;
+        let first_id = self.frames.len() + 1;
+        let stack = self.ensure_stack(tid)?;
+        if stack.subframes.is_empty() {
+            if let Some(e) = &stack.truncated {
+                return Err(e.clone());
+            }
+        }
+        let total = stack.subframes.len();
+        let end = if levels == 0 {total} else {total.min(start + levels)};
+        let mut frames: Vec<JsonValue> = Vec::new();
+        let mut new_refs: Vec<(pid_t, usize)> = Vec::new();
+        for subframe_idx in start.min(end)..end {
+            let subframe = &stack.subframes[subframe_idx];
+            let frame = &stack.frames[subframe.frame_idx];
+            let name = if subframe.function_name.is_empty() {format!("0x{:x}", frame.addr)} else {subframe.function_name.clone()};
+            let id = first_id + new_refs.len();
+            new_refs.push((tid, subframe_idx));
+            let mut fields = vec![("id", id.into()), ("name", name.into()), ("instructionPointerReference", format!("0x{:x}", frame.addr).into())];
+            match &subframe.line {
+                Some(line) => {
+                    fields.push(("line", line.line.line().into()));
+                    fields.push(("column", line.line.column().into()));
+                    fields.push(("source", JsonValue::object(vec![("name", line.filename.to_string_lossy().into_owned().into()), ("path", line.path.to_string_lossy().into_owned().into())])));
+                }
+                None => {
+                    fields.push(("line", 0usize.into()));
+                    fields.push(("column", 0usize.into()));
+                }
+            }
+            frames.push(JsonValue::object(fields));
+        }
+        self.frames.append(&mut new_refs);
+        Ok(JsonValue::object(vec![("stackFrames", frames.into()), ("totalFrames", total.into())]))
+    }
+
+    fn frame_for_args(&self, args: &JsonValue) -> Result<(pid_t, usize)> {
+        match args.get("frameId").as_i64() {
+            Some(id) if id >= 1 && id as usize <= self.frames.len() => Ok(self.frames[id as usize - 1]),
+            Some(id) => err!(Usage, "invalid or expired frameId: {}", id),
+            None => match &self.debugger {
+                // No frame specified (e.g. watch expression evaluated while the process is running). Use the top frame of the main thread.
+                Some(d) if d.threads.contains_key(&d.pid) => Ok((d.pid, 0)),
+                _ => err!(Usage, "no frame"),
+            }
+        }
+    }
+
+    fn add_variables_ref(&mut self, v: DapVariables) -> usize {
+        self.variables.push(v);
+        self.variables.len()
+    }
+
+    fn scopes(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let (tid, subframe_idx) = self.frame_for_args(args)?;
+        let locals = self.add_variables_ref(DapVariables::Locals {tid, subframe_idx});
+        let registers = self.add_variables_ref(DapVariables::Registers {tid, subframe_idx});
+        Ok(JsonValue::object(vec![("scopes", vec![
+            JsonValue::object(vec![("name", "Locals".into()), ("presentationHint", "locals".into()), ("variablesReference", locals.into()), ("expensive", false.into())]),
+            JsonValue::object(vec![("name", "Registers".into()), ("presentationHint", "registers".into()), ("variablesReference", registers.into()), ("expensive", false.into())]),
+        ].into())]))
+    }
+
+    // Calls `f` with an EvalContext for the given thread and stack subframe.
+    fn with_eval_context<R>(&mut self, tid: pid_t, subframe_idx: usize, f: impl FnOnce(&mut EvalState, &mut EvalContext) -> R) -> Result<R> {
+        self.ensure_stack(tid)?;
+        let debugger = self.debugger.as_mut().unwrap();
+        let stack = &self.stacks[&tid];
+        if subframe_idx >= stack.subframes.len() {
+            return err!(Usage, "frame is out of range");
+        }
+        let mut context = debugger.make_eval_context(stack, subframe_idx, tid);
+        let r = f(&mut self.eval_state, &mut context);
+        let modified = context.modified_debuggee;
+        let events = mem::take(&mut context.function_call_events);
+        if modified {
+            debugger.refresh_after_modifying_debuggee(events);
+            self.stacks.clear();
+        }
+        Ok(r)
+    }
+
+    fn variables(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let idx = match args.get("variablesReference").as_i64() {
+            Some(i) if i >= 1 && i as usize <= self.variables.len() => i as usize - 1,
+            _ => return err!(Usage, "invalid or expired variablesReference"),
+        };
+        // (name, formatted value, children to expand)
+        let mut list: Vec<(String, String, Option<Value>)> = Vec::new();
+        let (tid, subframe_idx) = match &self.variables[idx] {
+            DapVariables::Locals {tid, subframe_idx} | DapVariables::Registers {tid, subframe_idx} | DapVariables::Children {tid, subframe_idx, ..} => (*tid, *subframe_idx) };
+        let what = match &self.variables[idx] {
+            DapVariables::Locals {..} => None,
+            DapVariables::Registers {..} => {
+                let stack = self.ensure_stack(tid)?;
+                if let Some(subframe) = stack.subframes.get(subframe_idx) {
+                    let regs = &stack.frames[subframe.frame_idx].regs;
+                    for reg in RegisterIdx::all() {
+                        if let Ok((v, _dubious)) = regs.get(*reg) {
+                            list.push((regs.arch.register_name(*reg).to_string(), format!("0x{:x}", v), None));
+                        }
+                    }
+                }
+                return Ok(self.variables_response(list, tid, subframe_idx));
+            }
+            DapVariables::Children {value, ..} => Some(value.clone()),
+        };
+        self.with_eval_context(tid, subframe_idx, |state, context| -> Result<()> {
+            let values = match &what {
+                None => list_local_variables(context, subframe_idx)?,
+                Some(value) => value_children_plain(value, state, context),
+            };
+            for (name, value) in values {
+                match value {
+                    Ok(v) => {
+                        let (text, has_children) = format_value_plain(&v, state, context);
+                        list.push((name, text, if has_children {Some(v)} else {None}));
+                    }
+                    Err(e) => list.push((name, format!("<{}>", e), None)),
+                }
+            }
+            Ok(())
+        })??;
+        Ok(self.variables_response(list, tid, subframe_idx))
+    }
+
+    fn variables_response(&mut self, list: Vec<(String, String, Option<Value>)>, tid: pid_t, subframe_idx: usize) -> JsonValue {
+        let vars: Vec<JsonValue> = list.into_iter().map(|(name, value, children)| {
+            let reference = match children {
+                Some(v) => self.add_variables_ref(DapVariables::Children {tid, subframe_idx, value: v}),
+                None => 0 };
+            JsonValue::object(vec![("name", name.into()), ("value", value.into()), ("variablesReference", reference.into())])
+        }).collect();
+        JsonValue::object(vec![("variables", vars.into())])
+    }
+
+    fn evaluate(&mut self, args: &JsonValue) -> Result<JsonValue> {
+        let expression = args.get("expression").as_str().unwrap_or("").to_string();
+        let (tid, subframe_idx) = self.frame_for_args(args)?;
+        let (text, value) = self.with_eval_context(tid, subframe_idx, |state, context| -> Result<(String, Option<Value>)> {
+            let (value, _dubious) = eval_watch_expression(&expression, state, context)?;
+            let (text, has_children) = format_value_plain(&value, state, context);
+            Ok((text, if has_children {Some(value)} else {None}))
+        })??;
+        let reference = match value {
+            Some(value) => self.add_variables_ref(DapVariables::Children {tid, subframe_idx, value}),
+            None => 0 };
+        Ok(JsonValue::object(vec![("result", text.into()), ("variablesReference", reference.into())]))
+    }
+
+    fn step(&mut self, args: &JsonValue, kind: StepKind) -> Result<JsonValue> {
+        let tid = args.get("threadId").as_i64().unwrap_or(0) as pid_t;
+        let by_instructions = args.get("granularity").as_str() == Some("instruction");
+        self.debugger_mut()?.step(tid, 0, kind, by_instructions, /*use_line_number_with_column*/ false)?;
+        Ok(JsonValue::object(vec![]))
+    }
+}
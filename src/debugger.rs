@@ -1,7 +1,7 @@
-use crate::{*, elf::*, error::*, util::*, log::*, symbols::*, process_info::*, symbols_registry::*, unwind::*, procfs::*, registers::*, disassembly::*, pool::*, settings::*, context::*, disassembly::*, expr::*, persistent::*, interp::*, os::*, term_emu::*};
+use crate::{*, search::*, elf::*, error::*, util::*, log::*, symbols::*, process_info::*, symbols_registry::*, unwind::*, procfs::*, registers::*, arch::*, disassembly::*, pool::*, settings::*, context::*, disassembly::*, expr::*, types::*, persistent::*, interp::*, os::*, term_emu::*, pretty::*};
 use libc::{pid_t, c_char, c_void};
 use iced_x86::FlowControl;
-use std::{io, ptr, rc::Rc, collections::{HashMap, VecDeque, HashSet, hash_map::Entry}, mem, path::{Path, PathBuf}, sync::Arc, ffi::CStr, ops::Range, os::fd::AsRawFd, fs, time::{Instant, Duration}};
+use std::{io, iter, ptr, rc::Rc, collections::{HashMap, VecDeque, HashSet, hash_map::Entry}, mem, path::{Path, PathBuf}, sync::Arc, ffi::CStr, ops::Range, os::fd::AsRawFd, fs, time::{Instant, Duration}};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum RunMode {
@@ -44,6 +44,8 @@ pub enum ThreadState {
 pub struct Thread {
     pub idx: usize,
     pub tid: pid_t,
+    // Process that the thread belongs to: Debugger.pid, or one of Debugger.forked_processes (with --follow-fork=both).
+    pub pid: pid_t,
     // Whether the thread is running or suspended, as seen by ptrace.
     // We often want to do things when this changes, so keep the number of code sites that change it to a minimum.
     pub state: ThreadState,
@@ -112,6 +114,56 @@ pub struct Thread {
 
     // Got PTRACE_EVENT_EXIT, this thread will exit soon. If it's running, it may have already disappeared, so we shouldn't try to read its information from /proc/
     pub exiting: bool,
+
+    // Keep this thread suspended even though target_state says the process is running (or stepping another thread).
+    // Set by suspend_one_thread(), and in non-stop mode (settings.non_stop) when the thread hits a breakpoint, finishes a step, or crashes.
+    // Cleared when the thread is resumed or stepped.
+    pub held: bool,
+
+    // If the thread is stopped at a syscall-enter-stop that hit a syscall breakpoint. Cleared when the thread is resumed.
+    pub syscall: Option<SyscallStop>,
+    // If the last step finished by returning from a function, the function and its return value registers. Cleared when the thread is resumed.
+    pub function_return: Option<FunctionReturn>,
+
+    // Stopped in a ptrace stop inside a syscall: syscall-enter/exit-stop or PTRACE_EVENT_* (fork, exec, etc). Registers set in such stop
+    // may be overwritten by the syscall's return value, so we don't inject syscalls from such threads. Cleared when the thread is resumed.
+    in_syscall_stop: bool,
+    // The thread is single-stepping over an access to a page that we protected for data breakpoints. The protection is temporarily lifted.
+    // Contains the data breakpoints to report as hit after the step. See handle_page_trap().
+    page_trap_hits: Option<Vec<BreakpointId>>,
+    // (rip, address) of the last page fault that we suppressed because it looked like a stale fault on a page we had just unprotected.
+    // If the same fault happens again, it's a real fault.
+    suppressed_page_fault: Option<(usize, usize)>,
+}
+
+// Registers of all threads and the memory that was looked at, at some past stop. Lets the user go back and look at earlier stops (read-only) after stepping or continuing.
+// This is not a real record-replay: memory that nobody looked at during the stop (by unwinding the stack, evaluating watches, etc) is not captured, and can't be inspected later.
+pub struct Snapshot {
+    // start_count and (tid, stop_count) of all threads, sorted. If this changes, it's a new stop.
+    key: (usize, Vec<(pid_t, usize)>),
+    pub threads: HashMap<pid_t, SnapshotThread>,
+    pub memory: Arc<SnapshotMemReader>,
+}
+
+// Limit on memory captured per snapshot: 16 MiB.
+const SNAPSHOT_MAX_PAGES: usize = 4096;
+
+pub struct SnapshotThread {
+    pub regs: Registers,
+    pub stop_reasons: Vec<StopReason>,
+    is_after_user_debug_trap_instruction: bool,
+    // Memoized stack traces, like in ThreadInfo.
+    partial_stack: Option<StackTrace>,
+    stack: Option<StackTrace>,
+}
+
+#[derive(Default)]
+pub struct Snapshots {
+    // Oldest first. The last one is usually the current stop, still capturing memory as it's read (see SnapshotMemReader).
+    pub list: VecDeque<Snapshot>,
+    // Index in `list` of the snapshot the user is looking at. None if looking at the live process.
+    // Only possible while the process is suspended; resuming or stepping goes back to the live process.
+    pub viewing: Option<usize>,
 }
 
 // A debug session, where we are attached to some process (child or otherwise).
@@ -164,10 +216,32 @@ pub struct Debugger {
     pub breakpoint_locations: Vec<BreakpointLocation>, // sorted by address
     pub breakpoints: Pool<Breakpoint>,
     pub hardware_breakpoints: [HardwareBreakpoint; 4],
+    // Pages that we mprotect()-ed to catch accesses covered by data breakpoints that didn't fit into debug registers. Sorted by address.
+    // Updated in handle_breakpoints(), see update_protected_pages().
+    pub protected_pages: Vec<ProtectedPage>,
+    // Pages that we unprotected. A thread may report a fault on such page after we unprotected it, see handle_page_trap().
+    formerly_protected_pages: Vec<usize>,
+    // Addresses of software breakpoints that we temporarily removed from memory because the debuggee did vfork() (the child shares our memory and would crash if it hit them).
+    // Put back on PTRACE_EVENT_VFORK_DONE.
+    vfork_removed_breakpoints: Vec<usize>,
 
     // ptrace may report a signal for a thread before reporting the clone() that created that thread.
     // We buffer such signals in this queue and process them as soon as the thread appears in `threads`.
     pending_wait_events: VecDeque<(pid_t, i32)>,
+    // Processes that we detached from when following a forked child. If we started one of them, waitpid() will report its exit, which we ignore.
+    detached_pids: Vec<pid_t>,
+    // With --follow-fork=both: forked processes that we debug in addition to `pid`. Their threads are in `threads` too.
+    // They have the same executable as `pid` (we detach from a process when it does exec), so symbols are shared, but each has its own memory and address map (see ForkedProcess).
+    // If `pid` exits first, the oldest of these becomes `pid`.
+    pub forked_processes: Vec<ForkedProcess>,
+
+    // Functions and structs defined in watch expressions, made available to breakpoint conditions. Updated by the watches window.
+    pub script_definitions: ScriptDefinitions,
+    // Loaded from config file, see PersistentState.
+    pub pretty_printers: CustomPrettyPrinters,
+
+    // Registers and touched memory at recent stops, if enabled by settings.snapshots.
+    pub snapshots: Snapshots,
 
     pub log: Log,
     pub prof: Profiling,
@@ -188,6 +262,8 @@ pub struct StepState {
     pub tid: pid_t,
     pub keep_other_threads_suspended: bool, // must stay constant for the duration of the step (we rely on it for a small optimization in process_events())
     pub disable_breakpoints: bool,
+    // Other threads were running when the step started (non-stop mode). Then disable_breakpoints applies only to this thread; the others keep hitting breakpoints.
+    pub others_running: bool,
 
     // How to determine if the step is complete:
     //  * Into && by_instructions: addr not in step.addr_ranges
@@ -219,8 +295,13 @@ pub struct StepState {
     //  * If Into, find the LCA of the pre-step and post-step stacks. If it's the top of the pre-step stack (i.e. we really stepped into)
     //    then go one subframe deeper. Otherwise stay on the LCA.
     pub stack_digest: Vec<usize>,
+
+    // (binary_id, function_idx) of the (non-inlined) function in which the step started. If the step ends by returning from it, we show its return value.
+    pub function: Option<(usize, usize)>,
+    // Set by handle_step_stop() if the step ended because the cfa went up, i.e. the function returned.
+    pub returned: bool,
 }
-impl Default for StepState { fn default() -> Self { Self {tid: 0, keep_other_threads_suspended: false, disable_breakpoints: true, internal_kind: StepKind::Into, by_instructions: false, addr_ranges: Vec::new(), single_steps: false, cfa: 0, stop_only_on_statements: false, binary_id: 0, start_line: None, use_line_number_with_column: false, stack_digest: Vec::new()} } }
+impl Default for StepState { fn default() -> Self { Self {tid: 0, keep_other_threads_suspended: false, disable_breakpoints: true, others_running: false, internal_kind: StepKind::Into, by_instructions: false, addr_ranges: Vec::new(), single_steps: false, cfa: 0, stop_only_on_statements: false, binary_id: 0, start_line: None, use_line_number_with_column: false, stack_digest: Vec::new(), function: None, returned: false} } }
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 pub enum StepBreakpointType {
@@ -240,6 +321,18 @@ pub enum BreakpointRef {
 
 pub type BreakpointId = Id;
 
+// A process debugged in addition to the main one, with --follow-fork=both.
+pub struct ForkedProcess {
+    pub pid: pid_t,
+    // This process's counterparts of `Debugger.info.maps` and `Binary.addr_map` (binary id -> AddrMap, only for binaries mapped in this process).
+    // A child starts with a copy of the parent's address space, but they diverge if either one dlopen()s, unloads, or mmaps something after the fork.
+    // Refreshed on every non-trivial stop, like the main process's maps (see refresh_forked_process_maps()).
+    // Breakpoint locations are kept as addresses in the main process and translated through these (see addr_in_forked_process()).
+    // (Stepping doesn't translate addresses yet, so stepping through a binary that is mapped at different addresses in the two processes is not supported.)
+    pub maps: MemMapsInfo,
+    pub addr_maps: HashMap<usize, AddrMap>,
+}
+
 pub struct BreakpointLocation {
     pub addr: usize,
     pub original_byte: u8, // that we replaced with 0xcc
@@ -258,18 +351,27 @@ pub struct BreakpointLocation {
 #[derive(Clone, Debug, Default)]
 pub struct HardwareBreakpoint {
     pub active: bool,
-    // We allocate hardware breakpoint slots as if no breakpoints are thread-specific. This is fine currently since only steps use thread-specific breakpoints, but
-    // if we add support for user-provided thread-specific breakpoints we may want to make thread-specific hw breakpoint allocation be per thread.
+    // We allocate hardware breakpoint slots as if no breakpoints are thread-specific, i.e. a thread-specific breakpoint takes a slot in all threads.
+    // Could make allocation per thread, but there are only 4 debug registers per thread anyway, and it's rare to have many thread-specific breakpoints.
     pub thread_specific: Option<pid_t>,
     pub addr: usize,
 
-    pub data_breakpoint_id: Option<BreakpointId>, // iff it's a data breakpoint
+    // Iff it's a data breakpoint. A data breakpoint may take multiple slots if its range is not a single aligned 1/2/4/8-byte chunk.
+    pub data_breakpoint_id: Option<BreakpointId>,
     pub stop_on_read: bool,
     pub data_size: u8, // 1, 2, 4, or 8
 
     pub pushed_to_threads: bool,
 }
 
+#[derive(Clone, Debug)]
+pub struct ProtectedPage {
+    pub addr: usize,
+    pub original_prot: i32, // PROT_* flags from /proc/<pid>/maps at the time we protected it
+    pub prot: i32, // original_prot without PROT_WRITE, or PROT_NONE if some data breakpoint on this page stops on read
+    pub current_prot: i32, // what we last set it to; original_prot while a thread is stepping over an access, see handle_page_trap()
+}
+
 #[derive(Debug, Clone)]
 pub struct LineBreakpoint {
     pub path: PathBuf,
@@ -278,6 +380,22 @@ pub struct LineBreakpoint {
     // We don't modify `line` because that would be confusing in the UI in case when breakpoint is set before symbols are loaded (e.g. in a dynamic library), and we can't adjust the line right away.
     pub adjusted_line: Option<usize>,
 }
+impl LineBreakpoint {
+    // "path:line". The path may contain ':' characters. None if it doesn't look like that, e.g. it's a function name.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (path, line) = s.trim().rsplit_once(':')?;
+        if path.is_empty() {
+            return None;
+        }
+        let line = line.parse::<usize>().ok()?;
+        Some(Self {path: path.into(), line, adjusted_line: None})
+    }
+
+    // `path` may be a suffix of the path in debug info, e.g. "foo.cpp" or "src/foo.cpp" matches "/home/me/proj/src/foo.cpp". Whole path components only.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        path.ends_with(&self.path)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct InstructionBreakpoint {
@@ -290,8 +408,203 @@ pub struct InstructionBreakpoint {
 #[derive(Debug, Clone)]
 pub struct DataBreakpoint {
     pub addr: usize,
-    pub size: u8, // 1, 2, 4, or 8 bytes
+    // Any size. Small ranges use debug registers (one per naturally aligned 1/2/4/8-byte chunk), bigger ones use page protection, see activate_breakpoints().
+    pub size: usize,
     pub stop_on_read: bool, // we always stop on write regardless of this
+    // Stop only if the access changed the value, e.g. ignore writes of the same value that was already there.
+    // The condition expression can refer to the values before and after the change as `old` and `new`.
+    pub stop_on_change: bool,
+    // Type of the watched value in watch expression syntax (see print_type_in_watch_language_syntax()), for `old` and `new`. None if unknown, then they're typed by size.
+    pub type_name: Option<String>,
+
+    // Runtime state for stop_on_change. `value` is read when the breakpoint is activated and re-read on each hit.
+    // `old_value` is what `value` was before the last change.
+    pub value: Option<Vec<u8>>,
+    pub old_value: Option<Vec<u8>>,
+}
+impl DataBreakpoint {
+    // Splits the range into naturally aligned chunks of size 1, 2, 4, or 8 bytes, as required by debug registers.
+    pub fn hardware_chunks(&self) -> Vec<(/*addr*/ usize, /*size*/ u8)> {
+        let mut res = Vec::new();
+        let (mut addr, end) = (self.addr, self.addr.saturating_add(self.size));
+        while addr < end {
+            let mut size = 8usize;
+            while addr % size != 0 || addr + size > end {
+                size /= 2;
+            }
+            res.push((addr, size as u8));
+            addr += size;
+        }
+        res
+    }
+
+    // Page-aligned range covering the data.
+    pub fn pages(&self) -> Range<usize> {
+        let page_size = sysconf_PAGE_SIZE();
+        self.addr / page_size * page_size .. (self.addr + self.size).div_ceil(page_size) * page_size
+    }
+}
+
+// What to do when the debuggee receives a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalPolicy {
+    pub stop: bool, // suspend the process (subject to breakpoint condition)
+    pub log: bool, // print to the log window
+    pub pass: bool, // deliver the signal to the program; if false, the signal is suppressed
+}
+impl SignalPolicy {
+    // Behavior for signals not covered by any enabled signal breakpoint.
+    pub fn default_for(signal: i32) -> Self {
+        if [libc::SIGSEGV, libc::SIGABRT, libc::SIGILL, libc::SIGFPE].contains(&signal) {
+            Self {stop: true, log: true, pass: true}
+        } else {
+            Self {stop: false, log: false, pass: true}
+        }
+    }
+}
+
+// Acts as a row in the signal policy table: signals in the set are handled according to `policy`.
+// If multiple enabled signal breakpoints include the same signal, the most recently added one wins.
+#[derive(Debug, Clone)]
+pub struct SignalBreakpoint {
+    pub signals: Vec<i32>, // sorted, deduped
+    pub policy: SignalPolicy,
+}
+impl SignalBreakpoint {
+    // Parses something like "SIGUSR1 PIPE 12 nostop log nopass", similar to gdb's `handle` command.
+    // Policy words: stop/nostop, log/nolog (or print/noprint), pass/nopass (or suppress). Unspecified ones default to true.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut r = Self {signals: Vec::new(), policy: SignalPolicy {stop: true, log: true, pass: true}};
+        for word in s.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty()) {
+            match &word.to_ascii_lowercase()[..] {
+                "stop" => r.policy.stop = true,
+                "nostop" => r.policy.stop = false,
+                "log" | "print" => r.policy.log = true,
+                "nolog" | "noprint" => r.policy.log = false,
+                "pass" => r.policy.pass = true,
+                "nopass" | "suppress" => r.policy.pass = false,
+                _ => match signal_from_name(word) {
+                    Some(sig) => r.signals.push(sig),
+                    None => return err!(Usage, "unknown signal: {}", word),
+                }
+            }
+        }
+        if r.signals.is_empty() {
+            return err!(Usage, "no signals specified");
+        }
+        r.signals.sort_unstable();
+        r.signals.dedup();
+        Ok(r)
+    }
+
+    pub fn contains(&self, signal: i32) -> bool {
+        self.signals.binary_search(&signal).is_ok()
+    }
+
+    pub fn policy_str(&self) -> String {
+        let p = &self.policy;
+        format!("{} {} {}", if p.stop {"stop"} else {"nostop"}, if p.log {"log"} else {"nolog"}, if p.pass {"pass"} else {"nopass"})
+    }
+
+    pub fn signals_str(&self) -> String {
+        self.signals.iter().map(|&s| if s < 32 {signal_name(s).to_string()} else {format!("{}", s)}).collect::<Vec<_>>().join(" ")
+    }
+}
+
+// Stops when any of the syscalls is entered (syscall-enter-stop), before the kernel does anything. Arguments are available in breakpoint condition and watches as '#syscall'.
+#[derive(Debug, Clone)]
+pub struct SyscallBreakpoint {
+    pub syscalls: Vec<usize>, // sorted, deduped
+}
+impl SyscallBreakpoint {
+    // Parses something like "openat mmap 231".
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut syscalls: Vec<usize> = Vec::new();
+        for word in s.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty()) {
+            match syscall_from_name(word) {
+                Some(nr) => syscalls.push(nr),
+                None => return err!(Usage, "unknown syscall: {}", word),
+            }
+        }
+        if syscalls.is_empty() {
+            return err!(Usage, "no syscalls specified");
+        }
+        syscalls.sort_unstable();
+        syscalls.dedup();
+        Ok(Self {syscalls})
+    }
+
+    pub fn syscalls_str(&self) -> String {
+        self.syscalls.iter().map(|&nr| match syscall_name(nr) { "[unknown syscall]" => format!("{}", nr), s => s.to_string() }).collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionPatternKind {
+    Exact, // whole name, or last components of the name, e.g. "bar" and "foo::bar" both match "ns::foo::bar"
+    Substring,
+    Regex,
+}
+
+// Stops at the entry of every function whose demangled name (without Rust hash suffix) matches the pattern, in all loaded binaries.
+// Re-resolved when binaries are loaded, so it also catches functions in libraries loaded later.
+#[derive(Debug, Clone)]
+pub struct FunctionBreakpoint {
+    pub pattern: String, // as typed by the user, including the '*'s or '/'s
+    pub kind: FunctionPatternKind,
+    regex: Option<regex::Regex>,
+    // Parts of the pattern that must appear verbatim in the mangled name. Used to avoid demangling every function.
+    required_substrings: Vec<PaddedString>,
+}
+impl FunctionBreakpoint {
+    // "name" for exact match, "*substring*" for substring, "/regex/" for regex.
+    pub fn parse(s: &str) -> Result<Self> {
+        let pattern = s.trim();
+        let (kind, body) = if pattern.len() >= 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+            (FunctionPatternKind::Regex, &pattern[1..pattern.len()-1])
+        } else if pattern.len() >= 2 && pattern.starts_with('*') && pattern.ends_with('*') {
+            (FunctionPatternKind::Substring, &pattern[1..pattern.len()-1])
+        } else {
+            (FunctionPatternKind::Exact, pattern)
+        };
+        if body.is_empty() {
+            return err!(Usage, "empty function name");
+        }
+        let mut regex = None;
+        let mut required_substrings: Vec<PaddedString> = Vec::new();
+        if kind == FunctionPatternKind::Regex {
+            regex = match regex::Regex::new(body) {
+                Ok(r) => Some(r),
+                Err(e) => return err!(Usage, "invalid regex: {}", e),
+            };
+        } else {
+            // Identifiers appear in mangled names as is, but template arguments may not (e.g. 'int' is mangled as 'i'), so only look before the first '<'.
+            // Same for operators: 'operator+=' is mangled as 'pL', 'operator bool' as 'cvb', so stop at the 'operator' keyword.
+            let prefix = body.split(['<', '(']).next().unwrap();
+            required_substrings = prefix.split(|c: char| !c.is_alphanumeric() && c != '_').filter(|w| !w.is_empty()).take_while(|w| *w != "operator").map(PaddedString::new).collect();
+        }
+        Ok(Self {pattern: pattern.to_string(), kind, regex, required_substrings})
+    }
+
+    fn body(&self) -> &str {
+        match self.kind {
+            FunctionPatternKind::Exact => &self.pattern,
+            FunctionPatternKind::Substring | FunctionPatternKind::Regex => &self.pattern[1..self.pattern.len()-1],
+        }
+    }
+
+    pub fn may_match_mangled_name(&self, mangled_name: &[u8]) -> bool {
+        self.required_substrings.iter().all(|w| memmem_maybe_case_sensitive(mangled_name, w, /*case_sensitive*/ true).is_some())
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let body = self.body();
+        match self.kind {
+            FunctionPatternKind::Exact => name == body || (name.ends_with(body) && name[..name.len()-body.len()].ends_with("::")),
+            FunctionPatternKind::Substring => name.contains(body),
+            FunctionPatternKind::Regex => self.regex.as_ref().unwrap().is_match(name),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -301,15 +614,48 @@ pub enum BreakpointOn {
     InitialExec, // right after initial exec (PTRACE_EVENT_EXEC)
     PointOfInterest(PointOfInterest),
     Data(DataBreakpoint),
-    // Could add syscall, signal, etc.
+    Signal(SignalBreakpoint),
+    Syscall(SyscallBreakpoint),
+    Function(FunctionBreakpoint),
 }
 impl BreakpointOn {
     pub fn is_data(&self) -> bool { match self { Self::Data(_) => true, _ => false } }
+    pub fn is_signal(&self) -> bool { match self { Self::Signal(_) => true, _ => false } }
+    pub fn is_syscall(&self) -> bool { match self { Self::Syscall(_) => true, _ => false } }
+    pub fn is_function(&self) -> bool { match self { Self::Function(_) => true, _ => false } }
+    // Breakpoints that are added and edited by typing their description in the breakpoints window, see parse_typed().
+    pub fn is_typed(&self) -> bool { self.is_signal() || self.is_syscall() || self.is_function() }
     pub fn as_point_of_interest(&self) -> Option<&PointOfInterest> { match self { Self::PointOfInterest(x) => Some(x), _ => None } }
 
+    // "syscall openat mmap", "function foo::bar" (see FunctionBreakpoint::parse()), "foo.cpp:42", or a signal breakpoint spec (see SignalBreakpoint::parse()).
+    pub fn parse_typed(s: &str) -> Result<Self> {
+        let s = s.trim_start();
+        let word_rest = |prefix: &str| s.strip_prefix(prefix).filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+        if let Some(rest) = word_rest("syscall") {
+            Ok(Self::Syscall(SyscallBreakpoint::parse(rest)?))
+        } else if let Some(rest) = word_rest("function") {
+            Ok(Self::Function(FunctionBreakpoint::parse(rest)?))
+        } else if let Some(bp) = LineBreakpoint::parse(s) {
+            Ok(Self::Line(bp))
+        } else {
+            Ok(Self::Signal(SignalBreakpoint::parse(s)?))
+        }
+    }
+
+    // Initial step when starting the program, as requested by --stop or --stop-early.
+    pub fn initial_step_from_settings(settings: &Settings) -> Option<Self> {
+        if settings.stop_on_initial_exec {
+            Some(Self::InitialExec)
+        } else if settings.stop_on_main {
+            Some(Self::PointOfInterest(PointOfInterest::MainFunction))
+        } else {
+            None
+        }
+    }
+
     pub fn should_wait_for_symbols(&self) -> bool {
         match self {
-            Self::Data(_) | Self::InitialExec => false,
+            Self::Data(_) | Self::InitialExec | Self::Signal(_) | Self::Syscall(_) => false,
             // Hack: exempt Panic breakpoint from waiting for symbols to load.
             // This means panic will be missed if it happens early enough in the program execution.
             // Without this, we'd always wait for symbols to load before running
@@ -322,9 +668,74 @@ impl BreakpointOn {
     }
 }
 
+pub enum LogFormatPart {
+    Text(String),
+    Expression(Expression),
+}
+
+// Parses the format string of a log-only breakpoint (tracepoint): text with {expression} placeholders, e.g. "i = {i}, name = {s.name}".
+// Use {{ and }} for literal braces.
+pub fn parse_log_format(s: &str) -> Result<Vec<LogFormatPart>> {
+    let mut res: Vec<LogFormatPart> = Vec::new();
+    let mut text = String::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().is_some_and(|&(_, c)| c == '{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek().is_some_and(|&(_, c)| c == '}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return err!(Syntax, "unmatched '}}' at position {} (use '}}}}' for literal brace)", i),
+            '{' => {
+                // Find the matching closing brace, allowing nested braces inside the expression.
+                let mut depth = 1usize;
+                let mut end = None;
+                for (j, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(j);
+                                break;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                let end = match end {
+                    None => return err!(Syntax, "unmatched '{{' at position {} (use '{{{{' for literal brace)", i),
+                    Some(x) => x };
+                if !text.is_empty() {
+                    res.push(LogFormatPart::Text(mem::take(&mut text)));
+                }
+                res.push(LogFormatPart::Expression(parse_watch_expression(&s[i+1..end])?));
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        res.push(LogFormatPart::Text(text));
+    }
+    Ok(res)
+}
+
 pub struct Breakpoint {
     pub on: BreakpointOn,
     pub condition: Option<(String, Result<Expression>, Option<Error>)>,
+    // If set, the breakpoint doesn't stop. Instead, each hit evaluates this format string (see parse_log_format()) and prints it to the log window.
+    pub log: Option<(String, Result<Vec<LogFormatPart>>)>,
+    // This many hits (that passed the condition) are skipped before the breakpoint takes effect. Decremented on each skipped hit, like in gdb.
+    pub ignore_count: usize,
+    // Disable the breakpoint when it takes effect, i.e. stops or logs.
+    pub disable_after_hit: bool,
+    // If set, the breakpoint only stops this thread; other threads hitting it are resumed as if the condition was false.
+    // Not saved across restarts (tids change). If this is the only thread using the breakpoint's locations, we try to use hardware breakpoints set only in this thread.
+    pub thread: Option<pid_t>,
     pub hits: usize, // including spurious stops of all kinds
     // Cached list of instruction addresses, determined using the BreakpointOn and debug symbols. NotCalculated if we didn't resolve this yet.
     // (We don't put data breakpoint addresses here because that would invite bugs.)
@@ -373,8 +784,34 @@ impl Breakpoint {
             BreakpointOn::Data(b) => {
                 out.write_u8(4)?;
                 out.write_usize(b.addr)?;
-                out.write_u8(b.size)?;
+                out.write_usize(b.size)?;
                 out.write_bool(b.stop_on_read)?;
+                out.write_bool(b.stop_on_change)?;
+                if let Some(s) = &b.type_name {
+                    out.write_u8(1)?;
+                    out.write_str(s)?;
+                } else {
+                    out.write_u8(0)?;
+                }
+            }
+            BreakpointOn::Signal(b) => {
+                out.write_u8(5)?;
+                out.write_usize(b.signals.len())?;
+                for &sig in &b.signals {
+                    out.write_u8(sig as u8)?;
+                }
+                out.write_bool(b.policy.stop)?;
+                out.write_bool(b.policy.log)?;
+                out.write_bool(b.policy.pass)?;
+            }
+            BreakpointOn::Syscall(b) => {
+                // By name, in case we ever support other architectures.
+                out.write_u8(6)?;
+                out.write_str(&b.syscalls_str())?;
+            }
+            BreakpointOn::Function(b) => {
+                out.write_u8(7)?;
+                out.write_str(&b.pattern)?;
             }
         }
         if let Some((s, _, _)) = &self.condition {
@@ -383,11 +820,20 @@ impl Breakpoint {
         } else {
             out.write_u8(0)?;
         }
+        if let Some((s, _)) = &self.log {
+            out.write_u8(1)?;
+            out.write_str(s)?;
+        } else {
+            out.write_u8(0)?;
+        }
+        out.write_usize(self.ignore_count)?;
+        out.write_bool(self.disable_after_hit)?;
         out.write_bool(self.enabled)?;
         Ok(())
     }
 
-    fn load_state(inp: &mut &[u8]) -> Result<Breakpoint> {
+    // `version` is the save file format version, see STATE_FILE_VERSION.
+    fn load_state(inp: &mut &[u8], version: usize) -> Result<Breakpoint> {
         let on = match inp.read_u8()? {
             0 => BreakpointOn::Line(LineBreakpoint {path: inp.read_path()?, line: inp.read_usize()?, adjusted_line: None}),
             1 => {
@@ -400,7 +846,18 @@ impl Breakpoint {
             }
             2 => BreakpointOn::InitialExec,
             3 => BreakpointOn::PointOfInterest(PointOfInterest::load_state(inp)?),
-            4 => BreakpointOn::Data(DataBreakpoint {addr: inp.read_usize()?, size: inp.read_u8()?, stop_on_read: inp.read_bool()?}),
+            4 if version == 0 => BreakpointOn::Data(DataBreakpoint {addr: inp.read_usize()?, size: inp.read_u8()? as usize, stop_on_read: inp.read_bool()?, stop_on_change: false, type_name: None, value: None, old_value: None}),
+            4 => BreakpointOn::Data(DataBreakpoint {addr: inp.read_usize()?, size: inp.read_usize()?, stop_on_read: inp.read_bool()?, stop_on_change: inp.read_bool()?, type_name: if inp.read_u8()? != 0 {Some(inp.read_str()?)} else {None}, value: None, old_value: None}),
+            5 => {
+                let n = inp.read_usize()?;
+                let mut signals: Vec<i32> = Vec::new();
+                for _ in 0..n {
+                    signals.push(inp.read_u8()? as i32);
+                }
+                BreakpointOn::Signal(SignalBreakpoint {signals, policy: SignalPolicy {stop: inp.read_bool()?, log: inp.read_bool()?, pass: inp.read_bool()?}})
+            }
+            6 => BreakpointOn::Syscall(SyscallBreakpoint::parse(&inp.read_str()?)?),
+            7 => BreakpointOn::Function(FunctionBreakpoint::parse(&inp.read_str()?)?),
             x => return err!(Environment, "unexpected breakpoint type in save file: {}", x),
         };
         let condition = match inp.read_u8()? {
@@ -412,14 +869,31 @@ impl Breakpoint {
             }
             x => return err!(Environment, "unexpected breakpoint condition flag in save file: {}", x),
         };
+        if version == 0 {
+            let enabled = inp.read_bool()?;
+            return Ok(Breakpoint {on, condition, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled, active: false, hidden: false, builtin: false});
+        }
+        let log = match inp.read_u8()? {
+            0 => None,
+            1 => {
+                let s = inp.read_str()?;
+                let parts = parse_log_format(&s);
+                Some((s, parts))
+            }
+            x => return err!(Environment, "unexpected breakpoint log flag in save file: {}", x),
+        };
+        let ignore_count = inp.read_usize()?;
+        let disable_after_hit = inp.read_bool()?;
         let enabled = inp.read_bool()?;
-        Ok(Breakpoint {on, condition, hits: 0, addrs: err!(NotCalculated, ""), enabled, active: false, hidden: false, builtin: false})
+        Ok(Breakpoint {on, condition, log, ignore_count, disable_after_hit, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled, active: false, hidden: false, builtin: false})
     }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum StopReason {
     Breakpoint(BreakpointId),
+    // Data breakpoint with stop_on_change. The values are kept here rather than looked up in the breakpoint, so that snapshots show the right ones.
+    DataChange(BreakpointId, /*old*/ Vec<u8>, /*new*/ Vec<u8>),
     DebugTrap, // int3 instruction in the program, e.g. __builtin_debugtrap()
     Step,
     Signal(i32),
@@ -431,17 +905,47 @@ impl StopReason {
     pub fn priority(&self) -> isize /* >= 0 */ {
         match self {
             Self::DebugTrap => 0,
-            Self::Breakpoint(_) => 1,
+            Self::Breakpoint(_) | Self::DataChange(..) => 1,
             Self::Step => 2,
             Self::Exception => 3,
             Self::Signal(_) => 4,
         }
     }
+
+    pub fn breakpoint_id(&self) -> Option<BreakpointId> {
+        match self {
+            &Self::Breakpoint(id) | &Self::DataChange(id, _, _) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+// "0 -> 42" for values that look like integers, otherwise the first changed bytes, e.g. "+12: 00 00 -> 2a 00".
+pub fn format_data_change(old: &[u8], new: &[u8]) -> String {
+    if old.len() == new.len() && [1, 2, 4, 8].contains(&new.len()) {
+        let to_int = |b: &[u8]| {
+            let mut buf = [0u8; 8];
+            buf[..b.len()].copy_from_slice(b);
+            let shift = 64 - b.len() * 8;
+            (i64::from_le_bytes(buf) << shift) >> shift
+        };
+        return format!("{} -> {}", to_int(old), to_int(new));
+    }
+    let start = old.iter().zip(new.iter()).position(|(a, b)| a != b).unwrap_or(0);
+    let to_hex = |b: &[u8]| {
+        let end = (start + 8).min(b.len());
+        let mut r = b[start.min(end)..end].iter().map(|x| format!("{:02x}", x)).collect::<Vec<_>>().join(" ");
+        if end < b.len() {
+            r.push_str(" ...");
+        }
+        r
+    };
+    format!("+{}: {} -> {}", start, to_hex(old), to_hex(new))
 }
 
 impl Thread {
-    fn new(idx: usize, tid: pid_t, state: ThreadState) -> Self {
-        Thread {idx: idx, tid: tid, state: state, single_stepping: false, ignore_next_hw_breakpoint_hit_at_addr: None, stop_reasons: Vec::new(), info: ThreadInfo::default(), pending_signal: None, waiting_for_initial_stop: true, sent_interrupt: false, stop_count: 0, attached_late: false, exiting: false, subframe_to_select: None, is_after_user_debug_trap_instruction: false}
+    fn new(idx: usize, tid: pid_t, pid: pid_t, state: ThreadState) -> Self {
+        Thread {idx: idx, tid: tid, pid, state: state, single_stepping: false, ignore_next_hw_breakpoint_hit_at_addr: None, stop_reasons: Vec::new(), info: ThreadInfo::default(), pending_signal: None, waiting_for_initial_stop: true, sent_interrupt: false, stop_count: 0, attached_late: false, exiting: false, subframe_to_select: None, is_after_user_debug_trap_instruction: false, held: false, syscall: None, function_return: None, in_syscall_stop: false, page_trap_hits: None, suppressed_page_fault: None}
     }
 }
 
@@ -451,15 +955,15 @@ impl Debugger {
             // Add default breakpoints.
 
             // Hidden non-stopping breakpoint on library load to activate breakpoints on dlopen.
-            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::LibraryLoad), condition: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: true, builtin: true});
+            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::LibraryLoad), condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: true, builtin: true});
             // Regular breakpoints on panics (on by default) and exceptions (off by default).
-            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::Panic), condition: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: false, builtin: true});
-            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::Exception), condition: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: false, active: false, hidden: false, builtin: true});
+            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::Panic), condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: false, builtin: true});
+            breakpoints.add(Breakpoint {on: BreakpointOn::PointOfInterest(PointOfInterest::Exception), condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: false, active: false, hidden: false, builtin: true});
         } else {
             assert!(breakpoints.iter().filter(|(_, b)| b.hidden).count() == 1);
         }
 
-        Debugger {mode, command_line, pty: None, tty_size, context, pid: 0, target_state: ProcessState::NoProcess, log: Log::new(), prof, threads: HashMap::new(), pending_wait_events: VecDeque::new(), next_thread_idx: 1, info: ProcessInfo::default(), my_resource_stats, symbols, memory: MemReader::Invalid, waiting_for_initial_sigstop: false, initial_exec_failed: false, stepping: None, pending_step: None, breakpoint_locations: Vec::new(), breakpoints, stopping_to_handle_breakpoints: false, stopped_until_symbols_are_loaded: None, hardware_breakpoints: std::array::from_fn(|_| HardwareBreakpoint::default()), persistent, start_count: 0}
+        Debugger {mode, command_line, pty: None, tty_size, context, pid: 0, target_state: ProcessState::NoProcess, log: Log::new(), prof, threads: HashMap::new(), pending_wait_events: VecDeque::new(), detached_pids: Vec::new(), forked_processes: Vec::new(), next_thread_idx: 1, info: ProcessInfo::default(), my_resource_stats, symbols, memory: MemReader::Invalid, waiting_for_initial_sigstop: false, initial_exec_failed: false, stepping: None, pending_step: None, breakpoint_locations: Vec::new(), breakpoints, vfork_removed_breakpoints: Vec::new(), stopping_to_handle_breakpoints: false, stopped_until_symbols_are_loaded: None, hardware_breakpoints: std::array::from_fn(|_| HardwareBreakpoint::default()), protected_pages: Vec::new(), formerly_protected_pages: Vec::new(), persistent, start_count: 0, script_definitions: ScriptDefinitions::default(), pretty_printers: CustomPrettyPrinters::default(), snapshots: Snapshots::default()}
     }
 
     pub fn save_state(&self, out: &mut Vec<u8>) -> Result<()> {
@@ -477,7 +981,7 @@ impl Debugger {
         Ok(())
     }
 
-    pub fn load_state(&mut self, inp: &mut &[u8]) -> Result<()> {
+    pub fn load_state(&mut self, inp: &mut &[u8], version: usize) -> Result<()> {
         // Possibly overcomplicated management of default breakpoints (currently: Panic and Exception).
         // Add them on startup before loading state, but allow the user to make them conditional.
         // If a breakpoint is found in save file, it replaces the default breakpoint (to preserve the condition, if any).
@@ -494,7 +998,7 @@ impl Debugger {
             if !inp.read_bool()? {
                 break;
             }
-            let mut b = Breakpoint::load_state(inp)?;
+            let mut b = Breakpoint::load_state(inp, version)?;
 
             if let &BreakpointOn::PointOfInterest(p) = &b.on {
                 if let Entry::Occupied(o) = builtin_breakpoints.entry(p) {
@@ -504,9 +1008,9 @@ impl Debugger {
                 }
             }
 
-            if !b.builtin {
+            if !b.builtin && !b.on.is_signal() && !b.on.is_syscall() {
                 // Disable regular breakpoints on startup to allow running the program without waiting for symbols to load.
-                // For Panic and Exception, keep enabledness on restart.
+                // For Panic and Exception, keep enabledness on restart. Same for signal and syscall breakpoints: they don't need symbols (and signal breakpoints act more like settings).
                 // Kind of inconsistent behavior, but seems convenient. Maybe UI should show regular vs special breakpoints separately to make this more clear.
                 b.enabled = false;
             }
@@ -539,12 +1043,12 @@ impl Debugger {
                     continue;
                 }
                 found_new_threads = true;
-                match unsafe {ptrace(PTRACE_SEIZE, tid, 0, (PTRACE_O_TRACECLONE | PTRACE_O_TRACEEXEC | PTRACE_O_TRACEEXIT | PTRACE_O_TRACESYSGOOD) as u64)} {
+                match unsafe {ptrace(PTRACE_SEIZE, tid, 0, (PTRACE_O_TRACECLONE | PTRACE_O_TRACEFORK | PTRACE_O_TRACEVFORK | PTRACE_O_TRACEVFORKDONE | PTRACE_O_TRACEEXEC | PTRACE_O_TRACEEXIT | PTRACE_O_TRACESYSGOOD) as u64)} {
                     Ok(_) => (),
                     Err(e) if e.is_io_permission_denied() => return err!(Usage, "ptrace({}) failed: operation not permitted - missing sudo?", tid),
                     Err(e) => return Err(e),
                 }
-                let mut thread = Thread::new(r.next_thread_idx, tid, ThreadState::Running);
+                let mut thread = Thread::new(r.next_thread_idx, tid, pid, ThreadState::Running);
                 r.next_thread_idx += 1;
 
                 // The newly appeared thread may also be noticed by PTRACE_O_TRACECLONE (if we already attached parent thread when it was spawned). Set a flag saying that it's ok.
@@ -577,7 +1081,7 @@ impl Debugger {
         r.info.maps = maps;
         r.memory = MemReader::CoreDump(Arc::new(memory));
         for (tid, info, signal) in threads {
-            let mut t = Thread::new(r.next_thread_idx, tid, ThreadState::Suspended);
+            let mut t = Thread::new(r.next_thread_idx, tid, 0, ThreadState::Suspended);
             r.next_thread_idx += 1;
             t.info = info;
             if let Some(s) = signal {
@@ -608,6 +1112,8 @@ impl Debugger {
                 // Have to redo the mapping source-line -> address because dynamic libraries may be loaded at different addresses.
                 b.addrs = err!(NotCalculated, "");
                 b.active = false;
+                // Thread ids are per process.
+                b.thread = None;
             }
             let mut new_debugger = Debugger::new(self.mode, command_line, self.tty_size.clone(), context, symbols, breakpoints, persistent, my_resource_stats, prof);
             new_debugger.start_count = self.start_count + 1;
@@ -742,7 +1248,7 @@ impl Debugger {
 
             if pid < 0 { return errno_err!("fork() failed"); }
 
-            ptrace(PTRACE_SEIZE, pid, 0, (PTRACE_O_EXITKILL | PTRACE_O_TRACECLONE | PTRACE_O_TRACEEXEC | PTRACE_O_TRACEEXIT | PTRACE_O_TRACESYSGOOD) as u64)?;
+            ptrace(PTRACE_SEIZE, pid, 0, (PTRACE_O_EXITKILL | PTRACE_O_TRACECLONE | PTRACE_O_TRACEFORK | PTRACE_O_TRACEVFORK | PTRACE_O_TRACEVFORKDONE | PTRACE_O_TRACEEXEC | PTRACE_O_TRACEEXIT | PTRACE_O_TRACESYSGOOD) as u64)?;
         }
 
         self.pid = pid;
@@ -750,7 +1256,7 @@ impl Debugger {
         self.pending_step = initial_step.map(|on| (pid, on));
         self.waiting_for_initial_sigstop = true;
         self.memory = MemReader::Pid(PidMemReader::new(pid));
-        let thread = Thread::new(self.next_thread_idx, pid, ThreadState::Running);
+        let thread = Thread::new(self.next_thread_idx, pid, pid, ThreadState::Running);
         self.threads.insert(pid, thread);
         self.next_thread_idx += 1;
 
@@ -806,6 +1312,9 @@ impl Debugger {
                     }
                     if let Some(t) = self.threads.get_mut(&tid) {
                         thread = Some(t);
+                    } else if self.detached_pids.contains(&tid) && (libc::WIFEXITED(wstatus) || libc::WIFSIGNALED(wstatus)) {
+                        eprintln!("info: detached process {} exited", tid);
+                        continue;
                     } else {
                         eprintln!("info: deferring event {:x} for tid {} that doesn't exist yet", wstatus, tid);
                         self.pending_wait_events.push_back((tid, wstatus));
@@ -826,9 +1335,12 @@ impl Debugger {
                 }
 
                 let mut trivial_stop = false;
+                let mut fork_to_follow: Option<(pid_t, /*vfork*/ bool)> = None;
+                let mut process_to_detach: Option<pid_t> = None;
 
                 if libc::WIFEXITED(wstatus) || libc::WIFSIGNALED(wstatus) {
                     let stepping_this_thread = self.stepping.as_ref().is_some_and(|s| s.tid == tid);
+                    let pid = thread.pid;
                     if libc::WIFEXITED(wstatus) {
                         let exit_code = libc::WEXITSTATUS(wstatus);
                         eprintln!("info: thread {} exited with status {}", tid, exit_code);
@@ -836,14 +1348,14 @@ impl Debugger {
                             // Exited before initial execve(). Probably the user made a typo in the executable path.
                             // Maybe we should quit the whole debugger in this case, if this is the first attempt to start the process?
                             log!(self.log, "exec failed (incorrect executable path?)");
-                        } else if tid == self.pid || exit_code != 0 || stepping_this_thread {
-                            log!(self.log, "{} {} exited with status {}", if tid == self.pid {"process"} else {"thread"}, tid, exit_code);
+                        } else if tid == pid || exit_code != 0 || stepping_this_thread {
+                            log!(self.log, "{} {} exited with status {}", if tid == pid {"process"} else {"thread"}, tid, exit_code);
                         }
                     } else {
                         let signal = libc::WTERMSIG(wstatus);
                         eprintln!("info: thread {} was terminated by signal {} {}", tid, signal, signal_name(signal));
                         let core_dumped = libc::WCOREDUMP(wstatus);
-                        log!(self.log, "{} {} was terminated by signal {} {}{}", if tid == self.pid {"process"} else {"thread"}, tid, signal, signal_name(signal), if core_dumped {" (core dumped)"} else {""});
+                        log!(self.log, "{} {} was terminated by signal {} {}{}", if tid == pid {"process"} else {"thread"}, tid, signal, signal_name(signal), if core_dumped {" (core dumped)"} else {""});
                     }
                     self.threads.remove(&tid);
                     if self.threads.is_empty() {
@@ -851,8 +1363,15 @@ impl Debugger {
                         self.info.clear();
                         self.symbols.mark_all_as_unmapped();
                         self.memory = MemReader::Invalid;
-                    } else if stepping_this_thread {
-                        self.suspend()?;
+                        self.forked_processes.clear();
+                    } else {
+                        if !self.threads.values().any(|t| t.pid == pid) {
+                            // One of the processes is gone, but we're still debugging others (--follow-fork=both).
+                            self.forget_process(pid);
+                        }
+                        if stepping_this_thread {
+                            self.suspend()?;
+                        }
                     }
                 } else if libc::WIFSTOPPED(wstatus) {
                     let signal = libc::WSTOPSIG(wstatus);
@@ -885,6 +1404,7 @@ impl Debugger {
                             //eprintln!("trace: group-stop or interrupt, tid {} signal {} {}", tid, signal, signal_name(signal));
                         }
                     } else if signal == libc::SIGTRAP && wstatus>>16 != 0 { // various ptrace stops
+                        thread.in_syscall_stop = true;
                         match wstatus>>16 {
                             PTRACE_EVENT_EXEC => {
                                 eprintln!("info: exec tid {}", tid);
@@ -894,11 +1414,59 @@ impl Debugger {
                                     // This is a point after dynamic libraries are loaded, but before main() or any static variable initialization.
                                     is_initial_exec = true;
                                     self.target_state = ProcessState::Suspended; // will resume below if needed
+                                } else if !self.forked_processes.is_empty() {
+                                    // One of the processes that we debug with --follow-fork=both replaced itself with another executable.
+                                    // We debug one executable at a time (breakpoints are resolved in the main process's binaries), so let this one go.
+                                    process_to_detach = Some(thread.pid);
+                                } else if self.threads.len() == 1 {
+                                    // A single-threaded process replaced itself with another executable, e.g. a forked child that we followed did exec.
+                                    // The old address space is gone, including our breakpoint instructions. Redo the same setup as for initial exec.
+                                    log!(self.log, "process {} did exec", self.pid);
+                                    self.forget_address_space();
+                                    is_initial_exec = true;
+                                    self.target_state = ProcessState::Suspended; // will resume below if needed
                                 } else {
                                     // Here we're supposed to also handle the case when a multi-threaded process does an exec, and all its threads vanish.
                                     // See "execve(2) under ptrace" section in `man ptrace`. This is currently not implemented.
                                 }
                             }
+                            PTRACE_EVENT_FORK | PTRACE_EVENT_VFORK => {
+                                let vfork = wstatus>>16 == PTRACE_EVENT_VFORK;
+                                let child_pid;
+                                {
+                                    let mut t: pid_t = 0;
+                                    ptrace(PTRACE_GETEVENTMSG, tid, 0, &mut t as *mut pid_t as u64)?;
+                                    child_pid = t;
+                                }
+                                eprintln!("info: thread {} did {} -> pid {}", tid, if vfork {"vfork"} else {"fork"}, child_pid);
+                                match self.context.settings.follow_fork {
+                                    FollowFork::Parent => self.detach_forked_child(child_pid, vfork)?,
+                                    // Switch to the child after this event is fully processed.
+                                    FollowFork::Child => fork_to_follow = Some((child_pid, vfork)),
+                                    FollowFork::Both => self.add_forked_process(tid, child_pid, vfork)?,
+                                }
+                            }
+                            PTRACE_EVENT_VFORK_DONE => {
+                                // The vfork child did exec or exit, we no longer share memory with it. Put the breakpoints back.
+                                for addr in mem::take(&mut self.vfork_removed_breakpoints) {
+                                    if let Some(idx) = self.find_breakpoint_location(addr) {
+                                        if !self.breakpoint_locations[idx].breakpoints.is_empty() {
+                                            if let Err(e) = self.activate_breakpoint_location(idx, tid) {
+                                                // E.g. another process is running (--follow-fork=both). Stop all threads and retry in handle_breakpoints().
+                                                self.breakpoint_locations[idx].error = Some(e);
+                                                self.stopping_to_handle_breakpoints = true;
+                                            }
+                                        }
+                                    }
+                                }
+                                if self.stopping_to_handle_breakpoints {
+                                    self.ptrace_interrupt_all_running_threads()?;
+                                }
+                                if self.protected_pages.iter().any(|p| p.current_prot != p.prot) {
+                                    // Can't inject mprotect() from this thread while it's in the middle of the vfork syscall, do it from handle_breakpoints().
+                                    self.stopping_to_handle_breakpoints = true;
+                                }
+                            }
                             PTRACE_EVENT_CLONE => {
                                 let new_tid;
                                 {
@@ -913,7 +1481,7 @@ impl Debugger {
                                     }
                                 } else {
                                     if self.context.settings.trace_logging { eprintln!("info: new thread: {}", new_tid); }
-                                    let thread = Thread::new(self.next_thread_idx, new_tid, ThreadState::Running);
+                                    let thread = Thread::new(self.next_thread_idx, new_tid, self.threads[&tid].pid, ThreadState::Running);
                                     self.next_thread_idx += 1;
                                     self.threads.insert(new_tid, thread);
                                 }
@@ -938,6 +1506,16 @@ impl Debugger {
                             }
                             _ => return err!(Internal, "unexpected ptrace event: {}", wstatus >> 16),
                         }
+                    } else if signal == libc::SIGTRAP | 0x80 { // syscall-enter-stop or syscall-exit-stop (PTRACE_O_TRACESYSGOOD)
+                        thread.in_syscall_stop = true;
+                        let mut info = ptrace_syscall_info::default();
+                        ptrace(PTRACE_GET_SYSCALL_INFO, tid, mem::size_of::<ptrace_syscall_info>() as u64, &mut info as *mut _ as u64)?;
+                        if info.op == PTRACE_SYSCALL_INFO_ENTRY && self.handle_syscall_stop(tid, info.arch, SyscallStop {nr: (info.nr & !X32_SYSCALL_BIT) as usize, args: info.args}) {
+                            self.stop_for_user(tid)?;
+                        } else {
+                            force_resume = true;
+                            trivial_stop = true;
+                        }
                     } else if signal == libc::SIGTRAP { // hit a breakpoint
                         let mut si: libc::siginfo_t;
                         si = mem::zeroed();
@@ -951,10 +1529,27 @@ impl Debugger {
                         let (hit, refresh_process_info, _regs, stack_digest_to_select) = self.handle_breakpoint_trap(tid, si.si_code, thread_single_stepping, thread_ignore_next_hw_breakpoint_hit_at_addr)?;
 
                         if hit || self.stopping_to_handle_breakpoints {
-                            if hit || self.target_state == ProcessState::Running || self.stepping.as_ref().is_some_and(|s| !s.keep_other_threads_suspended || s.tid != tid) {
+                            let non_stop = self.context.settings.non_stop;
+                            // (In non-stop mode a hit doesn't stop other threads, unless we need to stop them anyway to handle breakpoints.)
+                            let interrupt_others = if hit {
+                                !non_stop || self.stopping_to_handle_breakpoints
+                            } else {
+                                self.target_state == ProcessState::Running || self.stepping.as_ref().is_some_and(|s| !s.keep_other_threads_suspended || s.tid != tid)
+                            };
+                            if interrupt_others {
                                 self.ptrace_interrupt_all_running_threads()?;
                             }
-                            if hit {
+                            if hit && non_stop {
+                                // Stop only this thread, let the others run (or keep stepping, if the step is in another thread).
+                                self.threads.get_mut(&tid).unwrap().held = true;
+                                if self.target_state == ProcessState::Stepping && self.stepping.is_none() {
+                                    // Finished the step.
+                                    self.target_state = ProcessState::Running;
+                                }
+                                if let Some(x) = stack_digest_to_select {
+                                    stack_digests_to_select.push((tid, x));
+                                }
+                            } else if hit {
                                 assert!(self.stepping.is_none());
                                 self.target_state = ProcessState::Suspended;
 
@@ -968,27 +1563,54 @@ impl Debugger {
                             force_resume = true;
                             trivial_stop = !refresh_process_info;
                         }
+                    } else if signal == libc::SIGSEGV && self.handle_page_trap(tid)? {
+                        // Access to a page protected for data breakpoints. Step over it.
+                        force_resume = true;
+                        trivial_stop = true;
                     } else { // other signals, with no special meaning for the debugger
                         if self.context.settings.trace_logging { eprintln!("trace: thread {} stopped by signal {} {}", tid, signal, signal_name(signal)); }
-                        thread.pending_signal = Some(signal);
-
-                        if [libc::SIGSEGV, libc::SIGABRT, libc::SIGILL, libc::SIGFPE].contains(&signal) {
-                            let mut si: libc::siginfo_t;
-                            si = mem::zeroed();
-                            ptrace(PTRACE_GETSIGINFO, tid, 0, &mut si as *mut _ as u64)?;
-
-                            thread.stop_reasons.push(StopReason::Signal(signal));
-                            log!(self.log, "thread {} got {} at 0x{:x}", tid, signal_name(signal), si.si_addr() as usize);
-                            self.target_state = ProcessState::Suspended;
-                            self.cancel_stepping();
-                            self.ptrace_interrupt_all_running_threads()?;
+                        let (policy, breakpoint_id) = self.signal_policy(signal);
+                        let mut stop_reasons: Vec<StopReason> = Vec::new();
+                        let stop = match breakpoint_id {
+                            Some(id) => {
+                                let stop = self.process_breakpoint_hit(id, tid, /*ignore_breakpoints*/ !policy.stop, SUBFUNCTION_LEVEL_MAX, &mut stop_reasons);
+                                stop_reasons.push(StopReason::Signal(signal));
+                                stop
+                            }
+                            None => {
+                                stop_reasons.push(StopReason::Signal(signal));
+                                policy.stop
+                            }
+                        };
+                        if policy.log {
+                            let suffix = if policy.pass {""} else {" (suppressed)"};
+                            if [libc::SIGSEGV, libc::SIGBUS, libc::SIGILL, libc::SIGFPE].contains(&signal) {
+                                let mut si: libc::siginfo_t;
+                                si = mem::zeroed();
+                                ptrace(PTRACE_GETSIGINFO, tid, 0, &mut si as *mut _ as u64)?;
+                                log!(self.log, "thread {} got {} at 0x{:x}{}", tid, signal_name(signal), si.si_addr() as usize, suffix);
+                            } else {
+                                log!(self.log, "thread {} got {}{}", tid, signal_name(signal), suffix);
+                            }
+                        }
+
+                        let thread = self.threads.get_mut(&tid).unwrap();
+                        if policy.pass {
+                            thread.pending_signal = Some(signal);
+                        }
+
+                        if stop {
+                            thread.stop_reasons.append(&mut stop_reasons);
+                            self.stop_for_user(tid)?;
                         } else {
                             force_resume = true;
                             trivial_stop = true;
                         }
                     }
 
-                    if force_resume || self.target_state_for_thread(tid) == ThreadState::Running {
+                    // (If we're going to detach from this process and follow the forked child, leave the thread stopped. Otherwise after vfork it would be
+                    //  blocked until the child does exec, and we wouldn't be able to stop it for detaching.)
+                    if fork_to_follow.is_none() && process_to_detach.is_none() && (force_resume || self.target_state_for_thread(tid) == ThreadState::Running) {
                         // When to refresh thread info after resuming the thread:
                         //  * For newly created thread - to assign thread name (without waiting for periodic timer).
                         //  * For thread that was previously suspended - to assign thread state (without waiting for periodic timer).
@@ -1009,6 +1631,13 @@ impl Debugger {
                 if !trivial_stop {
                     nontrivial_stop = true;
                 }
+
+                if let Some((child_pid, vfork)) = fork_to_follow {
+                    self.switch_to_forked_child(child_pid, vfork)?;
+                }
+                if let Some(pid) = process_to_detach {
+                    self.detach_process_after_exec(pid, tid);
+                }
             }
         }
 
@@ -1020,7 +1649,9 @@ impl Debugger {
         if nontrivial_stop && self.target_state.process_ready() {
             // Re-read /proc/<pid>/maps to see what dynamic libraries are loaded. Re-resolve breakpoints if there are any new libraries.
             // For simplicity we do it on every user-visible stop. If this turns out to be slow, we can be more careful and only do it on _dl_debug_state hit and maybe on periodic timer.
-            drop_caches |= refresh_maps_and_binaries_info(self);
+            let binaries_added = refresh_maps_and_binaries_info(self);
+            drop_caches |= binaries_added;
+            drop_caches |= self.refresh_forked_processes();
             drop_caches |= self.symbols.do_eviction();
 
             if is_initial_exec {
@@ -1028,6 +1659,11 @@ impl Debugger {
                 // Activate breakpoints, start initial step if requested (e.g. step to start of main()), stop right here if needed (if stop on exec was requested).
                 self.target_state = if self.pending_step.is_some() {ProcessState::Stepping} else {ProcessState::Running};
                 self.try_pending_step_and_activate_breakpoints()?;
+            } else if binaries_added {
+                // Resolve breakpoints in the new binaries right away rather than in drop_caches(), while the thread that hit the library load breakpoint is still stopped.
+                // Otherwise it may run past the breakpoint before it's activated.
+                self.refresh_function_breakpoints();
+                self.activate_breakpoints(self.breakpoints.iter().map(|t| t.0).collect())?;
             }
 
             // This looks O(n^2): any_thread_in_state iterates over all threads, and this happens after each thread stopping.
@@ -1054,6 +1690,11 @@ impl Debugger {
             self.threads.get_mut(&tid).unwrap().subframe_to_select = Self::determine_subframe_to_select(&stack, &digest, step_into, subfunction_level);
         }
 
+        self.update_state_if_all_threads_held();
+        if self.target_state != ProcessState::Suspended {
+            self.snapshots.viewing = None;
+        }
+
         Ok((stopped_early, drop_caches))
     }
 
@@ -1088,12 +1729,14 @@ impl Debugger {
         if self.stopping_to_handle_breakpoints || self.stopped_until_symbols_are_loaded.is_some() {
             return ThreadState::Suspended;
         }
+        let held = self.threads.get(&tid).is_some_and(|t| t.held);
         match self.target_state {
-            ProcessState::NoProcess | ProcessState::Starting | ProcessState::Exiting | ProcessState::Running => ThreadState::Running,
+            ProcessState::NoProcess | ProcessState::Starting | ProcessState::Exiting => ThreadState::Running,
+            ProcessState::Running => if held { ThreadState::Suspended } else { ThreadState::Running },
             ProcessState::Suspended | ProcessState::CoreDump => ThreadState::Suspended,
             ProcessState::Stepping => {
                 let s = self.stepping.as_ref().unwrap();
-                if s.tid == tid || !s.keep_other_threads_suspended { ThreadState::Running } else { ThreadState::Suspended }
+                if s.tid == tid || (!held && !s.keep_other_threads_suspended) { ThreadState::Running } else { ThreadState::Suspended }
             }
         }
     }
@@ -1117,12 +1760,19 @@ impl Debugger {
     pub fn drop_caches(&mut self) -> Result<()> {
         eprintln!("info: drop caches");
         self.info.drop_caches();
+        for snapshot in &mut self.snapshots.list {
+            for t in snapshot.threads.values_mut() {
+                t.stack = None;
+                t.partial_stack = None;
+            }
+        }
         if self.target_state.process_ready() {
             refresh_maps_and_binaries_info(self);
             for t in self.threads.values_mut() {
                 t.info.invalidate(self.mode);
                 refresh_thread_info(self.pid, t, &mut self.prof.bucket, &self.context.settings);
             }
+            self.refresh_function_breakpoints();
             self.try_pending_step_and_activate_breakpoints()?;
         } else if self.mode == RunMode::CoreDump {
             refresh_maps_and_binaries_info(self);
@@ -1139,10 +1789,15 @@ impl Debugger {
             ProcessState::Suspended => (),
             ProcessState::Stepping => self.cancel_stepping(),
             ProcessState::Running if self.stopped_until_symbols_are_loaded.is_some() => (),
+            ProcessState::Running if self.threads.values().any(|t| t.held) => (),
             _ => return err!(Usage, "not suspended, can't resume") }
         eprintln!("info: resume");
+        self.snapshots.viewing = None;
 
         self.target_state = ProcessState::Running;
+        for t in self.threads.values_mut() {
+            t.held = false;
+        }
 
         self.check_if_we_should_wait_for_symbols_to_load();
         self.resume_threads_if_needed(/*refresh_info*/ true)?;
@@ -1159,10 +1814,69 @@ impl Debugger {
 
         self.target_state = ProcessState::Suspended;
         self.stopped_until_symbols_are_loaded = None;
+        for t in self.threads.values_mut() {
+            t.held = false;
+        }
         self.ptrace_interrupt_all_running_threads()?;
         Ok(())
     }
 
+    // Suspend one thread, keep the others running. In non-stop mode this is the usual way to stop a thread by hand; otherwise it also works, e.g. to keep a thread from interfering while others run.
+    pub fn suspend_one_thread(&mut self, tid: pid_t) -> Result<()> {
+        match self.target_state {
+            ProcessState::Running | ProcessState::Stepping => (),
+            ProcessState::Suspended => return err!(Usage, "already suspended"),
+            _ => return err!(Usage, "not running, can't suspend"),
+        }
+        if self.stepping.as_ref().is_some_and(|s| s.tid == tid) {
+            return err!(Usage, "thread is stepping, suspend the whole process to interrupt the step");
+        }
+        let t = match self.threads.get_mut(&tid) {
+            Some(t) => t,
+            None => return err!(Usage, "no thread"),
+        };
+        eprintln!("info: suspend thread {}", tid);
+        t.held = true;
+        if t.state == ThreadState::Running && !t.exiting && !t.sent_interrupt {
+            unsafe {ptrace(PTRACE_INTERRUPT, tid, 0, 0)?};
+            t.sent_interrupt = true;
+        }
+        self.update_state_if_all_threads_held();
+        Ok(())
+    }
+
+    // Resume one thread. If the whole process is suspended, the other threads stay suspended.
+    pub fn resume_one_thread(&mut self, tid: pid_t) -> Result<()> {
+        match self.target_state {
+            ProcessState::Running | ProcessState::Stepping | ProcessState::Suspended => (),
+            _ => return err!(Usage, "not suspended, can't resume"),
+        }
+        if !self.threads.contains_key(&tid) {
+            return err!(Usage, "no thread");
+        }
+        eprintln!("info: resume thread {}", tid);
+        self.snapshots.viewing = None;
+        if self.target_state == ProcessState::Suspended {
+            // Switch to "running, but all other threads are held".
+            for t in self.threads.values_mut() {
+                t.held = true;
+            }
+            self.target_state = ProcessState::Running;
+        }
+        self.threads.get_mut(&tid).unwrap().held = false;
+        if self.target_state_for_thread(tid) == ThreadState::Running {
+            self.resume_thread(tid, /*refresh_info*/ true)?;
+        }
+        Ok(())
+    }
+
+    // If all threads are held, call the process suspended, so that it looks and behaves the same as after suspend() (e.g. to allow stepping and snapshots).
+    fn update_state_if_all_threads_held(&mut self) {
+        if self.target_state == ProcessState::Running && self.stopped_until_symbols_are_loaded.is_none() && !self.threads.is_empty() && self.threads.values().all(|t| t.held) {
+            self.target_state = ProcessState::Suspended;
+        }
+    }
+
     fn ptrace_interrupt_all_running_threads(&mut self) -> Result<usize> {
         let mut n = 0;
         for (tid, t) in &mut self.threads {
@@ -1181,8 +1895,8 @@ impl Debugger {
         if self.mode == RunMode::Attach { return err!(Usage, "not killing attached process"); }
         if !self.target_state.process_ready() { return err!(Usage, "no process"); }
         eprintln!("info: kill");
-        unsafe {
-            let r = libc::kill(self.pid, signal);
+        for pid in iter::once(self.pid).chain(self.forked_processes.iter().map(|p| p.pid)) {
+            let r = unsafe {libc::kill(pid, signal)};
             if r != 0 {
                 return errno_err!("kill failed");
             }
@@ -1217,10 +1931,35 @@ impl Debugger {
                 return; // found a binary that's still loading
             }
         }
+        if let Some(binary_id) = self.symbols.function_breakpoint_search_in_progress() {
+            self.stopped_until_symbols_are_loaded = Some(binary_id);
+            return;
+        }
         debug_assert!(false); // try_pending_step_and_activate_breakpoints() should've handled breakpoints and pending step
         self.stopped_until_symbols_are_loaded = None;
     }
 
+    // Function breakpoints may match functions in newly loaded binaries. Re-resolve them, and deactivate the ones whose set of addresses changed, for the next activate_breakpoints() to reinstall.
+    // Breakpoints that didn't change are left alone, so that loading a library that doesn't match any pattern doesn't stop all threads.
+    // (If the new library's symbols are still loading, we can't tell whether it matches. A breakpoint that already has addresses keeps them, and picks up the new
+    //  library's matches when its symbols arrive; a breakpoint with no addresses gets Err(Loading) and waits for symbols, as on startup.)
+    fn refresh_function_breakpoints(&mut self) {
+        let ids: Vec<BreakpointId> = self.breakpoints.iter().filter(|(_, b)| b.on.is_function()).map(|(id, _)| id).collect();
+        for id in ids {
+            let b = self.breakpoints.get_mut(id);
+            let old_addrs = mem::replace(&mut b.addrs, err!(NotCalculated, ""));
+            Self::determine_locations_for_breakpoint(&mut self.symbols, b);
+            let new_addrs = mem::replace(&mut b.addrs, old_addrs);
+            if let (Ok(old), Ok(new)) = (&b.addrs, &new_addrs) {
+                if old == new {
+                    continue;
+                }
+            }
+            self.deactivate_breakpoint(id);
+            self.breakpoints.get_mut(id).addrs = new_addrs;
+        }
+    }
+
     // Try resolving breakpoints into addresses and starting the pending step if present.
     // If this requires symbols to be loaded, and they're not loaded, sets stopped_until_symbols_are_loaded. (Then this is called again from drop_caches() when symbols are loaded.)
     fn try_pending_step_and_activate_breakpoints(&mut self) -> Result<()> {
@@ -1235,8 +1974,8 @@ impl Debugger {
                 self.target_state = ProcessState::Suspended;
                 self.pending_step = None;
             } else {
-                let mut breakpoint = Breakpoint {on: on.clone(), condition: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: true, builtin: true};
-                Self::determine_locations_for_breakpoint(&self.symbols, &mut breakpoint);
+                let mut breakpoint = Breakpoint {on: on.clone(), condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: true, builtin: true};
+                Self::determine_locations_for_breakpoint(&mut self.symbols, &mut breakpoint);
                 match breakpoint.addrs {
                     Err(e) if e.is_loading() => (),
                     Err(e) => {
@@ -1272,7 +2011,10 @@ impl Debugger {
         Ok(())
     }
 
-    fn make_instruction_decoder<'a>(&self, range: Range<usize>, buf: &'a mut Vec<u8>) -> Result<iced_x86::Decoder<'a>> {
+    fn make_instruction_decoder<'a>(&self, arch: Arch, range: Range<usize>, buf: &'a mut Vec<u8>) -> Result<iced_x86::Decoder<'a>> {
+        let bitness = match arch.x86_bitness() {
+            Some(x) => x,
+            None => return err!(NotImplemented, "instruction decoding is not supported for {}", arch.name()) };
         if range.len() > 100_000_000 { return err!(Sanity, "{} MB code range, suspiciously long", range.len() / 1_000_000); }
         buf.resize(range.len(), 0);
         self.memory.read(range.start, buf)?;
@@ -1287,7 +2029,7 @@ impl Debugger {
                 buf[b.addr - range.start] = b.original_byte;
             }
         }
-        Ok(iced_x86::Decoder::with_ip(64, buf, range.start as u64, 0))
+        Ok(iced_x86::Decoder::with_ip(bitness, buf, range.start as u64, 0))
     }
 
     fn jump_target_may_be_outside_ranges(instruction: &iced_x86::Instruction, ranges: &[Range<usize>]) -> bool {
@@ -1303,9 +2045,12 @@ impl Debugger {
     }
 
     pub fn step(&mut self, tid: pid_t, mut subframe_idx: usize, kind: StepKind, by_instructions: bool, use_line_number_with_column: bool) -> Result<()> {
-        if self.target_state != ProcessState::Suspended {
+        // Either the whole process is suspended, or this thread is held while others run (e.g. in non-stop mode).
+        let others_running = self.target_state == ProcessState::Running && self.threads.get(&tid).is_some_and(|t| t.held);
+        if self.target_state != ProcessState::Suspended && !others_running {
             return err!(Usage, "not suspended");
         }
+        self.snapshots.viewing = None;
         assert!(self.stepping.is_none());
         assert!(self.breakpoint_locations.iter().all(|loc| loc.breakpoints.iter().all(|b| match b { BreakpointRef::Step(_) => false, _ => true })));
 
@@ -1327,7 +2072,7 @@ impl Debugger {
         }
 
         let mut buf: Vec<u8> = Vec::new();
-        let mut step = StepState {tid, keep_other_threads_suspended: true, disable_breakpoints: true, internal_kind: kind, by_instructions, ..StepState::default()};
+        let mut step = StepState {tid, keep_other_threads_suspended: !others_running, disable_breakpoints: true, others_running, internal_kind: kind, by_instructions, ..StepState::default()};
         let mut breakpoint_types: Vec<StepBreakpointType> = Vec::new();
         let mut unwind: Option<(Arc<UnwindInfo>, AddrMap)> = None;
         let thread_is_after_user_debug_trap_instruction = thread.is_after_user_debug_trap_instruction;
@@ -1372,6 +2117,9 @@ impl Debugger {
             None => return err!(ProcessState, "no stack frame"), // possible if multiple things happened at once in UI
             Some(s) => s };
         let frame = &stack.frames[subframe.frame_idx];
+        if let (Ok(binary_id), Ok(function_idx)) = (&frame.binary_id, &stack.subframes[frame.subframes.end - 1].function_idx) {
+            step.function = Some((*binary_id, *function_idx));
+        }
 
         // 1. Decide the internal step kind, address ranges, and types of internal breakpoints to add.
 
@@ -1388,7 +2136,7 @@ impl Debugger {
             
             // Decode one instruction to check if it's a call/syscall, just as an optimization to avoid suspending other threads unnecessarily.
             // (This read will incorrectly fail if we're <15 bytes before end of mmap.)
-            match self.make_instruction_decoder(addr..addr+MAX_X86_INSTRUCTION_BYTES, &mut buf) {
+            match self.make_instruction_decoder(frame.regs.arch, addr..addr+MAX_X86_INSTRUCTION_BYTES, &mut buf) {
                 // Allow single-instruction-stepping even if we can't read the process's memory for some reason.
                 Err(e) if kind == StepKind::Into => {
                     eprintln!("warning: failed to read instruction at {:x}: {}", addr, e);
@@ -1589,14 +2337,14 @@ impl Debugger {
 
         let mut may_do_syscalls = false;
         for range in &step.addr_ranges {
-            self.determine_some_step_breakpoint_locations(&breakpoint_types, range.clone(), &step.addr_ranges, /*skip_first_instruction*/ false, &mut breakpoints_to_add, &mut may_do_syscalls, &mut buf)?;
+            self.determine_some_step_breakpoint_locations(frame.regs.arch, &breakpoint_types, range.clone(), &step.addr_ranges, /*skip_first_instruction*/ false, &mut breakpoints_to_add, &mut may_do_syscalls, &mut buf)?;
         }
         step.keep_other_threads_suspended &= !may_do_syscalls;
 
         if self.context.settings.exception_aware_steps && (step.internal_kind == StepKind::Over || step.internal_kind == StepKind::Out) {
             // If we're stepping over/out-of a function and the function throws an exception, control jumps to the 'catch' block, bypassing our AfterRet breakpoints.
             // Put breakpoints on all catch blocks in the call stack.
-            let mut memory = CachedMemReader::new(self.memory.clone());
+            let mut memory = CachedMemReader::new(self.process_memory(tid));
             let mut start_frame = subframe.frame_idx + 1;
             let mut addrs: Vec<usize> = Vec::new();
             if step.internal_kind == StepKind::Over {
@@ -1668,6 +2416,13 @@ impl Debugger {
 
         eprintln!("info: proceeding with step from addr 0x{:x} {:?}", frame.addr, step);
 
+        if self.context.settings.non_stop && !others_running {
+            // Other threads stay suspended after the step completes.
+            for t in self.threads.values_mut() {
+                t.held = true;
+            }
+        }
+        self.threads.get_mut(&tid).unwrap().held = false;
         self.stepping = Some(step);
         self.target_state = ProcessState::Stepping;
 
@@ -1684,7 +2439,7 @@ impl Debugger {
             //
             // There's a corresponding problem when adding a breakpoint while single-stepping. We solve it the same way: by making sure handle_breakpoints() happens
             // before the new breakpoint is activated (all threads are suspended first, including the single-stepping thread).
-            if let Some(idx) = self.find_breakpoint_location(stack.frames[0].addr) {
+            if let Some(idx) = self.addr_in_main_process(stack.frames[0].addr, tid).and_then(|a| self.find_breakpoint_location(a)) {
                 if !self.breakpoint_locations[idx].hardware {
                     self.arrange_handle_breakpoints()?;
                 }
@@ -1705,14 +2460,14 @@ impl Debugger {
     // Decode instructions in given address ranges and find things like calls, jump, and syscalls. Based on that, make a list of addresses for internal breakpoints needed for a step.
     // Annoyingly, this sometimes needs to be re-done in the middle of a step (see comment at one of the call sites), so it's extracted into a function.
     // Only some StepBreakpointType-s are handled here, others only need to be handled when starting a step.
-    fn determine_some_step_breakpoint_locations(&self, breakpoint_types: &[StepBreakpointType], addr_range: Range<usize>, all_addr_ranges: &[Range<usize>], mut skip_first_instruction: bool, breakpoints_to_add: &mut Vec<(StepBreakpointType, /*addr*/ usize)>, out_may_do_syscalls: &mut bool, buf: &mut Vec<u8>) -> Result<()> {
+    fn determine_some_step_breakpoint_locations(&self, arch: Arch, breakpoint_types: &[StepBreakpointType], addr_range: Range<usize>, all_addr_ranges: &[Range<usize>], mut skip_first_instruction: bool, breakpoints_to_add: &mut Vec<(StepBreakpointType, /*addr*/ usize)>, out_may_do_syscalls: &mut bool, buf: &mut Vec<u8>) -> Result<()> {
         if breakpoint_types.contains(&StepBreakpointType::AfterRange) {
             breakpoints_to_add.push((StepBreakpointType::AfterRange, addr_range.end));
         }
         let bp_on_call = breakpoint_types.contains(&StepBreakpointType::Call);
         let bp_on_jump_out = breakpoint_types.contains(&StepBreakpointType::JumpOut);
         if bp_on_call || bp_on_jump_out {
-            let mut decoder = self.make_instruction_decoder(addr_range.clone(), buf)?;
+            let mut decoder = self.make_instruction_decoder(arch, addr_range.clone(), buf)?;
             let mut instruction = iced_x86::Instruction::default();
             while decoder.can_decode() {
                 decoder.decode_out(&mut instruction);
@@ -1768,6 +2523,7 @@ impl Debugger {
         }
 
         self.cancel_stepping();
+        self.snapshots.viewing = None;
 
         self.pending_step = Some((tid, cursor));
         self.target_state = ProcessState::Stepping;
@@ -1780,6 +2536,62 @@ impl Debugger {
         Ok(())
     }
 
+    // Moves the thread's instruction pointer to the given line or instruction without executing anything in between.
+    // Returns a warning if the new location is outside the thread's current function: the stack frame is then whatever the caller left there, and returning from the function will likely crash.
+    pub fn set_next_statement(&mut self, tid: pid_t, cursor: BreakpointOn) -> Result<Option<String>> {
+        assert!(!cursor.is_data());
+        match self.target_state {
+            ProcessState::Suspended | ProcessState::Running => (),
+            ProcessState::CoreDump => return err!(Usage, "can't modify a core dump"),
+            _ => return err!(Usage, "not suspended"),
+        }
+        match self.threads.get(&tid) {
+            None => return err!(Usage, "no thread"),
+            Some(t) if t.state != ThreadState::Suspended => return err!(Usage, "thread is not suspended"),
+            Some(_) => (),
+        }
+        self.snapshots.viewing = None;
+
+        let mut breakpoint = Breakpoint {on: cursor, condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: true, builtin: true};
+        Self::determine_locations_for_breakpoint(&mut self.symbols, &mut breakpoint);
+        let addrs = breakpoint.addrs?;
+
+        // Prefer a location in the current function; a line may have code in other functions too, e.g. if it's in an inline function.
+        let stack = self.get_stack_trace(tid, /*partial*/ true);
+        let current_function = stack.frames.first().and_then(|frame| match (&frame.binary_id, &stack.subframes[frame.subframes.end - 1].function_idx) {
+            (Ok(binary_id), Ok(function_idx)) => Some((*binary_id, *function_idx)),
+            _ => None,
+        });
+        let function_at = |addr: usize| -> Option<(usize, usize)> {
+            let (_, static_addr, binary, _) = self.addr_to_binary(addr).ok()?;
+            let (_, function_idx) = binary.symbols.as_ref().ok()?.addr_to_function(static_addr).ok()?;
+            Some((binary.id, function_idx))
+        };
+        let (addr, warning) = match addrs.iter().find(|(a, _)| current_function.is_some() && function_at(*a) == current_function) {
+            Some(&(a, _)) => (a, None),
+            None => (addrs[0].0, Some("jumped outside the current function".to_string())),
+        };
+
+        eprintln!("info: set next statement thread {} addr {:x}", tid, addr);
+        let thread = self.threads.get_mut(&tid).unwrap();
+        let mut regs = Registers::default();
+        regs.set(RegisterIdx::Rip, addr as u64, false);
+        // If the thread is stopped in a syscall (orig_rax is the syscall number, otherwise -1), don't let the kernel restart it by moving rip back to the syscall instruction.
+        if thread.in_syscall_stop || thread.info.regs.get(RegisterIdx::OrigRax).is_ok_and(|(v, _)| (v as i64) >= 0) {
+            regs.set(RegisterIdx::OrigRax, u64::MAX, false);
+        }
+        ptrace_setregs(tid, &regs)?;
+        // These describe the instruction at the old ip, and would misattribute or swallow the next trap.
+        thread.is_after_user_debug_trap_instruction = false;
+        thread.ignore_next_hw_breakpoint_hit_at_addr = None;
+
+        self.refresh_after_modifying_debuggee(FunctionCallEvents::default());
+        if let Some(w) = &warning {
+            eprintln!("warning: {}", w);
+        }
+        Ok(warning)
+    }
+
     fn any_thread_in_state(&self, state: ThreadState) -> Option<pid_t> {
         for (tid, t) in &self.threads {
             if t.state == state {
@@ -1789,6 +2601,22 @@ impl Debugger {
         None
     }
 
+    // A thread hit a signal or syscall breakpoint (not a code breakpoint, those are more complicated, see handle_breakpoint_trap()). Stop the process, or just this thread in non-stop mode.
+    fn stop_for_user(&mut self, tid: pid_t) -> Result<()> {
+        if self.context.settings.non_stop {
+            self.threads.get_mut(&tid).unwrap().held = true;
+            if self.stepping.as_ref().is_some_and(|s| s.tid == tid) {
+                self.cancel_stepping();
+                self.target_state = ProcessState::Running;
+            }
+        } else {
+            self.target_state = ProcessState::Suspended;
+            self.cancel_stepping();
+            self.ptrace_interrupt_all_running_threads()?;
+        }
+        Ok(())
+    }
+
     fn resume_threads_if_needed(&mut self, refresh_info: bool) -> Result<()> {
         let tids_to_resume: Vec<pid_t> = self.threads.keys().filter(|tid| self.target_state_for_thread(**tid) == ThreadState::Running).copied().collect();
         for t in tids_to_resume {
@@ -1798,6 +2626,7 @@ impl Debugger {
     }
 
     fn resume_thread(&mut self, tid: pid_t, refresh_info: bool) -> Result<()> {
+        let need_syscall_stops = self.need_syscall_stops();
         let thread = self.threads.get_mut(&tid).unwrap();
         if thread.state == ThreadState::Running {
             return Ok(());
@@ -1807,11 +2636,18 @@ impl Debugger {
                 Some(step) if step.tid == tid && step.single_steps => thread.single_stepping = true,
                 _ => () };
         }
-        let op = if thread.single_stepping {PTRACE_SINGLESTEP} else {PTRACE_CONT};
+        let op = if thread.single_stepping {PTRACE_SINGLESTEP} else if need_syscall_stops {PTRACE_SYSCALL} else {PTRACE_CONT};
         let sig = thread.pending_signal.take().unwrap_or(0);
         unsafe {ptrace(op, tid, 0, sig as u64)?};
         thread.state = ThreadState::Running;
         thread.stop_reasons.clear();
+        thread.syscall = None;
+        thread.function_return = None;
+        thread.in_syscall_stop = false;
+        // Memory may change from now on, stop capturing it into the snapshot of the stop we're leaving.
+        if let Some(s) = self.snapshots.list.back() {
+            s.memory.freeze();
+        }
         thread.is_after_user_debug_trap_instruction = false;
 
         if refresh_info {
@@ -1822,7 +2658,31 @@ impl Debugger {
     }
 
 
+    // The thread with the highest-priority stop reason, and the reason. For frontends that show one thread after a stop (the TUI has its own more elaborate logic for switching threads).
+    // If no thread has a stop reason (e.g. the process was suspended by request), returns the main thread (or any thread if the main thread is gone).
+    pub fn most_interesting_stopped_thread(&self) -> Option<(pid_t, Option<StopReason>)> {
+        let mut best: Option<(isize, pid_t, Option<StopReason>)> = None;
+        for (tid, thread) in &self.threads {
+            for reason in &thread.stop_reasons {
+                if best.as_ref().is_none_or(|b| reason.priority() > b.0 || (reason.priority() == b.0 && *tid < b.1)) {
+                    best = Some((reason.priority(), *tid, Some(reason.clone())));
+                }
+            }
+        }
+        if let Some((_, tid, reason)) = best {
+            return Some((tid, reason));
+        }
+        if self.threads.contains_key(&self.pid) {
+            return Some((self.pid, None));
+        }
+        self.threads.keys().min().map(|tid| (*tid, None))
+    }
+
+    // If a snapshot is being viewed, returns the stack trace as of that snapshot.
     pub fn get_stack_trace(&mut self, tid: pid_t, partial: bool) -> StackTrace {
+        if let Some(idx) = self.snapshots.viewing {
+            return self.get_snapshot_stack_trace(idx, tid, partial);
+        }
         let t = match self.threads.get(&tid) {
             Some(t) if t.state != ThreadState::Suspended => return StackTrace::error(error!(Usage, "running")),
             Some(t) => t,
@@ -1833,11 +2693,22 @@ impl Debugger {
         if let Some(s) = memoized {
             s.clone()
         } else {
+            // First time looking at this stop, remember it. Needs to happen before unwinding, to capture the memory that the unwinding reads.
+            self.maybe_take_snapshot();
+
             let mut stack = StackTrace::default();
+            // Threads of forked processes are unwound and symbolized using their process's address map.
+            let forked_process_idx = self.forked_process_of_thread(tid);
+            if let Some(idx) = forked_process_idx {
+                self.swap_address_space_with_forked_process(idx);
+            }
             match self.unwind_stack(tid, partial, &mut stack) {
                 Ok(()) => (),
                 Err(e) => stack.truncated = Some(e),
             };
+            if let Some(idx) = forked_process_idx {
+                self.swap_address_space_with_forked_process(idx);
+            }
 
             let t = self.threads.get_mut(&tid).unwrap(); // have to re-lookup because rust
             let memoized = if partial { &mut t.info.partial_stack } else { &mut t.info.stack };
@@ -1846,18 +2717,43 @@ impl Debugger {
         }
     }
 
+    fn get_snapshot_stack_trace(&mut self, idx: usize, tid: pid_t, partial: bool) -> StackTrace {
+        let snapshot = &self.snapshots.list[idx];
+        let t = match snapshot.threads.get(&tid) {
+            Some(t) => t,
+            None => return StackTrace::error(error!(Usage, "thread didn't exist at this stop")),
+        };
+        let memoized = if partial { &t.partial_stack } else { &t.stack };
+        if let Some(s) = memoized {
+            return s.clone();
+        }
+        let mut stack = StackTrace::default();
+        match self.unwind_stack_from(&t.regs, t.is_after_user_debug_trap_instruction, MemReader::Snapshot(snapshot.memory.clone()), partial, &mut stack) {
+            Ok(()) => (),
+            Err(e) => stack.truncated = Some(e),
+        };
+        let t = self.snapshots.list[idx].threads.get_mut(&tid).unwrap();
+        let memoized = if partial { &mut t.partial_stack } else { &mut t.stack };
+        *memoized = Some(stack.clone());
+        stack
+    }
+
     fn unwind_stack(&self, tid: pid_t, partial: bool, stack: &mut StackTrace) -> Result<()> {
         let thread = self.threads.get(&tid).unwrap();
         if thread.state != ThreadState::Suspended {
             return err!(ProcessState, "running");
         }
+        self.unwind_stack_from(&thread.info.regs, thread.is_after_user_debug_trap_instruction, self.memory_for_inspection(tid), partial, stack)
+    }
 
-        let mut regs = thread.info.regs.clone();
+    fn unwind_stack_from(&self, regs: &Registers, is_after_user_debug_trap_instruction: bool, memory: MemReader, partial: bool, stack: &mut StackTrace) -> Result<()> {
+        let mut regs = regs.clone();
         let mut scratch = UnwindScratchBuffer::default();
-        let mut pseudo_addr = regs.get(RegisterIdx::Rip)?.0 as usize;
-        let mut memory = CachedMemReader::new(self.memory.clone());
+        let (pc, sp) = (regs.arch.pc(), regs.arch.sp());
+        let mut pseudo_addr = regs.get(pc)?.0 as usize;
+        let mut memory = CachedMemReader::new(memory);
 
-        if thread.is_after_user_debug_trap_instruction {
+        if is_after_user_debug_trap_instruction {
             pseudo_addr -= 1;
         }
 
@@ -1868,7 +2764,7 @@ impl Debugger {
                 return err!(ProcessState, "stack too deep");
             }
 
-            let addr = regs.get(RegisterIdx::Rip).unwrap().0 as usize;
+            let addr = regs.get(pc).unwrap().0 as usize;
             stack.subframes.push(StackSubframe {frame_idx: stack.frames.len(), function_idx: err!(MissingSymbols, "unwind failed"), ..Default::default()});
             stack.frames.push(StackFrame {addr, pseudo_addr, regs: regs.clone(), subframes: stack.subframes.len()-1..stack.subframes.len(), .. Default::default()});
             let frame = &mut stack.frames.last_mut().unwrap();
@@ -1906,7 +2802,7 @@ impl Debugger {
 
             if let &Ok((_, /*is_signal_trampoline*/ true)) = &step_result {
                 // Un-decrement the instruction pointer, there's no `call` in signal trampoline.
-                frame.pseudo_addr = frame.regs.get(RegisterIdx::Rip).unwrap().0 as usize;
+                frame.pseudo_addr = frame.regs.get(pc).unwrap().0 as usize;
             }
 
             // (This has to be after updating pseudo_addr above.)
@@ -1919,16 +2815,16 @@ impl Debugger {
             }
 
             let (next_regs, is_signal_trampoline) = step_result?;
-            if !next_regs.has(RegisterIdx::Rip) {
+            if !next_regs.has(pc) {
                 // This is how stacks usually end.
                 return Ok(());
             }
-            let next_addr = next_regs.get(RegisterIdx::Rip).unwrap().0 as usize;
+            let next_addr = next_regs.get(pc).unwrap().0 as usize;
             if next_addr == 0 {
                 // I've seen this in MUSL's clone.s, which has bad unwind info.
                 return err!(Dwarf, "zero return address");
             }
-            if next_addr == addr && next_regs.has(RegisterIdx::Rsp) && regs.has(RegisterIdx::Rsp) && next_regs.get(RegisterIdx::Rsp).unwrap().0 == regs.get(RegisterIdx::Rsp).unwrap().0 {
+            if next_addr == addr && next_regs.has(sp) && regs.has(sp) && next_regs.get(sp).unwrap().0 == regs.get(sp).unwrap().0 {
                 // RIP and RSP didn't change. We'd almost certainly be stuck in a loop if we continue. I've seen this in MUSL's clone.s, which has bad unwind info.
                 return err!(Dwarf, "cycle");
             }
@@ -2065,21 +2961,157 @@ impl Debugger {
     }
 
     pub fn make_eval_context<'a>(&'a self, stack: &'a StackTrace, selected_subframe: usize, tid: pid_t) -> EvalContext<'a> {
-        let (extra_regs, fs_base) = match self.threads.get(&tid) {
-            Some(t) => (Some(&t.info.extra_regs), if t.info.regs.has(RegisterIdx::FsBase) {Some(t.info.regs.get(RegisterIdx::FsBase).unwrap().0)} else {None}),
-            None => (None, None),
+        let (extra_regs, fs_base, writable_tid, syscall) = match (self.snapshots.viewing, self.threads.get(&tid)) {
+            // Snapshots are read-only and don't have extra registers.
+            (Some(idx), _) => (None, self.snapshots.list[idx].threads.get(&tid).and_then(|t| t.regs.get_option(RegisterIdx::FsBase)).map(|(v, _)| v), None, None),
+            (None, Some(t)) => (Some(&t.info.extra_regs), if t.info.regs.has(RegisterIdx::FsBase) {Some(t.info.regs.get(RegisterIdx::FsBase).unwrap().0)} else {None},
+                        if t.state == ThreadState::Suspended && self.mode != RunMode::CoreDump {Some(tid)} else {None}, t.syscall),
+            (None, None) => (None, None, None, None),
         };
-        EvalContext {memory: CachedMemReader::new(self.memory.clone()), process_info: &self.info, symbols_registry: &self.symbols, stack, selected_subframe, extra_regs, fs_base}
+        let function_return = match self.snapshots.viewing {
+            None => self.threads.get(&tid).and_then(|t| t.function_return),
+            Some(_) => None };
+        let other_threads_running = match self.threads.get(&tid) {
+            Some(thread) => self.threads.values().any(|t| t.pid == thread.pid && t.tid != tid && t.state != ThreadState::Suspended),
+            None => false };
+        EvalContext {memory: CachedMemReader::new(self.memory_for_inspection(tid)), process_info: &self.info, symbols_registry: &self.symbols, stack, selected_subframe, extra_regs, fs_base, syscall, function_return, tid: writable_tid, modified_debuggee: false, function_call_events: FunctionCallEvents::default(), breakpoint_locations: &self.breakpoint_locations, has_page_trapped_data_breakpoints: !self.protected_pages.is_empty(), other_threads_running, pretty_printers: &self.pretty_printers}
+    }
+
+    // Live memory of the process that thread `tid` belongs to.
+    fn process_memory(&self, tid: pid_t) -> MemReader {
+        match self.threads.get(&tid) {
+            Some(t) if t.pid != self.pid => MemReader::Pid(PidMemReader::new(t.pid)),
+            _ => self.memory.clone(),
+        }
+    }
+
+    // Memory for stack unwinding and expression evaluation: the viewed snapshot, or the live process (through the snapshot of the current stop, if any, so that it captures the pages we read).
+    // (Snapshots only capture the main process. Threads of forked_processes always see their live memory.)
+    fn memory_for_inspection(&self, tid: pid_t) -> MemReader {
+        if self.threads.get(&tid).is_some_and(|t| t.pid != self.pid) {
+            return self.process_memory(tid);
+        }
+        if let Some(idx) = self.snapshots.viewing {
+            return MemReader::Snapshot(self.snapshots.list[idx].memory.clone());
+        }
+        match self.snapshots.list.back() {
+            Some(s) if !s.memory.is_frozen() && self.snapshot_key().as_ref() == Some(&s.key) => MemReader::Snapshot(s.memory.clone()),
+            _ => self.memory.clone(),
+        }
+    }
+
+    // None if the process is not stopped in a way worth remembering, e.g. some threads are still running.
+    fn snapshot_key(&self) -> Option<(usize, Vec<(pid_t, usize)>)> {
+        if self.target_state != ProcessState::Suspended || self.threads.values().any(|t| t.state != ThreadState::Suspended) {
+            return None;
+        }
+        let mut threads: Vec<(pid_t, usize)> = self.threads.iter().map(|(tid, t)| (*tid, t.stop_count)).collect();
+        threads.sort_unstable();
+        Some((self.start_count, threads))
+    }
+
+    fn maybe_take_snapshot(&mut self) {
+        let max_snapshots = self.context.settings.snapshots;
+        if max_snapshots == 0 || self.snapshots.viewing.is_some() {
+            return;
+        }
+        let live_memory = match &self.memory {
+            MemReader::Pid(m) => m.clone(),
+            _ => return, // core dump doesn't change, nothing to remember
+        };
+        let key = match self.snapshot_key() {
+            Some(k) => k,
+            None => return,
+        };
+        if let Some(s) = self.snapshots.list.back() {
+            if s.key == key {
+                return;
+            }
+            s.memory.freeze();
+        }
+        let threads = self.threads.iter().map(|(tid, t)| (*tid, SnapshotThread {regs: t.info.regs.clone(), stop_reasons: t.stop_reasons.clone(), is_after_user_debug_trap_instruction: t.is_after_user_debug_trap_instruction, partial_stack: None, stack: None})).collect();
+        self.snapshots.list.push_back(Snapshot {key, threads, memory: Arc::new(SnapshotMemReader::new(live_memory, SNAPSHOT_MAX_PAGES))});
+        while self.snapshots.list.len() > max_snapshots {
+            self.snapshots.list.pop_front();
+        }
+    }
+
+    // Index of the snapshot of the current stop, if it was taken.
+    fn current_snapshot_idx(&self) -> Option<usize> {
+        match self.snapshots.list.back() {
+            Some(s) if !s.memory.is_frozen() && self.snapshot_key().as_ref() == Some(&s.key) => Some(self.snapshots.list.len() - 1),
+            _ => None,
+        }
+    }
+
+    // Switch to looking at the stop before the one currently shown. Stack traces and expressions then use the snapshot's registers and memory.
+    pub fn view_previous_snapshot(&mut self) -> Result<()> {
+        if self.context.settings.snapshots == 0 {
+            return err!(Disabled, "snapshots are disabled (see --snapshots)");
+        }
+        if self.target_state != ProcessState::Suspended {
+            return err!(Usage, "can only view snapshots while the process is suspended");
+        }
+        let idx = match self.snapshots.viewing {
+            Some(i) => i,
+            None => self.current_snapshot_idx().unwrap_or(self.snapshots.list.len()),
+        };
+        if idx == 0 {
+            return err!(Usage, "no earlier stops remembered");
+        }
+        self.snapshots.viewing = Some(idx - 1);
+        Ok(())
+    }
+
+    // Switch to the next stop's snapshot, or back to the live process after the last snapshot.
+    pub fn view_next_snapshot(&mut self) -> Result<()> {
+        let idx = match self.snapshots.viewing {
+            Some(i) => i + 1,
+            None => return err!(Usage, "already at the latest stop"),
+        };
+        self.snapshots.viewing = if idx >= self.snapshots.list.len() || Some(idx) == self.current_snapshot_idx() {None} else {Some(idx)};
+        Ok(())
+    }
+
+    // Called after an expression (watch or breakpoint condition) wrote to debuggee's memory or registers.
+    // Re-reads registers and forgets memoized stack traces, since either may be out of date now.
+    // `events` are things that happened while the expression was calling debuggee functions; we handle them like the main loop would.
+    pub fn refresh_after_modifying_debuggee(&mut self, events: FunctionCallEvents) {
+        // The current stop's snapshot may have captured memory from before the modification, retake it.
+        if self.current_snapshot_idx().is_some() {
+            self.snapshots.list.pop_back();
+        }
+        for (tid, parent_tid) in events.new_threads {
+            if !self.threads.contains_key(&tid) {
+                let pid = self.threads.get(&parent_tid).map_or(self.pid, |t| t.pid);
+                let thread = Thread::new(self.next_thread_idx, tid, pid, ThreadState::Running);
+                self.next_thread_idx += 1;
+                self.threads.insert(tid, thread);
+            }
+        }
+        self.pending_wait_events.extend(events.wait_events);
+        for t in self.threads.values_mut() {
+            if t.state != ThreadState::Suspended {
+                continue;
+            }
+            let old_rip = t.info.regs.get_option(RegisterIdx::Rip);
+            t.info.invalidate(self.mode);
+            refresh_thread_info(self.pid, t, &mut self.prof.bucket, &self.context.settings);
+            if t.info.regs.get_option(RegisterIdx::Rip) != old_rip {
+                // The user moved the instruction pointer, we're not after the int3 anymore.
+                t.is_after_user_debug_trap_instruction = false;
+            }
+        }
     }
 
     pub fn add_breakpoint(&mut self, on: BreakpointOn) -> Result<BreakpointId> {
         if let BreakpointOn::Data(d) = &on {
-            if ![1, 2, 4, 8].contains(&d.size) {
-                return err!(Internal, "unsupported size for data breakpoint size: {}", d.size);
+            if d.size == 0 {
+                return err!(Usage, "data breakpoint on 0 bytes");
             }
         }
         
-        let breakpoint = Breakpoint {on, condition: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: false, builtin: false};
+        let breakpoint = Breakpoint {on, condition: None, log: None, ignore_count: 0, disable_after_hit: false, thread: None, hits: 0, addrs: err!(NotCalculated, ""), enabled: true, active: false, hidden: false, builtin: false};
         let id = self.breakpoints.add(breakpoint).0;
         if self.target_state.process_ready() {
             self.activate_breakpoints(vec![id])?;
@@ -2111,6 +3143,83 @@ impl Debugger {
         Ok(true)
     }
 
+    // Replaces the signal set and policy of a signal breakpoint, the syscall list of a syscall breakpoint, or the pattern of a function breakpoint,
+    // keeping its condition and hit count. Can also turn one kind into another.
+    pub fn set_typed_breakpoint(&mut self, id: BreakpointId, on: BreakpointOn) -> Result<()> {
+        assert!(on.is_typed());
+        let b = match self.breakpoints.try_get_mut(id) {
+            None => return err!(Usage, "no such breakpoint"),
+            Some(x) => x };
+        if !b.on.is_typed() {
+            return err!(Usage, "not a signal, syscall or function breakpoint");
+        }
+        b.on = on;
+        b.addrs = err!(NotCalculated, "");
+        // Reactivate to re-resolve addresses, and to make sure running threads get resumed with PTRACE_SYSCALL if needed.
+        self.deactivate_breakpoint(id);
+        if self.target_state.process_ready() {
+            self.activate_breakpoints(vec![id])?;
+        }
+        Ok(())
+    }
+
+    // Whether threads should be resumed with PTRACE_SYSCALL instead of PTRACE_CONT.
+    // Not if the breakpoints were found to be unsupported for this process (see handle_syscall_stop()): each syscall-stop would slow down the program for nothing.
+    fn need_syscall_stops(&self) -> bool {
+        self.breakpoints.iter().any(|(_, b)| b.enabled && b.on.is_syscall() && !b.addrs.as_ref().is_err_and(|e| e.is_not_implemented()))
+    }
+
+    // Called at syscall-enter-stop. Returns true if a syscall breakpoint was hit and we should stop.
+    fn handle_syscall_stop(&mut self, tid: pid_t, audit_arch: u32, syscall: SyscallStop) -> bool {
+        if audit_arch != AUDIT_ARCH_X86_64 {
+            // Syscall names are x86-64 syscall numbers, and i386 numbering is completely different. Show an error on the breakpoints instead of stopping at random syscalls.
+            // This also makes need_syscall_stops() return false, so threads go back to PTRACE_CONT when resumed.
+            for (_, b) in self.breakpoints.iter_mut() {
+                if b.on.is_syscall() {
+                    b.addrs = err!(NotImplemented, "syscall breakpoints are not supported for 32-bit x86 processes");
+                }
+            }
+            return false;
+        }
+        let ids: Vec<BreakpointId> = self.breakpoints.iter().filter(|(_, b)| match &b.on {
+            BreakpointOn::Syscall(s) => b.enabled && s.syscalls.binary_search(&syscall.nr).is_ok(),
+            _ => false }).map(|(id, _)| id).collect();
+        if ids.is_empty() {
+            return false;
+        }
+        // Make the arguments available to breakpoint conditions.
+        self.threads.get_mut(&tid).unwrap().syscall = Some(syscall);
+        let ignore_breakpoints = self.step_ignores_breakpoints(tid) || self.pending_step.is_some();
+        let mut stop_reasons: Vec<StopReason> = Vec::new();
+        let mut hit = false;
+        for id in ids {
+            hit |= self.process_breakpoint_hit(id, tid, ignore_breakpoints, SUBFUNCTION_LEVEL_MAX, &mut stop_reasons);
+        }
+        let thread = self.threads.get_mut(&tid).unwrap();
+        if hit {
+            thread.stop_reasons.append(&mut stop_reasons);
+        } else {
+            thread.syscall = None;
+        }
+        hit
+    }
+
+    // The per-signal policy table lookup. Returns the signal breakpoint that determined the policy, if any.
+    pub fn signal_policy(&self, signal: i32) -> (SignalPolicy, Option<BreakpointId>) {
+        let mut res: Option<(BreakpointId, SignalPolicy)> = None;
+        for (id, b) in self.breakpoints.iter() {
+            if let BreakpointOn::Signal(s) = &b.on {
+                if b.enabled && s.contains(signal) && res.as_ref().is_none_or(|(idd, _)| idd.seqno <= id.seqno) {
+                    res = Some((id, s.policy));
+                }
+            }
+        }
+        match res {
+            Some((id, policy)) => (policy, Some(id)),
+            None => (SignalPolicy::default_for(signal), None),
+        }
+    }
+
     pub fn set_breakpoint_condition(&mut self, id: BreakpointId, condition: Option<String>) {
         let b = match self.breakpoints.try_get_mut(id) {
             None => return,
@@ -2123,6 +3232,89 @@ impl Debugger {
         }
     }
 
+    pub fn set_breakpoint_log(&mut self, id: BreakpointId, format: Option<String>) {
+        let b = match self.breakpoints.try_get_mut(id) {
+            None => return,
+            Some(x) => x };
+        b.log = format.map(|s| {
+            let parts = parse_log_format(&s);
+            (s, parts)
+        });
+    }
+
+    pub fn set_breakpoint_ignore_count(&mut self, id: BreakpointId, ignore_count: usize) {
+        if let Some(b) = self.breakpoints.try_get_mut(id) {
+            b.ignore_count = ignore_count;
+        }
+    }
+
+    pub fn set_breakpoint_disable_after_hit(&mut self, id: BreakpointId, disable_after_hit: bool) {
+        if let Some(b) = self.breakpoints.try_get_mut(id) {
+            b.disable_after_hit = disable_after_hit;
+        }
+    }
+
+    // Restricts the breakpoint to one thread, or removes the restriction if None.
+    pub fn set_breakpoint_thread(&mut self, id: BreakpointId, thread: Option<pid_t>) -> Result<()> {
+        let b = match self.breakpoints.try_get_mut(id) {
+            None => return err!(Usage, "no such breakpoint"),
+            Some(x) => x };
+        if b.thread == thread {
+            return Ok(());
+        }
+        b.thread = thread;
+        if !b.active || !self.target_state.process_ready() {
+            return Ok(());
+        }
+        if b.on.is_data() {
+            // Reactivate to update debug registers in all threads.
+            self.deactivate_breakpoint(id);
+            self.activate_breakpoints(vec![id])?;
+        } else {
+            // Convert between software and thread-specific hardware breakpoints as needed.
+            self.arrange_handle_breakpoints()?;
+        }
+        Ok(())
+    }
+
+    // Restricts all visible breakpoints to the given thread. If they're all already restricted to it, removes the restriction instead.
+    // Returns true if the breakpoints are now restricted.
+    pub fn lock_breakpoints_to_thread(&mut self, tid: pid_t) -> Result<bool> {
+        if !self.threads.contains_key(&tid) {
+            return err!(Usage, "no thread selected");
+        }
+        let ids: Vec<BreakpointId> = self.breakpoints.iter().filter(|(_, b)| !b.hidden).map(|(id, _)| id).collect();
+        let lock = ids.iter().any(|id| self.breakpoints.get(*id).thread != Some(tid));
+        for id in ids {
+            self.set_breakpoint_thread(id, if lock {Some(tid)} else {None})?;
+        }
+        Ok(lock)
+    }
+
+    // Parses thread filter for set_breakpoint_thread(): a tid, or a thread index (as shown in the threads window) prefixed with '#'.
+    // A plain number that is not a tid of any thread is also accepted as index. Empty string means no filter.
+    pub fn parse_thread_filter(&self, s: &str) -> Result<Option<pid_t>> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(None);
+        }
+        let (n, idx_only) = match s.strip_prefix('#') {
+            Some(rest) => (rest.trim().parse::<usize>(), true),
+            None => (s.parse::<usize>(), false),
+        };
+        let n = match n {
+            Ok(x) => x,
+            Err(_) => return err!(Usage, "expected tid or #thread_index, got '{}'", s),
+        };
+        if !idx_only && self.threads.contains_key(&(n as pid_t)) {
+            return Ok(Some(n as pid_t));
+        }
+        match self.threads.values().find(|t| t.idx == n) {
+            Some(t) => Ok(Some(t.tid)),
+            None => err!(Usage, "no thread {}", s),
+        }
+    }
+
     pub fn set_data_breakpoint_stop_on_read(&mut self, id: BreakpointId, stop_on_read: bool) -> Result<bool> {
         let b = match self.breakpoints.try_get_mut(id) {
             None => return Ok(false),
@@ -2143,16 +3335,56 @@ impl Debugger {
         Ok(true)
     }
 
+    pub fn set_data_breakpoint_stop_on_change(&mut self, id: BreakpointId, stop_on_change: bool) -> Result<bool> {
+        let b = match self.breakpoints.try_get_mut(id) {
+            None => return Ok(false),
+            Some(x) => x };
+        let d = match &mut b.on {
+            BreakpointOn::Data(x) => x,
+            _ => return err!(Usage, "not a data breakpoint") };
+        if d.stop_on_change == stop_on_change {
+            return Ok(false);
+        }
+        d.stop_on_change = stop_on_change;
+        d.old_value = None;
+        d.value = None;
+        if stop_on_change && b.active {
+            // Remember the current value to compare against.
+            let mut buf = vec![0u8; d.size];
+            d.value = self.memory.read(d.addr, &mut buf).ok().map(|_| buf);
+        }
+        Ok(true)
+    }
+
     pub fn find_line_breakpoint_fuzzy(&self, lb: &LineBreakpoint) -> Option<BreakpointId> {
         for (id, breakpoint) in self.breakpoints.iter() {
             match &breakpoint.on {
-                BreakpointOn::Line(bp) if bp.path == lb.path && (bp.line == lb.line || bp.adjusted_line == Some(lb.line)) => return Some(id.clone()),
+                BreakpointOn::Line(bp) if (bp.matches_path(&lb.path) || lb.matches_path(&bp.path)) && (bp.line == lb.line || bp.adjusted_line == Some(lb.line)) => return Some(id.clone()),
                 _ => (),
             }
         }
         None
     }
 
+    // Full path of a file matching the breakpoint's path (which may be just a suffix), from any loaded binary.
+    pub fn find_line_breakpoint_file(&self, bp: &LineBreakpoint) -> Option<PathBuf> {
+        for binary in self.symbols.iter() {
+            if let Ok(symbols) = &binary.symbols {
+                if symbols.path_to_used_file.contains_key(&bp.path as &Path) {
+                    return Some(bp.path.clone());
+                }
+                if let Some(p) = symbols.path_to_used_file.keys().find(|p| bp.matches_path(p)) {
+                    return Some(p.to_path_buf());
+                }
+            }
+        }
+        None
+    }
+
+    pub fn find_function_breakpoint(&self, pattern: &str) -> Option<BreakpointId> {
+        self.breakpoints.iter().find(|(_, b)| match &b.on { BreakpointOn::Function(f) => f.pattern == pattern, _ => false }).map(|(id, _)| id)
+    }
+
     fn activate_breakpoints(&mut self, ids: Vec<BreakpointId>) -> Result<()> {
         assert!(self.target_state.process_ready());
         let mut should_handle_breakpoints = false;
@@ -2162,28 +3394,48 @@ impl Debugger {
             if !b.enabled || b.active {
                 continue;
             }
-            if let BreakpointOn::Data(d) = &b.on {
-                // Allocate a hardware breakpoint slot right here.
-                let mut found = false;
-                for h in &mut self.hardware_breakpoints {
-                    if !h.active {
-                        *h = HardwareBreakpoint {active: true, addr: d.addr, data_breakpoint_id: Some(id), stop_on_read: d.stop_on_read, data_size: d.size, ..Default::default()};
-                        found = true;
-                        break;
-                    }
+            if b.on.is_signal() {
+                // Nothing to install, signals are matched against breakpoints when the thread stops.
+                b.active = true;
+                continue;
+            }
+            if b.on.is_syscall() {
+                // Running threads need to be re-resumed with PTRACE_SYSCALL.
+                b.active = true;
+                should_handle_breakpoints = true;
+                continue;
+            }
+            if let BreakpointOn::Data(d) = &mut b.on {
+                if d.stop_on_change {
+                    let mut buf = vec![0u8; d.size];
+                    d.value = self.memory.read(d.addr, &mut buf).ok().map(|_| buf);
+                    d.old_value = None;
                 }
-                if found {
+                // Allocate hardware breakpoint slots right here, one per aligned chunk.
+                // If the range needs multiple slots, leave at least one slot free, for converting software breakpoints to hardware when stepping.
+                // Otherwise fall back to page protection, which is much slower but has no size limit.
+                let chunks = d.hardware_chunks();
+                let free_slots: Vec<usize> = (0..4).filter(|&i| !self.hardware_breakpoints[i].active).collect();
+                if chunks.len() <= free_slots.len() && (chunks.len() == 1 || chunks.len() < free_slots.len()) {
+                    for (&(addr, size), &i) in chunks.iter().zip(free_slots.iter()) {
+                        self.hardware_breakpoints[i] = HardwareBreakpoint {active: true, thread_specific: b.thread, addr, data_breakpoint_id: Some(id), stop_on_read: d.stop_on_read, data_size: size, ..Default::default()};
+                    }
                     b.active = true;
                     b.addrs = err!(NotCalculated, "");
                     should_handle_breakpoints = true; // to set debug registers in all threads
-                } else {
+                } else if let Err(e) = Self::check_pages_for_data_breakpoint(&self.info.maps, d) {
                     b.enabled = false; // auto-disable, seems convenient
-                    b.addrs = err!(OutOfHardwareBreakpoints, "out of hardware breakpoints (max: 4)");
-                    log!(self.log, "out of hw breakpoints (max: 4)");
+                    log!(self.log, "data breakpoint doesn't fit in hw breakpoints, and can't protect pages: {}", e);
+                    b.addrs = Err(e);
+                } else {
+                    // The pages are protected in handle_breakpoints().
+                    b.active = true;
+                    b.addrs = err!(NotCalculated, "");
+                    should_handle_breakpoints = true;
                 }
             } else {
                 if let Err(e) = &b.addrs {
-                    Self::determine_locations_for_breakpoint(&self.symbols, b);
+                    Self::determine_locations_for_breakpoint(&mut self.symbols, b);
                 }
                 match &b.addrs {
                     Ok(addrs) => {
@@ -2225,7 +3477,7 @@ impl Debugger {
         }
     }
 
-    fn determine_locations_for_breakpoint(symbols_registry: &SymbolsRegistry, breakpoint: &mut Breakpoint) {
+    fn determine_locations_for_breakpoint(symbols_registry: &mut SymbolsRegistry, breakpoint: &mut Breakpoint) {
         assert!(breakpoint.addrs.is_err());
         match &mut breakpoint.on {
             BreakpointOn::Line(bp) => {
@@ -2244,53 +3496,56 @@ impl Debugger {
                         }
                         Err(_) => continue,
                     };
-                    let file_idx = match symbols.path_to_used_file.get(&bp.path as &Path) {
-                        Some(i) => *i,
-                        None => continue };
-                    found_file = true;
-                    let addrs = match symbols.line_to_addrs(file_idx, bp.line, true) {
-                        Ok(x) => x,
-                        Err(None) => continue,
-                        Err(Some(adjusted_line)) => match symbols.line_to_addrs(file_idx, adjusted_line, true){
+                    // Exact path match, or all files whose path ends with the breakpoint's path (e.g. breakpoint on "foo.cpp:42" added before the library with foo.cpp was loaded).
+                    let file_idxs: Vec<usize> = match symbols.path_to_used_file.get(&bp.path as &Path) {
+                        Some(i) => vec![*i],
+                        None => symbols.path_to_used_file.iter().filter(|(p, _)| bp.matches_path(p)).map(|(_, i)| *i).collect() };
+                    for file_idx in file_idxs {
+                        found_file = true;
+                        let addrs = match symbols.line_to_addrs(file_idx, bp.line, true) {
                             Ok(x) => x,
-                            Err(_) => continue,
+                            Err(None) => continue,
+                            Err(Some(adjusted_line)) => match symbols.line_to_addrs(file_idx, adjusted_line, true){
+                                Ok(x) => x,
+                                Err(_) => continue,
+                            }
+                        };
+                        if addrs.is_empty() {
+                            continue;
+                        }
+                        if addrs[0].line() > res.0 {
+                            continue;
+                        }
+                        if addrs[0].line() < res.0 {
+                            res = (addrs[0].line(), Vec::new());
                         }
-                    };
-                    if addrs.is_empty() {
-                        continue;
-                    }
-                    if addrs[0].line() > res.0 {
-                        continue;
-                    }
-                    if addrs[0].line() < res.0 {
-                        res = (addrs[0].line(), Vec::new());
-                    }
 
-                    // There are often multiple nearby addresses for the same line, usually with different column numbers.
-                    // E.g. if the line contains a function call where some arguments are function calls too, there would usually be an address for each of the arguments and a location for the outer function call.
-                    // We want to set breakpoint on only the first of those addresses. Otherwise continuing from such breakpoint is very confusing: you continue and immediately stop without leaving the line.
-                    // On the other hand, a line can have multiple addresses corresponding to different inlining sites of the containing function; we don't want to deduplicate those.
-                    // So we group addresses by the containing (sub)function and keep the lowest address in each.
-                    let mut subfuncs_and_addrs: Vec<(/*function_idx*/ usize, /*subfunction_idx*/ usize, /*addr*/ usize, u16)> = addrs.iter().map(|line| {
-                        let addr = line.addr();
-                        if let Ok((function, function_idx)) = symbols.addr_to_function(addr) {
-                            if let Some((sf, l)) = symbols.containing_subfunction_at_level(addr, line.subfunction_level(), function) {
-                                return (function_idx, sf, addr, l);
+                        // There are often multiple nearby addresses for the same line, usually with different column numbers.
+                        // E.g. if the line contains a function call where some arguments are function calls too, there would usually be an address for each of the arguments and a location for the outer function call.
+                        // We want to set breakpoint on only the first of those addresses. Otherwise continuing from such breakpoint is very confusing: you continue and immediately stop without leaving the line.
+                        // On the other hand, a line can have multiple addresses corresponding to different inlining sites of the containing function; we don't want to deduplicate those.
+                        // So we group addresses by the containing (sub)function and keep the lowest address in each.
+                        let mut subfuncs_and_addrs: Vec<(/*function_idx*/ usize, /*subfunction_idx*/ usize, /*addr*/ usize, u16)> = addrs.iter().map(|line| {
+                            let addr = line.addr();
+                            if let Ok((function, function_idx)) = symbols.addr_to_function(addr) {
+                                if let Some((sf, l)) = symbols.containing_subfunction_at_level(addr, line.subfunction_level(), function) {
+                                    return (function_idx, sf, addr, l);
+                                }
                             }
-                        }
-                        (usize::MAX, addr, addr, line.subfunction_level())
-                    }).collect();
-                    subfuncs_and_addrs.sort_unstable();
-                    for (i, &(f, sf, addr, level)) in subfuncs_and_addrs.iter().enumerate() {
-                        if i > 0 {
-                            let &(prev_f, prev_sf, _, _) = &subfuncs_and_addrs[i-1];
-                            if (prev_f, prev_sf) == (f, sf) {
-                                continue;
+                            (usize::MAX, addr, addr, line.subfunction_level())
+                        }).collect();
+                        subfuncs_and_addrs.sort_unstable();
+                        for (i, &(f, sf, addr, level)) in subfuncs_and_addrs.iter().enumerate() {
+                            if i > 0 {
+                                let &(prev_f, prev_sf, _, _) = &subfuncs_and_addrs[i-1];
+                                if (prev_f, prev_sf) == (f, sf) {
+                                    continue;
+                                }
                             }
-                        }
 
-                        let addr = binary.addr_map.static_to_dynamic(addr);
-                        res.1.push((addr, level));
+                            let addr = binary.addr_map.static_to_dynamic(addr);
+                            res.1.push((addr, level));
+                        }
                     }
                 }
                 if res.1.is_empty() {
@@ -2299,7 +3554,8 @@ impl Debugger {
                     } else if found_file {
                         err!(NoCodeLocations, "no machine code at or below line {}", bp.line)
                     } else {
-                        err!(NoCodeLocations, "no machine code for file")
+                        // Keep retrying when new binaries are loaded, see activate_breakpoints().
+                        err!(Pending, "pending: file not found in loaded binaries")
                     };
                 } else {
                     if res.0 != bp.line {
@@ -2348,6 +3604,32 @@ impl Debugger {
                     err!(NoFunction, "no locations found for {}", point.name_for_ui())
                 };
             }
+            BreakpointOn::Function(bp) => {
+                let mut loading = false;
+                let mut addrs: Vec<(usize, u16)> = Vec::new();
+                let binary_ids: Vec<usize> = symbols_registry.iter().filter(|b| b.is_mapped).map(|b| b.id).collect();
+                for id in binary_ids {
+                    // (Searched in the background, we'll retry when the search completes, same as when symbols are loaded.)
+                    match symbols_registry.functions_matching_breakpoint(id, bp) {
+                        Ok(static_addrs) => {
+                            let binary = symbols_registry.get(id).unwrap();
+                            addrs.extend(static_addrs.iter().map(|&a| (binary.addr_map.static_to_dynamic(a), SUBFUNCTION_LEVEL_MAX)));
+                        }
+                        Err(e) if e.is_loading() => loading = true,
+                        Err(_) => (),
+                    }
+                }
+                // (The same function may come from both .symtab and debug info.)
+                addrs.sort_unstable();
+                addrs.dedup();
+                breakpoint.addrs = if !addrs.is_empty() {
+                    Ok(addrs)
+                } else if loading {
+                    err!(Loading, "symbols are not loaded yet")
+                } else {
+                    err!(NoFunction, "no functions match {}", bp.pattern)
+                };
+            }
             b => breakpoint.addrs = err!(Internal, "unexpected breakpoint kind: {:?}", b),
         }
     }
@@ -2399,37 +3681,149 @@ impl Debugger {
                 None => return err!(OutOfHardwareBreakpoints, "out of hw breakpoints"),
                 Some(i) => i };
 
-            // Currently only step breakpoints are thread-specific.
-            let all_threads = location.breakpoints.iter().any(|b| match b { BreakpointRef::Step(_) => false, BreakpointRef::Id{..} => true });
-            let thread_specific = match &self.stepping {
-                Some(s) if !all_threads => Some(s.tid),
-                _ => None,
-            };
-
+            let thread_specific = self.breakpoint_location_thread(idx);
             self.hardware_breakpoints[hw_idx] = HardwareBreakpoint {active: true, thread_specific, addr, ..Default::default()};
         } else {
-            let byte_idx = addr % 8;
-            let bit_idx = byte_idx * 8;
-            let word = self.memory.read_u64(addr - byte_idx)?;
-
-            let original_byte = ((word >> bit_idx) & 0xff) as u8;
+            let original_byte = self.memory.read_u8(addr)?;
             let orig = &mut self.breakpoint_locations[idx].original_byte;
             if *orig != original_byte {
                 eprintln!("warning: original byte for breakpoint at 0x{:x} changed from 0x{:x} to 0x{:x}", addr, orig, original_byte);
                 *orig = original_byte;
             }
 
-            let word = word & !(0xff << bit_idx) | (0xcc << bit_idx);
-            // PTRACE_POKETEXT is dumb. It requires the provided thread to be suspended, but doesn't care if other threads are running.
-            // Presumably this requirement was added just in case, back when threads didn't exist.
-            // So we need to semi-artificially propagate a TID of any suspended thread into here, and we can't add/remove breakpoints
-            // while all threads are running. I wish process_vm_writev() had a flag to allow writing to read-only memory (.text is usually mapped as read-only).
-            unsafe { ptrace(PTRACE_POKETEXT, any_suspended_tid, (addr - byte_idx) as u64, word)?; }
+            self.poke_code_byte(addr, 0xcc, any_suspended_tid)?;
         }
         self.breakpoint_locations[idx].active = true;
         Ok(())
     }
 
+    // Converts a code address in the main process to the corresponding address in forked process `p`, through the binary's static address.
+    // None if the binary is not mapped in `p`. Addresses outside binaries (e.g. JIT code) are assumed to be the same as right after the fork.
+    fn addr_in_forked_process(&self, addr: usize, p: &ForkedProcess) -> Option<usize> {
+        let Some(binary_id) = self.info.maps.addr_to_map(addr).and_then(|m| m.binary_id) else { return Some(addr) };
+        let static_addr = self.symbols.get(binary_id)?.addr_map.dynamic_to_static(addr);
+        Some(p.addr_maps.get(&binary_id)?.static_to_dynamic(static_addr))
+    }
+
+    // The reverse of addr_in_forked_process(): address in the process of thread `tid` -> address in the main process.
+    fn addr_in_main_process(&self, addr: usize, tid: pid_t) -> Option<usize> {
+        let Some(p) = self.forked_process_of_thread(tid).map(|i| &self.forked_processes[i]) else { return Some(addr) };
+        let Some(binary_id) = p.maps.addr_to_map(addr).and_then(|m| m.binary_id) else { return Some(addr) };
+        let static_addr = p.addr_maps.get(&binary_id)?.dynamic_to_static(addr);
+        let binary = self.symbols.get(binary_id)?;
+        if !binary.is_mapped {
+            return None;
+        }
+        Some(binary.addr_map.static_to_dynamic(static_addr))
+    }
+
+    // Index in forked_processes, or None if the thread belongs to the main process.
+    fn forked_process_of_thread(&self, tid: pid_t) -> Option<usize> {
+        let pid = self.threads.get(&tid)?.pid;
+        if pid == self.pid {
+            return None;
+        }
+        self.forked_processes.iter().position(|p| p.pid == pid)
+    }
+
+    // Makes `info.maps` and binaries' `addr_map`/`is_mapped` describe forked process `idx` instead of the main process (and vice versa), for code that only knows about
+    // the main process's address space, e.g. stack unwinding and symbolization. Call again to swap back.
+    fn swap_address_space_with_forked_process(&mut self, idx: usize) {
+        let p = &mut self.forked_processes[idx];
+        mem::swap(&mut self.info.maps, &mut p.maps);
+        let mut main_addr_maps: HashMap<usize, AddrMap> = HashMap::new();
+        for id in self.symbols.priority_order.clone() {
+            let binary = self.symbols.get_mut(id).unwrap();
+            if binary.is_mapped {
+                main_addr_maps.insert(id, mem::take(&mut binary.addr_map));
+            }
+            let addr_map = p.addr_maps.remove(&id);
+            binary.is_mapped = addr_map.is_some();
+            binary.addr_map = addr_map.unwrap_or_default();
+        }
+        p.addr_maps = main_addr_maps;
+    }
+
+    // Writes one byte of machine code (breakpoint instruction or the original byte) in all processes we debug: `pid` and forked_processes.
+    // `addr` is in the main process; in forked processes the byte goes to the corresponding address, or nowhere if the binary is not mapped there.
+    //
+    // PTRACE_POKETEXT is dumb. It requires the provided thread to be suspended, but doesn't care if other threads are running.
+    // Presumably this requirement was added just in case, back when threads didn't exist.
+    // So we need to semi-artificially propagate a TID of any suspended thread into here, and we can't add/remove breakpoints
+    // while all threads are running. I wish process_vm_writev() had a flag to allow writing to read-only memory (.text is usually mapped as read-only).
+    // With multiple processes, each of them needs a suspended thread. If some process has none (e.g. only one process stopped at a breakpoint),
+    // nothing is written and an error is returned, so that the location doesn't end up active in some processes and not others.
+    // handle_breakpoints() suspends all threads, so activations that failed this way are retried there.
+    fn poke_code_byte(&self, addr: usize, byte: u8, any_suspended_tid: pid_t) -> Result<()> {
+        let tid_pid = self.threads.get(&any_suspended_tid).map_or(self.pid, |t| t.pid);
+        let mut targets: Vec<(/*pid*/ pid_t, /*tid*/ pid_t, /*addr*/ usize)> = Vec::new();
+        for (pid, addr) in iter::once((self.pid, Some(addr))).chain(self.forked_processes.iter().map(|p| (p.pid, self.addr_in_forked_process(addr, p)))) {
+            let Some(addr) = addr else {continue};
+            let tid = if pid == tid_pid {
+                any_suspended_tid
+            } else {
+                match self.threads.values().find(|t| t.pid == pid && t.state == ThreadState::Suspended) {
+                    Some(t) => t.tid,
+                    None => return err!(ProcessState, "process {} is running, can't write breakpoint instruction", pid),
+                }
+            };
+            targets.push((pid, tid, addr));
+        }
+        let mut res = Ok(());
+        for (pid, tid, addr) in targets {
+            if let Err(e) = self.poke_code_byte_in_process(pid, tid, addr, byte) {
+                res = if pid == self.pid {Err(e)} else {err!(ProcessState, "failed to write breakpoint instruction in process {}: {}", pid, e)};
+            }
+        }
+        res
+    }
+
+    // `tid` must be a suspended thread of process `pid`.
+    fn poke_code_byte_in_process(&self, pid: pid_t, tid: pid_t, addr: usize, byte: u8) -> Result<()> {
+        let byte_idx = addr % 8;
+        let bit_idx = byte_idx * 8;
+        let memory = if pid == self.pid {self.memory.clone()} else {MemReader::Pid(PidMemReader::new(pid))};
+        let word = memory.read_u64(addr - byte_idx)?;
+        let word = word & !(0xff << bit_idx) | ((byte as u64) << bit_idx);
+        unsafe { ptrace(PTRACE_POKETEXT, tid, (addr - byte_idx) as u64, word)?; }
+        Ok(())
+    }
+
+    // Re-reads /proc/<pid>/maps of the forked processes (--follow-fork=both) and puts breakpoint instructions into binaries that got mapped
+    // (or moved) in them since the last refresh, e.g. a library that the child dlopen()ed after fork. A process is skipped if none of its threads are suspended
+    // (PTRACE_POKETEXT needs one); it'll be refreshed on a later stop.
+    fn refresh_forked_processes(&mut self) -> /*binaries_added*/ bool {
+        let mut binaries_added = false;
+        for idx in 0..self.forked_processes.len() {
+            let pid = self.forked_processes[idx].pid;
+            if let Some(tid) = self.threads.values().find(|t| t.pid == pid && t.state == ThreadState::Suspended).map(|t| t.tid) {
+                binaries_added |= self.refresh_forked_process(idx, tid);
+            }
+        }
+        binaries_added
+    }
+
+    // `tid` must be a suspended thread of forked_processes[idx].
+    fn refresh_forked_process(&mut self, idx: usize, tid: pid_t) -> /*binaries_added*/ bool {
+        let prev_diffs: HashMap<usize, usize> = self.forked_processes[idx].addr_maps.iter().map(|(id, m)| (*id, m.diff)).collect();
+        let binaries_added = refresh_forked_process_maps(self, idx);
+        let p = &self.forked_processes[idx];
+        let moved: Vec<usize> = p.addr_maps.iter().filter(|(id, m)| prev_diffs.get(id) != Some(&m.diff)).map(|(id, _)| *id).collect();
+        if moved.is_empty() {
+            return binaries_added;
+        }
+        for location in &self.breakpoint_locations {
+            if location.hardware || !location.active || !self.info.maps.addr_to_map(location.addr).and_then(|m| m.binary_id).is_some_and(|id| moved.contains(&id)) {
+                continue;
+            }
+            let Some(addr) = self.addr_in_forked_process(location.addr, p) else {continue};
+            if let Err(e) = self.poke_code_byte_in_process(p.pid, tid, addr, 0xcc) {
+                eprintln!("warning: failed to write breakpoint byte at 0x{:x} in process {}: {}", addr, p.pid, e);
+            }
+        }
+        binaries_added
+    }
+
     fn deactivate_breakpoint_location(&mut self, idx: usize, any_suspended_tid: pid_t) -> Result<()> {
         let location = &mut self.breakpoint_locations[idx];
         if !location.active { return Ok(()); }
@@ -2441,17 +3835,31 @@ impl Debugger {
             }
             // Don't actively update threads' debug registers. We'll update them if the stale breakpoint gets hit.
         } else {
-            let byte_idx = location.addr % 8;
-            let bit_idx = byte_idx * 8;
             // Other threads may be running, but it's fine.
-            let word = self.memory.read_u64(location.addr - byte_idx)?;
-            let word = word & !(0xff << bit_idx) | ((location.original_byte as u64) << bit_idx);
-            unsafe { ptrace(PTRACE_POKETEXT, any_suspended_tid, (location.addr - byte_idx) as u64, word)?; }
+            let (addr, original_byte) = (location.addr, location.original_byte);
+            self.poke_code_byte(addr, original_byte, any_suspended_tid)?;
         }
-        location.active = false;
+        self.breakpoint_locations[idx].active = false;
         Ok(())
     }
 
+    // If all breakpoints at this location apply only to the same one thread (steps, and breakpoints with `thread` filter), returns that thread.
+    fn breakpoint_location_thread(&self, idx: usize) -> Option<pid_t> {
+        let mut res: Option<pid_t> = None;
+        for b in &self.breakpoint_locations[idx].breakpoints {
+            let t = match b {
+                BreakpointRef::Step(_) => self.stepping.as_ref().map(|s| s.tid),
+                BreakpointRef::Id {id, ..} => self.breakpoints.get(*id).thread,
+            };
+            match (t, res) {
+                (None, _) => return None,
+                (Some(t), Some(r)) if t != r => return None,
+                _ => res = t,
+            }
+        }
+        res
+    }
+
     fn find_breakpoint_location(&self, addr: usize) -> Option<usize> {
         let idx = self.breakpoint_locations.partition_point(|b| b.addr < addr);
         if idx < self.breakpoint_locations.len() && self.breakpoint_locations[idx].addr == addr {
@@ -2476,6 +3884,12 @@ impl Debugger {
 
     // All threads must be suspended.
     fn handle_breakpoints(&mut self) -> Result<()> {
+        // Deactivate breakpoints that disabled themselves when hit (disable_after_hit).
+        let disabled: Vec<BreakpointId> = self.breakpoints.iter().filter(|(_, b)| b.active && !b.enabled).map(|(id, _)| id).collect();
+        for id in disabled {
+            self.deactivate_breakpoint(id);
+        }
+
         // Deactivate lingering locations from removed breakpoints.
         let mut res_idx = 0usize;
         for idx in 0..self.breakpoint_locations.len() {
@@ -2491,13 +3905,15 @@ impl Debugger {
         // Convert between hardware and software breakpoints as needed:
         // if any thread is standing at a breakpoint, make it hardware, otherwise make it software.
 
-        let mut thread_addresses: HashMap<usize, Vec<pid_t>> = HashMap::new();
+        // Address in the main process -> threads standing there, with the address in the thread's process (different if it's one of forked_processes with a different address map).
+        let mut thread_addresses: HashMap<usize, Vec<(pid_t, usize)>> = HashMap::new();
         for (tid, t) in self.threads.iter() {
             if t.info.regs.has(RegisterIdx::Rip) {
-                let addr = t.info.regs.get(RegisterIdx::Rip).unwrap().0 as usize;
+                let thread_addr = t.info.regs.get(RegisterIdx::Rip).unwrap().0 as usize;
+                let Some(addr) = self.addr_in_main_process(thread_addr, *tid) else {continue};
                 let tids = thread_addresses.entry(addr).or_default();
                 if !t.is_after_user_debug_trap_instruction {
-                    tids.push(*tid);
+                    tids.push((*tid, thread_addr));
                 } else {
                     // Don't set ignore_next_hw_breakpoint_hit_at_addr in this case.
                     // When single-instruction-stepping from is_after_user_debug_trap_instruction state,
@@ -2507,11 +3923,17 @@ impl Debugger {
         }
 
         for idx in 0..self.breakpoint_locations.len() {
+            let thread_specific = self.breakpoint_location_thread(idx);
+            let user_only = self.breakpoint_locations[idx].breakpoints.iter().all(|b| matches!(b, BreakpointRef::Id {..}));
             let loc = &self.breakpoint_locations[idx];
             let addr = loc.addr;
             let tids = thread_addresses.get(&loc.addr);
             if loc.hardware {
-                if tids.is_none() {
+                if loc.active && self.hardware_breakpoints.iter().any(|h| h.active && h.data_breakpoint_id.is_none() && h.addr == addr && h.thread_specific != thread_specific) {
+                    // Breakpoint's thread changed, reassign the hw breakpoint (below).
+                    self.deactivate_breakpoint_location(idx, self.pid)?;
+                }
+                if tids.is_none() && !(user_only && thread_specific.is_some()) {
                     self.deactivate_breakpoint_location(idx, self.pid)?;
                     self.breakpoint_locations[idx].hardware = false;
                 }
@@ -2520,7 +3942,7 @@ impl Debugger {
                     self.deactivate_breakpoint_location(idx, self.pid)?;
                     self.breakpoint_locations[idx].hardware = true;
 
-                    for tid in tids {
+                    for (tid, thread_addr) in tids {
                         // The thread is stopped at a sw breakpoint address. After we convert it to hw breakpoint,
                         // the thread may immediately hit it. Ignore such hit. Possible situations:
                         //  * We've already handled this sw breakpoint hit. Don't want to handle it again.
@@ -2532,12 +3954,32 @@ impl Debugger {
                         //    one of those threads happened to be interrupted at the same breakpoint address (just *before* executing the int3 instruction, so no SIGTRAP);
                         //    that thread then incorrectly ignores the breakpoint hit.
                         //    Maybe we should check for breakpoint hits when thread stops for any reason. Or maybe we should assign ignore_next_hw_breakpoint_hit_at_addr only on SIGTRAP and when adding breakpoint. TODO: Probably the latter.
-                        self.threads.get_mut(tid).unwrap().ignore_next_hw_breakpoint_hit_at_addr = Some(addr);
+                        self.threads.get_mut(tid).unwrap().ignore_next_hw_breakpoint_hit_at_addr = Some(*thread_addr);
                     }
                 }
             }
         }
 
+        // Use hardware breakpoints for thread-specific user breakpoints if there are free slots, so that other threads don't get spurious stops.
+        // If we're short on slots, give them back to breakpoints that need to be hardware because a thread is standing on them.
+        let free_slots = self.hardware_breakpoints.iter().filter(|h| !h.active).count() as isize;
+        let mut spare_slots = free_slots - self.breakpoint_locations.iter().filter(|l| l.hardware && !l.active).count() as isize;
+        for idx in 0..self.breakpoint_locations.len() {
+            let loc = &self.breakpoint_locations[idx];
+            if thread_addresses.contains_key(&loc.addr) || !loc.breakpoints.iter().all(|b| matches!(b, BreakpointRef::Id {..})) || self.breakpoint_location_thread(idx).is_none() {
+                continue;
+            }
+            if loc.hardware && spare_slots < 0 {
+                self.deactivate_breakpoint_location(idx, self.pid)?;
+                self.breakpoint_locations[idx].hardware = false;
+                spare_slots += 1;
+            } else if !loc.hardware && spare_slots > 0 {
+                self.deactivate_breakpoint_location(idx, self.pid)?;
+                self.breakpoint_locations[idx].hardware = true;
+                spare_slots -= 1;
+            }
+        }
+
         for idx in 0..self.breakpoint_locations.len() {
             if self.breakpoint_locations[idx].no_retry {
                 continue;
@@ -2566,6 +4008,11 @@ impl Debugger {
             }
         }
 
+        // Protect or unprotect pages for data breakpoints that didn't fit into debug registers. (Only in the main process, see add_forked_process().)
+        if let Some(tid) = self.threads.iter().find(|(_, t)| t.pid == self.pid && t.state == ThreadState::Suspended && !t.exiting && !t.in_syscall_stop).map(|(tid, _)| *tid) {
+            self.update_protected_pages(tid)?;
+        }
+
         Ok(())
     }
 
@@ -2605,8 +4052,16 @@ impl Debugger {
         unsafe {ptrace(PTRACE_POKEUSER, tid, (mem::offset_of!(libc::user, u_debugreg) + 7*8) as u64, dr7)? };
         let mut assigned_dr7 = dr7; // last successfully written dr7 value
 
+        let forked_process = self.forked_process_of_thread(tid).map(|idx| &self.forked_processes[idx]);
         for i in 0..4 {
             if let Some((addr, d7)) = Self::get_debug_register_values_for_hardware_breakpoint(&self.hardware_breakpoints[i], i, tid) {
+                let addr = match forked_process {
+                    None => addr,
+                    Some(p) => match self.addr_in_forked_process(addr as usize, p) {
+                        Some(a) => a as u64,
+                        None => continue,
+                    }
+                };
                 unsafe { ptrace(PTRACE_POKEUSER, tid, (mem::offset_of!(libc::user, u_debugreg) + i * 8) as u64, addr)? };
                 dr7 |= d7;
             }
@@ -2643,6 +4098,10 @@ impl Debugger {
             let mut new_dr7 = dr7_common_bits;
             for i in 0..4 {
                 if let Some((_, d7)) = Self::get_debug_register_values_for_hardware_breakpoint(&self.hardware_breakpoints[i], i, tid) {
+                    if dr7 & (1 << (i*2)) == 0 {
+                        // Skipped above because the address is not mapped in this thread's process.
+                        continue;
+                    }
                     let single_dr7 = d7 | dr7_common_bits;
                     if single_dr7 == dr7 {
                         // There's only one enabled breakpoint, we already tried to activate it above and got EINVAL, no need to try again.
@@ -2680,6 +4139,245 @@ impl Debugger {
         }
     }
 
+    // Whether the active data breakpoint uses page protection instead of debug registers. For UI.
+    pub fn is_data_breakpoint_page_trapped(&self, id: BreakpointId) -> bool {
+        let b = self.breakpoints.get(id);
+        b.active && b.on.is_data() && !self.hardware_breakpoints.iter().any(|h| h.active && h.data_breakpoint_id == Some(id))
+    }
+
+    // Data breakpoints that didn't fit into debug registers and use page protection instead.
+    fn page_trapped_data_breakpoints(&self) -> Vec<(BreakpointId, DataBreakpoint)> {
+        self.breakpoints.iter().filter_map(|(id, b)| match &b.on {
+            BreakpointOn::Data(d) if b.enabled && self.is_data_breakpoint_page_trapped(id) => Some((id, d.clone())),
+            _ => None,
+        }).collect()
+    }
+
+    // PROT_* flags of the mapping that contains the page, according to the last read of /proc/<pid>/maps.
+    fn page_protection_from_maps(maps: &MemMapsInfo, page: usize) -> Option<i32> {
+        let map = maps.maps.get(maps.maps.partition_point(|m| m.start + m.len <= page))?;
+        if map.start > page {
+            return None;
+        }
+        let mut prot = libc::PROT_NONE;
+        for (perm, p) in [(MemMapPermissions::READ, libc::PROT_READ), (MemMapPermissions::WRITE, libc::PROT_WRITE), (MemMapPermissions::EXECUTE, libc::PROT_EXEC)] {
+            if map.perms.contains(perm) {
+                prot |= p;
+            }
+        }
+        Some(prot)
+    }
+
+    fn check_pages_for_data_breakpoint(maps: &MemMapsInfo, d: &DataBreakpoint) -> Result<()> {
+        for page in d.pages().step_by(sysconf_PAGE_SIZE()) {
+            if Self::page_protection_from_maps(maps, page).is_none() {
+                return err!(ProcessState, "address 0x{:x} is not mapped", page.max(d.addr));
+            }
+        }
+        Ok(())
+    }
+
+    // Calls mprotect() on one page, from the suspended thread `tid`.
+    fn inject_mprotect(&mut self, tid: pid_t, page: usize, prot: i32) -> Result<()> {
+        let code_addr = self.symbols.main_binary_entry_point()?;
+        let mut events = FunctionCallEvents::default();
+        let r = ptrace_inject_syscall(tid, code_addr, (libc::SYS_mprotect, /*i386 __NR_mprotect*/ 125), &[page as u64, sysconf_PAGE_SIZE() as u64, prot as u64], &mut events);
+        self.pending_wait_events.extend(events.wait_events);
+        let r = r?;
+        if r < 0 {
+            return err!(Environment, "mprotect(0x{:x}) failed: {}", page, errno_name(-r as i32));
+        }
+        Ok(())
+    }
+
+    // Makes page protections match page_trapped_data_breakpoints(): protects newly covered pages, unprotects pages that are not needed anymore,
+    // re-protects pages that were temporarily unprotected by handle_page_trap(). `tid` is a suspended thread to do the mprotect() calls from.
+    fn update_protected_pages(&mut self, tid: pid_t) -> Result<()> {
+        let mut desired: Vec<(/*page*/ usize, /*stop_on_read*/ bool)> = Vec::new();
+        for (_, d) in self.page_trapped_data_breakpoints() {
+            for page in d.pages().step_by(sysconf_PAGE_SIZE()) {
+                desired.push((page, d.stop_on_read));
+            }
+        }
+        desired.sort_unstable();
+        let mut new_pages: Vec<ProtectedPage> = Vec::new();
+        for (page, stop_on_read) in desired {
+            if let Some(p) = new_pages.last_mut() {
+                if p.addr == page {
+                    if stop_on_read {
+                        p.prot = libc::PROT_NONE;
+                    }
+                    continue;
+                }
+            }
+            let (original_prot, current_prot) = match self.protected_pages.binary_search_by_key(&page, |p| p.addr) {
+                Ok(i) => (self.protected_pages[i].original_prot, self.protected_pages[i].current_prot),
+                Err(_) => match Self::page_protection_from_maps(&self.info.maps, page) {
+                    None => continue, // unmapped
+                    Some(p) => (p, p),
+                }
+            };
+            let prot = if stop_on_read {libc::PROT_NONE} else {original_prot & !libc::PROT_WRITE};
+            new_pages.push(ProtectedPage {addr: page, original_prot, prot, current_prot});
+        }
+
+        for p in mem::replace(&mut self.protected_pages, new_pages) {
+            if self.protected_pages.binary_search_by_key(&p.addr, |x| x.addr).is_ok() {
+                continue;
+            }
+            if p.current_prot != p.original_prot {
+                if let Err(e) = self.inject_mprotect(tid, p.addr, p.original_prot) {
+                    log!(self.log, "failed to unprotect page 0x{:x}: {}", p.addr, e);
+                }
+            }
+            self.formerly_protected_pages.push(p.addr);
+        }
+        let mut failed: Vec<(/*page*/ usize, Error)> = Vec::new();
+        for i in 0..self.protected_pages.len() {
+            let p = &self.protected_pages[i];
+            if p.current_prot != p.prot {
+                let (addr, prot) = (p.addr, p.prot);
+                match self.inject_mprotect(tid, addr, prot) {
+                    Ok(()) => self.protected_pages[i].current_prot = prot,
+                    Err(e) => failed.push((addr, e)),
+                }
+            }
+        }
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        // There's no fallback (e.g. single-stepping all threads and comparing values) if we can't mprotect() from inside the process, e.g. on aarch64 or if a seccomp filter forbids it.
+        // Disable the affected data breakpoints, with the error shown in the breakpoints window, rather than leave them silently not working.
+        for (id, d) in self.page_trapped_data_breakpoints() {
+            if let Some((page, e)) = failed.iter().find(|(page, _)| d.pages().contains(page)) {
+                log!(self.log, "failed to protect page 0x{:x} for data breakpoint: {}", page, e);
+                self.deactivate_breakpoint(id);
+                let b = self.breakpoints.get_mut(id);
+                b.enabled = false;
+                b.addrs = err!(Environment, "can't protect page 0x{:x}: {}", page, e);
+            }
+        }
+        // Unprotect the pages that are not needed anymore. (Doesn't recurse again: the disabled breakpoints' pages aren't protected.)
+        self.update_protected_pages(tid)
+    }
+
+    // Memory ranges that the instruction at RIP accesses, and whether each access is a write.
+    // None if the instruction couldn't be decoded or its accesses couldn't be figured out (e.g. vector gather).
+    fn decode_memory_accesses(&self, regs: &Registers) -> Option<Vec<(Range<usize>, /*write*/ bool)>> {
+        let rip = regs.get(RegisterIdx::Rip).ok()?.0 as usize;
+        let page_size = sysconf_PAGE_SIZE();
+        // Instructions are at most 15 bytes long, but the instruction may be at the end of the mapping.
+        let end = if self.memory.read_u8(rip + 14).is_ok() {rip + 15} else {(rip / page_size + 1) * page_size};
+        let mut buf: Vec<u8> = Vec::new();
+        let mut decoder = self.make_instruction_decoder(regs.arch, rip..end, &mut buf).ok()?;
+        let instruction = decoder.decode();
+        if instruction.is_invalid() {
+            return None;
+        }
+        let mut factory = iced_x86::InstructionInfoFactory::new();
+        let mut res: Vec<(Range<usize>, bool)> = Vec::new();
+        for m in factory.info(&instruction).used_memory() {
+            let write = match m.access() {
+                iced_x86::OpAccess::Read | iced_x86::OpAccess::CondRead => false,
+                iced_x86::OpAccess::Write | iced_x86::OpAccess::CondWrite | iced_x86::OpAccess::ReadWrite | iced_x86::OpAccess::ReadCondWrite => true,
+                _ => continue, // e.g. lea
+            };
+            let size = m.memory_size().size();
+            if size == 0 {
+                return None;
+            }
+            let addr = m.virtual_address(0, |reg, _, _| {
+                use iced_x86::Register as R;
+                let idx = match reg.full_register() {
+                    R::RAX => RegisterIdx::Rax, R::RCX => RegisterIdx::Rcx, R::RDX => RegisterIdx::Rdx, R::RBX => RegisterIdx::Rbx,
+                    R::RSP => RegisterIdx::Rsp, R::RBP => RegisterIdx::Rbp, R::RSI => RegisterIdx::Rsi, R::RDI => RegisterIdx::Rdi,
+                    R::R8 => RegisterIdx::R8, R::R9 => RegisterIdx::R9, R::R10 => RegisterIdx::R10, R::R11 => RegisterIdx::R11,
+                    R::R12 => RegisterIdx::R12, R::R13 => RegisterIdx::R13, R::R14 => RegisterIdx::R14, R::R15 => RegisterIdx::R15,
+                    // For segment registers, the callback returns the segment base.
+                    R::FS => return regs.get(RegisterIdx::FsBase).ok().map(|x| x.0),
+                    R::GS => return regs.get(RegisterIdx::GsBase).ok().map(|x| x.0),
+                    R::ES | R::CS | R::SS | R::DS => return Some(0),
+                    _ => return None, // vector registers in vsib addressing
+                };
+                let x = regs.get(idx).ok()?.0;
+                Some(match reg.size() { 4 => x as u32 as u64, 2 => x as u16 as u64, _ => x })
+            })? as usize;
+            res.push((addr..addr + size, write));
+        }
+        Some(res)
+    }
+
+    // Handles a SIGSEGV caused by the page protection that we set up for data breakpoints (see update_protected_pages()).
+    // Returns false if the fault is not ours and should be treated as a normal signal.
+    //
+    // We look at the faulting instruction to see which data breakpoints it touches, unprotect the pages, and single-step the thread over the instruction.
+    // When the single-step completes, handle_breakpoint_trap() protects the pages again and reports the hits.
+    // While the pages are unprotected, accesses from other threads go unnoticed. That's one of the reasons to prefer debug registers when the range is small.
+    fn handle_page_trap(&mut self, tid: pid_t) -> Result<bool> {
+        if self.protected_pages.is_empty() && self.formerly_protected_pages.is_empty() {
+            return Ok(false);
+        }
+        let mut si: libc::siginfo_t = unsafe {mem::zeroed()};
+        unsafe {ptrace(PTRACE_GETSIGINFO, tid, 0, &mut si as *mut _ as u64)?};
+        if si.si_code != SEGV_ACCERR {
+            return Ok(false);
+        }
+        let fault_addr = unsafe {si.si_addr()} as usize;
+        let page = fault_addr / sysconf_PAGE_SIZE() * sysconf_PAGE_SIZE();
+        let regs = ptrace_getregs(tid)?;
+        let rip = regs.get(RegisterIdx::Rip)?.0 as usize;
+
+        if self.protected_pages.binary_search_by_key(&page, |p| p.addr).is_err() {
+            if !self.formerly_protected_pages.contains(&page) {
+                return Ok(false);
+            }
+            // The fault may have happened while the page was still protected, but got reported after we unprotected it (e.g. the thread was stopped
+            // by PTRACE_INTERRUPT just before reporting the signal). Retry the instruction once; if it faults again, it's the program's own fault.
+            let thread = self.threads.get_mut(&tid).unwrap();
+            if thread.suppressed_page_fault == Some((rip, fault_addr)) {
+                thread.suppressed_page_fault = None;
+                return Ok(false);
+            }
+            thread.suppressed_page_fault = Some((rip, fault_addr));
+            return Ok(true);
+        }
+
+        let accesses = self.decode_memory_accesses(&regs);
+        let mut hits: Vec<BreakpointId> = Vec::new();
+        for (id, d) in self.page_trapped_data_breakpoints() {
+            let range = d.addr..d.addr + d.size;
+            let hit = match &accesses {
+                Some(v) => v.iter().any(|(r, write)| r.start < range.end && range.start < r.end && (*write || d.stop_on_read)),
+                // Couldn't decode the instruction, go by the fault address.
+                None => range.contains(&fault_addr),
+            };
+            if hit {
+                hits.push(id);
+            }
+        }
+        if self.context.settings.trace_logging { eprintln!("trace: thread {} page trap at 0x{:x} accessing 0x{:x}, accesses: {:?}, hits: {:?}", tid, rip, fault_addr, accesses, hits); }
+
+        // Unprotect all pages, since the instruction may access more than one (e.g. movs).
+        for i in 0..self.protected_pages.len() {
+            let p = &self.protected_pages[i];
+            if p.current_prot != p.original_prot {
+                let (addr, prot) = (p.addr, p.original_prot);
+                self.inject_mprotect(tid, addr, prot)?;
+                self.protected_pages[i].current_prot = prot;
+            }
+        }
+        let thread = self.threads.get_mut(&tid).unwrap();
+        thread.page_trap_hits = Some(hits);
+        thread.single_stepping = true;
+        Ok(true)
+    }
+
+    // Whether a breakpoint hit in thread `tid` should be ignored because of the step in progress.
+    fn step_ignores_breakpoints(&self, tid: pid_t) -> bool {
+        self.stepping.as_ref().is_some_and(|s| s.disable_breakpoints && (!s.others_running || s.tid == tid))
+    }
+
     fn handle_step_breakpoint_hit(step: &StepState, type_: StepBreakpointType, request_single_step: &mut bool) {
         match type_ {
             StepBreakpointType::Call | StepBreakpointType::JumpOut => *request_single_step = true,
@@ -2702,8 +4400,9 @@ impl Debugger {
             }
             return !in_ranges;
         }
+        let memory = self.process_memory(step.tid);
         let step = self.stepping.as_mut().unwrap();
-        let cfa = Self::get_cfa_for_step(&self.info, &self.symbols, &mut self.log, &self.memory, addr, regs);
+        let cfa = Self::get_cfa_for_step(&self.info, &self.symbols, &mut self.log, &memory, addr, regs);
         let cfa = match cfa {
             None => return step.internal_kind == StepKind::Into,
             Some(c) => c };
@@ -2715,6 +4414,7 @@ impl Debugger {
         };
         if cfa_done {
             // Stepped in or out.
+            step.returned = cfa > step.cfa;
             return true;
         }
         if !ranges_done {
@@ -2783,7 +4483,7 @@ impl Debugger {
         let mut breakpoints_to_add: Vec<(StepBreakpointType, usize)> = Vec::new();
         let mut may_do_syscalls = false;
         // (It may be possible for the new range to intersect some existing ranges. This shouldn't break anything.)
-        match self.determine_some_step_breakpoint_locations(&breakpoint_types, new_range.clone(), &addr_ranges, skip_first_instruction, &mut breakpoints_to_add, &mut may_do_syscalls, &mut Vec::new()) {
+        match self.determine_some_step_breakpoint_locations(regs.arch, &breakpoint_types, new_range.clone(), &addr_ranges, skip_first_instruction, &mut breakpoints_to_add, &mut may_do_syscalls, &mut Vec::new()) {
             Err(_) => return true,
             Ok(()) => (),
         }
@@ -2884,10 +4584,28 @@ impl Debugger {
     }
 
     fn process_breakpoint_hit(&mut self, id: BreakpointId, tid: pid_t, ignore_breakpoints: bool, subfunction_level: u16, stop_reasons: &mut Vec<StopReason>) -> bool {
+        let memory = self.process_memory(tid);
         let bp = self.breakpoints.get_mut(id);
+        let mut change: Option<(Vec<u8>, Vec<u8>)> = None;
+        if let BreakpointOn::Data(d) = &mut bp.on {
+            if d.stop_on_change {
+                // Data breakpoints trigger after the access, so the memory already has the new value.
+                let mut new = vec![0u8; d.size];
+                if memory.read(d.addr, &mut new).is_ok() {
+                    if d.value.as_ref() == Some(&new) {
+                        return false;
+                    }
+                    d.old_value = d.value.replace(new.clone());
+                    change = d.old_value.clone().map(|old| (old, new));
+                } else {
+                    d.old_value = None;
+                    d.value = None;
+                }
+            }
+        }
         bp.hits += 1;
 
-        if ignore_breakpoints {
+        if ignore_breakpoints || bp.thread.is_some_and(|t| t != tid) {
             return false;
         }
         if let Some((_, Ok(_), _)) = &bp.condition {
@@ -2905,7 +4623,32 @@ impl Debugger {
             }
         }
 
-        stop_reasons.push(StopReason::Breakpoint(id));
+        let bp = self.breakpoints.get_mut(id);
+        if bp.ignore_count > 0 {
+            bp.ignore_count -= 1;
+            return false;
+        }
+        if bp.disable_after_hit {
+            bp.enabled = false;
+            if bp.on.is_signal() || bp.on.is_syscall() {
+                self.deactivate_breakpoint(id);
+            } else {
+                // Can't deactivate right here because the caller may be iterating over the breakpoint location. handle_breakpoints() will do it.
+                self.stopping_to_handle_breakpoints = true;
+            }
+        }
+        if let Some((_, Ok(_))) = &self.breakpoints.get(id).log {
+            let line = match self.format_breakpoint_log(tid, id, subfunction_level) {
+                Ok(s) => s,
+                Err(e) => format!("<{}>", e),
+            };
+            log!(self.log, "thread {}: {}", tid, line);
+            return false;
+        }
+
+        stop_reasons.push(match change {
+            Some((old, new)) => StopReason::DataChange(id, old, new),
+            None => StopReason::Breakpoint(id) });
         true
     }
     
@@ -2985,12 +4728,26 @@ impl Debugger {
         let mut stack_digest_to_select: Option<(Vec<usize>, bool, u16)> = None;
         let mut hit_step_breakpoint: Option<StepBreakpointType> = None;
 
-        let breakpoint_location_idx = self.find_breakpoint_location(addr);
+        // Stepped over an access to a page protected for data breakpoints (see handle_page_trap()). Protect the pages again, report the hits below.
+        let mut page_trap_hits: Vec<BreakpointId> = Vec::new();
+        if self.threads.get(&tid).unwrap().page_trap_hits.is_some() {
+            if single_stepped {
+                page_trap_hits = self.threads.get_mut(&tid).unwrap().page_trap_hits.take().unwrap();
+                if self.threads.values().all(|t| t.page_trap_hits.is_none()) {
+                    self.update_protected_pages(tid)?;
+                }
+            } else {
+                request_single_step = true;
+            }
+        }
+
+        // (Breakpoint locations are addresses in the main process, see ForkedProcess.)
+        let breakpoint_location_idx = self.addr_in_main_process(addr, tid).and_then(|a| self.find_breakpoint_location(a));
 
         // Check if there's int3 instruction in the actual program, i.e. not injected by the debugger.
         let original_instruction_byte = match &breakpoint_location_idx {
             &Some(idx) => self.breakpoint_locations[idx].original_byte,
-            None => match self.memory.read_u8(addr) {
+            None => match self.process_memory(tid).read_u8(addr) {
                 Ok(x) => x,
                 Err(e) => {
                     eprintln!("warning: failed to read instruction byte at 0x{:x}: {}", addr, e);
@@ -3011,7 +4768,7 @@ impl Debugger {
         let ignore_breakpoints =
             // Ignore regular breakpoints when stepping.
             // (It would be better for performance to also deactivate them as we go, then reactivate after the step completes.)
-            self.stepping.as_ref().is_some_and(|s| s.disable_breakpoints) ||
+            self.step_ignores_breakpoints(tid) ||
             self.pending_step.is_some() ||
             // Solves this race condition:
             //  1. A step completes. It's added to thread's stop_reasons. Other threads are requested to suspend.
@@ -3040,7 +4797,10 @@ impl Debugger {
 
             if location.breakpoints.is_empty() {
                 // Lazily deactivate obsolete breakpoint location if we hit it. This is just for performance, to avoid repeatedly hitting+ignoring a deleted hot breakpoint.
-                self.deactivate_breakpoint_location(idx, tid)?;
+                // (If another process is running, leave it to handle_breakpoints().)
+                if let Err(e) = self.deactivate_breakpoint_location(idx, tid) {
+                    if self.context.settings.trace_logging { eprintln!("trace: couldn't deactivate obsolete breakpoint location 0x{:x}: {}", addr, e); }
+                }
             } else {
                 is_active_breakpoint_location = true;
             }
@@ -3071,7 +4831,13 @@ impl Debugger {
                             let bp = self.breakpoints.get(id);
                             if bp.hidden {
                                 match &bp.on {
-                                    BreakpointOn::PointOfInterest(PointOfInterest::LibraryLoad) => refresh_process_info = true,
+                                    BreakpointOn::PointOfInterest(PointOfInterest::LibraryLoad) => {
+                                        refresh_process_info = true;
+                                        // If some breakpoints may resolve in the new library, keep the threads stopped until we activate them (see process_events()).
+                                        if self.breakpoints.iter().any(|(_, b)| b.enabled && (b.on.is_function() || b.addrs.as_ref().is_err_and(|e| e.is_pending()))) {
+                                            self.stopping_to_handle_breakpoints = true;
+                                        }
+                                    }
                                     // (Hardcoded behavior for this breakpoint, for now. We should make it a normal user-visible breakpoint instead, allowing attaching condition to it etc.)
                                     BreakpointOn::PointOfInterest(PointOfInterest::Panic) => hit = true,
                                     _ => panic!("unexpected hidden breakpoint"),
@@ -3096,18 +4862,25 @@ impl Debugger {
         }
 
         // Data breakpoints.
+        let mut data_breakpoint_hits = page_trap_hits;
         if stopped_on_hw_breakpoint {
             for i in 0..4 {
                 if (dr6 & (1 << i)) != 0 {
                     let bp = &self.hardware_breakpoints[i];
                     if bp.active {
                         if let &Some(id) = &bp.data_breakpoint_id {
-                            hit |= self.process_breakpoint_hit(id, tid, ignore_breakpoints, /*subfunction_level*/ SUBFUNCTION_LEVEL_MAX, &mut stop_reasons);
+                            data_breakpoint_hits.push(id);
                         }
                     }
                 }
             }
         }
+        // (A data breakpoint may span multiple debug registers, and an access may hit more than one of them.)
+        data_breakpoint_hits.sort_unstable();
+        data_breakpoint_hits.dedup();
+        for id in data_breakpoint_hits {
+            hit |= self.process_breakpoint_hit(id, tid, ignore_breakpoints, /*subfunction_level*/ SUBFUNCTION_LEVEL_MAX, &mut stop_reasons);
+        }
 
         if (is_debug_trap || is_user_signal) && !is_active_breakpoint_location && !ignore_breakpoints {
             // Hit something like __builtin_debugtrap or raise(SIGTRAP), and there's no debugger breakpoint at the same location.
@@ -3119,19 +4892,40 @@ impl Debugger {
 
         if let Some(step) = &self.stepping {
             if hit {
-                self.cancel_stepping();
+                // In non-stop mode, another thread hitting a breakpoint doesn't interrupt the step.
+                if !self.context.settings.non_stop || tid == step.tid {
+                    self.cancel_stepping();
+                }
             } else if tid == step.tid && self.handle_step_stop(hit_step_breakpoint.is_some(), single_stepped, &regs) {
                 let step = self.stepping.as_mut().unwrap();
                 if step.internal_kind != StepKind::Cursor {
                     stack_digest_to_select = Some((mem::take(&mut step.stack_digest), step.internal_kind == StepKind::Into, SUBFUNCTION_LEVEL_MAX));
                 }
                 let stop_reason = if hit_step_breakpoint.is_some_and(|t| t == StepBreakpointType::Catch) {StopReason::Exception} else {StopReason::Step};
+                // (Return values are in different registers on i386, and we don't support that.)
+                if let (true, StopReason::Step, Some((binary_id, function_idx)), false) = (step.returned, &stop_reason, step.function, regs.arch == Arch::I386) {
+                    // Just returned from the function, so the return value is still in registers.
+                    match ptrace_get_xstate(tid) {
+                        Ok(xstate) => {
+                            let regs = ReturnRegisters {rax: regs.get(RegisterIdx::Rax)?.0, rdx: regs.get(RegisterIdx::Rdx)?.0, xmm0: xsave_get_xmm(&xstate, 0), xmm1: xsave_get_xmm(&xstate, 1)};
+                            self.threads.get_mut(&tid).unwrap().function_return = Some(FunctionReturn {binary_id, function_idx, regs});
+                        }
+                        Err(e) => eprintln!("warning: failed to get xstate after step-out: {}", e),
+                    }
+                }
                 stop_reasons.push(stop_reason);
                 self.cancel_stepping();
                 hit = true;
             }
         }
 
+        if refresh_process_info {
+            if let Some(idx) = self.forked_process_of_thread(tid) {
+                // A forked process loaded or unloaded a library. Put breakpoints into it while this thread is stopped, it may be resumed right away.
+                self.refresh_forked_process(idx, tid);
+            }
+        }
+
         if request_single_step {
             self.threads.get_mut(&tid).unwrap().single_stepping = true;
         }
@@ -3156,7 +4950,34 @@ impl Debugger {
             return Ok(false);
         }
         let need_full_stack = does_expression_need_full_stack(expr);
+        self.eval_at_breakpoint(tid, id, subfunction_level, need_full_stack, |bp, eval_state, eval_context| {
+            let expr = bp.condition.as_ref().unwrap().1.as_ref().unwrap();
+            eval_parsed_expression(expr, eval_state, eval_context).and_then(|(val, _dubious)| is_value_truthy(&val, &mut eval_context.memory))
+        })
+    }
+
+    fn format_breakpoint_log(&mut self, tid: pid_t, id: BreakpointId, subfunction_level: u16) -> Result<String> {
+        let parts = self.breakpoints.get(id).log.as_ref().unwrap().1.as_ref().unwrap();
+        let need_full_stack = parts.iter().any(|p| match p {
+            LogFormatPart::Expression(e) => does_expression_need_full_stack(e),
+            LogFormatPart::Text(_) => false });
+        self.eval_at_breakpoint(tid, id, subfunction_level, need_full_stack, |bp, eval_state, eval_context| {
+            let mut out = String::new();
+            for part in bp.log.as_ref().unwrap().1.as_ref().unwrap() {
+                match part {
+                    LogFormatPart::Text(s) => out.push_str(s),
+                    LogFormatPart::Expression(e) => match eval_parsed_expression(e, eval_state, eval_context) {
+                        Ok((val, _dubious)) => out.push_str(&format_value_plain(&val, eval_state, eval_context).0),
+                        Err(e) => out.push_str(&format!("<{}>", e)),
+                    }
+                }
+            }
+            Ok(out)
+        })
+    }
 
+    // Sets up expression evaluation in a thread stopped at a breakpoint, for its condition or log format.
+    fn eval_at_breakpoint<T>(&mut self, tid: pid_t, id: BreakpointId, subfunction_level: u16, need_full_stack: bool, f: impl FnOnce(&Breakpoint, &mut EvalState, &mut EvalContext) -> Result<T>) -> Result<T> {
         let t = self.threads.get_mut(&tid).unwrap();
         t.info.regs = ptrace_getregs(tid)?;
         t.info.extra_regs.reset_with_tid(tid);
@@ -3167,12 +4988,275 @@ impl Debugger {
         } else {
             0
         };
-        let bp = self.breakpoints.get(id);
-        let expr = bp.condition.as_ref().unwrap().1.as_ref().unwrap();
         let mut eval_state = EvalState::new();
+        eval_state.definitions = self.script_definitions.clone();
         let mut eval_context = self.make_eval_context(&stack, selected_subframe, tid);
-        let (val, _dubious) = eval_parsed_expression(expr, &mut eval_state, &mut eval_context)?;
-        Ok(is_value_truthy(&val, &mut eval_context.memory)?)
+        if let BreakpointOn::Data(d) = &self.breakpoints.get(id).on {
+            if let (Some(old), Some(new)) = (&d.old_value, &d.value) {
+                // The watched value's type, if it's still there and has the same size (e.g. the binary was rebuilt). Otherwise go by size.
+                let type_ = d.type_name.as_ref().and_then(|s| eval_type_name(s, &mut eval_state, &mut eval_context).ok()).filter(|&t| unsafe {(*t).calculate_size()} == new.len());
+                let type_ = type_.unwrap_or_else(|| match new.len() {
+                    1 => eval_state.builtin_types.i8_,
+                    2 => eval_state.builtin_types.i16_,
+                    4 => eval_state.builtin_types.i32_,
+                    8 => eval_state.builtin_types.i64_,
+                    n => eval_state.types.add_array(eval_state.builtin_types.u8_, Some(n), ArrayFlags::empty()),
+                });
+                for (name, bytes) in [("old", old), ("new", new)] {
+                    eval_state.variables.insert(name.to_string(), Value {val: AddrOrValueBlob::Blob(ValueBlob::from_slice(bytes)), type_, flags: ValueFlags::empty()});
+                }
+            }
+        }
+        let r = f(self.breakpoints.get(id), &mut eval_state, &mut eval_context);
+        if eval_context.modified_debuggee {
+            let events = mem::take(&mut eval_context.function_call_events);
+            self.refresh_after_modifying_debuggee(events);
+        }
+        r
+    }
+
+    // Called when the process did exec. The old address space is gone, so all breakpoint locations and hardware breakpoints are meaningless.
+    fn forget_address_space(&mut self) {
+        self.cancel_stepping();
+        self.breakpoint_locations.clear();
+        self.vfork_removed_breakpoints.clear();
+        self.hardware_breakpoints = std::array::from_fn(|_| HardwareBreakpoint::default());
+        self.protected_pages.clear();
+        self.formerly_protected_pages.clear();
+        for (_, b) in self.breakpoints.iter_mut() {
+            b.addrs = err!(NotCalculated, "");
+            b.active = false;
+        }
+        self.info.clear();
+        self.symbols.mark_all_as_unmapped();
+    }
+
+    // After fork, ptrace auto-attaches to the new process and it reports an initial stop. Wait for that stop (it may have already been reported and deferred).
+    // Returns false if the child died instead.
+    fn wait_for_forked_child_stop(&mut self, child_pid: pid_t) -> Result<bool> {
+        let wstatus = if let Some(idx) = self.pending_wait_events.iter().position(|(t, _)| *t == child_pid) {
+            self.pending_wait_events.remove(idx).unwrap().1
+        } else {
+            loop {
+                let mut wstatus = 0i32;
+                let r = unsafe {libc::waitpid(child_pid, &mut wstatus, libc::__WALL)};
+                if r < 0 {
+                    if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return errno_err!("waitpid() for forked child failed");
+                }
+                break wstatus;
+            }
+        };
+        if !libc::WIFSTOPPED(wstatus) {
+            eprintln!("warning: forked child {} exited before initial stop: {:x}", child_pid, wstatus);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // Following the parent after fork or vfork. Remove our breakpoint instructions from the child's memory (otherwise it would die of SIGTRAP when hitting one), then let it go.
+    fn detach_forked_child(&mut self, child_pid: pid_t, vfork: bool) -> Result<()> {
+        if !self.wait_for_forked_child_stop(child_pid)? {
+            return Ok(());
+        }
+        let child_memory = PidMemReader::new(child_pid);
+        for location in &mut self.breakpoint_locations {
+            if location.hardware || !location.active {
+                continue;
+            }
+            if let Err(e) = child_memory.write(location.addr, &[location.original_byte], child_pid) {
+                eprintln!("warning: failed to remove breakpoint at 0x{:x} from forked child {}: {}", location.addr, child_pid, e);
+                continue;
+            }
+            if vfork {
+                // The memory is shared with the child, so the breakpoint is gone for us too until the child does exec or exit.
+                location.active = false;
+                self.vfork_removed_breakpoints.push(location.addr);
+            }
+        }
+        // Page protections for data breakpoints are inherited (or shared, for vfork).
+        for i in 0..self.protected_pages.len() {
+            let p = &self.protected_pages[i];
+            if p.current_prot == p.original_prot {
+                continue;
+            }
+            let (addr, prot) = (p.addr, p.original_prot);
+            if let Err(e) = self.inject_mprotect(child_pid, addr, prot) {
+                eprintln!("warning: failed to unprotect page 0x{:x} in forked child {}: {}", addr, child_pid, e);
+                continue;
+            }
+            if vfork {
+                // Protected again on PTRACE_EVENT_VFORK_DONE.
+                self.protected_pages[i].current_prot = prot;
+            }
+        }
+        // (Hardware breakpoints are not inherited by the child.)
+        unsafe { ptrace(PTRACE_DETACH, child_pid, 0, 0)?; }
+        eprintln!("info: detached from forked child {}", child_pid);
+        Ok(())
+    }
+
+    // Debugging both processes after fork or vfork (--follow-fork=both). The child is a copy of the parent, including our breakpoint instructions
+    // (or shares the parent's memory, for vfork), so mostly it just needs to be added to `threads`.
+    fn add_forked_process(&mut self, parent_tid: pid_t, child_pid: pid_t, vfork: bool) -> Result<()> {
+        if !self.wait_for_forked_child_stop(child_pid)? {
+            return Ok(());
+        }
+        if !vfork {
+            // Page protections for data breakpoints are inherited, but we track them for one process only. Unprotect them in the child.
+            for p in &self.protected_pages.clone() {
+                if p.current_prot != p.original_prot {
+                    if let Err(e) = self.inject_mprotect(child_pid, p.addr, p.original_prot) {
+                        eprintln!("warning: failed to unprotect page 0x{:x} in forked child {}: {}", p.addr, child_pid, e);
+                    }
+                }
+            }
+        }
+        let parent_pid = self.threads[&parent_tid].pid;
+        log!(self.log, "process {} did {}, debugging child {} too", parent_pid, if vfork {"vfork"} else {"fork"}, child_pid);
+        self.forked_processes.push(ForkedProcess {pid: child_pid, maps: MemMapsInfo::default(), addr_maps: HashMap::new()});
+        refresh_forked_process_maps(self, self.forked_processes.len() - 1);
+        let mut thread = Thread::new(self.next_thread_idx, child_pid, child_pid, ThreadState::Suspended);
+        thread.waiting_for_initial_stop = false;
+        self.next_thread_idx += 1;
+        self.threads.insert(child_pid, thread);
+
+        // (Hardware breakpoints are not inherited by the child.)
+        self.set_debug_registers_for_thread(child_pid)?;
+        if self.target_state_for_thread(child_pid) == ThreadState::Running {
+            self.resume_thread(child_pid, /*refresh_info*/ true)?;
+        } else {
+            refresh_thread_info(child_pid, self.threads.get_mut(&child_pid).unwrap(), &mut self.prof.bucket, &self.context.settings);
+        }
+        Ok(())
+    }
+
+    // With --follow-fork=both, process `pid` did exec (in thread `tid`). Stop debugging it, keep debugging the others.
+    // exec() removed our breakpoint instructions, page protections, and debug registers along with the old address space, so there's nothing to clean up in the process.
+    fn detach_process_after_exec(&mut self, pid: pid_t, tid: pid_t) {
+        log!(self.log, "process {} did exec, detaching from it", pid);
+        if self.stepping.as_ref().is_some_and(|s| self.threads.get(&s.tid).is_some_and(|t| t.pid == pid)) {
+            self.cancel_stepping();
+            if self.target_state == ProcessState::Stepping {
+                self.target_state = ProcessState::Running;
+            }
+        }
+        // The process's other threads vanished during exec. waitpid() may still report their exits, ignore those.
+        let tids: Vec<pid_t> = self.threads.values().filter(|t| t.pid == pid).map(|t| t.tid).collect();
+        for t in tids {
+            self.threads.remove(&t);
+            self.detached_pids.push(t);
+        }
+        self.pending_wait_events.retain(|(t, _)| !self.detached_pids.contains(t));
+        if let Err(e) = unsafe { ptrace(PTRACE_DETACH, tid, 0, 0) } {
+            eprintln!("warning: failed to detach from process {} after exec: {}", pid, e);
+        }
+        self.forget_process(pid);
+    }
+
+    // Called when we stop debugging one of several processes (--follow-fork=both) because it exited or did exec. Its threads must already be removed from `threads`.
+    fn forget_process(&mut self, pid: pid_t) {
+        if pid != self.pid {
+            self.forked_processes.retain(|p| p.pid != pid);
+            return;
+        }
+        if self.forked_processes.is_empty() {
+            return;
+        }
+        let new_pid = self.forked_processes[0].pid;
+        log!(self.log, "process {} is gone, switching to process {}", pid, new_pid);
+        if self.stepping.is_some() {
+            self.cancel_stepping();
+        }
+
+        // Breakpoint addresses are in the main process's address space. Move them to the new main process's, dropping the ones in binaries it doesn't have mapped
+        // (there are no breakpoint instructions for them in its memory).
+        let new_addr = |addr: usize| self.addr_in_forked_process(addr, &self.forked_processes[0]);
+        let translated_locations: Vec<Option<usize>> = self.breakpoint_locations.iter().map(|l| new_addr(l.addr)).collect();
+        let translated_hardware: Vec<Option<usize>> = self.hardware_breakpoints.iter().map(|h| new_addr(h.addr)).collect();
+        let mut translated_breakpoints: Vec<(BreakpointId, Vec<(usize, u16)>)> = Vec::new();
+        for (id, b) in self.breakpoints.iter() {
+            if let Ok(addrs) = &b.addrs {
+                translated_breakpoints.push((id, addrs.iter().filter_map(|&(a, level)| new_addr(a).map(|a| (a, level))).collect()));
+            }
+        }
+        for (location, addr) in self.breakpoint_locations.iter_mut().zip(translated_locations) {
+            location.addr = addr.unwrap_or(usize::MAX);
+        }
+        self.breakpoint_locations.retain(|l| l.addr != usize::MAX);
+        self.breakpoint_locations.sort_by_key(|l| l.addr);
+        for (h, addr) in self.hardware_breakpoints.iter_mut().zip(translated_hardware) {
+            match addr {
+                Some(a) => h.addr = a,
+                None => *h = HardwareBreakpoint::default(),
+            }
+        }
+        for (id, addrs) in translated_breakpoints {
+            let b = self.breakpoints.get_mut(id);
+            if addrs.is_empty() {
+                b.addrs = err!(NotCalculated, "");
+                b.active = false;
+            } else {
+                b.addrs = Ok(addrs);
+            }
+        }
+
+        self.swap_address_space_with_forked_process(0);
+        self.forked_processes.remove(0);
+        self.pid = new_pid;
+        self.memory = MemReader::Pid(PidMemReader::new(self.pid));
+        // The new main process's pages were unprotected when it was forked. They'll be protected again next time handle_breakpoints() runs.
+        for p in &mut self.protected_pages {
+            p.current_prot = p.original_prot;
+        }
+    }
+
+    // Following the child after fork or vfork. Detach from all threads of the parent (removing breakpoints from its memory) and make the child the debuggee.
+    // The child inherited our breakpoint instructions (unless it's a vfork, in which case they're put back when the child does exec).
+    fn switch_to_forked_child(&mut self, child_pid: pid_t, vfork: bool) -> Result<()> {
+        if !self.wait_for_forked_child_stop(child_pid)? {
+            return Ok(());
+        }
+        self.cancel_stepping();
+        if self.target_state == ProcessState::Stepping {
+            self.target_state = ProcessState::Running;
+        }
+        self.detach_all_threads();
+        if vfork {
+            // detach_all_threads() unprotected the pages in the memory that we share with the child.
+            for p in &mut self.protected_pages {
+                p.current_prot = p.original_prot;
+            }
+        }
+        log!(self.log, "process {} did {}, following child {}", self.pid, if vfork {"vfork"} else {"fork"}, child_pid);
+        self.detached_pids.push(self.pid);
+
+        self.pid = child_pid;
+        self.memory = MemReader::Pid(PidMemReader::new(child_pid));
+        self.threads.clear();
+        self.pending_wait_events.clear();
+        self.vfork_removed_breakpoints.clear();
+        let mut thread = Thread::new(self.next_thread_idx, child_pid, child_pid, ThreadState::Suspended);
+        thread.waiting_for_initial_stop = false;
+        self.next_thread_idx += 1;
+        self.threads.insert(child_pid, thread);
+        if vfork {
+            for location in &mut self.breakpoint_locations {
+                if !location.hardware {
+                    location.active = false;
+                }
+            }
+        }
+
+        self.set_debug_registers_for_thread(child_pid)?;
+        if self.target_state_for_thread(child_pid) == ThreadState::Running {
+            self.resume_thread(child_pid, /*refresh_info*/ true)?;
+        } else {
+            refresh_thread_info(self.pid, self.threads.get_mut(&child_pid).unwrap(), &mut self.prof.bucket, &self.context.settings);
+        }
+        Ok(())
     }
 
     // Do cleanup just before exit. The Debugger is not usable after this.
@@ -3194,17 +5278,48 @@ impl Debugger {
         }
         self.target_state = ProcessState::NoProcess;
         eprintln!("info: detaching");
+        self.detach_all_threads();
+    }
 
-        let memory = self.memory.clone();
-        let mut memory_bytes_to_restore: Vec<(usize, u8)> = Vec::new();
+    // Removes breakpoints from the process memory and detaches from all threads. Used by shutdown() and when following a forked child.
+    fn detach_all_threads(&mut self) {
+        let main_pid = self.pid;
+        let main_memory = self.memory.clone();
+        // Processes from which we removed breakpoints. Each process has its own copy of the code (see forked_processes).
+        let mut processes_restored: Vec<pid_t> = Vec::new();
+        let mut memory_bytes_to_restore: HashMap<pid_t, Vec<(usize, u8)>> = HashMap::new();
         for location in &self.breakpoint_locations {
             if !location.hardware && location.active {
-                memory_bytes_to_restore.push((location.addr, location.original_byte));
+                memory_bytes_to_restore.entry(main_pid).or_default().push((location.addr, location.original_byte));
+                for p in &self.forked_processes {
+                    if let Some(addr) = self.addr_in_forked_process(location.addr, p) {
+                        memory_bytes_to_restore.entry(p.pid).or_default().push((addr, location.original_byte));
+                    }
+                }
             }
         }
-        let mut detach_thread = |tid: pid_t| {
-            // Remove software breakpoints when we see the first stopped thread (which is required for PTRACE_POKETEXT).
-            for (addr, byte) in mem::take(&mut memory_bytes_to_restore) {
+        let mut pages_to_unprotect: Vec<(usize, i32)> = self.protected_pages.iter().filter(|p| p.current_prot != p.original_prot).map(|p| (p.addr, p.original_prot)).collect();
+        let syscall_code_addr = self.symbols.main_binary_entry_point();
+        let mut detach_thread = |tid: pid_t, pid: pid_t, in_syscall_stop: bool| {
+            // Remove page protections for data breakpoints, from the first thread (of the main process) that can do syscalls.
+            if !in_syscall_stop && pid == main_pid {
+                for (page, prot) in mem::take(&mut pages_to_unprotect) {
+                    let r = syscall_code_addr.clone().and_then(|code_addr| ptrace_inject_syscall(tid, code_addr, (libc::SYS_mprotect, /*i386 __NR_mprotect*/ 125), &[page as u64, sysconf_PAGE_SIZE() as u64, prot as u64], &mut FunctionCallEvents::default()));
+                    match r {
+                        Ok(r) if r < 0 => eprintln!("warning: detach failed to unprotect page 0x{:x}: {}", page, errno_name(-r as i32)),
+                        Ok(_) => (),
+                        Err(e) => eprintln!("warning: detach failed to unprotect page 0x{:x}: {}", page, e),
+                    }
+                }
+            }
+
+            // Remove software breakpoints when we see the first stopped thread of each process (which is required for PTRACE_POKETEXT).
+            let bytes_to_restore: &[(usize, u8)] = match memory_bytes_to_restore.get(&pid) {
+                Some(v) if !processes_restored.contains(&pid) => v,
+                _ => &[] };
+            processes_restored.push(pid);
+            let memory = if pid == main_pid {main_memory.clone()} else {MemReader::Pid(PidMemReader::new(pid))};
+            for &(addr, byte) in bytes_to_restore {
                 let byte_idx = addr % 8;
                 let bit_idx = byte_idx * 8;
                 let word = match memory.read_u64(addr - byte_idx) {
@@ -3231,7 +5346,7 @@ impl Debugger {
         let mut running_threads: HashSet<pid_t> = HashSet::new();
         for (tid, thread) in &self.threads {
             if thread.state == ThreadState::Suspended {
-                detach_thread(*tid);
+                detach_thread(*tid, thread.pid, thread.in_syscall_stop);
             } else if let Err(e) = unsafe {ptrace(PTRACE_INTERRUPT, *tid, 0, 0)} {
                 eprintln!("warning: detach failed to stop thread {}: {}", tid, e);
             } else {
@@ -3262,7 +5377,8 @@ impl Debugger {
             // TODO: Do a subset of handle_breakpoint_trap() logic here, to decrement RIP after hitting our breakpoint instruction.
 
             if !libc::WIFEXITED(wstatus) && !libc::WIFSIGNALED(wstatus) {
-                detach_thread(tid);
+                let event = wstatus >> 16;
+                detach_thread(tid, self.threads.get(&tid).map_or(main_pid, |t| t.pid), libc::WSTOPSIG(wstatus) == libc::SIGTRAP | 0x80 || (event != 0 && event != PTRACE_EVENT_STOP));
             }
 
             running_threads.remove(&tid);
@@ -3275,3 +5391,80 @@ impl Drop for Debugger {
         self.shutdown();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_names() {
+        assert_eq!(signal_from_name("SIGUSR1"), Some(libc::SIGUSR1));
+        assert_eq!(signal_from_name("usr1"), Some(libc::SIGUSR1));
+        assert_eq!(signal_from_name("sigsegv"), Some(libc::SIGSEGV));
+        assert_eq!(signal_from_name("HUP"), Some(libc::SIGHUP));
+        assert_eq!(signal_from_name("SIGSYS"), Some(libc::SIGSYS));
+        assert_eq!(signal_from_name("13"), Some(libc::SIGPIPE));
+        // Realtime signals only by number.
+        assert_eq!(signal_from_name("34"), Some(34));
+        assert_eq!(signal_from_name("64"), Some(64));
+        for bad in ["", "SIG", "SIGFOO", "0", "65", "-1", "SIGRTMIN", "USR1X", "1.5"] {
+            assert_eq!(signal_from_name(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_signal_breakpoint() {
+        let b = SignalBreakpoint::parse("SIGUSR1 PIPE 12 nostop log nopass").unwrap();
+        assert_eq!(b.signals, vec![libc::SIGUSR1, libc::SIGUSR2, libc::SIGPIPE]);
+        assert_eq!((b.policy.stop, b.policy.log, b.policy.pass), (false, true, false));
+        assert_eq!(b.policy_str(), "nostop log nopass");
+        assert_eq!(b.signals_str(), "SIGUSR1 SIGUSR2 SIGPIPE");
+
+        // Unspecified policy words default to true, duplicates are merged, commas separate too.
+        let b = SignalBreakpoint::parse("segv,SIGSEGV, 11 noprint").unwrap();
+        assert_eq!(b.signals, vec![libc::SIGSEGV]);
+        assert_eq!(b.policy_str(), "stop nolog pass");
+        assert!(b.contains(libc::SIGSEGV) && !b.contains(libc::SIGBUS));
+
+        let b = SignalBreakpoint::parse("40 34 Suppress").unwrap();
+        assert_eq!(b.signals, vec![34, 40]);
+        assert_eq!(b.policy_str(), "stop log nopass");
+        assert_eq!(b.signals_str(), "34 40");
+
+        for bad in ["", "stop nolog", "SIGFOO", "usr1 65", "usr1 maybe"] {
+            assert!(SignalBreakpoint::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn breakpoint_save_state_versions() {
+        // Version 0: no log message, ignore count or disable-after-hit.
+        let mut old: Vec<u8> = Vec::new();
+        old.write_u8(0).unwrap();
+        old.write_path(Path::new("/src/a.c")).unwrap();
+        old.write_usize(12).unwrap();
+        old.write_u8(1).unwrap();
+        old.write_str("x > 1").unwrap();
+        old.write_bool(true).unwrap();
+        let mut inp: &[u8] = &old;
+        let b = Breakpoint::load_state(&mut inp, 0).unwrap();
+        assert!(inp.is_empty());
+        assert!(matches!(&b.on, BreakpointOn::Line(l) if l.path == Path::new("/src/a.c") && l.line == 12));
+        assert_eq!(b.condition.as_ref().map(|c| &c.0[..]), Some("x > 1"));
+        assert!(b.log.is_none() && b.ignore_count == 0 && !b.disable_after_hit && b.enabled);
+
+        // Current version round trips.
+        let mut b = Breakpoint::load_state(&mut &old[..], 0).unwrap();
+        b.log = Some(("x = {x}".to_string(), parse_log_format("x = {x}")));
+        b.ignore_count = 3;
+        b.disable_after_hit = true;
+        let mut new: Vec<u8> = Vec::new();
+        b.save_state(&mut new).unwrap();
+        let mut inp: &[u8] = &new;
+        let b = Breakpoint::load_state(&mut inp, 1).unwrap();
+        assert!(inp.is_empty());
+        assert_eq!(b.condition.as_ref().map(|c| &c.0[..]), Some("x > 1"));
+        assert_eq!(b.log.as_ref().map(|l| &l.0[..]), Some("x = {x}"));
+        assert!(b.ignore_count == 3 && b.disable_after_hit && b.enabled);
+    }
+}
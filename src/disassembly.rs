@@ -177,6 +177,7 @@ pub fn disassemble_function(function_idx: usize, mut static_addr_ranges: Vec<Ran
     let mut subfunc_idxs: Vec<Range<usize>> = Vec::new();
     let mut subfunctions: &[Subfunction] = &[];
     let mut seen_subfunction_identities: HashSet<u32> = HashSet::new();
+    let mut bitness = 64;
 
     while res.lines.len() < res.text.num_lines() {
         res.lines.push(DisassemblyLineInfo {kind: DisassemblyLineKind::Intro, static_addr: 0, ..Default::default()});
@@ -187,6 +188,11 @@ pub fn disassemble_function(function_idx: usize, mut static_addr_ranges: Vec<Ran
         res.symbols_shard = Some(function.shard_idx());
         subfunc_idxs = (1..function.num_levels()).map(|i| symbols.subfunction_idxs_at_level(i, function)).collect();
         subfunctions = &symbols.shards[function.shard_idx()].subfunctions;
+        let arch = symbols.elves[0].arch;
+        bitness = match arch.x86_bitness() {
+            Some(b) => b,
+            None => return res.with_error(error!(NotImplemented, "disassembly is not supported for {}", arch.name()), palette),
+        };
     }
 
     for (addr_range_idx, static_addr_range) in static_addr_ranges.iter().enumerate() {
@@ -218,7 +224,7 @@ pub fn disassemble_function(function_idx: usize, mut static_addr_ranges: Vec<Ran
         // We trust that the SymbolResolver reference isn't retained after the formatter is destroyed, so it should be ok to fudge the lifetime here.
         let resolver: Resolver<'static> = unsafe { std::mem::transmute(resolver) };
 
-        let mut decoder = Decoder::with_ip(64, code, static_addr_range.start as u64, DecoderOptions::NONE);
+        let mut decoder = Decoder::with_ip(bitness, code, static_addr_range.start as u64, DecoderOptions::NONE);
         let mut formatter = NasmFormatter::with_options(Some(Box::new(resolver)), None);
 
         let mut line_iter = if let Some(symbols) = &symbols {
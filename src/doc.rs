@@ -11,6 +11,7 @@ pub enum HelpParagraph {
     KeyBinds,
     KnownProblems,
     Watches,
+    Batch,
     Files,
     Misc,
     Licenses,
@@ -33,7 +34,8 @@ static HELP_CHAPTERS: &'static [HelpChapter] = &[
     HelpChapter {cli: "--help-overview", dialog: "overview", description: "general information and first steps, start here", paragraphs: &[HelpParagraph::Overview]},
     HelpChapter {cli: "--help-known-problems", dialog: "known problems", description: "some known bugs and missing features", paragraphs: &[HelpParagraph::KnownProblems]},
     HelpChapter {cli: "--help-watches", dialog: "watch expressions", description: "watch expression language documentation", paragraphs: &[HelpParagraph::Watches]},
-    HelpChapter {cli: "--help-files", dialog: "files", description: "files in ~/.nnd/ - keys config, log file, default stdout/stderr redirects, saved state", paragraphs: &[HelpParagraph::Files]},
+    HelpChapter {cli: "--help-batch", dialog: "batch mode", description: "non-interactive mode for scripts and CI: --batch, --json, script commands", paragraphs: &[HelpParagraph::Batch]},
+    HelpChapter {cli: "--help-files", dialog: "files", description: "files in ~/.nnd/ - keys config, pretty-printers, log file, default stdout/stderr redirects, saved state", paragraphs: &[HelpParagraph::Files]},
     HelpChapter {cli: "--help-misc", dialog: "misc", description: "additional random notes", paragraphs: &[HelpParagraph::Misc]},
     HelpChapter {cli: "--licenses", dialog: "licenses", description: "boring legal info", paragraphs: &[HelpParagraph::Licenses]},
 ];
@@ -96,6 +98,7 @@ Create an issue at https://github.com/al13n321/nnd/issues"###);
 nnd command [args...]   - run a program under the debugger (i.e. just prepend 'nnd' to the command line)
 sudo nnd -p pid   - attach to an existing process
 nnd -c core_dump_path [executable_path]   - open core dump; -o flag (see below) is recommended if the core was produced on a different machine (with different version of libc than available locally)
+nnd --batch script [--json] (command [args...] | -p pid | -c core_dump_path)   - no UI, run commands from a script file and print results to stdout (see --help-batch)
 nnd --dump-core [--mode=direct|live|fork] -p pid > out   - instead of running the debugger, make a core dump snapshot of a running program, similar to gdump
 
 Additional arguments:
@@ -107,7 +110,7 @@ Additional arguments:
 -o   - try to get debug info from debuginfod server at https://debuginfod.elfutils.org/ ; alternatively, set environment variable DEBUGINFOD_URLS to a space-separated list of URLs to use
 --mouse full|no-hover|disabled   - mouse mode; 'no-hover' to react only to clicking and dragging, 'disabled' to disable mouse altogether; default is 'full' (if it doesn't work, check if mouse reporting is enabled in the terminal application)
 -n name   - session name, to identify saved state like open files and breakpoints; "-" for temporary session that doesn't save state; "--" to avoid touching any files at all (at ~/.nnd/)
---breakpoint path:line   - set a breakpoint at the specified source file and line number (e.g. src/main.c:42); repeat the parameter to set multiple breakpoints; the path must exactly match a path appearing in debug info
+--breakpoint path:line|function   - set a breakpoint at the specified source file and line number (e.g. src/main.c:42), or at the start of a function (e.g. ns::foo, *substring*, /regex/); repeat the parameter to set multiple breakpoints; the path can be a suffix of the path in debug info, e.g. main.c:42
 --help   - show this help message; see below for more help pages"###),
         HelpParagraph::CliChapterList => {
             styled_write!(text, palette.default, "Documentation chapters (also available inside the UI by pressing '?'):");
@@ -154,12 +157,13 @@ Additional arguments:
 
 Limitations:
  * Linux only
- * x86 only
- * 64-bit only
- * TUI only (no REPL, GUI, or DAP)
+ * x86 only; aarch64 only for core dumps, on an x86 host. For aarch64 core dumps: stack traces (including return addresses signed with pointer authentication), registers, variables, and watch expressions work;
+   there's no disassembly. Running or attaching to aarch64 processes is not supported at all, so no breakpoints, stepping, data breakpoints, or function calls.
+ * 32-bit programs (i386, x32) work, except thread-local variables; on i386 also no function calls in watch expressions and no $ret
+ * TUI, DAP server, or scripted --batch mode (no GUI); the DAP mode supports only a basic subset of the protocol
  * no remote debugging (but works fine over ssh)
- * single process (doesn't follow forks)
- * no record-replay or other backwards stepping
+ * one executable at a time (on fork, follows the parent, the child, or both, see --follow-fork; a forked process that does exec is detached)
+ * no record-replay or other backwards stepping (but earlier stops can be inspected read-only, with --snapshots)
 
 Properties:
  * Not based on gdb or lldb, implemented mostly from scratch.
@@ -206,31 +210,67 @@ Debugging tips:
  * Steps are stack-frame-dependent: step-out steps out of the *currently selected* stack frame (as seen in the 'threads' window), not the innermost stack frame.
    Step-over steps over the calls inside the currently selected stack frame, i.e. it may involve an internal step-out.
    (This may seem like an unnecessary feature, but if you think through how stepping interacts with inlined functions, it's pretty much required, things get very confusing otherwise.)
+ * After a step that returns from a function (step-out, or step-over/into that happened to leave the function), the function's return value is shown in the watches window as '$ret', and can be used in watch expressions.
+   It's reconstructed from registers according to the function's return type. Structs returned in memory are read through the pointer that the function returns, and may get overwritten as the caller continues. It's cleared when the thread resumes.
  * While a step is in progress, breakpoints are automatically disabled for the duration of the step.
  * Stepping can be interrupted with the suspend key ('p' key). Useful e.g. if you try to step-over a function, but the function turns out to run forever.
+ * Individual threads can be suspended and resumed with M-p and M-c (the selected thread, as seen in the 'threads' window). E.g. resume just one thread while keeping the others suspended.
+   A thread suspended while others run can be inspected and stepped as usual. With --non-stop, breakpoints and steps stop only the thread that hit them.
  * Run-to-cursor works like a step: it runs until the selected (not any) thread hits the requested line, and it disables other breakpoints for the duration of the step.
+ * Jump-to-cursor ('J' key in the 'code' or 'disassembly' window) moves the selected thread's instruction pointer to the cursor line or instruction without running anything, e.g. to re-run a branch that just failed.
+   Nothing else is adjusted, so jumping outside the current function (the debugger warns about it) or past variable initializations can leave the program in a strange state.
  * To start the program and run to start of main(), press step-into ('s' key) when the program is not running (e.g. after killing it with 'C-k' key).
  * Step-into-instruction ('S' key) works no matter what, even if there's no debug info or if disassembly or stack unwinding fails. Use it when other steps fail.
+ * With --snapshots <n>, press '<' and '>' to go back and forth through the last few stops (e.g. to look at the previous step's local variables). This is read-only: the process stays where it is.
+   At each stop the debugger remembers the registers of all threads and the memory that was looked at (stack unwinding, watches, etc), so expressions that read other memory fail on old stops.
+   Memory mappings and loaded binaries are not remembered, the current ones are used. Any step or continue returns to the live process. See --snapshots.
  * Breakpoints are preserved across debugger restarts, but they're put into disabled state on startup. Use Enter key in the 'breakpoints' window to reactivate.
  * To make a conditional breakpoint, press M-enter on a regular breakpoint and edit the condition expression (in the 'breakpoints' window).
    Conditional breakpoint stops the program if the condition expression evaluates to nonzero or fails to evaluate.
    If you don't want to stop on evaluation error, wrap the expression in 'try(...)' (it returns 0 if evaluation failed for any reason).
    E.g.: 'try(abstract_class_ptr.concrete_class_field)' will stop only if abstract_class_ptr was auto-downcast to a class that has the given field, and the field is nonzero.
+ * To make a breakpoint stop only one thread, press 'T' in the 'breakpoints' window and type the thread's tid or '#' followed by its index in the 'threads' window.
+   Other threads hitting it are resumed right away; if there are free hardware breakpoint slots, they don't even stop. Press 'L' to restrict all breakpoints to the selected thread, again to undo.
+   Thread restrictions are cleared when the program is restarted.
+ * Tracepoints: press 'l' in the 'breakpoints' window and type a format string, e.g. 'i = {{i}}, name = {{s.name}}' ('{{{{' and '}}}}' for literal braces).
+   Then the breakpoint doesn't stop; instead each hit evaluates the expressions and prints the text to the log window (which only keeps the last 100 lines).
+   Press 'i' to ignore the next N hits (only hits that pass the condition are counted), 'O' to disable the breakpoint after it stops or logs once.
  * There are default special breakpoints for exceptions (C++, disabled by default) and panics (Rust, enabled by default). They can be enabled/disabled/conditioned like normal breakpoints.
    Quirk: panic breakpoint is not activated until debug info is loaded, so it may fail to trigger if a panic happens very soon after debugger startup.
+ * Signal breakpoints control what happens when the program gets a signal. Press 'a' in the 'breakpoints' window and type signals followed by policy, e.g. 'SIGUSR1 SIGPIPE nostop log pass'.
+   Policy words: stop/nostop (suspend the program), log/nolog (print to the log window), pass/nopass (deliver the signal to the program or suppress it). Omitted ones default to on.
+   Press 'a' on an existing signal breakpoint to edit it. If several enabled signal breakpoints include the same signal, the last one wins.
+   Signals not covered by any enabled signal breakpoint are passed silently, except SIGSEGV, SIGABRT, SIGILL, SIGFPE, which stop the program.
+   Unlike other breakpoints, signal breakpoints stay enabled on restart. Conditions work too, they only affect stopping.
+ * Syscall breakpoints (aka catchpoints) stop when the program enters a syscall: press 'a' in the 'breakpoints' window and type e.g. 'syscall openat mmap exit_group' (names or numbers).
+   The syscall number and arguments are shown in the watches window as '#syscall', with argument names and types for common syscalls, e.g. 'path' as a string and 'flags' as O_* flags.
+   '#syscall' can be used in conditions too, e.g. '#syscall.flags & 0x40' (O_CREAT). Like signal breakpoints, they stay enabled on restart.
+   While any syscall breakpoint is enabled, every syscall of the program stops in the debugger for a moment, so syscall-heavy programs become noticeably slower.
+   Syscall names and numbers are x86-64's (x32 works too); syscall breakpoints are not supported for 32-bit x86 (i386) programs.
+ * Line breakpoints match files by path suffix, e.g. 'foo.cpp:42' or 'src/foo.cpp:42' (whole path components). To add one for a file that's not in any loaded binary yet, press 'a' in the 'breakpoints' window and type e.g. 'foo.cpp:42'.
+   Such breakpoint is shown as [pending] until a binary with a matching file is loaded (e.g. a library is dlopen()ed), then it activates automatically.
+ * Function breakpoints stop at the start of every function matching a name: press 'a' in the 'breakpoints' window and type e.g. 'function ns::foo' (matches 'ns::foo' and 'a::ns::foo').
+   Use 'function *substr*' to match names containing a substring, or 'function /regex/' for a regex. Names are demangled and include template arguments, e.g. '/^std::vector<.*>::push_back$/'.
+   Matches are looked up in all loaded binaries, and looked up again when a library is loaded, so a function in a dlopen()ed library gets the breakpoint as soon as the library appears.
  * The function search (in the 'disassembly' window, 'o' key) currently does fuzzy search over *mangled* function names, for peformance reasons.
    The search results display demangled names, i.e. slightly different from what's actually searched. Press tab to see mangled name.
  * In the 'watches' window, on non-root tree nodes press Enter to add a corresponding watch. E.g. for local variable or struct field or array element.
  * In the 'watches' window, press 'b' to add data breakpoint (aka watchpoint) on the address of the curent value. 'b' for write-only, 'B' for read/write. Conditional data breakpoints are allowed as well. Limitations:
-    * There can be at most 2-3 active data breakpoints. (x86 supports 4 hardware breakpoints, but the debugger transintly uses some of them for regular breakpoints and stepping.)
+    * The value can be of any size. Hardware breakpoints (x86 has 4 of them) each cover an aligned 1, 2, 4, or 8 bytes, so a value takes one or more of them, e.g. a 16-byte struct takes 2. (The debugger also transiently uses hardware breakpoints for regular breakpoints and stepping, so a data breakpoint that needs more than one never takes the last free one.)
+    * If the value doesn't fit into the free hardware breakpoints (e.g. a 64-byte struct), the debugger makes the memory pages containing it write-protected (or inaccessible, for 'B') and catches the faults. The breakpoints window shows "[page protection]" for such data breakpoints. This works for any size, but:
+       - Every access to the same pages traps into the debugger, even if it doesn't touch the watched value, so the program may get much slower. Avoid page protection on the stack.
+       - Syscalls that write into protected pages fail with EFAULT, e.g. read() into a buffer that shares a page with the watched value.
+       - While the debugger steps a thread over an access to a protected page, the pages are briefly unprotected, and accesses from other threads during that time are missed.
+       - The debugger protects pages by making the program call mprotect() (x86 only). If that fails (e.g. a seccomp filter forbids it), the data breakpoint is disabled with an error; there's no slower fallback like single-stepping every instruction.
     * Data breakpoints only catch memory reads/writes. If a variable resides in a register for all or part of its lifetime, data breakpoints won't work on it during that time.
     * Data breakpoints don't catch writes by syscalls, e.g. read() writing to the buffer.
     * When data breakpoint is hit, control stops just *after* the instruction that did the write, after the value is changed.
     * Data breakpoints aren't automatically removed when the variable goes out of scope. Use the 'breakpoints' window to clean up obsolete data breakpoints.
+ * In the 'watches' window, press shift-v to add a data breakpoint that stops only when the value actually changes, e.g. to skip writes that store the same value again. In the 'breakpoints' window, shift-v toggles this for an existing data breakpoint. The status line shows the old and new values, e.g. "value changed: 0 -> 42". The breakpoint's condition can refer to them as `old` and `new`, e.g. `new == 0` to stop when the value becomes zero. `old` and `new` have the type of the watched value, e.g. `new.len > old.len` for a struct. (The status line shows them as signed integers if the value is 1, 2, 4, or 8 bytes, as bytes otherwise.)
  * In the 'watches' window, press shift-d to take the pointer to the current value and add a watch on it. E.g. pressing enter on `my_thing_ptr.buffer` would add a watch `*(0x12345 as *[u8; 1024])`, where 0x12345 is the current address of `my_thing_ptr.buffer`, and `[u8; 1024]` is the type of `my_thing.buffer`. Useful for data breakpoints, as this watch will work regardless of the selected thread and stack frame, not relying on `my_thing` being visible.
  * Expect debugger's memory usage around 4x the size of the executable. E.g. 20 GB for 5 GB clickhouse, release build. This is mostly debug information.
    (If you're curious, see ~/.nnd/<number>/log for a breakdown of which parts of the debug info take how much memory and take how long to load.)
- * For clickhouse server, use CLICKHOUSE_WATCHDOG_ENABLE=0. Otherwise it forks on startup, and the debugger doesn't follow forks."###),
+ * For clickhouse server, use CLICKHOUSE_WATCHDOG_ENABLE=0 or --follow-fork=child. Otherwise it forks on startup, and the debugger stays with the watchdog parent process."###),
         HelpParagraph::KnownProblems => styled_write!(text, palette.default, r###"Current limitations:
  * When a source code line maps to multiple machine code locations, breakpoint sometimes picks a wrong one and doesn't work. Sometimes it's because of bad debug info produced by compiler, sometimes it's nnd's fault for not supporting multiple locations.
    If you run into this problem, use ',' and '.' keys on the line in the 'code' window to cycle through the corresponding locations in the 'disassembly' window; find the correct one(s) and put a disassembly breakpoint on it.
@@ -241,11 +281,12 @@ Debugging tips:
    E.g. `std::vector<int>` doesn't work, you have to write `std::__1::vector<int, std::__1::allocator<int> >` (whitespace matters).
    The plan is to add a fuzzy search dialog for type names, similar to file and function search.
    (There is no plan to actually parse the template type names into their component parts; doing it correctly would be crazy complicated like everything else in C++.)
- * No custom pretty-printers, only the built-in ones for C++ and Rust standard libraries.
- * Can't assign to the debugged program's variables or registers.
- * No whole-file breakpoints, signal breakpoints (except for always-on stop on fatal signals).
+ * Custom pretty-printers are written in the watch expression language, not Python; gdb and lldb pretty-printers can't be reused.
+   They go in ~/.nnd/pretty_printers: a line 'printer <type name pattern>', followed by an expression that turns 'self' into the value to show (e.g. a slice, a struct, or a string).
+   In the pattern, '*' matches any sequence of characters, e.g. 'printer my::SmallVector<*>' or 'printer *::Handle'; everything else must match the type name exactly as spelled in the debug info (see above).
+   The first matching printer applies. See ~/.nnd/pretty_printers.default for examples.
+ * No whole-file breakpoints.
  * Conditional breakpoints are not super fast: a few thousand evaluations per second.
- * Inside libraries that were dlopen()ed at runtime, breakpoints get disabled on program restart. Manually disable-enable the breakpoint after the dlopen() to reactivate it.
  * The 'disassembly' window can only open functions that appear in .symtab or debug info. Can't disassemble arbitrary memory, e.g. JIT-generated code or code from binaries without .symtab or debug info.
  * The debugger gets noticeably slow when the program has > 1K threads, and unusably slow with 20K threads. Part of it is inevitable syscalls
    (to start/stop all n threads we have to do n*const syscalls, then wait for n notifications - that takes a while), but there's a lot of room for improvement anyway
@@ -258,7 +299,7 @@ Debugging tips:
     This removes the need to scp the source code to the server, but leaves all the other problems.)"###),
         HelpParagraph::Watches => styled_write!(text, palette.default, r###"In the 'watches' window, you can enter expressions to be evaluated. It uses a custom scripting language, documented here.
 
-Currently the language has no loops or conditionals, just expressions. The syntax is C-like/Rust-like.
+The syntax is C-like/Rust-like. Mostly it's used for simple expressions, but it also has loops, conditionals, and functions (see 'Control flow and functions' below).
 
 (Throughout this document, single quotes '' denote example watch expressions. The quotes themselves are not part of the expression.)
 
@@ -303,13 +344,49 @@ Inspecting types:
 
 Script variables:
  * 'x=foo.bar.baz' to assign to a new script variable 'x', which can then be used by later watch expressions.
- * Can't assign to the debugged program's variables. Script variables live in the debugger, outside the debugged program.
+ * Script variables live in the debugger, outside the debugged program. Assigning to a name that is a variable of the debugged program writes to the program instead, see below.
  * Watch expressions are evaluated in order from top to bottom.
    Watches lower down the list can use script variables assigned above.
  * Values are held by reference when possible. E.g. '&x' works after 'x=foo.bar', but not after 'x=1'.
- * No scopes, all script variables are global.
+ * Variables assigned with '=' are global. Variables declared with 'let' are local to the enclosing block, see below.
  * If the debugged program has a variable with the same name, it can be accessed with backticks: '`x`', while the script variable can be accessed without backticks: 'x'
 
+Control flow and functions:
+ * Blocks: '{{let n = foo.len; n * 2}}'. The value of the block is the value of the last expression (or empty tuple if it ends with ';').
+ * 'let x = 5', 'let x: u32 = 5', 'let x: MyStruct' (zero-initialized). Visible until the end of the enclosing block. The type annotation only applies to the initial value.
+ * 'if a < b {{a}} else {{b}}', 'while i < 10 {{...}}', 'loop {{...}}', 'break', 'continue'.
+ * 'for i in 0..n {{...}}' iterates over integers; 'for x in my_array {{...}}' iterates over elements of an array or slice, including pretty-printed containers: 'for x in my_vector {{...}}'.
+ * Compound assignments: 'x += 1', 'x <<= 2', etc.
+ * Functions: 'fn list_len(p: *Node) -> u64 {{let n = 0; while p != 0 {{n += 1; p = p.next}}; n}}', then 'list_len(my_list.head)'.
+   Arguments and return value are converted to the declared types, as if with 'as'. 'return x' returns early.
+ * Struct types: 'struct Pair {{a: u32, b: *Node}}', then e.g. 'p as *Pair' or 'let x: Pair'. Fields are laid out like in C.
+   Struct values: 'struct {{a: 1, b: foo}}'. Arrays: '[1, 2, 3]'.
+ * Functions and structs are defined by watch expressions, like script variables: watches lower down the list can use them.
+   They can also be used in breakpoint conditions.
+ * To keep the debugger responsive, evaluating one watch expression is limited to 1000000 steps and function calls to 64 levels of nesting.
+
+Modifying the debugged program:
+ * 'my_var = 5', 'my_struct.flag = 1', 'my_array[3] = 0', '*p = 42', 'my_tuple.1 = 7' write to the debugged program's memory.
+   The value is converted to the target type (e.g. integer to float), or copied as bytes if the sizes match (e.g. struct to struct).
+ * 'rax = 0', 'rip = 0x12345' modify registers. Only in the top stack frame; the thread must be stopped.
+ * Values that are not in memory can't be assigned, e.g. variables held in registers, bit fields, or values produced by pretty-printers.
+ * Read-only memory (e.g. string literals or code) can be modified too.
+ * The watch expressions are re-evaluated every time the program stops, so the assignment is repeated each time. Delete the watch after using it.
+
+Calling functions of the debugged program:
+ * 'my_fn(a, 42)', 'ns::helper(p)', 'my_vector.size()', 'p.is_valid()' (where p is a pointer) call functions in the debugged program, on the selected thread. Works in breakpoint conditions too.
+ * Only integers, floats, pointers, enums, and references can be passed and returned. Structs can't be passed or returned by value.
+   Methods that are inlined everywhere (common for small templates) can't be called, there's no machine code for them.
+ * If the function has no debug info, the arguments are passed as is and the result is shown as u64.
+ * Breakpoints (including hardware breakpoints and data breakpoints) are disabled during the call, so the function runs through them without stopping.
+   Exception: if a data breakpoint had to fall back to page protection ("[page protection]" in the breakpoints window), function calls are refused; disable the data breakpoint first.
+ * If the function gets a signal like SIGSEGV, or function calls in one expression run for more than 1 second in total, the call is aborted and the registers are restored.
+   Changes made to memory by the function are not undone.
+ * Other threads stay stopped during the call, so calling a function that waits for a lock held by another thread will time out.
+ * Calls are refused while other threads of the process are running: in non-stop mode, and in breakpoint conditions of multithreaded programs
+   (other threads keep running while the condition is evaluated). Breakpoints are lifted for the whole process during the call, and running threads would miss them.
+ * Functions can't be called while the thread is stopped at a syscall breakpoint, because the call would skip the pending syscall. Same for assigning rax and orig_rax.
+
 Pretty-printers:
  * "Pretty-printers" are actually not printers, they're transformations that turn values into other values, which may then be printed using normal printers.
    E.g. an std::map is turned into an array of pairs.
@@ -322,7 +399,8 @@ Pretty-printers:
  * Fields of base classes are inlined. Otherwise the base class is a field named '#base'.
  * There are designated pretty-printers for most C++ and Rust containers. If some container is not pretty-printed, maybe you're using libstdc++/libc++ version
    newer or older than mine - please report and I'll probably make pretty-printers work for it too.
- * Currently no support for custom pretty-printers.
+ * Custom pretty-printers can be defined in the watch expression language in ~/.nnd/pretty_printers, matched by type name pattern, e.g. 'printer my::SmallVector<*>' followed by 'self.data.[self.size]'.
+   See ~/.nnd/pretty_printers.default for details. They take precedence over the built-in ones and are reloaded automatically when the file changes.
  * All of the above transformations can be disabled by adding ".#r" to the expression.
  * Pretty-printers apply to intermediate values as well. E.g. 'my_vector[42]' uses a pretty-printer to turn the vector into a slice before applying the [] operator to it.
    Fields of the original struct are accessible too: `my_vector.__M_begin` works, as does `my_vector.#r.__M_begin` if you want to be more explicit.
@@ -342,11 +420,45 @@ Value modifiers:
 Misc:
  * `my_array_or_slice.#len` is the number of elements in an array or slice. `&my_array_or_slice[0]` is the start address. (Slice is a built-in type that can only be produced by pretty-printers.)
  * `try(a, b, ...)` takes any number of expressions and returns the result of the first expression that evaluates without errors. If all expressions fail, returns 0. Useful in breakpoint conditions, e.g. when relying on auto-downcasting to concrete types."###),
+        HelpParagraph::Batch => styled_write!(text, palette.default, r###"With --batch, nnd doesn't show the UI. Instead, it executes commands from a script and prints results to stdout. E.g. for printing stack traces of crashed programs in CI:
+
+  nnd --batch script.nnd ./my_program arg1 arg2
+  nnd --batch - -c core   - read commands from stdin (all of them, until EOF, before doing anything else)
+  nnd -c core --batch   - without a script (--batch at the end of the arguments or followed by another flag): print stack traces of all threads and exit;
+                          when running a program: 'run' followed by 'bt all'
+
+With --json, each output item is printed as a JSON object on a separate line, e.g.: {{"event":"stopped","tid":123,"reason":"signal SIGSEGV","frame":{{...}}}}
+Errors in commands are reported and the script continues; the exit code is 1 if any command failed (unless overridden by 'quit <code>').
+The debugged program's stdout and stderr go to ~/.nnd/<session-name>/{{stdout,stderr}}, as with --no-pty. Other settings (--stop, --breakpoint, --follow-fork, etc) apply as usual.
+
+Commands (one per line; lines starting with '#' are ignored):
+  run | r   - start the program and wait for it to stop or exit
+  continue | c   - resume the program and wait for it to stop or exit
+  step | s, next | n, finish | fin   - step into, over, or out, by lines; wait for the step to complete
+  stepi | si, nexti | ni   - same but by instructions
+  pause   - suspend the running program (e.g. after attaching with -p)
+  kill   - kill the program
+  break | b <file>:<line>|<function> [if <condition>]   - add a breakpoint on a line or function (same syntax as --breakpoint); the condition is a watch expression
+  threads   - list threads, with stop reasons (and process ids, when debugging multiple processes with --follow-fork=both)
+  thread <tid>   - select thread
+  frame | f <n>   - select stack frame in the selected thread
+  bt | backtrace [all]   - print stack trace of the selected thread, or of all threads
+  print | p <expression>   - evaluate a watch expression in the selected frame (see --help-watches)
+  locals   - print local variables in the selected frame
+  echo <text>   - print text
+  quit | q [exit code]   - stop executing the script and exit
+
+When the program stops (e.g. after 'run' or 'continue'), the thread that hit a breakpoint or got a signal is selected, with its top frame.
+With --non-stop, the wait ends as soon as any thread stops (after the step completes, when stepping), while the other threads keep running.
+Before executing each command, nnd waits for debug symbols to finish loading, for at most --symbols-timeout <seconds> (default 300).
+After that, it prints a warning and runs the remaining commands with whatever symbols are loaded (stack traces may lack function names, variables may be missing)."###),
         HelpParagraph::Files => styled_write!(text, palette.default, r###"The debugger creates directory ~/.nnd/ and stores a few things there, such as log file and saved state (watches, breakpoints, open tabs).
 It doesn't create files anywhere else or make any other changes to your system.
 
 Key bindings can be customized by creating ~/.nnd/keys . Read the comments in ~/.nnd/keys.default to get started.
 
+Custom pretty-printers (written in the watch expression language) can be added by creating ~/.nnd/pretty_printers . See ~/.nnd/pretty_printers.default for the format and examples.
+
 Things like watches, breakpoints, open files, etc persist when closing and reopening the debugger. Such state is associated with a "session" and is saved at '~/.nnd/<session-name>/state'.
 Each session can have at most one debugger process running at any given time (synchronized by '~/.nnd/<session-name>/lock').
 The session directory also contains these files:
@@ -369,12 +481,29 @@ When using `sudo nnd -p`, keep in mind that the `~/.nnd` will be in the home dir
 Additional flags:
   --no-pty   - don't create a tty for the debugged program; instead, redirect its stdout and stderr to files in ~/.nnd/<session-name>/{{stdout,stderr}}, and stdin to /dev/null
   --external-tty <file>   - redirect stdout, stderr, and stdin to the same file
+  --ui-tty <file>   - the reverse: run the program in the current terminal, draw the UI in the terminal <file> (e.g. /dev/pts/3; pacify its shell with `sleep inf`);
+    with --ui-tty -, wait for `nnd --ui-here` to be run in another terminal and draw the UI there
+  --ui-here   - lend this terminal to an nnd started with --ui-tty - elsewhere; returns when that nnd exits
   --echo-input   - run a tool that prints names of all pressed keys, in format suitable for the key bindings config file (see ~/.nnd/keys.default); --mouse-mode can be specified *before* --echo-input to also print mouse events (which can't be used in key config)
   --load-symbols <path>   - load debug info from a given binary and exit; useful for benchmarking or debugging debug info loading
   --fixed-fps, --period <seconds>   - some rendering debug options
   --verbose   - print more info to ~/.nnd/<session-name>/log
   --num-threads <n>   - how many threads to use for things like loading debug info; default is the number of cpu cores (or hyperthreads) minus one
   --aslr   - do not disable address space layout randomization
+  --follow-fork parent|child|both   - which process to keep debugging when the program forks; default is parent. The other process is detached, with breakpoints removed from its memory.
+    With 'child', the detached parent keeps running after the debugger quits or restarts the program.
+    With 'both', the forked processes are debugged together, e.g. the workers of a pre-forking server; the threads window gets a 'pid' column. A process that does exec is detached
+    (nnd debugs one executable at a time), and data breakpoints that use page protection only work in the original process.
+  --dap, --dap-port <port>   - instead of the TUI, act as a Debug Adapter Protocol server, for use from an IDE (e.g. VS Code);
+    --dap talks over stdin/stdout, --dap-port accepts one connection on 127.0.0.1:<port>. The program is specified by the 'launch' request
+    ('program', 'args', 'stopOnEntry') or in the nnd command line, or attached to by the 'attach' request ('pid').
+    Supported: breakpoints on lines (with conditions), threads, stack traces, local variables and registers, evaluating watch expressions, stepping, pause, terminate.
+    The program's stdout and stderr go to ~/.nnd/<session-name>/{{stdout,stderr}}, as with --no-pty.
+  --non-stop   - when a thread hits a breakpoint, finishes a step, or crashes, suspend only that thread and let the other threads keep running.
+    Useful for programs that must keep running in the background, e.g. to not lose network connections because of missed heartbeats.
+    Use M-p/M-c to suspend/resume the selected thread; 'p'/'c' suspend/resume all threads as usual. Not supported with --dap.
+  --snapshots <n>   - how many recent stops to remember for viewing with '<' and '>' keys; default is 0 (disabled). Each stop remembers at most 16 MiB of memory,
+    and while enabled every memory read at a stop is copied into the snapshot, which makes stepping somewhat slower.
   --no-syntax-highlight   - disable source code syntax highlighting
   --version   - print version number and build time
 
@@ -402,6 +531,10 @@ Alternatively, you can make the debuggee use tty of another terminal window (e.g
  5. Now you have the debugger running in window B while the debugged program inhabits the terminal in window A
     (which will come to life when you resume the program in the debugger, `sleep` notwithstanding).
 This doesn't work if the program uses /dev/tty, which doesn't get redirected.
+Or the other way around, if the program needs to be started from its terminal (e.g. it's an interactive tool that checks that stdin is its controlling terminal):
+     A$ nnd --ui-tty - the_program_to_debug
+     B$ nnd --ui-here
+The program runs in window A, with A's terminal as its controlling terminal, and the debugger UI appears in window B. Ctrl-C in window A goes to the program, not the debugger.
 (This can even be chained multiple levels deep: `nnd --external-tty /dev/pts/1 nnd --external-tty /dev/pts/2 my_program`. The longest chain I've used in practice is 4 nnd-s + 1 other program.)
 
 Underdeveloped feature: locations window. Tells where each variable lives (register, memory location, expression, etc), for the selected line in the 'disassembly' window. Currently it just prints the DWARF expression, which is inconvenient to read.
@@ -16,19 +16,19 @@ use bitflags::bitflags;
 //
 // (This looks aggressively microoptimized in some ways, but actually it turned out only a little faster than gimli. I'm probably doing something wrong.)
 
-// We currently don't support .debug_types section. If we were to support it, we would pack section id and offset into one 8-byte value and use that as DieOffset (just like UnitSectionOffset, but 8 bytes instead of 16).
+// Offset in .debug_info. Units from .debug_types (DWARF 4 type units), from .dwo files, and from the supplementary file (DWZ) don't live in this .debug_info,
+// so list_units() assigns them made-up offsets after the end of .debug_info, and the rest of the code doesn't need to know which section a DIE came from.
 pub type DieOffset = DebugInfoOffset<usize>;
 
 // Set of unsupported DWARF features that we've encountered in a file, so that we can show a warning about it.
 bitflags! { pub struct DwarfUnsupportedFeatures: usize {
-    const TYPE_SIGNATURES = 0x1;
     const SUPPLEMENTARY_OBJECT_FILES = 0x2;
     const SKELETON_UNITS = 0x4;
 }}
 impl fmt::Display for DwarfUnsupportedFeatures {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut first = true;
-        for (val, name) in [(Self::TYPE_SIGNATURES, "type signatures"), (Self::SUPPLEMENTARY_OBJECT_FILES, "supplementary object files"), (Self::SKELETON_UNITS, "skeleton units (spooky)")] {
+        for (val, name) in [(Self::SUPPLEMENTARY_OBJECT_FILES, "supplementary object files"), (Self::SKELETON_UNITS, "split units without .dwo file")] {
             if self.contains(val) {
                 if !first {
                     write!(f, ", ")?;
@@ -297,7 +297,7 @@ impl SliceReader {
                 let name = DwAt((action.param & 0xffff) as u16);
                 let layout = &context.shared.layouts.layouts[layout_idx].1;
                 let mut ignore_unsupported_features = DwarfUnsupportedFeatures::empty(); // hopefully there aren't executables where some features are used only behind DW_FORM_indirect
-                action = prepare_attribute_action(AttributeSpecification {name, form, implicit_const_value}, layout, context.unit.unit.header.encoding(), &context.shared.warnings, &mut ignore_unsupported_features, &mut flags)?;
+                action = prepare_attribute_action(AttributeSpecification {name, form, implicit_const_value}, layout, context.unit.unit.header.encoding(), context.shared.has_supplementary_file, &context.shared.warnings, &mut ignore_unsupported_features, &mut flags)?;
             }
 
             let out_field = out.add(action.field_offset as usize);
@@ -401,6 +401,23 @@ impl SliceReader {
                 DW_FORM_ref4 => set_unit_offset(self.read_u32_or_zero() as usize, context),
                 DW_FORM_ref8 => set_unit_offset(self.read_u64_or_zero() as usize, context),
                 DW_FORM_ref_udata => set_unit_offset(self.read_uleb128_or_zero(), context),
+                DW_EXTRA_FORM_ref_addr4 => set_usize(self.read_u32_or_zero() as usize + context.unit.offset_shift),
+                DW_EXTRA_FORM_ref_addr8 => set_usize(self.read_u64_or_zero() + context.unit.offset_shift),
+                DW_FORM_ref_sup4 => set_usize(self.read_u32_or_zero() as usize + context.shared.supplementary_offset_shift),
+                DW_FORM_ref_sup8 => set_usize(self.read_u64_or_zero() + context.shared.supplementary_offset_shift),
+                DW_FORM_ref_sig8 => {
+                    let signature = DebugTypeSignature(self.read_u64_or_zero() as u64);
+                    let offset = match context.shared.type_units.get(&signature) {
+                        Some(o) => o.0,
+                        None => {
+                            if context.shared.warnings.warn(DW_AT_null, DW_FORM_ref_sig8) {
+                                eprintln!("warning: type unit not found for signature 0x{:x}", signature.0);
+                            }
+                            0
+                        }
+                    };
+                    set_usize(offset);
+                }
 
                 DW_FORM_addrx => set_addrx(self.read_uleb128_or_zero(), context)?,
                 DW_FORM_addrx1 => set_addrx(self.read_u8_or_zero() as usize, context)?,
@@ -441,16 +458,19 @@ impl SliceReader {
                 }
                 DW_EXTRA_FORM_skip_string => {self.read_null_terminated_slice_or_empty();}
 
-                // These are unexpected here because prepare_attribute_action() converts them to other forms.
-                // DW_FORM_ref_addr | DW_FORM_sec_offset => ,
+                DW_EXTRA_FORM_strp_sup4 => {
+                    let offset = self.read_u32_or_zero() as usize;
+                    let s = context.supplementary_dwarf()?.debug_str.reader().get_null_terminated_slice(offset)?;
+                    set_slice(s, action.param != 0);
+                }
+                DW_EXTRA_FORM_strp_sup8 => {
+                    let offset = self.read_u64_or_zero();
+                    let s = context.supplementary_dwarf()?.debug_str.reader().get_null_terminated_slice(offset)?;
+                    set_slice(s, action.param != 0);
+                }
 
-                // These are not supported, prepare_attribute_action() converts them into DW_EXTRA_FORM_skip_* forms.
-                // DW_FORM_ref_sig8 => ,
-                // DW_FORM_ref_sup4 => ,
-                // DW_FORM_ref_sup8 => ,
-                // DW_FORM_GNU_ref_alt => ,
-                // DW_EXTRA_FORM_strp_sup4 => ,
-                // DW_EXTRA_FORM_strp_sup8 => ,
+                // These are unexpected here because prepare_attribute_action() converts them to other forms.
+                // DW_FORM_ref_addr | DW_FORM_sec_offset | DW_FORM_GNU_ref_alt => ,
 
                 _ => panic!("unexpected attribute form in prepared action: {}", action.form), // (we already validated action.form in prepare_attribute_action())
             }
@@ -598,7 +618,7 @@ impl AllAttributeStructLayouts {
                 layouts.push((tag, layout.clone()));
             }
         }
-        for tag in [DW_TAG_compile_unit, DW_TAG_partial_unit, DW_TAG_type_unit] {
+        for tag in [DW_TAG_compile_unit, DW_TAG_partial_unit, DW_TAG_type_unit, DW_TAG_skeleton_unit] {
             layouts.push((tag, UnitLateAttributes::layout()));
         }
 
@@ -633,7 +653,7 @@ impl AllAttributeStructLayouts {
     }
 
     fn find_secondary(&self, tag: DwTag) -> Option<usize> {
-        if tag == DW_TAG_compile_unit || tag == DW_TAG_partial_unit || tag == DW_TAG_type_unit {
+        if tag == DW_TAG_compile_unit || tag == DW_TAG_partial_unit || tag == DW_TAG_type_unit || tag == DW_TAG_skeleton_unit {
             Some(self.unit_layout_idx)
         } else if self.secondary_tags.contains(&tag) {
             Some(self.secondary_layout_idx)
@@ -659,11 +679,23 @@ pub struct AbbreviationsSharedData {
     layouts: AllAttributeStructLayouts,
     warnings: FormWarningLimiter,
     pub unsupported_features: DwarfUnsupportedFeatures,
+    // Type signature -> offset of the type DIE in the type unit. For resolving DW_FORM_ref_sig8. Populated by list_units().
+    pub type_units: HashMap<DebugTypeSignature, DieOffset>,
+    // Whether we have the supplementary file (.gnu_debugaltlink or .debug_sup), i.e. whether to parse DW_FORM_GNU_ref_alt, DW_FORM_ref_sup*, DW_FORM_strp_sup, etc.
+    // Must be set before list_units() for the files that refer to it.
+    pub has_supplementary_file: bool,
+    // The `offset_shift` of the supplementary file's units, added to DW_FORM_GNU_ref_alt and DW_FORM_ref_sup* values. May be assigned after list_units().
+    pub supplementary_offset_shift: usize,
 
     actions_pool: Vec<AttributeAction>,
     abbreviations_pool: Vec<Abbreviation>,
     code_to_idx_pool: Vec<(/*code*/ usize, /*idx_in_abbreviations_pool*/ usize)>,
 }
+impl AbbreviationsSharedData {
+    pub fn new(layouts: AllAttributeStructLayouts) -> Self {
+        Self {layouts, warnings: FormWarningLimiter::default(), unsupported_features: DwarfUnsupportedFeatures::empty(), type_units: HashMap::new(), has_supplementary_file: false, supplementary_offset_shift: 0, actions_pool: Vec::new(), abbreviations_pool: Vec::new(), code_to_idx_pool: Vec::new()}
+    }
+}
 
 #[derive(Clone)]
 pub struct AttributeContext<'a> {
@@ -673,8 +705,17 @@ pub struct AttributeContext<'a> {
     pub shared: &'a AbbreviationsSharedData,
 }
 impl<'a> AttributeContext<'a> {
-    pub fn switch_unit(&mut self, unit: &'a DwarfUnit) {
+    // `main_dwarf` is the executable's sections; if the new unit came from a .dwo file, its own sections are used instead.
+    pub fn switch_unit(&mut self, unit: &'a DwarfUnit, main_dwarf: &'a Dwarf<DwarfSlice>) {
         self.unit = unit;
+        self.dwarf = unit.dwarf(main_dwarf);
+    }
+
+    fn supplementary_dwarf(&self) -> Result<&'a Dwarf<DwarfSlice>> {
+        match self.dwarf.sup() {
+            Some(d) => Ok(d),
+            None => err!(Dwarf, "reference to supplementary file, but it's not loaded"),
+        }
     }
 }
 
@@ -708,8 +749,9 @@ pub enum AttributeType {
     UnitOffset,
     // Type: usize (DebugInfoOffset).
     // Class: reference.
-    // Forms: DW_FORM_ref{1,2,4,8,_udata}, DW_FORM_ref_addr.
-    // Unit offset is converted to .debug_info offset.
+    // Forms: DW_FORM_ref{1,2,4,8,_udata}, DW_FORM_ref_addr, DW_FORM_ref_sig8, DW_FORM_ref_sup{4,8}, DW_FORM_GNU_ref_alt.
+    // Unit offset is converted to .debug_info offset. Type signature is converted to the offset of the type's DIE in its type unit.
+    // References to the supplementary file are converted to the made-up offsets of its units (see DieOffset).
     DebugInfoOffset,
     // Type: usize.
     // Class: address.
@@ -747,7 +789,7 @@ pub enum AttributeType {
     // Type: DwarfReference, aka usize.
     // Attributes: DW_AT_specification, DW_AT_abstract_origin.
     // Class: reference.
-    // Forms: DW_FORM_ref{1,2,4,8,_udata}, DW_FORM_ref_addr.
+    // Forms: DW_FORM_ref{1,2,4,8,_udata}, DW_FORM_ref_addr, DW_FORM_ref_sup{4,8}, DW_FORM_GNU_ref_alt.
     // Normally at most one of these two attributes is present, so we merge them into one field.
     // If either of the attributes is present, bit DwarfReference::HAS_SPECIFICATION_OR_ABSTRACT_ORIGIN is set in `fields`.
     // The attribute may have offset relative to the current unit or relative to the whole .debug_info section. In the latter case, bit DwarfReference::GLOBAL is set in `fields`.
@@ -889,10 +931,47 @@ pub struct DwarfUnit {
     pub ranges: DwarfRanges,
     pub fields: u32, // for `ranges`
 
+    // Split DWARF. For skeleton units: DW_AT_dwo_name (relative to comp_dir), and unit.dwo_id is set.
+    // For units loaded from .dwo/.dwp: the sections to read DIEs from (the executable's sections are still used for the line program and the unit's own address ranges).
+    pub dwo_name: &'static str,
+    pub split_dwarf: Option<Arc<Dwarf<DwarfSlice>>>,
+    // The `offset_shift` passed to list_units(). DW_FORM_ref_addr values are offsets in the .debug_info of the file the unit came from, so they need to be shifted too.
+    pub offset_shift: usize,
+
     // These are used by Symbols.
     pub shard_idx: usize,
     pub file_idx_remap: Vec<usize>, // DWARF file index -> index in Symbols.files
 }
+impl DwarfUnit {
+    // Sections to use for this unit's DIEs and attributes.
+    pub fn dwarf<'a>(&'a self, main_dwarf: &'a Dwarf<DwarfSlice>) -> &'a Dwarf<DwarfSlice> {
+        self.split_dwarf.as_deref().unwrap_or(main_dwarf)
+    }
+
+    // Sections to use for the unit's own line program and address ranges. Split units inherit these from the skeleton unit in the executable,
+    // while units from the supplementary file have their own.
+    pub fn line_program_dwarf<'a>(&'a self, main_dwarf: &'a Dwarf<DwarfSlice>) -> &'a Dwarf<DwarfSlice> {
+        match &self.split_dwarf {
+            Some(d) if d.file_type == DwarfFileType::Main => d,
+            _ => main_dwarf,
+        }
+    }
+
+    // Turns a unit from a .dwo file into a replacement for the skeleton unit from the executable.
+    // The split unit doesn't have the attributes that the skeleton has: line program, address ranges, comp_dir, addr_base.
+    pub fn inherit_from_skeleton(&mut self, skeleton: DwarfUnit, split_dwarf: Arc<Dwarf<DwarfSlice>>) {
+        self.unit.copy_relocated_attributes(&skeleton.unit);
+        self.unit.line_program = skeleton.unit.line_program;
+        self.unit.dwo_id = skeleton.unit.dwo_id;
+        if self.name.is_empty() {
+            self.name = skeleton.name;
+        }
+        self.comp_dir = skeleton.comp_dir;
+        self.ranges = skeleton.ranges;
+        self.fields = skeleton.fields;
+        self.split_dwarf = Some(split_dwarf);
+    }
+}
 
 dwarf_struct!{ UnitEarlyAttributes {
     // Be careful to not include any attributes that may require knowing unit's section offsets. E.g. name, comp_dir, low_pc can't be here.
@@ -900,6 +979,9 @@ dwarf_struct!{ UnitEarlyAttributes {
     addr_base: usize, DW_AT_addr_base, SectionOffset;
     loclists_base: usize, DW_AT_loclists_base, SectionOffset;
     rnglists_base: usize, DW_AT_rnglists_base, SectionOffset;
+    // Pre-DWARF-5 split DWARF extension.
+    gnu_addr_base: usize, DW_AT_GNU_addr_base, SectionOffset;
+    gnu_ranges_base: usize, DW_AT_GNU_ranges_base, SectionOffset;
 }}
 
 dwarf_struct!{ UnitLateAttributes {
@@ -908,10 +990,13 @@ dwarf_struct!{ UnitLateAttributes {
     ranges: DwarfRanges, DW_AT_ranges, Ranges;
     language: usize, DW_AT_language, Unsigned;
     stmt_list: usize, DW_AT_stmt_list, SectionOffset; // (this could be in UnitEarlyAttributes, but more convenient here)
+    dwo_name: &'static str, DW_AT_dwo_name, String;
+    gnu_dwo_name: &'static str, DW_AT_GNU_dwo_name, String;
+    gnu_dwo_id: usize, DW_AT_GNU_dwo_id, Unsigned;
 }}
 
-pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: AllAttributeStructLayouts) -> Result<(Vec<DwarfUnit>, AbbreviationsSharedData)> {
-    let mut shared = AbbreviationsSharedData {layouts, warnings: FormWarningLimiter::default(), unsupported_features: DwarfUnsupportedFeatures::empty(), actions_pool: Vec::new(), abbreviations_pool: Vec::new(), code_to_idx_pool: Vec::new()};
+// `offset_shift` is added to all DIE offsets. It's nonzero for .dwo files, to keep DieOffset-s unique across all units of a binary.
+pub fn list_units(dwarf: &Dwarf<DwarfSlice>, binary_name: &str, offset_shift: usize, shared: &mut AbbreviationsSharedData) -> Result<Vec<DwarfUnit>> {
 
     let mut unit_headers: Vec<(UnitHeader<DwarfSlice>, AbbreviationSet)> = Vec::new();
     let mut abbrev_offsets: Vec<((/*offset*/ usize, Encoding), /*unit_idx*/ usize)> = Vec::new();
@@ -922,6 +1007,12 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
             abbrev_offsets.push(((header.debug_abbrev_offset().0, header.encoding()), unit_headers.len()));
             unit_headers.push((header, AbbreviationSet::new()));
         }
+        // DWARF 4 type units (-fdebug-types-section).
+        let mut iter = dwarf.debug_types.units();
+        while let Some(header) = iter.next()? {
+            abbrev_offsets.push(((header.debug_abbrev_offset().0, header.encoding()), unit_headers.len()));
+            unit_headers.push((header, AbbreviationSet::new()));
+        }
     }
     {
         let _prof = ProfileScope::with_threshold(0.01, format!("loading abbreviations ({})", binary_name));
@@ -991,12 +1082,12 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
                     Entry::Vacant(v) => {
                         let mut actions = [AbbreviationActions::default(), AbbreviationActions::default(), AbbreviationActions::default()];
                         if let Some(layout_idx) = shared.layouts.find(tag) {
-                            actions[0] = prepare_abbreviation_actions(&attributes, layout_idx, encoding, &mut shared)?;
+                            actions[0] = prepare_abbreviation_actions(&attributes, layout_idx, encoding, shared)?;
                         }
                         if let Some(layout_idx) = shared.layouts.find_secondary(tag) {
-                            actions[1] = prepare_abbreviation_actions(&attributes, layout_idx, encoding, &mut shared)?;
+                            actions[1] = prepare_abbreviation_actions(&attributes, layout_idx, encoding, shared)?;
                         }
-                        actions[2] = prepare_abbreviation_actions(&attributes, shared.layouts.empty_layout_idx, encoding, &mut shared)?;
+                        actions[2] = prepare_abbreviation_actions(&attributes, shared.layouts.empty_layout_idx, encoding, shared)?;
 
                         let idx = shared.abbreviations_pool.len();
                         shared.abbreviations_pool.push(Abbreviation {tag, has_children: has_children != 0, actions});
@@ -1046,11 +1137,26 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
     let mut units: Vec<DwarfUnit> = Vec::with_capacity(unit_headers.len());
     {
         let _prof = ProfileScope::with_threshold(0.01, format!("preparing units ({})", binary_name));
-        for (header, abbreviation_set) in unit_headers {
+        for (mut header, abbreviation_set) in unit_headers {
             let offset = match header.offset() {
-                UnitSectionOffset::DebugInfoOffset(o) => o,
-                _ => return err!(Internal, "unit offset has unexpected type"),
+                UnitSectionOffset::DebugInfoOffset(o) => DebugInfoOffset(o.0 + offset_shift),
+                UnitSectionOffset::DebugTypesOffset(o) => DebugInfoOffset(o.0 + offset_shift + dwarf.debug_info.reader().len()),
+            };
+            if header.offset() != UnitSectionOffset::DebugInfoOffset(offset) {
+                let entries = header.range_from(UnitOffset(header.header_size())..)?;
+                header = UnitHeader::new(header.encoding(), header.unit_length(), header.type_(), header.debug_abbrev_offset(), UnitSectionOffset::DebugInfoOffset(offset), entries);
+            }
+            let dwo_id = match header.type_() {
+                UnitType::Skeleton(id) | UnitType::SplitCompilation(id) => Some(id),
+                _ => None,
             };
+            if let UnitType::Type {type_signature, type_offset} | UnitType::SplitType {type_signature, type_offset} = header.type_() {
+                // The same type unit may appear in multiple files (e.g. .dwo files), or even multiple times in one file. Keep only the first one.
+                match shared.type_units.entry(type_signature) {
+                    Entry::Occupied(_) => continue,
+                    Entry::Vacant(v) => {v.insert(DebugInfoOffset(offset.0 + type_offset.0));}
+                }
+            }
             let mut unit = DwarfUnit {
                 offset,
                 unit: Unit {
@@ -1060,12 +1166,12 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
                     // We'll assign these below.
                     low_pc: 0, str_offsets_base: DebugStrOffsetsBase(0), addr_base: DebugAddrBase(0), loclists_base: DebugLocListsBase(0),
                     rnglists_base: DebugRngListsBase(0), line_program: None,
-                    dwo_id: None, // not supported
+                    dwo_id,
                 },
                 abbreviation_set,
-                name: "", comp_dir: "", ranges: DwarfRanges::default(), fields: 0, language: LanguageFamily::Unknown, shard_idx: 0, file_idx_remap: Vec::new(),
+                name: "", comp_dir: "", ranges: DwarfRanges::default(), fields: 0, language: LanguageFamily::Unknown, dwo_name: "", split_dwarf: None, offset_shift, shard_idx: 0, file_idx_remap: Vec::new(),
             };
-            let attribute_context = AttributeContext {unit: &unit, dwarf, shared: &shared};
+            let attribute_context = AttributeContext {unit: &unit, dwarf, shared};
 
             let initial_cursor = SliceReader::new(header.range_from(UnitOffset(unit.unit.header.header_size())..)?.slice());
 
@@ -1078,12 +1184,20 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
             let mut attrs = UnitEarlyAttributes::default();
             unsafe {cursor.read_attributes(&abbrev, /*which_layout*/ 1, &attribute_context, &raw mut attrs as *mut u8)?};
 
-            unit.unit.str_offsets_base = DebugStrOffsetsBase(attrs.str_offsets_base);
-            unit.unit.addr_base = DebugAddrBase(attrs.addr_base);
-            unit.unit.loclists_base = DebugLocListsBase(attrs.loclists_base);
-            unit.unit.rnglists_base = DebugRngListsBase(attrs.rnglists_base);
+            // In .dwo files, the bases are implicit.
+            let encoding = unit.unit.header.encoding();
+            unit.unit.str_offsets_base = if attrs.fields & UnitEarlyAttributes::str_offsets_base != 0 {DebugStrOffsetsBase(attrs.str_offsets_base)} else {DebugStrOffsetsBase::default_for_encoding_and_file(encoding, dwarf.file_type)};
+            unit.unit.addr_base = DebugAddrBase(if attrs.fields & UnitEarlyAttributes::addr_base != 0 {attrs.addr_base} else {attrs.gnu_addr_base});
+            unit.unit.loclists_base = if attrs.fields & UnitEarlyAttributes::loclists_base != 0 {DebugLocListsBase(attrs.loclists_base)} else {DebugLocListsBase::default_for_encoding_and_file(encoding, dwarf.file_type)};
+            unit.unit.rnglists_base = if attrs.fields & UnitEarlyAttributes::rnglists_base != 0 {
+                DebugRngListsBase(attrs.rnglists_base)
+            } else if attrs.fields & UnitEarlyAttributes::gnu_ranges_base != 0 {
+                DebugRngListsBase(attrs.gnu_ranges_base)
+            } else {
+                DebugRngListsBase::default_for_encoding_and_file(encoding, dwarf.file_type)
+            };
 
-            let attribute_context = AttributeContext {unit: &unit, dwarf, shared: &shared};
+            let attribute_context = AttributeContext {unit: &unit, dwarf, shared};
             let mut cursor = initial_cursor.clone();
             let Some(abbrev) = cursor.read_abbreviation(&attribute_context)? else { return err!(Sanity, "file changed") }; // re-lookup because of borrow checker
             let mut attrs = UnitLateAttributes::default();
@@ -1098,6 +1212,10 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
             unit.comp_dir = attrs.comp_dir;
             unit.ranges = attrs.ranges;
             unit.fields = attrs.fields;
+            unit.dwo_name = if attrs.fields & UnitLateAttributes::dwo_name != 0 {attrs.dwo_name} else {attrs.gnu_dwo_name};
+            if unit.unit.dwo_id.is_none() && attrs.fields & UnitLateAttributes::gnu_dwo_id != 0 {
+                unit.unit.dwo_id = Some(DwoId(attrs.gnu_dwo_id as u64));
+            }
             unit.language = match DwLang(attrs.language as u16) {
                 // Unfortunately the C_plus_plus_* constants don't follow any predictable pattern, so we'll have to update this list every few years as new C++ versions are added to DWARF.
                 DW_LANG_C | DW_LANG_C11 | DW_LANG_C17 | DW_LANG_C89 | DW_LANG_C99 | DW_LANG_C_plus_plus | DW_LANG_C_plus_plus_03 | DW_LANG_C_plus_plus_11 | DW_LANG_C_plus_plus_14 | DW_LANG_C_plus_plus_17 | DW_LANG_C_plus_plus_20 => LanguageFamily::Cpp,
@@ -1109,7 +1227,12 @@ pub fn list_units(dwarf: &mut Dwarf<DwarfSlice>, binary_name: &str, layouts: All
         }
     }
 
-    Ok((units, shared))
+    Ok(units)
+}
+
+// Size of the DieOffset range taken by the units from list_units(dwarf, ...): .debug_info followed by .debug_types.
+pub fn unit_sections_len(dwarf: &Dwarf<DwarfSlice>) -> usize {
+    dwarf.debug_info.reader().len() + dwarf.debug_types.reader().len()
 }
 
 // Boilerplate adapter so that we can use gimli parsing code for some things.
@@ -1271,17 +1394,15 @@ const DW_EXTRA_FORM_line_strp4: DwForm = DwForm(0xfe09);
 const DW_EXTRA_FORM_line_strp8: DwForm = DwForm(0xfe0a);
 const DW_EXTRA_FORM_strp_sup4: DwForm = DwForm(0xfe0b);
 const DW_EXTRA_FORM_strp_sup8: DwForm = DwForm(0xfe0c);
+const DW_EXTRA_FORM_ref_addr4: DwForm = DwForm(0xfe0d);
+const DW_EXTRA_FORM_ref_addr8: DwForm = DwForm(0xfe0e);
 
 
-fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStructLayout, encoding: Encoding, warnings: &FormWarningLimiter, unsupported_features: &mut DwarfUnsupportedFeatures, flags: &mut u32) -> Result<AttributeAction> {
+fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStructLayout, encoding: Encoding, has_supplementary_file: bool, warnings: &FormWarningLimiter, unsupported_features: &mut DwarfUnsupportedFeatures, flags: &mut u32) -> Result<AttributeAction> {
     assert!(attr.form != DW_FORM_indirect);
 
     let unsupported_form = match attr.form {
-        DW_FORM_ref_sig8 => {
-            unsupported_features.insert(DwarfUnsupportedFeatures::TYPE_SIGNATURES);
-            true
-        }
-        DW_FORM_ref_sup4 | DW_FORM_ref_sup8 | DW_FORM_GNU_ref_alt | DW_FORM_strp_sup | DW_EXTRA_FORM_strp_sup4 | DW_EXTRA_FORM_strp_sup8 | DW_FORM_GNU_strp_alt =>
+        DW_FORM_ref_sup4 | DW_FORM_ref_sup8 | DW_FORM_GNU_ref_alt | DW_FORM_strp_sup | DW_EXTRA_FORM_strp_sup4 | DW_EXTRA_FORM_strp_sup8 | DW_FORM_GNU_strp_alt if !has_supplementary_file =>
         {
             unsupported_features.insert(DwarfUnsupportedFeatures::SUPPLEMENTARY_OBJECT_FILES);
             true
@@ -1334,6 +1455,7 @@ fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStru
             }
             AttributeType::DebugInfoOffset => match attr.form {
                 DW_FORM_ref1 | DW_FORM_ref2 | DW_FORM_ref4 | DW_FORM_ref8 | DW_FORM_ref_udata | DW_FORM_ref_addr => param = 1,
+                DW_FORM_ref_sig8 | DW_FORM_ref_sup4 | DW_FORM_ref_sup8 | DW_FORM_GNU_ref_alt => (),
                 _ => found_match = false,
             }
             AttributeType::Address => match attr.form {
@@ -1360,21 +1482,21 @@ fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStru
 
             AttributeType::Ranges => match attr.name {
                 DW_AT_ranges => match attr.form {
-                    // Compatibility: the sec_offset case would need additional logic to support split dwarf (dwo).
+                    // (For the sec_offset case in pre-DWARF-5 .dwo files, the reader of the field needs to add DW_AT_GNU_ranges_base, see Dwarf::ranges_offset_from_raw().)
                     DW_FORM_rnglistx | DW_FORM_sec_offset | DW_FORM_data4 | DW_FORM_data8 => *flags |= DwarfRanges::RANGES,
                     _ => found_match = false,
                 }
                 DW_AT_low_pc => match attr.form {
-                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 => *flags |= DwarfRanges::LOW_PC,
+                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 | DW_FORM_GNU_addr_index => *flags |= DwarfRanges::LOW_PC,
                     _ => found_match = false,
                 }
                 DW_AT_high_pc => match attr.form {
-                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 => *flags |= DwarfRanges::HIGH_PC,
+                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 | DW_FORM_GNU_addr_index => *flags |= DwarfRanges::HIGH_PC,
                     DW_FORM_data1 | DW_FORM_data2 | DW_FORM_data4 | DW_FORM_data8 | DW_FORM_udata => *flags |= DwarfRanges::HIGH_PC | DwarfRanges::HIGH_PC_IS_RELATIVE,
                     _ => found_match = false,
                 }
                 DW_AT_entry_pc => match attr.form {
-                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 => *flags |= DwarfRanges::ENTRY_PC,
+                    DW_FORM_addr | DW_FORM_addrx | DW_FORM_addrx1 | DW_FORM_addrx2 | DW_FORM_addrx3 | DW_FORM_addrx4 | DW_FORM_GNU_addr_index => *flags |= DwarfRanges::ENTRY_PC,
                     DW_FORM_data1 | DW_FORM_data2 | DW_FORM_data4 | DW_FORM_data8 | DW_FORM_udata => *flags |= DwarfRanges::ENTRY_PC | DwarfRanges::ENTRY_PC_IS_RELATIVE,
                     _ => found_match = false,
                 }
@@ -1392,7 +1514,7 @@ fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStru
                     *flags |= DwarfReference::HAS_SPECIFICATION_OR_ABSTRACT_ORIGIN;
                     match attr.form {
                         DW_FORM_ref1 | DW_FORM_ref2 | DW_FORM_ref4 | DW_FORM_ref8 | DW_FORM_ref_udata => (),
-                        DW_FORM_ref_addr => *flags |= DwarfReference::GLOBAL,
+                        DW_FORM_ref_addr | DW_FORM_ref_sup4 | DW_FORM_ref_sup8 | DW_FORM_GNU_ref_alt => *flags |= DwarfReference::GLOBAL,
                         _ => found_match = false,
                     }
                 }
@@ -1405,7 +1527,9 @@ fn prepare_attribute_action(attr: AttributeSpecification, layout: &AttributeStru
             match form {
                 DW_FORM_GNU_str_index => form = DW_FORM_strx,
                 DW_FORM_GNU_addr_index => form = DW_FORM_addrx,
-                DW_FORM_ref_addr | DW_FORM_sec_offset => form = if encoding.format.word_size() == 4 {DW_FORM_data4} else {DW_FORM_data8},
+                DW_FORM_sec_offset => form = if encoding.format.word_size() == 4 {DW_FORM_data4} else {DW_FORM_data8},
+                DW_FORM_ref_addr => form = if encoding.format.word_size() == 4 {DW_EXTRA_FORM_ref_addr4} else {DW_EXTRA_FORM_ref_addr8},
+                DW_FORM_GNU_ref_alt => form = if encoding.format.word_size() == 4 {DW_FORM_ref_sup4} else {DW_FORM_ref_sup8},
                 DW_FORM_strp => form = if encoding.format.word_size() == 4 {DW_EXTRA_FORM_strp4} else {DW_EXTRA_FORM_strp8},
                 DW_FORM_line_strp => form = if encoding.format.word_size() == 4 {DW_EXTRA_FORM_line_strp4} else {DW_EXTRA_FORM_line_strp8},
                 DW_FORM_strp_sup | DW_FORM_GNU_strp_alt => form = if encoding.format.word_size() == 4 {DW_EXTRA_FORM_strp_sup4} else {DW_EXTRA_FORM_strp_sup8},
@@ -1463,7 +1587,7 @@ fn prepare_abbreviation_actions(attributes: &[AttributeSpecification], layout_id
             continue;
         }
 
-        let action = prepare_attribute_action(*attr, layout, encoding, &shared.warnings, &mut shared.unsupported_features, &mut flags_value)?;
+        let action = prepare_attribute_action(*attr, layout, encoding, shared.has_supplementary_file, &shared.warnings, &mut shared.unsupported_features, &mut flags_value)?;
         if action.form == DW_EXTRA_FORM_skip_bytes {
             if action.param == 0 {
                 continue;
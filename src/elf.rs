@@ -1,4 +1,4 @@
-use crate::{*, error::*, log::*, util::*, registers::*, procfs::*, process_info::*, os::*};
+use crate::{*, error::*, log::*, util::*, registers::*, arch::*, procfs::*, process_info::*, os::*};
 use std::{fs::{File}, mem, mem::MaybeUninit, io::{self, BufReader, SeekFrom, Seek, Read, BufRead}, sync::{Arc, OnceLock}, collections::{HashMap, hash_map::Entry}, str, ptr, fmt::Debug, fmt, result, slice, ops::Range};
 use libc::pid_t;
 
@@ -44,6 +44,7 @@ pub struct ElfNote<'a> {
 
 pub struct ElfFile {
     pub name: String, // just for logging
+    pub arch: Arch,
 
     pub segments: Vec<ElfSegment>,
     pub sections: Vec<ElfSection>,
@@ -99,6 +100,11 @@ pub struct CoreDumpMemReader {
 }
 
 impl ElfFile {
+    // ELFCLASS32.
+    pub fn is_32_bit(&self) -> bool {
+        self.arch.pointer_size() == 4
+    }
+
     pub fn data(&self) -> &[u8] {
         self.data as _
     }
@@ -137,7 +143,7 @@ impl ElfFile {
             // We parse ELF headers for such binaries in a blocking way on startup (to get build id), before even showing UI.
             &section.decompressed_data.get_or_init(|| {
                 let compressed = &self.data()[section.offset..section.offset + section.size_in_file()];
-                let compressed = &compressed[mem::size_of::<libc::Elf64_Chdr>()..];
+                let compressed = &compressed[if self.is_32_bit() {mem::size_of::<libc::Elf32_Chdr>()} else {mem::size_of::<libc::Elf64_Chdr>()}..];
                 let mut decompressed = vec![0u8; header.ch_size as usize + ELF_PAD_RIGHT];
                 decompressed.truncate(header.ch_size as usize);
 
@@ -258,14 +264,35 @@ pub fn parse_core_dump(elf: Arc<ElfFile>) -> Result<(CoreDumpMemReader, Vec<(pid
         let name = String::from_utf8_lossy(note.name);
         match note.type_ {
             NT_PRSTATUS => {
-                let (prstatus, _) = unsafe {memcpy_struct::<elf_prstatus>(note.desc, "NT_PRSTATUS")}?;
+                let (si_signo, pr_pid, regs) = match elf.arch {
+                    Arch::X86_64 => {
+                        let (prstatus, _) = unsafe {memcpy_struct::<elf_prstatus>(note.desc, "NT_PRSTATUS")}?;
+                        (prstatus.si_signo, prstatus.pr_pid, Registers::from_ptrace(&prstatus.pr_reg))
+                    }
+                    Arch::Aarch64 => {
+                        // The header part of elf_prstatus is the same on all 64-bit architectures, but pr_reg is user_pt_regs instead of user_regs_struct.
+                        let (prstatus, _) = unsafe {memcpy_struct::<elf_prstatus>(note.desc, "NT_PRSTATUS")}?;
+                        let (pr_reg, _) = unsafe {memcpy_struct::<[u64; 34]>(&note.desc[mem::offset_of!(elf_prstatus, pr_reg)..], "NT_PRSTATUS")}?;
+                        (prstatus.si_signo, prstatus.pr_pid, Registers::from_aarch64_user_regs(&pr_reg))
+                    }
+                    Arch::I386 | Arch::X32 => {
+                        // 32-bit header, then i386 user_regs_struct or (for x32) the usual 64-bit user_regs_struct.
+                        let (prstatus, rest) = unsafe {memcpy_struct::<compat_elf_prstatus_common>(note.desc, "NT_PRSTATUS")}?;
+                        let regs = if elf.arch == Arch::I386 {
+                            Registers::from_i386_user_regs(&unsafe {memcpy_struct::<[u32; 17]>(rest, "NT_PRSTATUS")}?.0)
+                        } else {
+                            Registers::from_ptrace(&unsafe {memcpy_struct::<libc::user_regs_struct>(rest, "NT_PRSTATUS")}?.0)
+                        };
+                        (prstatus.si_signo, prstatus.pr_pid, regs)
+                    }
+                };
                 // Core dump has the same signo on all threads for some reason, but we want to show it only on the thread that received it (which is always listed first).
-                let signal = if threads.is_empty() && prstatus.si_signo != 0 {Some(prstatus.si_signo)} else {None};
+                let signal = if threads.is_empty() && si_signo != 0 {Some(si_signo)} else {None};
                 let mut extra_regs = LazyExtraRegisters::default();
                 extra_regs.set_error(error!(ProcessState, "no simd registers in core dump"));
-                threads.push((prstatus.pr_pid, ThreadInfo {regs: Registers::from_ptrace(&prstatus.pr_reg), extra_regs, ..Default::default()}, signal));
+                threads.push((pr_pid, ThreadInfo {regs, extra_regs, ..Default::default()}, signal));
             }
-            NT_PRPSINFO => {
+            NT_PRPSINFO if !elf.is_32_bit() => {
                 let (_prpsinfo, _) = unsafe {memcpy_struct::<elf_prpsinfo>(note.desc, "NT_PRPSINFO")}?;
                 // (elf_prpsinfo doesn't seem to have any information useful to us.)
             }
@@ -284,14 +311,17 @@ pub fn parse_core_dump(elf: Arc<ElfFile>) -> Result<(CoreDumpMemReader, Vec<(pid
             }
             NT_FILE => {
                 let mut reader = io::Cursor::new(note.desc);
-                let count = reader.read_u64()? as usize;
-                let page_size = reader.read_u64()? as usize;
+                // Array of longs, which are 4 bytes in 32-bit processes.
+                let is_32_bit = elf.is_32_bit();
+                let read_word = |reader: &mut io::Cursor<&[u8]>| -> io::Result<usize> { Ok(if is_32_bit {reader.read_u32()? as usize} else {reader.read_u64()? as usize}) };
+                let count = read_word(&mut reader)?;
+                let page_size = read_word(&mut reader)?;
                 if !maps.is_empty() { return err!(MalformedExecutable, "multiple NT_FILE notes in core dump"); }
                 for i in 0..count {
-                    let start_address = reader.read_u64()? as usize;
-                    let end_address = reader.read_u64()? as usize;
+                    let start_address = read_word(&mut reader)?;
+                    let end_address = read_word(&mut reader)?;
                     if end_address < start_address { return err!(MalformedExecutable, "inverted address range in NT_FILE"); }
-                    let offset_pages = reader.read_u64()? as usize;
+                    let offset_pages = read_word(&mut reader)?;
 
                     // Find mmaps overlapping this range.
                     let mut perms = MemMapPermissions::empty();
@@ -342,8 +372,9 @@ pub fn parse_core_dump(elf: Arc<ElfFile>) -> Result<(CoreDumpMemReader, Vec<(pid
     for range in ranges.iter_mut().rev() {
         if range.permissions.contains(MemMapPermissions::EXECUTE) && range.size >= 4096 && range.size <= 65536 {
             if let &CoreDumpMemorySource::File {ref file, offset} = &range.source {
-                // Beginning of expected ELF header: magic bytes, 64-bit, little-endian, version 1, System V ABI, ABI version 0, padding.
-                if &file.data[offset..offset+16] == &[0x7f, 0x45, 0x4c, 0x46, 0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00] {
+                // Beginning of expected ELF header: magic bytes, 64-bit (or 32-bit if the process is), little-endian, version 1, System V ABI, ABI version 0, padding.
+                let class = if elf.is_32_bit() {0x01} else {0x02};
+                if &file.data[offset..offset+16] == &[0x7f, 0x45, 0x4c, 0x46, class, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00] {
                     let path = "[vdso]".to_string();
                     maps.push(MemMapInfo {start: range.start_address, len: range.size, perms: range.permissions, offset: 0, inode: 0, path: Some(path.clone()), binary_locator: Some(BinaryLocator {path: path.clone(), inode: 0, special: SpecialSegmentId::Vdso(range.start_address..range.start_address+range.size)}), binary_id: None, elf_seen: false});
                 }
@@ -431,13 +462,12 @@ pub fn is_core_dump_file(path: &str) -> Result<bool> {
 // `len` is the length of the prefix of the file that we have available, not necessarily the whole file.
 pub fn extract_build_id_from_mapped_elf(memory: &MemReader, addr: usize, len: usize) -> Result<Vec<u8>> {
     let mut reader = CachedMemReader::new(memory.clone());
-    let mut header: libc::Elf64_Ehdr;
-    unsafe {
-        header = mem::zeroed();
-        reader.read(addr, slice::from_raw_parts_mut(&raw mut header as *mut u8, mem::size_of::<libc::Elf64_Ehdr>()))?;
-    }
+    let mut buf = [0u8; mem::size_of::<libc::Elf64_Ehdr>()];
+    reader.read(addr, &mut buf)?;
+    let header = parse_elf_header(&buf)?;
     if &header.e_ident[..4] != &[0x7f, 0x45, 0x4c, 0x46] { return err!(MalformedExecutable, "invalid ELF magic bytes: {}", hexdump(&header.e_ident[..4], 100)); }
-    let phdr_size = mem::size_of::<libc::Elf64_Phdr>();
+    let is_32_bit = header.e_ident[4] == 1;
+    let phdr_size = program_header_size(is_32_bit);
     if (header.e_phentsize as usize) < phdr_size { return err!(MalformedExecutable, "section header size too small: {}", header.e_phentsize); }
 
     let mut have_notes_out_of_bounds = false;
@@ -446,11 +476,9 @@ pub fn extract_build_id_from_mapped_elf(memory: &MemReader, addr: usize, len: us
         if offset.saturating_add(phdr_size) > len {
             return err!(MalformedExecutable, "segment header is outside of mapped range");
         }
-        let mut segment: libc::Elf64_Phdr;
-        unsafe {
-            segment = mem::zeroed();
-            reader.read(offset.saturating_add(addr), slice::from_raw_parts_mut(&raw mut segment as *mut u8, phdr_size))?;
-        }
+        let mut buf = [0u8; mem::size_of::<libc::Elf64_Phdr>()];
+        reader.read(offset.saturating_add(addr), &mut buf[..phdr_size])?;
+        let segment = parse_program_header(&buf, is_32_bit)?;
         if segment.p_type != PT_NOTE {
             continue;
         }
@@ -491,16 +519,16 @@ pub fn extract_build_id_from_mapped_elf(memory: &MemReader, addr: usize, len: us
 // ... After implementing this, I noticed that usually core dumps don't have .eh_frame anyway. Either it's usually not resident or the core dump code excludes it. Oops. This code can probably be removed.
 pub fn reconstruct_elf_from_mapped_parts(name: String, memory: &Arc<CoreDumpMemReader>, maps: MemMapsInfo, elf_prefix_addr: Range<usize>) -> Result<ElfFile> {
     let mut reader = CachedMemReader::new(MemReader::CoreDump(memory.clone()));
-    let mut header: libc::Elf64_Ehdr;
-    unsafe {
-        header = mem::zeroed();
-        reader.read(elf_prefix_addr.start, slice::from_raw_parts_mut(&raw mut header as *mut u8, mem::size_of::<libc::Elf64_Ehdr>()))?;
-    }
+    let mut buf = [0u8; mem::size_of::<libc::Elf64_Ehdr>()];
+    reader.read(elf_prefix_addr.start, &mut buf)?;
+    let header = parse_elf_header(&buf)?;
     if &header.e_ident[..4] != &[0x7f, 0x45, 0x4c, 0x46] { return err!(MalformedExecutable, "invalid ELF magic bytes: {}", hexdump(&header.e_ident[..4], 100)); }
-    let phdr_size = mem::size_of::<libc::Elf64_Phdr>();
+    let is_32_bit = header.e_ident[4] == 1;
+    let phdr_size = program_header_size(is_32_bit);
     if (header.e_phentsize as usize) < phdr_size { return err!(MalformedExecutable, "section header size too small: {}", header.e_phentsize); }
 
-    let mut elf = ElfFile {name, segments: Vec::new(), sections: Vec::new(), entry_point: 0, section_by_offset: Vec::new(), section_by_name: HashMap::new(), text_section: None, is_core_dump: false, is_reconstructed: true, build_id: None, mmapped: None, owned: Vec::new(), data: &[], r_debug_ptr_addr: None, interp: None};
+    let arch = Arch::from_elf_header(header.e_machine, is_32_bit)?;
+    let mut elf = ElfFile {name, arch, segments: Vec::new(), sections: Vec::new(), entry_point: 0, section_by_offset: Vec::new(), section_by_name: HashMap::new(), text_section: None, is_core_dump: false, is_reconstructed: true, build_id: None, mmapped: None, owned: Vec::new(), data: &[], r_debug_ptr_addr: None, interp: None};
 
     // Find .eh_frame, .text (very roughly), .dynstr, .dynsym.
 
@@ -516,11 +544,9 @@ pub fn reconstruct_elf_from_mapped_parts(name: String, memory: &Arc<CoreDumpMemR
         if offset.saturating_add(phdr_size) > elf_prefix_addr.len() {
             return err!(MalformedExecutable, "segment header is outside of mapped range");
         }
-        let mut segment: libc::Elf64_Phdr;
-        unsafe {
-            segment = mem::zeroed();
-            reader.read(offset.saturating_add(elf_prefix_addr.start), slice::from_raw_parts_mut(&raw mut segment as *mut u8, phdr_size))?;
-        }
+        let mut buf = [0u8; mem::size_of::<libc::Elf64_Phdr>()];
+        reader.read(offset.saturating_add(elf_prefix_addr.start), &mut buf[..phdr_size])?;
+        let segment = parse_program_header(&buf, is_32_bit)?;
 
         if segment.p_memsz == 0 || segment.p_filesz == 0 {
             continue;
@@ -628,6 +654,58 @@ pub fn reconstruct_elf_from_mapped_parts(name: String, memory: &Arc<CoreDumpMemR
     Ok(elf)
 }
 
+// 32-bit ELF structs have the same fields as the 64-bit ones, just narrower (and reordered in Elf32_Phdr and Elf32_Sym).
+// We convert them to the 64-bit structs right after reading, so the rest of the code doesn't care.
+
+fn parse_elf_header(data: &[u8]) -> Result<libc::Elf64_Ehdr> {
+    if data.len() <= 4 || data[4] != 1 {
+        return Ok(unsafe {memcpy_struct::<libc::Elf64_Ehdr>(data, "Elf64_Ehdr")}?.0);
+    }
+    let (h, _) = unsafe {memcpy_struct::<libc::Elf32_Ehdr>(data, "Elf32_Ehdr")}?;
+    Ok(libc::Elf64_Ehdr {e_ident: h.e_ident, e_type: h.e_type, e_machine: h.e_machine, e_version: h.e_version, e_entry: h.e_entry as u64, e_phoff: h.e_phoff as u64, e_shoff: h.e_shoff as u64, e_flags: h.e_flags, e_ehsize: h.e_ehsize, e_phentsize: h.e_phentsize, e_phnum: h.e_phnum, e_shentsize: h.e_shentsize, e_shnum: h.e_shnum, e_shstrndx: h.e_shstrndx})
+}
+
+fn program_header_size(is_32_bit: bool) -> usize {
+    if is_32_bit {mem::size_of::<libc::Elf32_Phdr>()} else {mem::size_of::<libc::Elf64_Phdr>()}
+}
+
+fn parse_program_header(data: &[u8], is_32_bit: bool) -> Result<libc::Elf64_Phdr> {
+    if !is_32_bit {
+        return Ok(unsafe {memcpy_struct::<libc::Elf64_Phdr>(data, "Elf64_Phdr")}?.0);
+    }
+    let (p, _) = unsafe {memcpy_struct::<libc::Elf32_Phdr>(data, "Elf32_Phdr")}?;
+    Ok(libc::Elf64_Phdr {p_type: p.p_type, p_flags: p.p_flags, p_offset: p.p_offset as u64, p_vaddr: p.p_vaddr as u64, p_paddr: p.p_paddr as u64, p_filesz: p.p_filesz as u64, p_memsz: p.p_memsz as u64, p_align: p.p_align as u64})
+}
+
+fn parse_section_header(data: &[u8], is_32_bit: bool) -> Result<libc::Elf64_Shdr> {
+    if !is_32_bit {
+        return Ok(unsafe {memcpy_struct::<libc::Elf64_Shdr>(data, "Elf64_Shdr")}?.0);
+    }
+    let (s, _) = unsafe {memcpy_struct::<libc::Elf32_Shdr>(data, "Elf32_Shdr")}?;
+    Ok(libc::Elf64_Shdr {sh_name: s.sh_name, sh_type: s.sh_type, sh_flags: s.sh_flags as u64, sh_addr: s.sh_addr as u64, sh_offset: s.sh_offset as u64, sh_size: s.sh_size as u64, sh_link: s.sh_link, sh_info: s.sh_info, sh_addralign: s.sh_addralign as u64, sh_entsize: s.sh_entsize as u64})
+}
+
+fn parse_compression_header(data: &[u8], is_32_bit: bool) -> Result<libc::Elf64_Chdr> {
+    if !is_32_bit {
+        return Ok(unsafe {memcpy_struct::<libc::Elf64_Chdr>(data, "Elf64_Chdr")}?.0);
+    }
+    let (c, _) = unsafe {memcpy_struct::<libc::Elf32_Chdr>(data, "Elf32_Chdr")}?;
+    Ok(libc::Elf64_Chdr {ch_type: c.ch_type, ch_reserved: 0, ch_size: c.ch_size as u64, ch_addralign: c.ch_addralign as u64})
+}
+
+pub fn elf_symbol_size(is_32_bit: bool) -> usize {
+    if is_32_bit {mem::size_of::<libc::Elf32_Sym>()} else {mem::size_of::<libc::Elf64_Sym>()}
+}
+
+// .symtab or .dynsym entry.
+pub fn parse_elf_symbol(data: &[u8], is_32_bit: bool) -> Result<libc::Elf64_Sym> {
+    if !is_32_bit {
+        return Ok(unsafe {memcpy_struct::<libc::Elf64_Sym>(data, "Elf64_Sym")}?.0);
+    }
+    let (s, _) = unsafe {memcpy_struct::<libc::Elf32_Sym>(data, "Elf32_Sym")}?;
+    Ok(libc::Elf64_Sym {st_name: s.st_name, st_info: s.st_info, st_other: s.st_other, st_shndx: s.st_shndx, st_value: s.st_value as u64, st_size: s.st_size as u64})
+}
+
 // Read the ELF headers.
 fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned: Vec<u8>) -> Result<ElfFile> {
     let len;
@@ -647,12 +725,12 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
         None => &owned[..len],
     };
 
-    let (header, _) = unsafe {memcpy_struct::<libc::Elf64_Ehdr>(data, "Elf64_Ehdr")}?;
+    let header = parse_elf_header(data)?;
 
     if &header.e_ident[..4] != &[0x7f, 0x45, 0x4c, 0x46] { return err!(MalformedExecutable, "invalid ELF magic bytes: {}", hexdump(&header.e_ident[..4], 100)); }
 
-    if header.e_ident[4] == 1 { return err!(UnsupportedExecutable, "32-bit executables are not supported"); }
-    if header.e_ident[4] != 2 { return err!(MalformedExecutable, "invalid EI_CLASS: {}", header.e_ident[4]); }
+    if header.e_ident[4] != 1 && header.e_ident[4] != 2 { return err!(MalformedExecutable, "invalid EI_CLASS: {}", header.e_ident[4]); }
+    let is_32_bit = header.e_ident[4] == 1;
 
     if header.e_ident[5] == 2 { return err!(UnsupportedExecutable, "big-endian executables are not supported"); }
     if header.e_ident[5] != 1 { return err!(MalformedExecutable, "invalid EI_DATA: {}", header.e_ident[5]); }
@@ -663,23 +741,23 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
     if abi != 0 && abi != 3 { return err!(UnsupportedExecutable, "only Linux and System V ABIs are supported (got: EI_OSABI = {})", abi); }
 
     let is_core_dump = header.e_type == 4;
-    // 3 is "Shared object", and some executables use it.
-    if !is_core_dump && header.e_type != 2 && header.e_type != 3 { return err!(UnsupportedExecutable, "unexpected or unsupported species of elf: e_type = {}", header.e_type); }
+    // 3 is "Shared object", and some executables use it. 1 is "Relocatable", e.g. .dwo files with split debug info.
+    if !is_core_dump && header.e_type != 1 && header.e_type != 2 && header.e_type != 3 { return err!(UnsupportedExecutable, "unexpected or unsupported species of elf: e_type = {}", header.e_type); }
 
-    if header.e_machine != 0x3e { return err!(UnsupportedExecutable, "only AMD x86-64 executables are supported, for now (got: e_machine = {})", header.e_machine); }
+    let arch = Arch::from_elf_header(header.e_machine, is_32_bit)?;
 
     if header.e_version != 1 { return err!(MalformedExecutable, "invalid e_version: {}", header.e_version); }
 
     let entry_point = header.e_entry as usize;
 
-    if header.e_phnum > 0 && (header.e_phentsize as usize) < mem::size_of::<libc::Elf64_Phdr>() { return err!(MalformedExecutable, "ELF e_phentsize too small in {}", name); }
-    if header.e_shnum > 0 && (header.e_shentsize as usize) < mem::size_of::<libc::Elf64_Shdr>() { return err!(MalformedExecutable, "ELF e_shentsize too small in {}", name); }
+    if header.e_phnum > 0 && (header.e_phentsize as usize) < program_header_size(is_32_bit) { return err!(MalformedExecutable, "ELF e_phentsize too small in {}", name); }
+    if header.e_shnum > 0 && (header.e_shentsize as usize) < if is_32_bit {mem::size_of::<libc::Elf32_Shdr>()} else {mem::size_of::<libc::Elf64_Shdr>()} { return err!(MalformedExecutable, "ELF e_shentsize too small in {}", name); }
     if (header.e_phnum as usize).saturating_mul(header.e_phentsize as usize).saturating_add(header.e_phoff as usize) > data.len() { return err!(MalformedExecutable, "ELF program header out of bounds in {}", name); }
     if (header.e_shnum as usize).saturating_mul(header.e_shentsize as usize).saturating_add(header.e_shoff as usize) > data.len() { return err!(MalformedExecutable, "ELF section header out of bounds in {}", name); }
 
     let mut segments: Vec<ElfSegment> = Vec::new();
     for idx in 0..header.e_phnum as usize {
-        let (segment, _) = unsafe {memcpy_struct::<libc::Elf64_Phdr>(&data[header.e_phoff as usize + idx * header.e_phentsize as usize..], "Elf64_Phdr").unwrap()};
+        let segment = parse_program_header(&data[header.e_phoff as usize + idx * header.e_phentsize as usize..], is_32_bit).unwrap();
 
         let mut offset = segment.p_offset as usize;
         let mut size_in_file = segment.p_filesz as usize;
@@ -694,7 +772,7 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
 
     let mut sections: Vec<ElfSection> = Vec::new();
     for idx in 0..header.e_shnum as usize {
-        let (section, _) = unsafe {memcpy_struct::<libc::Elf64_Shdr>(&data[header.e_shoff as usize + idx * header.e_shentsize as usize..], "Elf64_Shdr").unwrap()};
+        let section = parse_section_header(&data[header.e_shoff as usize + idx * header.e_shentsize as usize..], is_32_bit).unwrap();
         sections.push(ElfSection {
             idx, name: String::new(), name_offset_in_strtab: section.sh_name, section_type: section.sh_type, flags: section.sh_flags,
             address: section.sh_addr as usize, offset: section.sh_offset as usize, size: section.sh_size as usize, link: section.sh_link,
@@ -703,7 +781,7 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
 
     let data: &'static [u8] = unsafe {mem::transmute(data)};
 
-    let mut elf = ElfFile {name, mmapped, owned, data, segments, sections, entry_point, section_by_offset: Vec::new(), section_by_name: HashMap::new(), text_section: None, is_core_dump, is_reconstructed: false, build_id: None, r_debug_ptr_addr: None, interp: None};
+    let mut elf = ElfFile {name, arch, mmapped, owned, data, segments, sections, entry_point, section_by_offset: Vec::new(), section_by_name: HashMap::new(), text_section: None, is_core_dump, is_reconstructed: false, build_id: None, r_debug_ptr_addr: None, interp: None};
 
     for idx in 0..elf.sections.len() {
         let name = unsafe{elf.str_from_strtab(elf.sections[header.e_shstrndx as usize].offset, elf.sections[idx].name_offset_in_strtab as usize)?}.to_string();
@@ -719,7 +797,7 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
 
         if s.flags & SHF_COMPRESSED != 0 {
             let compressed = &elf.data[s.offset..s.offset+s.size_in_file()];
-            s.compression_header = Some(unsafe {memcpy_struct(compressed, "Elf64_Chdr")}?.0);
+            s.compression_header = Some(parse_compression_header(compressed, is_32_bit)?);
         }
 
         if s.size_in_file() != 0 {
@@ -727,8 +805,8 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
         }
 
         let prev = elf.section_by_name.insert(s.name.clone(), idx);
-        // (Core dumps produced by gcore have lots of sections named "load".)
-        if prev.is_some() && !is_core_dump {
+        // (Core dumps produced by gcore have lots of sections named "load". Relocatable files have a section per COMDAT group, e.g. .debug_types.dwo in .dwo files.)
+        if prev.is_some() && !is_core_dump && header.e_type != 1 {
             eprintln!("warning: ELF has duplicate section name: {}", s.name);
         }
 
@@ -770,14 +848,17 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
     let mut r_debug_ptr_addr = None;
     if let Some(&section_idx) = elf.section_by_name.get(".dynamic") {
         let data = elf.section_data(section_idx)?;
+        // Array of (tag, value) pairs of pointer-sized words.
+        let word = arch.pointer_size();
         let mut offset = 0;
-        while offset + 16 <= data.len() {
-            let tag = usize::from_le_bytes(data[offset..offset+8].try_into().unwrap());
-            if tag == 21 { // DT_DEBUG
-                r_debug_ptr_addr = Some(elf.sections[section_idx].address + offset + 8);
+        while offset + word * 2 <= data.len() {
+            let mut tag = [0u8; 8];
+            tag[..word].copy_from_slice(&data[offset..offset+word]);
+            if u64::from_le_bytes(tag) == 21 { // DT_DEBUG
+                r_debug_ptr_addr = Some(elf.sections[section_idx].address + offset + word);
                 break;
             }
-            offset += 16;
+            offset += word * 2;
         }
     }
     elf.r_debug_ptr_addr = r_debug_ptr_addr;
@@ -792,7 +873,7 @@ fn open_elf(name: String, file: Option<(&File, /*file_len*/ usize)>, mut owned:
 
     if let Some(s) = &elf.build_id {
         eprintln!("info: build id {} in {}", hexdump(s, 1000), elf.name);
-    } else if !elf.is_core_dump && elf.name != "[vdso]" {
+    } else if !elf.is_core_dump && elf.name != "[vdso]" && header.e_type != 1 { // .dwo and .dwp files don't have build ids
         eprintln!("warning: no build id in {}", elf.name);
     }
 
@@ -30,6 +30,7 @@ pub enum ErrorCode {
     NotContainer = 29,
     Network = 30,
     Disabled = 31,
+    Pending = 32,
 }
 
 #[derive(Debug)]
@@ -70,6 +71,7 @@ impl Error {
     pub fn is_io_invalid_input(&self) -> bool { match &self.error { ErrorEnum::IO(e) if e.kind() == io::ErrorKind::InvalidInput => true, _ => false, } }
     pub fn is_not_calculated(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::NotCalculated) => true, _ => false, } }
     pub fn is_no_field(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::NoField) => true, _ => false, } }
+    pub fn is_pending(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::Pending) => true, _ => false, } }
     pub fn is_no_function(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::NoFunction) => true, _ => false, } }
     pub fn is_no_variable(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::NoVariable) => true, _ => false, } }
     pub fn is_not_container(&self) -> bool { match &self.error { ErrorEnum::Code(ErrorCode::NotContainer) => true, _ => false, } }
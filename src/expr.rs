@@ -1,6 +1,6 @@
-use crate::{*, error::{*, Result, Error}, util::*, registers::*, types::*, procfs::*, symbols_registry::*, process_info::*, unwind::*, symbols::*, arena::*, pretty::*, settings::*, common_ui::*, dwarf::*};
-use std::{fmt, fmt::Write, mem, collections::{HashMap, HashSet}, io::Write as ioWrite, borrow::Cow, ops::Range, path::Path};
-use gimli::{Operation, Expression, Encoding, EvaluationResult, ValueType, DieReference, DW_AT_location, Location, DebugInfoOffset, Reader};
+use crate::{*, error::{*, Result, Error}, util::*, registers::*, arch::*, types::*, procfs::*, symbols_registry::*, process_info::*, unwind::*, symbols::*, arena::*, pretty::*, settings::*, common_ui::*, dwarf::*, os::*, debugger::BreakpointLocation};
+use std::{fmt, fmt::Write, mem, str, collections::{HashMap, HashSet}, io::Write as ioWrite, borrow::Cow, ops::Range, path::Path, time::{Duration, Instant}};
+use gimli::{Operation, Expression, Encoding, EvaluationResult, ValueType, DieReference, DW_AT_location, Location, DebugInfoOffset, Reader, AttributeValue, DebuggingInformationEntry, Abbreviations, UnitOffset, DW_AT_type, DW_AT_name, DW_AT_specification, DW_AT_abstract_origin, DW_AT_linkage_name, DW_AT_MIPS_linkage_name, DW_AT_byte_size, DW_AT_encoding, DW_TAG_pointer_type, DW_TAG_reference_type, DW_TAG_rvalue_reference_type, DW_TAG_const_type, DW_TAG_volatile_type, DW_TAG_restrict_type, DW_TAG_atomic_type, DW_TAG_typedef, DW_TAG_unspecified_type, DW_TAG_base_type, DW_TAG_structure_type, DW_TAG_class_type, DW_TAG_union_type, DW_TAG_enumeration_type, DW_TAG_subprogram, DW_TAG_namespace, DW_ATE_boolean, DW_ATE_float, DW_ATE_signed, DW_ATE_signed_char, DW_ATE_unsigned_char, DW_ATE_UTF, DW_ATE_address, DW_ATE_unsigned, DW_AT_calling_convention, DW_CC_pass_by_reference, DW_AT_artificial, DW_AT_virtuality, DW_AT_defaulted, DW_DEFAULTED_in_class, DW_TAG_inheritance, DW_TAG_formal_parameter};
 use bitflags::*;
 use rand::random;
 
@@ -413,6 +413,22 @@ pub struct EvalState {
     pub types: Types,
     pub builtin_types: BuiltinTypes,
     pub variables: HashMap<String, Value>,
+    // Variables declared with `let`, innermost last. Only the suffix starting at local_variables_start is visible (the rest belongs to script function calls up the stack).
+    pub local_variables: Vec<(String, Value)>,
+    pub local_variables_start: usize,
+    // Functions and structs defined in the script language. Copied into Debugger for breakpoint conditions to use.
+    pub definitions: interp::ScriptDefinitions,
+    // Types instantiated from `definitions.structs`, by name. The TypeInfo-s live in `types`.
+    pub script_types: HashMap<String, *const TypeInfo>,
+    // Values of the lhs of compound assignments (`a += b`) being evaluated, innermost last.
+    pub compound_assignment_lhs: Vec<Value>,
+    // Set when `break`, `continue` or `return` is unwinding to the enclosing loop or function (as an Err).
+    pub control_flow: Option<interp::ControlFlow>,
+    // How many more AST nodes we may evaluate before giving up, to not hang the UI on infinite loops. Reset by eval_parsed_expression().
+    pub steps_left: usize,
+    // Time that debuggee function calls may still take, shared by all calls in the expression. Reset together with steps_left.
+    pub function_call_time_left: Duration,
+    pub call_depth: usize,
     // We may add things like name lookup cache (for types and global variables) here, though maybe we should avoid slow lookups here and expect the user to use search dialog to look up canonical names for things, maybe even automatically adding alias watches to shorten.
 }
 
@@ -420,21 +436,39 @@ impl EvalState {
     pub fn new() -> Self {
         let mut types = Types::new();
         let builtin_types = types.add_builtins();
-        Self { currently_evaluated_value_dubious: false, types, builtin_types, variables: HashMap::new() } }
+        Self { currently_evaluated_value_dubious: false, types, builtin_types, variables: HashMap::new(), local_variables: Vec::new(), local_variables_start: 0, definitions: interp::ScriptDefinitions::default(), script_types: HashMap::new(), compound_assignment_lhs: Vec::new(), control_flow: None, steps_left: interp::MAX_EVAL_STEPS, function_call_time_left: interp::MAX_FUNCTION_CALL_TIME, call_depth: 0 } }
 
     pub fn clear(&mut self) {
         self.types = Types::new();
         self.builtin_types = self.types.add_builtins();
         self.variables.clear();
+        self.local_variables.clear();
+        self.local_variables_start = 0;
+        self.definitions = interp::ScriptDefinitions::default();
+        self.script_types.clear();
+        self.control_flow = None;
     }
 
     pub fn get_variable(&mut self, context: &mut EvalContext, name: &str, maybe_register: bool, from_any_frame: bool, only_type: bool, meta: bool) -> Result<Value> {
         assert!(!meta || (!maybe_register && !only_type));
+        if name == "#syscall" && !meta {
+            return match &context.syscall {
+                Some(s) => Ok(self.make_syscall_value(s)),
+                None => err!(NoVariable, "not stopped at a syscall"),
+            };
+        }
+        if name == "$ret" && !meta {
+            return match context.function_return {
+                Some(r) => self.make_function_return_value(&r, context),
+                None => err!(NoVariable, "no function returned during the last step"),
+            };
+        }
         let global_alt_name = if name.starts_with("::") {Some(&name[2..])} else {None};
         if !context.stack.frames.is_empty() && (global_alt_name.is_none() || from_any_frame) {
             // Try register.
             if maybe_register {
-                if let Some(reg) = RegisterIdx::parse_ignore_case(name) {
+                let arch = context.stack.frames[0].regs.arch;
+                if let Some(reg) = arch.parse_register(name) {
                     let type_ = self.builtin_types.u64_;
                     if only_type {
                         return Ok(Value {val: Default::default(), type_, flags: ValueFlags::empty()});
@@ -520,9 +554,161 @@ impl EvalState {
                 }
             }
         }
+        if let Some(t) = self.builtin_types.find_primitive_by_name(name) {
+            return Ok(t);
+        }
         err!(TypeMismatch, "no type '{}'", name)
     }
 
+    // Looks up a debuggee function to call from an expression, by qualified name (e.g. "foo" or "ns::Foo::bar") or by mangled name.
+    // If there are multiple functions with this name (e.g. overloads), prefers the one with debug info and matching number of parameters.
+    pub fn find_function(&mut self, context: &mut EvalContext, name: &str, num_args: usize) -> Result<DebuggeeFunction> {
+        let name = name.strip_prefix("::").unwrap_or(name);
+        self.find_function_by_patterns(context, &mangled_name_patterns(name), num_args, name)
+    }
+
+    // Looks up a method of a struct or class. `num_args` includes `this`.
+    pub fn find_method(&mut self, context: &mut EvalContext, t: &TypeInfo, name: &str, num_args: usize) -> Result<DebuggeeFunction> {
+        // C++ classes list their methods in debug info, with mangled names. This works for templates too, whose mangled names we can't construct from the type name.
+        if t.die.0 != 0 && t.die.0 < FAKE_DWARF_OFFSET_START {
+            if let Some(Ok(symbols)) = context.symbols_registry.get(t.binary_id).map(|b| &b.symbols) {
+                if let Ok(linkage_names) = DieReader::new(symbols, t.die).and_then(|r| r.method_linkage_names(t.die, name)) {
+                    if !linkage_names.is_empty() {
+                        let patterns: Vec<(String, MangledNameSuffix)> = linkage_names.into_iter().map(|s| (s, MangledNameSuffix::None)).collect();
+                        return self.find_function_by_patterns(context, &patterns, num_args, &format!("{}::{}", t.name, name));
+                    }
+                }
+            }
+        }
+        if t.name.is_empty() {
+            return err!(NoFunction, "method '{}' not found in anonymous type", name);
+        }
+        self.find_function(context, &format!("{}::{}", t.name, name), num_args)
+    }
+
+    fn find_function_by_patterns(&mut self, context: &mut EvalContext, patterns: &[(String, MangledNameSuffix)], num_args: usize, display_name: &str) -> Result<DebuggeeFunction> {
+        // Score: 2 if parameters match, 1 if they're unknown (no debug info), 0 if they don't match.
+        let mut best: Option<(usize, &Binary, &Symbols, &FunctionInfo)> = None;
+        for binary in context.symbols_registry.iter() {
+            if !binary.is_mapped {
+                continue;
+            }
+            let symbols = match &binary.symbols {
+                Ok(x) => x,
+                Err(_) => continue };
+            for shard in &symbols.shards {
+                let a = &shard.mangled_name_to_function;
+                for (prefix, suffix) in patterns {
+                    let mut idx = a.partition_point(|t| t.0 < prefix.as_bytes());
+                    while idx < a.len() && a[idx].0.starts_with(prefix.as_bytes()) {
+                        let (mangled_name, _, function_idx) = a[idx];
+                        idx += 1;
+                        if !suffix.matches(&mangled_name[prefix.len()..]) {
+                            continue;
+                        }
+                        let function = &symbols.functions[function_idx];
+                        if function.entry_addr().is_none() {
+                            continue;
+                        }
+                        let score = match function_parameter_types(symbols, function) {
+                            Some(p) if p.len() == num_args => 2,
+                            None => 1,
+                            Some(_) => 0 };
+                        if best.is_none_or(|b| score > b.0) {
+                            best = Some((score, binary, symbols, function));
+                        }
+                    }
+                }
+            }
+        }
+        let (_, binary, symbols, function) = match best {
+            None => return err!(NoFunction, "function '{}' not found", display_name),
+            Some(x) => x };
+        let param_types = function_parameter_types(symbols, function);
+        if let Some(p) = &param_types {
+            if p.len() != num_args {
+                return err!(TypeMismatch, "function '{}' expects {} arguments, got {}", display_name, p.len(), num_args);
+            }
+        }
+        let return_type = match function.debug_info_offset() {
+            None => None,
+            Some(offset) => Some(self.function_return_type(symbols, offset)?) };
+        Ok(DebuggeeFunction {addr: binary.addr_map.static_to_dynamic(function.entry_addr().unwrap()), param_types, return_type})
+    }
+
+    // We don't keep return types in FunctionInfo, so read it from debug info on demand.
+    fn function_return_type(&mut self, symbols: &Symbols, mut offset: DieOffset) -> Result<*const TypeInfo> {
+        // Out-of-line method definitions and concrete instances of inline functions refer to the DIE that has the signature.
+        for _ in 0..4 {
+            let reader = DieReader::new(symbols, offset)?;
+            let entry = reader.entry(offset)?;
+            if let Some(a) = entry.attr_value(DW_AT_type)? {
+                return self.type_from_die(symbols, reader.reference(a)?, 0);
+            }
+            match entry.attr_value(DW_AT_specification)?.or(entry.attr_value(DW_AT_abstract_origin)?) {
+                Some(a) => offset = reader.reference(a)?,
+                None => break,
+            }
+        }
+        Ok(self.builtin_types.void)
+    }
+
+    fn type_from_die(&mut self, symbols: &Symbols, offset: DieOffset, depth: usize) -> Result<*const TypeInfo> {
+        if depth > 20 {
+            return err!(Dwarf, "type reference chain too long @0x{:x}", offset.0);
+        }
+        let reader = DieReader::new(symbols, offset)?;
+        let entry = reader.entry(offset)?;
+        let inner_offset = match entry.attr_value(DW_AT_type)? {
+            None => None,
+            Some(a) => Some(reader.reference(a)?) };
+        match entry.tag() {
+            DW_TAG_pointer_type | DW_TAG_reference_type | DW_TAG_rvalue_reference_type => {
+                // Even if we can't figure out the pointee type, the pointer is still useful.
+                let pointee = match inner_offset {
+                    None => Ok(self.builtin_types.void),
+                    Some(o) => self.type_from_die(symbols, o, depth + 1) };
+                let flags = if entry.tag() == DW_TAG_pointer_type {PointerFlags::empty()} else {PointerFlags::REFERENCE};
+                Ok(self.types.add_pointer(pointee.unwrap_or(self.builtin_types.void), flags))
+            }
+            DW_TAG_const_type | DW_TAG_volatile_type | DW_TAG_restrict_type | DW_TAG_atomic_type | DW_TAG_typedef => match inner_offset {
+                None => Ok(self.builtin_types.void),
+                Some(o) => self.type_from_die(symbols, o, depth + 1),
+            }
+            DW_TAG_unspecified_type => Ok(self.builtin_types.void_pointer), // decltype(nullptr)
+            DW_TAG_base_type => {
+                let name = reader.name(&entry)?.unwrap_or("");
+                let size = match entry.attr_value(DW_AT_byte_size)?.and_then(|a| a.udata_value()) {
+                    None => return err!(Dwarf, "base type without size @0x{:x}", offset.0),
+                    Some(s) => s as usize };
+                let presumably_cpp = reader.unit.language.presumably_cpp();
+                let flags = match entry.attr_value(DW_AT_encoding)? {
+                    Some(AttributeValue::Encoding(DW_ATE_boolean)) => PrimitiveFlags::BOOL,
+                    Some(AttributeValue::Encoding(DW_ATE_float)) => PrimitiveFlags::FLOAT,
+                    Some(AttributeValue::Encoding(DW_ATE_signed)) => PrimitiveFlags::SIGNED,
+                    Some(AttributeValue::Encoding(DW_ATE_signed_char)) => PrimitiveFlags::SIGNED | PrimitiveFlags::CHAR | if presumably_cpp {PrimitiveFlags::AMBIGUOUS_CHAR} else {PrimitiveFlags::empty()},
+                    Some(AttributeValue::Encoding(DW_ATE_unsigned_char)) => PrimitiveFlags::CHAR,
+                    Some(AttributeValue::Encoding(DW_ATE_UTF)) => PrimitiveFlags::CHAR | if presumably_cpp {PrimitiveFlags::AMBIGUOUS_CHAR} else {PrimitiveFlags::empty()},
+                    Some(AttributeValue::Encoding(DW_ATE_address | DW_ATE_unsigned)) => PrimitiveFlags::empty(),
+                    e => return err!(NotImplemented, "unsupported base type encoding: {:?}", e),
+                };
+                let name = self.types.misc_arena.add_str(name);
+                Ok(self.types.add_primitive(name, size, flags))
+            }
+            DW_TAG_structure_type | DW_TAG_class_type | DW_TAG_union_type | DW_TAG_enumeration_type => {
+                // Find the type that the symbols loader created for this DIE, by name.
+                let name = reader.qualified_name(offset)?;
+                for shard in &symbols.shards {
+                    if let Some(t) = shard.types.find_by_name(&name) {
+                        return Ok(t);
+                    }
+                }
+                err!(Dwarf, "type '{}' not found", name)
+            }
+            tag => err!(NotImplemented, "unsupported type: {}", tag),
+        }
+    }
+
     // TODO: Check that the value size is not too small, in both get_*_variable(). Often debug info has invalid (?) expressions that return 8 bytes instead of sizeof(struct). E.g. I saw that for `fde` in list_lsdas().
     
     fn get_local_variable(&mut self, context: &mut EvalContext, name: &str, subframe_idx: usize, only_type: bool, meta: bool, found: &mut bool) -> Result<Value> {
@@ -578,6 +764,456 @@ impl EvalState {
         let blob = ValueBlob::from_u64_slice(val);
         Value {val: AddrOrValueBlob::Blob(blob), type_: array_type, flags: ValueFlags::HEX}
     }
+
+    // Struct with syscall number and arguments, decoded according to syscall_args().
+    pub fn make_syscall_value(&mut self, s: &SyscallStop) -> Value {
+        let mut builder = StructBuilder::default();
+        let nr_type = self.make_enum_type(self.builtin_types.u64_, [(syscall_name(s.nr), s.nr)].into_iter());
+        builder.add_usize_field("nr", s.nr, nr_type);
+        for (&(name, kind), &arg) in syscall_args(s.nr).iter().zip(s.args.iter()) {
+            let int = self.builtin_types.i32_;
+            let type_ = match kind {
+                SyscallArg::Int => int,
+                SyscallArg::UInt => self.builtin_types.u32_,
+                SyscallArg::Long => self.builtin_types.i64_,
+                SyscallArg::Size => self.builtin_types.u64_,
+                SyscallArg::Ptr => self.builtin_types.void_pointer,
+                SyscallArg::Str => {
+                    // C char, so that the pointer is shown as null-terminated string.
+                    let c_char = self.types.add_primitive("char", 1, PrimitiveFlags::SIGNED | PrimitiveFlags::CHAR | PrimitiveFlags::AMBIGUOUS_CHAR);
+                    self.types.add_pointer(c_char, PointerFlags::empty())
+                }
+                SyscallArg::DirFd if arg as i32 == AT_FDCWD => self.make_enum_type(int, [("AT_FDCWD", AT_FDCWD as isize as usize)].into_iter()),
+                SyscallArg::DirFd => int,
+                SyscallArg::OpenFlags => self.make_enum_type(int, OPEN_FLAG_NAMES.iter().copied()),
+                SyscallArg::Prot => self.make_enum_type(int, PROT_FLAG_NAMES.iter().copied()),
+                SyscallArg::MapFlags => self.make_enum_type(int, MAP_FLAG_NAMES.iter().copied()),
+                SyscallArg::Signal if (1..32).contains(&(arg as i32)) => self.make_enum_type(int, [(signal_name(arg as i32), arg as u32 as usize)].into_iter()),
+                SyscallArg::Signal => int,
+            };
+            let size = unsafe {(*type_).calculate_size()};
+            builder.add_blob_field(name, &arg.to_le_bytes()[..size], type_);
+        }
+        builder.finish("syscall", ValueFlags::empty(), &mut self.types)
+    }
+
+    pub fn make_function_return_value(&mut self, r: &FunctionReturn, context: &mut EvalContext) -> Result<Value> {
+        let symbols = match context.symbols_registry.get(r.binary_id) {
+            None => return err!(ProcessState, "binary was unloaded"),
+            Some(b) => b.symbols.as_ref_clone_error()? };
+        let offset = match symbols.functions.get(r.function_idx).and_then(|f| f.debug_info_offset()) {
+            None => return err!(MissingSymbols, "no debug info for the function"),
+            Some(o) => o };
+        let type_ = self.function_return_type(symbols, offset)?;
+        self.make_return_value(type_, &r.regs, context)
+    }
+
+    // Finds the return value of a function according to SysV x86-64 calling convention, given the registers right after the function returned.
+    // Structs returned in memory are read through the hidden pointer, which the function returns in rax. (That's only valid until the caller moves the value elsewhere.)
+    pub fn make_return_value(&mut self, type_: *const TypeInfo, r: &ReturnRegisters, context: &mut EvalContext) -> Result<Value> {
+        let mut inner = type_;
+        while let Type::Enum(e) = unsafe {&(*inner).t} {
+            inner = e.type_;
+        }
+        let t = unsafe {&*inner};
+        let size = t.calculate_size();
+        let blob = match &t.t {
+            Type::Primitive(_) if size == 0 => return Ok(StructBuilder::default().finish("()", ValueFlags::empty(), &mut self.types)),
+            Type::Primitive(f) if f.contains(PrimitiveFlags::FLOAT) => {
+                if size > 8 {
+                    return err!(NotImplemented, "{}-byte float return values are not supported", size);
+                }
+                ValueBlob::new(r.xmm0[0] as usize)
+            }
+            Type::Primitive(_) | Type::Pointer(_) if size <= 8 => ValueBlob::new(r.rax as usize),
+            Type::Primitive(_) if size == 16 => ValueBlob::from_two_usizes([r.rax as usize, r.rdx as usize]),
+            Type::Struct(_) => match Self::classify_returned_struct(t, size, context) {
+                None => return Ok(Value {val: AddrOrValueBlob::Addr(r.rax as usize), type_, flags: ValueFlags::empty()}),
+                Some(eightbytes) => {
+                    let (mut ints, mut floats) = ([r.rax, r.rdx].into_iter(), [r.xmm0[0], r.xmm1[0]].into_iter());
+                    let mut bytes: Vec<u8> = Vec::new();
+                    for is_float in eightbytes {
+                        let x = if is_float {floats.next()} else {ints.next()};
+                        bytes.extend_from_slice(&x.unwrap().to_le_bytes());
+                    }
+                    bytes.truncate(size);
+                    ValueBlob::from_vec(bytes)
+                }
+            }
+            _ => return err!(NotImplemented, "returning {} is not supported", t.t.kind_name()),
+        };
+        Ok(Value {val: AddrOrValueBlob::Blob(blob), type_, flags: ValueFlags::empty()})
+    }
+
+    // For a struct returned in registers, for each 8-byte chunk, whether it's in an xmm register (all fields overlapping it are floats) or a general-purpose one.
+    // None if the struct is returned in memory: bigger than 16 bytes, not trivially copyable (C++), or has fields that don't fit the scheme (long double, misaligned).
+    fn classify_returned_struct(t: &TypeInfo, size: usize, context: &EvalContext) -> Option<Vec<bool>> {
+        if size == 0 || size > 16 {
+            return None;
+        }
+        if Self::is_non_trivially_copyable(t, context, 0) {
+            return None;
+        }
+        let mut is_float = vec![true; size.div_ceil(8)];
+        if !Self::classify_fields(t, 0, &mut is_float) {
+            return None;
+        }
+        Some(is_float)
+    }
+
+    // C++ classes with non-trivial copy/move constructors or destructors, or virtual functions, are returned in memory regardless of size. Same for structs containing them.
+    fn is_non_trivially_copyable(t: &TypeInfo, context: &EvalContext, depth: usize) -> bool {
+        match &t.t {
+            Type::Struct(s) if depth < 20 => {
+                if let Some(Ok(symbols)) = context.symbols_registry.get(t.binary_id).map(|b| &b.symbols) {
+                    if DieReader::new(symbols, t.die).and_then(|r| r.has_non_trivial_special_members(t.die)).unwrap_or(false) {
+                        return true;
+                    }
+                }
+                s.fields().iter().any(|f| Self::is_non_trivially_copyable(unsafe {&*f.type_}, context, depth + 1))
+            }
+            Type::Array(a) if depth < 20 => Self::is_non_trivially_copyable(unsafe {&*a.type_}, context, depth + 1),
+            _ => false,
+        }
+    }
+
+    fn classify_fields(t: &TypeInfo, bit_offset: usize, is_float: &mut [bool]) -> bool {
+        let leaf_float = match &t.t {
+            Type::Struct(s) => return s.fields().iter().all(|f| {
+                if f.flags.contains(FieldFlags::SIZE_KNOWN) && f.bit_size != unsafe {(*f.type_).calculate_size() * 8} {
+                    // Bit field. (SIZE_KNOWN alone isn't enough, it's also set for regular fields with DW_AT_byte_size.)
+                    let r = (bit_offset + f.bit_offset)/64..(bit_offset + f.bit_offset + f.bit_size).div_ceil(64);
+                    is_float.get_mut(r).map(|s| s.fill(false)).is_some()
+                } else {
+                    Self::classify_fields(unsafe {&*f.type_}, bit_offset + f.bit_offset, is_float)
+                }
+            }),
+            Type::Array(a) if a.flags.contains(ArrayFlags::LEN_KNOWN) => {
+                let stride = if a.stride != 0 {a.stride} else {unsafe {(*a.type_).calculate_size()}};
+                return (0..a.len).all(|i| Self::classify_fields(unsafe {&*a.type_}, bit_offset + i * stride * 8, is_float));
+            }
+            Type::Enum(e) => return Self::classify_fields(unsafe {&*e.type_}, bit_offset, is_float),
+            Type::Primitive(f) if f.contains(PrimitiveFlags::FLOAT) => true,
+            Type::Primitive(_) | Type::Pointer(_) | Type::PointerToMember(_) => false,
+            _ => return false,
+        };
+        let size = t.calculate_size();
+        if size > 8 && leaf_float {
+            return false; // long double
+        }
+        if !bit_offset.is_multiple_of(8) || !(bit_offset / 8).is_multiple_of(size.max(1)) {
+            return false; // misaligned (packed struct)
+        }
+        let r = bit_offset/64..(bit_offset + size * 8).div_ceil(64);
+        match is_float.get_mut(r) {
+            None => false,
+            Some(s) => {
+                if !leaf_float {
+                    s.fill(false);
+                }
+                true
+            }
+        }
+    }
+
+    fn make_enum_type(&mut self, type_: *const TypeInfo, enumerands: impl Iterator<Item = (&'static str, usize)>) -> *const TypeInfo {
+        let mut en = EnumType {enumerands: &[], type_};
+        for (name, value) in enumerands {
+            self.types.add_enumerand(&mut en, Enumerand {name, value, flags: EnumerandFlags::empty()});
+        }
+        self.types.add_enum(en)
+    }
+}
+
+// A debuggee function that an expression wants to call.
+pub struct DebuggeeFunction {
+    pub addr: usize, // dynamic address of the entry point
+    pub param_types: Option<Vec<*const TypeInfo>>, // None if the function has no debug info
+    pub return_type: Option<*const TypeInfo>, // None if unknown; void if the function doesn't return anything
+}
+
+// How a mangled function name may continue after the prefix constructed by mangled_name_patterns().
+#[derive(Clone, Copy)]
+enum MangledNameSuffix {
+    None, // exact match
+    Params, // _Z3foo + parameter types, e.g. _Z3fooid
+    Nested, // _ZN2ns3foo + 'E' (end of name), 'I' (template arguments), 'B' (ABI tag), or Rust hash "17h<16 hex digits>E"
+}
+impl MangledNameSuffix {
+    fn matches(self, s: &[u8]) -> bool {
+        match self {
+            Self::None => s.is_empty(),
+            Self::Params => !s.is_empty() && !s[0].is_ascii_digit(),
+            Self::Nested => match s.first() {
+                Some(b'E' | b'I' | b'B') => true,
+                _ => s.len() >= 20 && s.starts_with(b"17h") && s[3..19].iter().all(|c| c.is_ascii_hexdigit()) && s[19] == b'E',
+            }
+        }
+    }
+}
+
+// Prefixes of mangled names that a function with the given qualified name may have: as is (C functions, or mangled name typed by the user),
+// Itanium C++ ABI, and legacy Rust mangling (which is the same plus a hash).
+fn mangled_name_patterns(name: &str) -> Vec<(String, MangledNameSuffix)> {
+    let mut r = vec![(name.to_string(), MangledNameSuffix::None)];
+    let components: Vec<&str> = name.split("::").collect();
+    if components.iter().any(|c| c.is_empty() || !c.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')) {
+        // Templates and such. Would need a real mangler.
+        return r;
+    }
+    let encoded: String = components.iter().map(|c| format!("{}{}", c.len(), c)).collect();
+    if components.len() == 1 {
+        r.push((format!("_Z{}", encoded), MangledNameSuffix::Params));
+    } else {
+        r.push((format!("_ZN{}", encoded), MangledNameSuffix::Nested));
+        r.push((format!("_ZNK{}", encoded), MangledNameSuffix::Nested)); // const method
+    }
+    r
+}
+
+// Types of formal parameters (including `this`), in order. None if there's no debug info.
+fn function_parameter_types(symbols: &Symbols, function: &FunctionInfo) -> Option<Vec<*const TypeInfo>> {
+    let (subfunction, _) = symbols.root_subfunction(function)?;
+    let mut seen: Vec<DieOffset> = Vec::new();
+    let mut types: Vec<*const TypeInfo> = Vec::new();
+    // A variable appears once per address range, so deduplicate. Variables without a DIE offset can't be told apart, keep them all.
+    for v in symbols.local_variables_in_subfunction(subfunction, function.shard_idx()) {
+        if !v.flags().contains(VariableFlags::PARAMETER) {
+            continue;
+        }
+        if let Some(offset) = v.debug_info_offset() {
+            if seen.contains(&offset) {
+                continue;
+            }
+            seen.push(offset);
+        }
+        types.push(v.type_);
+    }
+    Some(types)
+}
+
+// For reading individual DIEs on demand, outside the symbols loader.
+// The loader parses abbreviations its own way and doesn't keep gimli's Abbreviations, so we parse them again for the unit.
+struct DieReader<'a> {
+    symbols: &'a Symbols,
+    unit: &'a DwarfUnit,
+    abbreviations: Abbreviations,
+}
+impl<'a> DieReader<'a> {
+    // `offset` is any DIE in the unit.
+    fn new(symbols: &'a Symbols, offset: DieOffset) -> Result<Self> {
+        let unit = symbols.find_unit(offset)?;
+        let abbreviations = unit.unit.header.abbreviations(&unit.dwarf(&symbols.dwarf).debug_abbrev)?;
+        Ok(Self {symbols, unit, abbreviations})
+    }
+
+    fn unit_offset(&self, offset: DieOffset) -> Result<UnitOffset> {
+        match offset.to_unit_offset(&self.unit.unit.header) {
+            None => err!(Dwarf, "DIE offset out of bounds: 0x{:x}", offset.0),
+            Some(o) => Ok(o),
+        }
+    }
+
+    fn entry(&self, offset: DieOffset) -> Result<DebuggingInformationEntry<'_, '_, DwarfSlice>> {
+        Ok(self.unit.unit.header.entry(&self.abbreviations, self.unit_offset(offset)?)?)
+    }
+
+    fn reference(&self, attr: AttributeValue<DwarfSlice>) -> Result<DieOffset> {
+        match attr {
+            AttributeValue::UnitRef(o) => match o.to_debug_info_offset(&self.unit.unit.header) {
+                None => err!(Dwarf, "DIE reference out of bounds"),
+                Some(o) => Ok(o),
+            }
+            AttributeValue::DebugInfoRef(o) => Ok(DebugInfoOffset(o.0 + self.unit.offset_shift)),
+            AttributeValue::DebugInfoRefSup(o) => match self.symbols.supplementary_offset_shift {
+                None => err!(MissingSymbols, "reference to supplementary file, but it's not loaded"),
+                Some(shift) => Ok(DebugInfoOffset(o.0 + shift)),
+            }
+            AttributeValue::DebugTypesRef(signature) => match self.symbols.type_units.get(&signature) {
+                None => err!(Dwarf, "type unit not found for signature 0x{:x}", signature.0),
+                Some(&o) => Ok(o),
+            }
+            _ => err!(NotImplemented, "unsupported DIE reference: {:?}", attr),
+        }
+    }
+
+    fn string(&self, attr: AttributeValue<DwarfSlice>) -> Result<&'static [u8]> {
+        Ok(self.unit.dwarf(&self.symbols.dwarf).attr_string(&self.unit.unit, attr)?.slice())
+    }
+
+    fn name(&self, entry: &DebuggingInformationEntry<DwarfSlice>) -> Result<Option<&'static str>> {
+        Ok(match entry.attr_value(DW_AT_name)? {
+            None => None,
+            Some(a) => Some(str::from_utf8(self.string(a)?)?) })
+    }
+
+    // Mangled names of the methods with the given name, declared in the struct at `offset`. Multiple if overloaded.
+    fn method_linkage_names(&self, offset: DieOffset, method: &str) -> Result<Vec<String>> {
+        let mut cursor = self.unit.unit.header.entries_at_offset(&self.abbreviations, self.unit_offset(offset)?)?;
+        cursor.next_dfs()?; // the struct itself
+        let mut res: Vec<String> = Vec::new();
+        let mut depth = 0isize;
+        while let Some((delta, entry)) = cursor.next_dfs()? {
+            depth += delta;
+            if depth <= 0 {
+                break;
+            }
+            if depth != 1 || entry.tag() != DW_TAG_subprogram || self.name(entry)? != Some(method) {
+                continue;
+            }
+            if let Some(a) = entry.attr_value(DW_AT_linkage_name)?.or(entry.attr_value(DW_AT_MIPS_linkage_name)?) {
+                res.push(String::from_utf8_lossy(self.string(a)?).into_owned());
+            }
+        }
+        Ok(res)
+    }
+
+    // For a struct/class DIE, whether it has user-provided copy/move constructor or destructor, or virtual functions or bases.
+    // Clang tells us directly with DW_AT_calling_convention, GCC doesn't, so we look at the member function declarations.
+    fn has_non_trivial_special_members(&self, offset: DieOffset) -> Result<bool> {
+        let mut cursor = self.unit.unit.header.entries_at_offset(&self.abbreviations, self.unit_offset(offset)?)?;
+        let class_name = match cursor.next_dfs()? {
+            None => return Ok(false),
+            Some((_, entry)) => {
+                if let Some(AttributeValue::CallingConvention(cc)) = entry.attr_value(DW_AT_calling_convention)? {
+                    return Ok(cc == DW_CC_pass_by_reference);
+                }
+                self.name(entry)?
+            }
+        };
+        let mut depth = 0isize;
+        // Non-artificial parameters of the constructor we're in, if any. A constructor whose only parameter is a reference is a copy or move constructor.
+        let mut constructor_params: Option<(usize, bool)> = None;
+        let is_copy_constructor = |p: Option<(usize, bool)>| p == Some((1, true));
+        while let Some((delta, entry)) = cursor.next_dfs()? {
+            depth += delta;
+            if depth <= 1 && is_copy_constructor(constructor_params.take()) {
+                return Ok(true);
+            }
+            if depth <= 0 {
+                break;
+            }
+            if depth == 2 && entry.tag() == DW_TAG_formal_parameter {
+                if let Some(p) = &mut constructor_params {
+                    if entry.attr_value(DW_AT_artificial)?.is_none() {
+                        p.0 += 1;
+                        p.1 = self.is_reference_to(entry.attr_value(DW_AT_type)?, offset)?;
+                    }
+                }
+                continue;
+            }
+            if depth != 1 {
+                continue;
+            }
+            let virtual_ = match entry.attr_value(DW_AT_virtuality)? {
+                None => false,
+                Some(AttributeValue::Virtuality(v)) => v != gimli::DW_VIRTUALITY_none,
+                Some(_) => true };
+            match entry.tag() {
+                DW_TAG_inheritance if virtual_ => return Ok(true),
+                DW_TAG_subprogram if virtual_ => return Ok(true),
+                DW_TAG_subprogram if entry.attr_value(DW_AT_artificial)?.is_none() => {
+                    if entry.attr_value(DW_AT_defaulted)?.and_then(|a| a.udata_value()) == Some(DW_DEFAULTED_in_class.0 as u64) {
+                        continue;
+                    }
+                    let name = self.name(entry)?;
+                    if name.is_some_and(|n| n.starts_with('~')) {
+                        return Ok(true);
+                    }
+                    if name.is_some() && name == class_name {
+                        constructor_params = Some((0, false));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(false)
+    }
+
+    // Whether the type is a reference (lvalue or rvalue) to the given type, possibly const or volatile.
+    fn is_reference_to(&self, type_attr: Option<AttributeValue<DwarfSlice>>, target: DieOffset) -> Result<bool> {
+        let mut offset = match type_attr {
+            None => return Ok(false),
+            Some(a) => self.reference(a)? };
+        let entry = self.entry(offset)?;
+        if ![DW_TAG_reference_type, DW_TAG_rvalue_reference_type].contains(&entry.tag()) {
+            return Ok(false);
+        }
+        let mut attr = entry.attr_value(DW_AT_type)?;
+        for _ in 0..4 {
+            offset = match attr {
+                None => return Ok(false),
+                Some(a) => self.reference(a)? };
+            if offset == target {
+                return Ok(true);
+            }
+            let entry = self.entry(offset)?;
+            if ![DW_TAG_const_type, DW_TAG_volatile_type].contains(&entry.tag()) {
+                return Ok(false);
+            }
+            attr = entry.attr_value(DW_AT_type)?;
+        }
+        Ok(false)
+    }
+
+    // Fully qualified name of a type DIE, constructed the same way as in the symbols loader (see append_namespace_to_scope_name()),
+    // so that it can be looked up with Types::find_by_name(). Walks the unit from the start, so it's slow-ish.
+    fn qualified_name(&self, offset: DieOffset) -> Result<String> {
+        let target = self.unit_offset(offset)?;
+        let mut scopes: Vec<(isize, &'static str)> = Vec::new();
+        let mut depth = 0isize;
+        let mut cursor = self.unit.unit.header.entries(&self.abbreviations);
+        while let Some((delta, entry)) = cursor.next_dfs()? {
+            depth += delta;
+            while scopes.last().is_some_and(|s| s.0 >= depth) {
+                scopes.pop();
+            }
+            if entry.offset() == target {
+                let mut r = String::new();
+                for (_, s) in &scopes {
+                    r.push_str(s);
+                    r.push_str("::");
+                }
+                r.push_str(self.name(entry)?.unwrap_or("_"));
+                return Ok(r);
+            }
+            if !entry.has_children() {
+                continue;
+            }
+            let s = match entry.tag() {
+                DW_TAG_subprogram => "_",
+                DW_TAG_namespace | DW_TAG_structure_type | DW_TAG_class_type | DW_TAG_union_type | DW_TAG_enumeration_type => self.name(entry)?.unwrap_or("_"),
+                _ => continue,
+            };
+            scopes.push((depth, s));
+        }
+        err!(Dwarf, "DIE 0x{:x} not found in its unit", offset.0)
+    }
+}
+
+const FUNCTION_CALL_INT_REGISTERS: [RegisterIdx; 6] = [RegisterIdx::Rdi, RegisterIdx::Rsi, RegisterIdx::Rdx, RegisterIdx::Rcx, RegisterIdx::R8, RegisterIdx::R9];
+
+#[derive(Clone, Copy)]
+pub enum FunctionCallArg {
+    Int(u64), // integers and pointers, passed in general-purpose registers
+    Float(u64), // bits of f32 or f64, passed in xmm registers
+}
+
+// Registers that may hold a function's return value, as of right after the function returned.
+#[derive(Clone, Copy, Debug)]
+pub struct ReturnRegisters {
+    pub rax: u64,
+    pub rdx: u64,
+    pub xmm0: [u64; 2],
+    pub xmm1: [u64; 2],
+}
+
+// The function that the thread just returned from (by a step), and its return registers. Available as '$ret'.
+#[derive(Clone, Copy, Debug)]
+pub struct FunctionReturn {
+    pub binary_id: usize,
+    pub function_idx: usize,
+    pub regs: ReturnRegisters,
 }
 
 pub struct EvalContext<'a> {
@@ -594,6 +1230,25 @@ pub struct EvalContext<'a> {
 
     pub extra_regs: Option<&'a LazyExtraRegisters>,
     pub fs_base: Option<u64>, // same as FsBase register in `stack`, but present even if `stack` is empty (when program is running)
+    // If the thread is stopped at a syscall catchpoint, the syscall and its arguments, available as '#syscall'.
+    pub syscall: Option<SyscallStop>,
+    // If the thread's last step returned from a function.
+    pub function_return: Option<FunctionReturn>,
+
+    // Suspended thread through which we write registers and memory. None if the thread is running or it's a core dump.
+    pub tid: Option<libc::pid_t>,
+    // Set when the expression wrote to debuggee's memory or registers, or called a debuggee function. Then the caller should call Debugger::refresh_after_modifying_debuggee().
+    pub modified_debuggee: bool,
+    // Passed to Debugger::refresh_after_modifying_debuggee() too.
+    pub function_call_events: FunctionCallEvents,
+    // Software breakpoints poked into the code. Removed for the duration of function calls, so that the called function runs through them.
+    pub breakpoint_locations: &'a [BreakpointLocation],
+    // Some data breakpoints are implemented by mprotect()ing pages, and the called function would get SIGSEGV when touching them.
+    pub has_page_trapped_data_breakpoints: bool,
+    // Whether other threads of `tid`'s process are running (e.g. in non-stop mode, or while a breakpoint condition is evaluated). Function calls lift breakpoints for the whole process, so they're not allowed then.
+    pub other_threads_running: bool,
+
+    pub pretty_printers: &'a CustomPrettyPrinters,
 }
 impl EvalContext<'_> {
     pub fn check_has_stack(&self) -> Result<()> {
@@ -603,7 +1258,168 @@ impl EvalContext<'_> {
         }
         Ok(())
     }
+
+    pub fn write_memory(&mut self, addr: usize, data: &[u8]) -> Result<()> {
+        self.check_has_stack()?;
+        let tid = match self.tid {
+            None => return err!(ProcessState, "can't modify memory: no suspended thread"),
+            Some(t) => t };
+        let r = self.memory.mem.write(addr, data, tid);
+        // Even if the write failed, part of it may have happened.
+        self.memory.invalidate();
+        self.modified_debuggee = true;
+        r
+    }
+
+    // Only registers of the top stack frame can be assigned. In other frames, the register values are reconstructed by unwinding,
+    // and they're either callee-saved somewhere on the stack or not known at all.
+    pub fn write_register(&mut self, reg: RegisterIdx, value: u64) -> Result<()> {
+        self.check_has_stack()?;
+        let tid = match self.tid {
+            None => return err!(ProcessState, "can't modify registers: no suspended thread"),
+            Some(t) => t };
+        if self.stack.subframes[self.selected_subframe].frame_idx != 0 {
+            return err!(NotImplemented, "can only assign registers in the top stack frame");
+        }
+        let arch = self.stack.frames[0].regs.arch;
+        if arch.x86_bitness().is_none() || !arch.registers().contains(&reg) {
+            return err!(Runtime, "{} is not a real {} register", arch.register_name(reg), arch.name());
+        }
+        if self.syscall.is_some() && (reg == RegisterIdx::Rax || reg == RegisterIdx::OrigRax) {
+            // At syscall-enter-stop orig_rax is the pending syscall's number (changing it replaces or skips the syscall), and rax is overwritten by the syscall's return value.
+            return err!(ProcessState, "can't assign {} while stopped at a syscall", arch.register_name(reg));
+        }
+        let mut regs = Registers::default();
+        regs.set(reg, value, false);
+        ptrace_setregs(tid, &regs)?;
+        self.modified_debuggee = true;
+        Ok(())
+    }
     
+    // Calls a function in the debuggee on the selected thread, using SysV x86-64 calling convention. Only scalar arguments and return values are supported.
+    // Registers are restored afterwards, but the function may have any side effects on memory.
+    // `time_left` is the time budget shared by all calls in the expression; the call is interrupted if it runs out, and the time taken is subtracted.
+    pub fn call_function(&mut self, addr: usize, args: &[FunctionCallArg], time_left: &mut Duration) -> Result<ReturnRegisters> {
+        self.check_has_stack()?;
+        let tid = match self.tid {
+            None => return err!(ProcessState, "can't call functions: no suspended thread"),
+            Some(t) => t };
+        if self.syscall.is_some() {
+            // The call would have to set orig_rax to -1 (to avoid syscall restart logic), which skips the pending syscall.
+            return err!(ProcessState, "can't call functions while stopped at a syscall");
+        }
+        if self.other_threads_running {
+            // Breakpoints are lifted for the whole process during the call, so running threads would silently miss them.
+            return err!(ProcessState, "can't call functions while other threads are running");
+        }
+        if time_left.is_zero() {
+            return err!(TooLong, "function calls took more than {} ms in total", interp::MAX_FUNCTION_CALL_TIME.as_millis());
+        }
+        let return_addr = self.function_call_return_addr()?;
+        let saved_regs = ptrace_getregs(tid)?;
+        if saved_regs.arch == Arch::I386 {
+            // (The i386 calling convention passes arguments on the stack and returns floats on the x87 stack; not implemented.)
+            return err!(NotImplemented, "function calls are not supported for i386");
+        }
+        if self.has_page_trapped_data_breakpoints {
+            return err!(NotImplemented, "can't call functions while a data breakpoint uses page protection (disable it first)");
+        }
+        let saved_xstate = ptrace_get_xstate(tid)?;
+
+        let mut regs = saved_regs.clone();
+        let mut xstate = saved_xstate.clone();
+        let mut stack_args: Vec<u64> = Vec::new();
+        let (mut num_ints, mut num_floats) = (0usize, 0usize);
+        for arg in args {
+            match *arg {
+                FunctionCallArg::Int(x) if num_ints < FUNCTION_CALL_INT_REGISTERS.len() => {
+                    regs.set(FUNCTION_CALL_INT_REGISTERS[num_ints], x, false);
+                    num_ints += 1;
+                }
+                FunctionCallArg::Float(x) if num_floats < 8 => {
+                    xsave_set_xmm(&mut xstate, num_floats, [x, 0])?;
+                    num_floats += 1;
+                }
+                FunctionCallArg::Int(x) | FunctionCallArg::Float(x) => stack_args.push(x),
+            }
+        }
+
+        // Skip the red zone, push stack arguments and return address. The stack must be 16-byte aligned before the (imaginary) call instruction.
+        let mut sp = (saved_regs.get(RegisterIdx::Rsp)?.0 as usize - 128 - stack_args.len() * 8) & !15;
+        let mut stack: Vec<u8> = (return_addr as u64).to_le_bytes().to_vec();
+        for x in &stack_args {
+            stack.extend_from_slice(&x.to_le_bytes());
+        }
+        sp -= 8;
+        self.memory.mem.write(sp, &stack, tid)?;
+
+        regs.set(RegisterIdx::Rip, addr as u64, false);
+        regs.set(RegisterIdx::Rsp, sp as u64, false);
+        regs.set(RegisterIdx::Rax, num_floats as u64, false); // upper bound on the number of vector registers used, for varargs functions
+        // Don't let the kernel restart an interrupted syscall on resume, that would move rip. Also clear single-step and direction flags.
+        regs.set(RegisterIdx::OrigRax, u64::MAX, false);
+        regs.set(RegisterIdx::Flags, saved_regs.get(RegisterIdx::Flags)?.0 & !(0x100 | 0x400), false);
+
+        // Lift all breakpoints for the duration of the call: software breakpoints from the code, hardware breakpoints from this thread's debug registers.
+        // Otherwise any breakpoint in the called function (or its callees) would abort the call.
+        self.modified_debuggee = true;
+        let dr7_offset = (mem::offset_of!(libc::user, u_debugreg) + 7*8) as u64;
+        let saved_dr7 = unsafe {ptrace(PTRACE_PEEKUSER, tid, dr7_offset, 0)}? as u64;
+        let mut lifted: Vec<usize> = Vec::new();
+        let r = (|| -> Result<()> {
+            unsafe {ptrace(PTRACE_POKEUSER, tid, dr7_offset, 0)}?;
+            for location in self.breakpoint_locations {
+                if location.active && !location.hardware {
+                    self.memory.mem.write(location.addr, &[location.original_byte], tid)?;
+                    lifted.push(location.addr);
+                }
+            }
+            Ok(())
+        })();
+        let start_time = Instant::now();
+        let r = r.and_then(|()| self.call_function_with_breakpoints_lifted(tid, return_addr, *time_left, (&saved_regs, &saved_xstate), (&regs, &xstate)));
+        *time_left = time_left.saturating_sub(start_time.elapsed());
+        let r = match r {
+            Err(e) if e.is_too_long() => err!(TooLong, "function calls took more than {} ms in total", interp::MAX_FUNCTION_CALL_TIME.as_millis()),
+            r => r,
+        };
+
+        // Put the breakpoints back, unless the thread is gone.
+        if !self.function_call_events.wait_events.iter().any(|(t, _)| *t == tid) {
+            let mut repoked = Ok(());
+            for &addr in &lifted {
+                repoked = repoked.and(self.memory.mem.write(addr, &[0xcc], tid));
+            }
+            let dr7_restored = unsafe {ptrace(PTRACE_POKEUSER, tid, dr7_offset, saved_dr7)}.map(|_| ());
+            repoked.and(dr7_restored)?;
+        }
+        self.memory.invalidate();
+        r
+    }
+
+    fn call_function_with_breakpoints_lifted(&mut self, tid: pid_t, return_addr: usize, timeout: Duration, (saved_regs, saved_xstate): (&Registers, &[u8]), (regs, xstate): (&Registers, &[u8])) -> Result<ReturnRegisters> {
+        let original_byte = self.memory.read_u8(return_addr)?;
+        self.memory.mem.write(return_addr, &[0xcc], tid)?;
+        let r = ptrace_setregs(tid, regs).and_then(|()| ptrace_set_xstate(tid, xstate)).and_then(|()| {
+            let regs = ptrace_run_function_call(tid, return_addr, timeout, &mut self.function_call_events)?;
+            let xstate = ptrace_get_xstate(tid)?;
+            Ok(ReturnRegisters {rax: regs.get(RegisterIdx::Rax)?.0, rdx: regs.get(RegisterIdx::Rdx)?.0, xmm0: xsave_get_xmm(&xstate, 0), xmm1: xsave_get_xmm(&xstate, 1)})
+        });
+
+        // Clean up, unless the thread is gone (exited or exec-ed, so there's nothing to restore).
+        if !self.function_call_events.wait_events.iter().any(|(t, _)| *t == tid) {
+            let restored = ptrace_setregs(tid, saved_regs).and_then(|()| ptrace_set_xstate(tid, saved_xstate));
+            let unpatched = self.memory.mem.write(return_addr, &[original_byte], tid);
+            restored.and(unpatched)?;
+        }
+        r
+    }
+
+    // Where called functions return to. We put a temporary int3 there.
+    fn function_call_return_addr(&self) -> Result<usize> {
+        self.symbols_registry.main_binary_entry_point()
+    }
+
     // Collect information needed to retrieve values of local variables.
     pub fn make_local_dwarf_eval_context<'a>(&'a mut self, selected_subframe: usize) -> Result<(DwarfEvalContext<'a>, &'a FunctionInfo)> {
         self.check_has_stack()?;
@@ -658,9 +1474,12 @@ bitflags! { pub struct ValueFlags: u16 {
     // Interpret integers as big-endian.
     // (The implementation of this is pretty error-prone, it's easy to miss a code site that needs to check this flag and flip endianness. Idk what to do about it.)
     const BIG_ENDIAN = 0x40;
+
+    // Don't apply custom (user-defined) pretty-printer to this value. Set on the value passed to the printer itself. Not inherited by fields.
+    const NO_CUSTOM_PRETTY_PRINTER = 0x80;
 }}
 impl ValueFlags {
-    pub fn inherit(self) -> Self { self & !Self::SHOW_TYPE_NAME }
+    pub fn inherit(self) -> Self { self & !(Self::SHOW_TYPE_NAME | Self::NO_CUSTOM_PRETTY_PRINTER) }
 }
 
 #[derive(Clone)]
@@ -707,6 +1526,37 @@ pub fn format_value(v: &Value, expanded: bool, state: &mut EvalState, context: &
     format_value_recurse(v, false, &mut FormatValueState {expanded, state, context, out, names_out, palette, text_start_lines, text_start_chars})
 }
 
+// For frontends other than the TUI (dap.rs, batch.rs): the value as one line of plain text, and whether it has children.
+pub fn format_value_plain(v: &Value, state: &mut EvalState, context: &mut EvalContext) -> (String, /*has_children*/ bool) {
+    let (mut out, mut names) = (StyledText::default(), StyledText::default());
+    let (has_children, _, _) = format_value(v, false, state, context, &mut out, &mut names, &Palette::default());
+    (out.chars, has_children)
+}
+
+// Lists children of the value, with names, as plain text. Counterpart of format_value_plain().
+pub fn value_children_plain(v: &Value, state: &mut EvalState, context: &mut EvalContext) -> Vec<(String, Result<Value>)> {
+    let (mut out, mut names) = (StyledText::default(), StyledText::default());
+    let (_, children, _) = format_value(v, true, state, context, &mut out, &mut names, &Palette::default());
+    children.into_iter().map(|c| (names.get_line_str(c.name_line).to_string(), c.value)).collect()
+}
+
+// Local variables in scope at the given subframe, like the 'locals' window shows them (but without the UI's value tree). For non-TUI frontends.
+pub fn list_local_variables(context: &mut EvalContext, subframe_idx: usize) -> Result<Vec<(String, Result<Value>)>> {
+    let subframe = &context.stack.subframes[subframe_idx];
+    let pseudo_addr = context.stack.frames[subframe.frame_idx].pseudo_addr;
+    let (mut dwarf_context, _) = context.make_local_dwarf_eval_context(subframe_idx)?;
+    let static_pseudo_addr = dwarf_context.addr_map.dynamic_to_static(pseudo_addr);
+    let mut res: Vec<(String, Result<Value>)> = Vec::new();
+    for v in dwarf_context.local_variables {
+        if !v.range().contains(&static_pseudo_addr) || v.flags().contains(VariableFlags::FRAME_BASE) {
+            continue;
+        }
+        let value = eval_variable(&v.location, &mut dwarf_context).map(|(val, _dubious)| Value {val, type_: v.type_, flags: ValueFlags::empty()});
+        res.push((unsafe {v.name()}.to_string(), value));
+    }
+    Ok(res)
+}
+
 struct FormatValueState<'a, 'b> {
     expanded: bool,
     state: &'a mut EvalState,
@@ -1347,10 +2197,11 @@ pub fn eval_dwarf_expression(mut expression: Expression<DwarfSlice>, context: &m
                 } else {
                     return err!(Dwarf, "can't look up base type (register) without symbols");
                 };
-                let (reg_val, dub) = if let Some(reg) = RegisterIdx::from_dwarf(*register) {
+                let arch = context.regs.map_or(Arch::default(), |r| r.arch);
+                let (reg_val, dub) = if let Some(reg) = arch.register_from_dwarf(*register) {
                     let regs = match &context.regs { Some(r) => r, None => return err!(Dwarf, "register op unexpected") };
                     regs.get(reg)?
-                } else if let Some(reg) = ExtraRegisterIdx::from_dwarf(*register) {
+                } else if let Some(reg) = arch.extra_register_from_dwarf(*register) {
                     let &Some(regs) = &context.extra_regs else {return err!(ProcessState, "no simd registers")};
                     let regs = regs.get();
                     let (vec, dub) = regs.get(reg)?;
@@ -1387,7 +2238,8 @@ pub fn eval_dwarf_expression(mut expression: Expression<DwarfSlice>, context: &m
                             Some(u) => &u.unit },
                          *offset),
                     DieReference::DebugInfoRef(offset) => {
-                        let u = symbols.find_unit(*offset)?;
+                        let offset = DebugInfoOffset(offset.0 + context.unit.map_or(0, |u| u.offset_shift));
+                        let u = symbols.find_unit(offset)?;
                         let unit_offset = match offset.to_unit_offset(&u.unit.header) { None => return err!(Dwarf, "DWARF call offset out of bounds"), Some(o) => o };
                         (&u.unit, unit_offset)
                     }
@@ -1413,7 +2265,7 @@ pub fn eval_dwarf_expression(mut expression: Expression<DwarfSlice>, context: &m
             }
             EvaluationResult::RequiresIndexedAddress {index, relocate} => {
                 let (symbols, unit) = match (&context.symbols, &context.unit) { (&Some(s), &Some(u)) => (s, u), _ => return err!(Dwarf, "indexed addr op unexpected") };
-                let mut addr = symbols.dwarf.address(&unit.unit, *index)?;
+                let mut addr = unit.dwarf(&symbols.dwarf).address(&unit.unit, *index)?;
                 if *relocate {
                     addr = context.addr_map.static_to_dynamic(addr as usize) as u64;
                 }
@@ -1458,7 +2310,8 @@ pub fn eval_dwarf_expression(mut expression: Expression<DwarfSlice>, context: &m
                 AddrOrValueBlob::Blob(ValueBlob::from_slice(b.slice()))
             }
             Location::Register{register: reg} => {
-                if let Some(reg) = RegisterIdx::from_dwarf(reg) {
+                let arch = context.regs.map_or(Arch::default(), |r| r.arch);
+                if let Some(reg) = arch.register_from_dwarf(reg) {
                     let &Some(regs) = &context.regs else {return err!(Dwarf, "register location unexpected")};
                     match regs.get(reg) {
                         Err(_) => return err!(Dwarf, "register {} optimized away", reg),
@@ -1467,7 +2320,7 @@ pub fn eval_dwarf_expression(mut expression: Expression<DwarfSlice>, context: &m
                             AddrOrValueBlob::Blob(ValueBlob::new(v as usize))
                         }
                     }
-                } else if let Some(reg) = ExtraRegisterIdx::from_dwarf(reg) {
+                } else if let Some(reg) = arch.extra_register_from_dwarf(reg) {
                     let &Some(regs) = &context.extra_regs else {return err!(Dwarf, "no simd registers")};
                     let regs = regs.get();
                     let (v, dub) = regs.get(reg)?;
@@ -1585,8 +2438,8 @@ pub fn flip_endianness_if_needed(x: &mut usize, size: usize, flags: ValueFlags)
 }
 
 #[cfg(test)]
-mod tests {
-    use crate::expr::*;
+pub mod tests {
+    use crate::{expr::*, interp::*, context::*};
 
     #[test]
     fn value_blob_nonsense() {
@@ -1611,4 +2464,123 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn classify_returned_struct_fields() {
+        let mut state = EvalState::new();
+        let (f64_, i32_) = (state.builtin_types.f64_, state.builtin_types.i32_);
+        let mut make_struct = |fields: Vec<StructField>| -> TypeInfo {
+            let mut s = StructType::default();
+            s.set_fields(Box::leak(fields.into_boxed_slice()));
+            let t = state.types.types_arena.add(TypeInfo {size: 16, flags: TypeFlags::SIZE_KNOWN, t: Type::Struct(s), ..TypeInfo::default()});
+            unsafe {(*t).clone()}
+        };
+        let field = |bit_offset: usize, bit_size: Option<usize>, type_: *const TypeInfo| StructField {name: "", flags: if bit_size.is_some() {FieldFlags::SIZE_KNOWN} else {FieldFlags::empty()}, bit_offset, bit_size: bit_size.unwrap_or(0), type_, discr_value: 0};
+
+        // {double x; double y;}, with and without DW_AT_byte_size on the members. Not bit fields, both in xmm registers.
+        for size in [None, Some(64)] {
+            let t = make_struct(vec![field(0, size, f64_), field(64, size, f64_)]);
+            let mut is_float = vec![true; 2];
+            assert!(EvalState::classify_fields(&t, 0, &mut is_float));
+            assert_eq!(is_float, [true, true]);
+        }
+
+        // {double x; int y : 3;}
+        let t = make_struct(vec![field(0, None, f64_), field(64, Some(3), i32_)]);
+        let mut is_float = vec![true; 2];
+        assert!(EvalState::classify_fields(&t, 0, &mut is_float));
+        assert_eq!(is_float, [true, false]);
+    }
+
+    // Expression evaluation with no debuggee process, like watches in the watches window before the program is started.
+    fn with_test_context<R>(pretty_printers: &CustomPrettyPrinters, f: impl FnOnce(&mut EvalState, &mut EvalContext) -> R) -> R {
+        let symbols = SymbolsRegistry::new(Context::invalid(), SupplementaryBinaries::default());
+        let process_info = ProcessInfo::default();
+        let stack = StackTrace::default();
+        let mut context = EvalContext {symbols_registry: &symbols, memory: CachedMemReader::new(MemReader::Invalid), process_info: &process_info, stack: &stack, selected_subframe: 0, extra_regs: None, fs_base: None, syscall: None, function_return: None, tid: None, modified_debuggee: false, function_call_events: FunctionCallEvents::default(), breakpoint_locations: &[], has_page_trapped_data_breakpoints: false, other_threads_running: false, pretty_printers};
+        f(&mut EvalState::new(), &mut context)
+    }
+
+    // Evaluates expressions in order, sharing script variables and definitions. Returns the formatted values.
+    pub fn eval_script_with_printers(exprs: &[&str], pretty_printers: &CustomPrettyPrinters) -> Vec<Result<String>> {
+        with_test_context(pretty_printers, |state, context| exprs.iter().map(|e| eval_watch_expression(e, state, context).map(|(v, _)| format_value_plain(&v, state, context).0)).collect())
+    }
+
+    fn eval_script(exprs: &[&str]) -> Vec<Result<String>> {
+        eval_script_with_printers(exprs, &CustomPrettyPrinters::default())
+    }
+
+    fn eval_one(expr: &str) -> Result<String> {
+        eval_script(&[expr]).pop().unwrap()
+    }
+
+    #[test]
+    fn script_loops() {
+        assert_eq!(eval_one("{let i = 0; let s = 0; while i < 5 {s += i; i += 1}; s}").unwrap(), "10");
+        assert_eq!(eval_one("{let i = 0; while 1 {i += 1; if i == 3 {break}}; i}").unwrap(), "3");
+        assert_eq!(eval_one("{let s = 0; for i in 2..5 {s += i}; s}").unwrap(), "9");
+        assert_eq!(eval_one("{let s = 0; for x in [1, 2, 3] {if x == 2 {continue}; s += x}; s}").unwrap(), "4");
+        assert_eq!(eval_one("{let i = 0; loop {i += 2; if i > 7 {break}}; i}").unwrap(), "8");
+    }
+
+    #[test]
+    fn script_let_scoping() {
+        assert_eq!(eval_one("{let x = 1; {let x = 2; x += 1}; x}").unwrap(), "1");
+        assert_eq!(eval_one("{let x = 1; {x = 5}; x}").unwrap(), "5");
+        assert_eq!(eval_one("{let x: u8 = 300; x}").unwrap(), "44");
+        assert!(eval_one("{{let y = 1}; y}").is_err());
+        // Loop variables are scoped to the loop, `let` inside the body is per iteration.
+        assert!(eval_one("{for i in 0..3 {}; i}").is_err());
+        assert_eq!(eval_one("{let s = 0; for i in 0..3 {let t = 10; t += i; s += t}; s}").unwrap(), "33");
+        // Global script variables outlive the expression, locals don't.
+        let r = eval_script(&["g = 7", "{let l = 1; g + l}", "g", "l"]);
+        assert_eq!(r[1].as_ref().unwrap(), "8");
+        assert_eq!(r[2].as_ref().unwrap(), "7");
+        assert!(r[3].is_err());
+    }
+
+    #[test]
+    fn script_step_budget() {
+        let e = eval_one("loop {}").unwrap_err();
+        assert!(e.is_too_long(), "{}", e);
+        let e = eval_one("{let i = 0; while i >= 0 {i += 1}; i}").unwrap_err();
+        assert!(e.is_too_long(), "{}", e);
+        // The budget is per expression, the next one starts fresh.
+        let r = eval_script(&["loop {}", "{let s = 0; for i in 0..1000 {s += i}; s}"]);
+        assert!(r[0].is_err());
+        assert_eq!(r[1].as_ref().unwrap(), "499500");
+    }
+
+    #[test]
+    fn script_functions() {
+        let r = eval_script(&[
+            "fn sq(x: i64) -> i64 {x * x}",
+            "sq(7)",
+            "fn fact(n: u64) -> u64 {if n <= 1 {return 1}; n * fact(n - 1)}",
+            "fact(10)",
+            "fn forever(n: u64) -> u64 {forever(n + 1)}",
+            "forever(0)",
+            "sq(1, 2)",
+        ]);
+        assert_eq!(r[1].as_ref().unwrap(), "49");
+        assert_eq!(r[3].as_ref().unwrap(), "3628800");
+        assert!(r[5].is_err());
+        assert!(r[6].is_err());
+    }
+
+    #[test]
+    fn script_structs() {
+        let r = eval_script(&[
+            "struct Pair {a: u32, b: u8}",
+            "{let p: Pair; p.a}",
+            "{let p: Pair; p}",
+            "fn first(p: Pair) -> u32 {p.a}",
+            "first(struct {a: 5, b: 6})",
+            "{let p: Pair; p.c}",
+        ]);
+        assert_eq!(r[1].as_ref().unwrap(), "0");
+        assert!(r[2].as_ref().unwrap().contains("a: 0"), "{}", r[2].as_ref().unwrap());
+        assert_eq!(r[4].as_ref().unwrap(), "5");
+        assert!(r[5].is_err());
+    }
 }
@@ -1,7 +1,17 @@
-use crate::{*, types::*, expr::*, error::*, procfs::*, pretty::*, registers::*};
-use std::{ops::Range, mem, borrow::Cow, fmt::Write as fmtWrite};
+use crate::{*, types::*, expr::*, error::*, procfs::*, pretty::*, registers::*, arch::*};
+use std::{ops::Range, mem, borrow::Cow, fmt::Write as fmtWrite, rc::Rc, collections::HashMap, time::Duration};
 use bitflags::*;
 
+// Limit on the number of AST nodes evaluated per expression, to not hang on infinite loops.
+pub const MAX_EVAL_STEPS: usize = 1000000;
+// Limit on the total time spent in debuggee function calls per expression (e.g. a loop calling a function), to not block the UI for long.
+pub const MAX_FUNCTION_CALL_TIME: Duration = Duration::from_secs(1);
+// Step limit for one application of a custom pretty-printer. Separate from the enclosing expression's budget because pretty-printers also run when formatting values.
+const MAX_PRETTY_PRINTER_STEPS: usize = 100000;
+// Limit on script function call nesting, to not overflow the stack on infinite recursion.
+const MAX_CALL_DEPTH: usize = 64;
+
+#[derive(Clone)]
 pub struct Expression {
     ast: Vec<ASTNode>,
     root: ASTIdx,
@@ -15,6 +25,27 @@ impl Expression {
     }
 }
 
+// Functions and structs defined by `fn` and `struct` in watch expressions. Available to all subsequent watches and to breakpoint conditions.
+#[derive(Clone, Default)]
+pub struct ScriptDefinitions {
+    pub functions: HashMap<String, ScriptDefinition>,
+    pub structs: HashMap<String, ScriptDefinition>,
+}
+
+// Points to the definition's AST node in the (shared) expression it came from.
+#[derive(Clone)]
+pub struct ScriptDefinition {
+    expr: Rc<Expression>,
+    node: ASTIdx,
+}
+
+// `break`, `continue`, or `return` in progress. Propagated up as an error (so that `?` does the unwinding), with this in EvalState saying what it is.
+pub enum ControlFlow {
+    Break,
+    Continue,
+    Return(Value),
+}
+
 pub fn parse_watch_expression(s: &str) -> Result<Expression> {
     let mut lex = Lexer {input: InputStream {input: s, pos: 0}, next_tokens: Vec::new(), previous_token_end: 0, previous_dot: false};
     let mut expr = Expression {ast: Vec::new(), root: ASTIdx(0)};
@@ -34,6 +65,18 @@ pub fn parse_watch_expression(s: &str) -> Result<Expression> {
     Ok(expr)
 }
 
+// Type in the same syntax as in casts, e.g. "*[u8; 16]". Used for values that remember their type by name, e.g. `old` and `new` of data breakpoints.
+pub fn eval_type_name(s: &str, state: &mut EvalState, context: &mut EvalContext) -> Result<*const TypeInfo> {
+    let mut lex = Lexer {input: InputStream {input: s, pos: 0}, next_tokens: Vec::new(), previous_token_end: 0, previous_dot: false};
+    let mut expr = Expression {ast: Vec::new(), root: ASTIdx(0)};
+    expr.root = parse_type(&mut lex, &mut expr)?;
+    let (r, t) = lex.peek(1)?;
+    if !t.is_eof() {
+        return err!(Syntax, "unexpected {:?} after type at {}", t, r.start);
+    }
+    eval_type(&expr, expr.root, state, context)
+}
+
 pub fn eval_watch_expression(expr_str: &str, state: &mut EvalState, context: &mut EvalContext) -> Result<(Value, /*dubious*/ bool)> {
     let expr = parse_watch_expression(expr_str)?;
     eval_parsed_expression(&expr, state, context)
@@ -41,7 +84,43 @@ pub fn eval_watch_expression(expr_str: &str, state: &mut EvalState, context: &mu
 
 pub fn eval_parsed_expression(expr: &Expression, state: &mut EvalState, context: &mut EvalContext) -> Result<(Value, /*dubious*/ bool)> {
     state.currently_evaluated_value_dubious = false;
-    Ok((eval_expression(expr, expr.root, state, context, false)?, state.currently_evaluated_value_dubious))
+    state.steps_left = MAX_EVAL_STEPS;
+    state.function_call_time_left = MAX_FUNCTION_CALL_TIME;
+    state.call_depth = 0;
+    state.local_variables.clear();
+    state.local_variables_start = 0;
+    state.compound_assignment_lhs.clear();
+    state.control_flow = None;
+    let r = eval_expression(expr, expr.root, state, context, false);
+    state.local_variables.clear();
+    state.control_flow = None;
+    Ok((r?, state.currently_evaluated_value_dubious))
+}
+
+// Evaluates a custom pretty-printer's expression with `val` available as variable `self`.
+// May be called in the middle of evaluating another expression (e.g. on field access), so it gets its own scope and step budget.
+pub fn eval_custom_pretty_printer(expr: &Expression, val: Value, state: &mut EvalState, context: &mut EvalContext) -> Result<Value> {
+    if state.call_depth >= MAX_CALL_DEPTH {
+        return err!(TooLong, "pretty-printers nested more than {} deep", MAX_CALL_DEPTH);
+    }
+    let saved_steps = state.steps_left;
+    let saved_start = state.local_variables_start;
+    state.steps_left = MAX_PRETTY_PRINTER_STEPS;
+    state.local_variables_start = state.local_variables.len();
+    state.local_variables.push(("self".to_string(), val));
+    state.call_depth += 1;
+    let r = eval_expression(expr, expr.root, state, context, false);
+    state.call_depth -= 1;
+    state.local_variables.truncate(state.local_variables_start);
+    state.local_variables_start = saved_start;
+    state.steps_left = saved_steps.saturating_sub(MAX_PRETTY_PRINTER_STEPS - state.steps_left);
+    match r {
+        Ok(v) => Ok(v),
+        Err(e) => match state.control_flow.take() {
+            Some(ControlFlow::Return(v)) => Ok(v),
+            _ => Err(e),
+        }
+    }
 }
 
 // Make expression suitable for appending things like "[5]" or ".foo" to it. Used when adding a watch from a node in value tree.
@@ -63,10 +142,10 @@ pub fn adjust_expression_for_appending_child_path(expr_str: &str) -> Result<Stri
 
         _ if node.flags.contains(ASTNodeFlags::HAS_PARENS) => false,
         // These don't need parentheses.
-        AST::Literal(_) | AST::Variable {..} | AST::Field {..} | AST::Array | AST::Tuple | AST::StructExpression(_) | AST::TupleIndexing(_) | AST::Call(_) | AST::Block | AST::TypeInfo => false,
+        AST::Literal(_) | AST::Variable {..} | AST::Field {..} | AST::Array | AST::Tuple | AST::StructExpression(_) | AST::TupleIndexing(_) | AST::Call(_) | AST::MethodCall(_) | AST::Block | AST::TypeInfo => false,
         AST::BinaryOperator(BinaryOperator::Index) | AST::BinaryOperator(BinaryOperator::Slicify) => false,
         // These are unexpected at top level of a watch expression.
-        AST::Type {..} | AST::PointerType | AST::ArrayType(_) | AST::Continue | AST::Break | AST::Return => false,
+        AST::Type {..} | AST::PointerType | AST::ArrayType(_) | AST::Continue | AST::Break | AST::Return | AST::CompoundAssignmentLhs => false,
 
         // These need parentheses.
         AST::UnaryOperator(_) | AST::BinaryOperator(_) | AST::TypeCast | AST::While | AST::For(_) | AST::If | AST::Let {..} | AST::FunctionDefinition {..} | AST::StructDefinition {..} => true,
@@ -156,6 +235,10 @@ pub fn print_type_in_watch_language_syntax(t: *const TypeInfo, out: &mut String,
 // may fail unnecessarily, but that seems ok as long as the important case of getting types of unavailable variable is supported.
 fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, context: &mut EvalContext, only_type: bool) -> Result<Value> {
     let node = &expr.ast[node_idx.0];
+    if state.steps_left == 0 {
+        return err!(TooLong, "evaluation took more than {} steps (infinite loop?)", MAX_EVAL_STEPS);
+    }
+    state.steps_left -= 1;
     match &node.a {
         AST::Literal(v) => {
             let type_ = match v {
@@ -172,6 +255,9 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
         }
         AST::Variable {name, quoted, from_any_frame} => {
             if !quoted && !from_any_frame {
+                if let Some(i) = find_local_variable(state, name) {
+                    return Ok(state.local_variables[i].1.clone());
+                }
                 if let Some(v) = state.variables.get(name) {
                     return Ok(v.clone());
                 }
@@ -301,7 +387,7 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
             match op {
                 UnaryOperator::Dereference => match &type_.t {
                     Type::Pointer(p) => {
-                        let addr = val.val.into_value(8, &mut context.memory)?.get_usize()?;
+                        let addr = val.val.into_value(type_.calculate_size(), &mut context.memory)?.get_usize()?;
                         val.val = AddrOrValueBlob::Addr(addr);
                         val.type_ = p.type_;
                     }
@@ -352,111 +438,110 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
             let mut val = eval_expression(expr, node.children[0], state, context, false)?;
             follow_references_and_prettify(&mut val, None, false, state, context)?;
             let type_ = eval_type(expr, node.children[1], state, context)?;
-            let is_reinterpretable = |type_: *const TypeInfo, to: bool| -> bool {
-                match unsafe {&(*type_).t} {
-                    Type::Primitive(_) | Type::Pointer(_) | Type::PointerToMember(_) | Type::Struct(_) | Type::Enum(_) => true,
-                    Type::Array(a) => a.flags.contains(ArrayFlags::LEN_KNOWN) || to,
-                    Type::Slice(_) => !to,
-                    _ => false,
-                }
-            };
-            if !is_reinterpretable(val.type_, false) {
-                return err!(TypeMismatch, "can't cast from {}", unsafe {(*val.type_).t.kind_name()});
-            }
-            if !is_reinterpretable(type_, true) {
-                return err!(TypeMismatch, "can't cast to {}", unsafe {(*type_).t.kind_name()});
-            }
-
-            // Special casts for numeric types, e.g. int to float.
-            match to_basic(&val, &mut context.memory, "cast") {
-                Ok(b) => {
-                    if let Some(v) = from_basic(b, type_)? {
-                        val.val = v;
-                        val.type_ = type_;
-                        val.flags.remove(ValueFlags::BIG_ENDIAN); // to_basic converted it to little-endian
-                        return Ok(val);
-                    }
-                }
-                Err(e) if e.is_type_mismatch() => (),
-                Err(e) => return Err(e),
-            }
-
-            // Reinterpret cast for everything else, e.g. array to struct (by value).
-            // (Casting by value is useful when a value has no address, e.g. casting a simd register from [u64; 8] to [u32; 16].)
-            // Currently we're extra permissive and allow casts even when sizes don't match, and even when the value is a reference (so could be cast through pointer instead);
-            // if some of this turns out too error-prone in practice, we can add some constraints.
-            let from_t = unsafe {&*val.type_};
-            let (from_size, mut from_val) = match &from_t.t {
-                Type::Slice(s) => {
-                    // Cast slice as if it were an array. Useful for e.g. casting pretty-printed vectors to string (v as [char8]).
-                    let slice_val = mem::take(&mut val.val).into_value(16, &mut context.memory)?;
-                    let addr = slice_val.get_usize_at(0).unwrap();
-                    let len = slice_val.get_usize_at(8).unwrap();
-                    let inner_type = unsafe {&*s.type_};
-                    let inner_size = inner_type.calculate_size();
-                    (inner_size * len, AddrOrValueBlob::Addr(addr))
-                }
-                _ => {
-                    (unsafe {(*val.type_).calculate_size()}, mem::take(&mut val.val))
-                }
-            };
-            let t = unsafe {&*type_};
-            match &t.t {
-                Type::Array(a) if !a.flags.contains(ArrayFlags::LEN_KNOWN) => {
-                    // Special cast to unsized array. Type of the result is a sized array of the ~same size as the left hand side.
-                    // E.g. `ymm0 as [u8]` is the same as `ymm0 as [u8; 32]`.
-                    let element_size = unsafe {(*a.type_).calculate_size()};
-                    let n = from_size / element_size;
-                    let sized_array = state.types.add_array(a.type_, Some(n), ArrayFlags::empty());
-                    if let AddrOrValueBlob::Blob(blob) = &mut from_val {
-                        blob.resize(n * element_size);
-                    }
-                    val.val = from_val;
-                    val.type_ = sized_array;
-                    return Ok(val);
-                }
-                _ => (),
-            }
-            let to_size = t.calculate_size();
-            let v = match from_val {
-                AddrOrValueBlob::Addr(addr) if to_size <= from_size => AddrOrValueBlob::Addr(addr),
-                AddrOrValueBlob::Addr(addr) => AddrOrValueBlob::Blob(from_val.into_value(to_size, &mut context.memory)?),
-                AddrOrValueBlob::Blob(mut blob) => {
-                    blob.resize(to_size);
-                    AddrOrValueBlob::Blob(blob)
-                }
-            };
-            val.val = v;
-            val.type_ = type_;
-            Ok(val)
+            cast_value(val, type_, state, context)
         }
         AST::TypeInfo => {
             let type_ = eval_type(expr, node.children[0], state, context)?;
             Ok(Value {val: AddrOrValueBlob::Blob(ValueBlob::new(type_ as usize)), type_: state.types.types_arena.add(TypeInfo {name: "type", size: 8, flags: TypeFlags::SIZE_KNOWN, t: Type::MetaType, ..Default::default()}), flags: ValueFlags::empty()})
         }
+        AST::CompoundAssignmentLhs => match state.compound_assignment_lhs.last() {
+            Some(v) => Ok(v.clone()),
+            None => err!(Internal, "compound assignment lhs outside assignment"),
+        }
         &AST::BinaryOperator(op) if op == BinaryOperator::Assign => {
-            match &expr.ast[node.children[0].0].a {
-                AST::Variable {name, quoted, from_any_frame} if !quoted && !from_any_frame => {
-                    if !state.variables.contains_key(&name[..]) {
-                        // Check that there's no debuggee variable with this name.
-                        match state.get_variable(context, name, /*maybe_register*/ !quoted, *from_any_frame, /*only_type*/ true, /*meta*/ false) {
-                            Err(e) if e.is_no_variable() => (),
+            let compound_lhs = if node.flags.contains(ASTNodeFlags::COMPOUND_ASSIGNMENT) {
+                Some(eval_expression(expr, node.children[0], state, context, false)?)
+            } else {
+                None
+            };
+            if let AST::Variable {name, quoted, from_any_frame} = &expr.ast[node.children[0].0].a {
+                if !quoted && !from_any_frame {
+                    if let Some(i) = find_local_variable(state, name) {
+                        let mut val = eval_assignment_rhs(expr, node, &compound_lhs, state, context)?;
+                        if val.flags.contains(ValueFlags::PRETTY) {
+                            follow_references_and_prettify(&mut val, None, /*pointers_too*/ false, state, context)?;
+                        }
+                        state.local_variables[i].1 = val.clone();
+                        return Ok(val);
+                    }
+                    let mut is_script_variable = state.variables.contains_key(&name[..]);
+                    if !is_script_variable {
+                        // If there's no debuggee variable with this name, create a script variable.
+                        match state.get_variable(context, name, /*maybe_register*/ true, /*from_any_frame*/ false, /*only_type*/ true, /*meta*/ false) {
+                            // (If there's no stack, e.g. the process is running, local variables can't be looked up; don't fail the assignment because of that.)
+                            Err(e) if e.is_no_variable() || context.stack.frames.is_empty() => is_script_variable = true,
                             Err(e) => return Err(e),
-                            Ok(_) => return err!(Runtime, "assigning to debuggee variables is not supported; to assign to a script variable use a different name (shadowing not allowed)"),
+                            Ok(_) => (),
                         }
                     }
-                    let mut val = eval_expression(expr, node.children[1], state, context, false)?;
-                    if val.flags.contains(ValueFlags::PRETTY) {
-                        follow_references_and_prettify(&mut val, None, /*pointers_too*/ false, state, context)?;
+                    if is_script_variable {
+                        let mut val = eval_assignment_rhs(expr, node, &compound_lhs, state, context)?;
+                        if val.flags.contains(ValueFlags::PRETTY) {
+                            follow_references_and_prettify(&mut val, None, /*pointers_too*/ false, state, context)?;
+                        }
+                        state.variables.insert(name.clone(), val.clone());
+                        return Ok(val);
+                    }
+                    if !context.stack.frames.is_empty() {
+                        let arch = context.stack.frames[0].regs.arch;
+                        if let Some(reg) = arch.parse_register(name) {
+                            let mut rhs = eval_assignment_rhs(expr, node, &compound_lhs, state, context)?;
+                            follow_references_and_prettify(&mut rhs, None, false, state, context)?;
+                            let x = to_basic(&rhs, &mut context.memory, "assign to register")?.cast_to_usize();
+                            context.write_register(reg, x as u64)?;
+                            return Ok(Value {val: AddrOrValueBlob::Blob(ValueBlob::new(x)), type_: state.builtin_types.u64_, flags: ValueFlags::empty()});
+                        } else if ExtraRegisterIdx::parse_ignore_case(name).is_some() {
+                            return err!(NotImplemented, "assigning to simd registers is not supported");
+                        }
                     }
-                    state.variables.insert(name.clone(), val.clone());
-                    Ok(val)
                 }
-                AST::Variable {..} => return err!(NotImplemented, "assigning debuggee's variables is not supported"),
-                AST::Field {..} => return err!(NotImplemented, "assigning to fields is not supported"),
-                AST::TupleIndexing(_) => return err!(NotImplemented, "assigning to tuple elements is not supported"),
+            }
+            match &expr.ast[node.children[0].0].a {
+                AST::Variable {..} | AST::Field {..} | AST::TupleIndexing(_) | AST::BinaryOperator(BinaryOperator::Index) | AST::UnaryOperator(UnaryOperator::Dereference) => (),
                 _ => return err!(Syntax, "invalid assignment target"),
             }
+            // Assign to debuggee's memory.
+            let mut lhs = match &compound_lhs {
+                Some(v) => v.clone(),
+                None => eval_expression(expr, node.children[0], state, context, false)?,
+            };
+            while let Type::Pointer(p) = unsafe {&(*lhs.type_).t} {
+                if !p.flags.contains(PointerFlags::REFERENCE) {
+                    break;
+                }
+                let addr = lhs.val.into_value(unsafe {(*lhs.type_).calculate_size()}, &mut context.memory)?.get_usize()?;
+                lhs.val = AddrOrValueBlob::Addr(addr);
+                lhs.type_ = p.type_;
+            }
+            let addr = match &lhs.val {
+                &AddrOrValueBlob::Addr(a) => a,
+                AddrOrValueBlob::Blob(_) => return err!(Runtime, "can't assign: value is not in memory (e.g. in a register, or a bit field, or computed by a pretty-printer)"),
+            };
+            let mut rhs = eval_assignment_rhs(expr, node, &compound_lhs, state, context)?;
+            follow_references_and_prettify(&mut rhs, None, false, state, context)?;
+            let size = unsafe {(*lhs.type_).calculate_size()};
+            let basic = match to_basic(&rhs, &mut context.memory, "assign") {
+                Ok(b) => from_basic(b, lhs.type_)?,
+                Err(e) if e.is_type_mismatch() => None,
+                Err(e) => return Err(e),
+            };
+            let blob = match basic {
+                Some(AddrOrValueBlob::Blob(blob)) if size <= 8 => {
+                    let mut x = blob.get_usize()?;
+                    flip_endianness_if_needed(&mut x, size, lhs.flags);
+                    ValueBlob::new(x)
+                }
+                _ => {
+                    // Not a number, e.g. struct or array. Copy bytes if sizes match.
+                    let rhs_size = unsafe {(*rhs.type_).calculate_size()};
+                    if rhs_size != size {
+                        return err!(TypeMismatch, "can't assign {} ({} bytes) to {} ({} bytes)", unsafe {(*rhs.type_).t.kind_name()}, rhs_size, unsafe {(*lhs.type_).t.kind_name()}, size);
+                    }
+                    rhs.val.into_value(size, &mut context.memory)?
+                }
+            };
+            context.write_memory(addr, &blob.as_slice()[..size])?;
+            Ok(Value {val: AddrOrValueBlob::Addr(addr), type_: lhs.type_, flags: lhs.flags})
         }
         &AST::BinaryOperator(op) => {
             let mut lhs = eval_expression(expr, node.children[0], state, context, false)?;
@@ -485,7 +570,7 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
                 return Ok(match &t.t {
                     Type::Pointer(p) => {
                         let idx = b.cast_to_isize();
-                        let addr = lhs.val.into_value(8, &mut context.memory)?.get_usize()?;
+                        let addr = lhs.val.into_value(t.calculate_size(), &mut context.memory)?.get_usize()?;
                         let stride = unsafe {(*p.type_).calculate_size()} as isize;
                         if stride == 0 { return err!(TypeMismatch, "array element type has size 0"); }
                         let addr_offset = isize::checked_mul(idx, stride).and_then(|res| isize::checked_add(addr as isize, res));
@@ -539,7 +624,8 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
                 if size == 0 {
                     return err!(TypeMismatch, "range of size-0 types is not allowed");
                 }
-                let (addr1, addr2) = (lhs.val.into_value(8, &mut context.memory)?.get_usize()?, rhs.val.into_value(8, &mut context.memory)?.get_usize()?);
+                let pointer_size = unsafe {(*lhs.type_).calculate_size()};
+                let (addr1, addr2) = (lhs.val.into_value(pointer_size, &mut context.memory)?.get_usize()?, rhs.val.into_value(pointer_size, &mut context.memory)?.get_usize()?);
                 if addr1 > addr2 {
                     return err!(Runtime, "range has negative length: 0x{:x} > 0x{:x}", addr1, addr2);
                 }
@@ -823,23 +909,542 @@ fn eval_expression(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, c
             }
             "try" => {
                 for &child in &node.children {
-                    if let Ok(val) = eval_expression(expr, child, state, context, false) {
-                        return Ok(val);
+                    match eval_expression(expr, child, state, context, false) {
+                        Ok(val) => return Ok(val),
+                        // Don't swallow `break`/`continue`/`return` or running out of steps.
+                        Err(e) if state.control_flow.is_some() || e.is_too_long() => return Err(e),
+                        Err(_) => (),
                     }
                 }
                 Ok(Value {val: AddrOrValueBlob::Blob(ValueBlob::new(0)), type_: state.builtin_types.u64_, flags: ValueFlags::empty()})
             }
-            _ => return err!(NoFunction, "no builtin function '{}' (calling debuggee functions is not supported)", name),
+            _ => match state.definitions.functions.get(name) {
+                Some(def) => {
+                    let def = def.clone();
+                    call_script_function(&def, expr, node, state, context)
+                }
+                None => {
+                    let function = state.find_function(context, name, node.children.len())?;
+                    call_debuggee_function(&function, None, expr, &node.children, state, context)
+                }
+            }
+        }
+        AST::MethodCall(name) => {
+            // Find the struct, without pretty-printing it, through any number of pointers and references.
+            let mut val = eval_expression(expr, node.children[0], state, context, false)?;
+            val.flags.insert(ValueFlags::RAW);
+            follow_references_and_prettify(&mut val, None, /*pointers_too*/ true, state, context)?;
+            let type_ = unsafe {&*val.type_};
+            if type_.t.as_struct().is_none() {
+                return err!(TypeMismatch, "can't call method of {}", type_.t.kind_name());
+            }
+            let this = match &val.val {
+                AddrOrValueBlob::Addr(a) => *a,
+                AddrOrValueBlob::Blob(_) => return err!(Runtime, "can't call method of a value that's not in memory"),
+            };
+            let function = state.find_method(context, type_, name, node.children.len())?;
+            call_debuggee_function(&function, Some(this), expr, &node.children[1..], state, context)
+        }
+        AST::Tuple => {
+            let mut builder = StructBuilder::default();
+            for (i, &child) in node.children.iter().enumerate() {
+                let val = eval_expression(expr, child, state, context, false)?;
+                let val = materialize_value(val, &mut context.memory)?;
+                builder.add_field(state.types.misc_arena.add_str(&format!("{}", i)), val);
+            }
+            Ok(builder.finish("", ValueFlags::empty(), &mut state.types))
+        }
+        AST::StructExpression(field_names) => {
+            let mut builder = StructBuilder::default();
+            for (name, &child) in field_names.iter().zip(node.children.iter()) {
+                let val = eval_expression(expr, child, state, context, false)?;
+                let val = materialize_value(val, &mut context.memory)?;
+                builder.add_field(state.types.misc_arena.add_str(name), val);
+            }
+            Ok(builder.finish("", ValueFlags::empty(), &mut state.types))
+        }
+        AST::Array => {
+            let mut blob: Vec<u8> = Vec::new();
+            let mut element_type = state.builtin_types.u64_;
+            for (i, &child) in node.children.iter().enumerate() {
+                let mut val = eval_expression(expr, child, state, context, false)?;
+                follow_references_and_prettify(&mut val, None, false, state, context)?;
+                let val = materialize_value(val, &mut context.memory)?;
+                let size = unsafe {(*val.type_).calculate_size()};
+                if i == 0 {
+                    element_type = val.type_;
+                } else if size != unsafe {(*element_type).calculate_size()} {
+                    return err!(TypeMismatch, "array elements have different sizes: {} vs {} bytes", unsafe {(*element_type).calculate_size()}, size);
+                }
+                blob.extend_from_slice(&val.val.blob_ref().unwrap().as_slice()[..size]);
+            }
+            let type_ = state.types.add_array(element_type, Some(node.children.len()), ArrayFlags::empty());
+            Ok(Value {val: AddrOrValueBlob::Blob(ValueBlob::from_vec(blob)), type_, flags: ValueFlags::empty()})
+        }
+        AST::Block => {
+            let scope_start = state.local_variables.len();
+            let mut r = err!(Internal, "empty block");
+            for &child in &node.children {
+                r = eval_expression(expr, child, state, context, false);
+                if r.is_err() {
+                    break;
+                }
+            }
+            state.local_variables.truncate(scope_start);
+            r
+        }
+        AST::Let {name, has_type} => {
+            let type_ = if *has_type {Some(eval_type(expr, node.children[0], state, context)?)} else {None};
+            let init_idx = if *has_type {1} else {0};
+            let val = if let Some(&child) = node.children.get(init_idx) {
+                let mut val = eval_expression(expr, child, state, context, false)?;
+                if let Some(type_) = type_ {
+                    follow_references_and_prettify(&mut val, None, false, state, context)?;
+                    cast_value(val, type_, state, context)?
+                } else {
+                    if val.flags.contains(ValueFlags::PRETTY) {
+                        follow_references_and_prettify(&mut val, None, /*pointers_too*/ false, state, context)?;
+                    }
+                    val
+                }
+            } else if let Some(type_) = type_ {
+                let size = unsafe {(*type_).calculate_size()};
+                Value {val: AddrOrValueBlob::Blob(ValueBlob::from_vec(vec![0u8; size])), type_, flags: ValueFlags::empty()}
+            } else {
+                return err!(Syntax, "'let {}' needs a type or an initial value", name);
+            };
+            state.local_variables.push((name.clone(), val));
+            Ok(unit_value(state))
+        }
+        AST::If => {
+            if eval_condition(expr, node.children[0], state, context)? {
+                eval_expression(expr, node.children[1], state, context, false)
+            } else if node.children.len() > 2 {
+                eval_expression(expr, node.children[2], state, context, false)
+            } else {
+                Ok(unit_value(state))
+            }
+        }
+        AST::While => {
+            // `while cond {...}` or `loop {...}`
+            let body = *node.children.last().unwrap();
+            loop {
+                if node.children.len() > 1 && !eval_condition(expr, node.children[0], state, context)? {
+                    break;
+                }
+                if !eval_loop_body(expr, body, state, context)? {
+                    break;
+                }
+            }
+            Ok(unit_value(state))
+        }
+        AST::For(name) => {
+            let body = node.children[1];
+            let iterable = &expr.ast[node.children[0].0];
+            if let AST::BinaryOperator(BinaryOperator::Range) = &iterable.a {
+                // Integer range, e.g. `for i in 0..n`. (Ranges of pointers produce arrays and are handled below.)
+                let mut lhs = eval_expression(expr, iterable.children[0], state, context, false)?;
+                follow_references_and_prettify(&mut lhs, None, false, state, context)?;
+                if unsafe {(*lhs.type_).t.as_pointer().is_none()} {
+                    let mut rhs = eval_expression(expr, iterable.children[1], state, context, false)?;
+                    follow_references_and_prettify(&mut rhs, None, false, state, context)?;
+                    let (from, to) = (to_basic(&lhs, &mut context.memory, "iterate over")?, to_basic(&rhs, &mut context.memory, "iterate over")?);
+                    if from.is_f64() || to.is_f64() {
+                        return err!(TypeMismatch, "can't iterate over float range");
+                    }
+                    let signed = from.is_isize() || to.is_isize();
+                    let type_ = if signed {state.builtin_types.i64_} else {state.builtin_types.u64_};
+                    let as_i128 = |b: BasicValue| if signed {b.cast_to_isize() as i128} else {b.cast_to_usize() as i128};
+                    let (mut i, end) = (as_i128(from), as_i128(to));
+                    while i < end {
+                        let val = Value {val: AddrOrValueBlob::Blob(ValueBlob::new(i as usize)), type_, flags: ValueFlags::empty()};
+                        if !eval_for_loop_iteration(name, val, expr, body, state, context)? {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    return Ok(unit_value(state));
+                }
+            }
+
+            // Array or slice (possibly pretty-printed container).
+            let mut val = eval_expression(expr, node.children[0], state, context, false)?;
+            follow_references_and_prettify(&mut val, None, false, state, context)?;
+            let t = unsafe {&*val.type_};
+            let (base, len, element_type, stride) = match &t.t {
+                Type::Array(a) if a.flags.contains(ArrayFlags::LEN_KNOWN) => {
+                    let stride = if a.stride == 0 {unsafe {(*a.type_).calculate_size()}} else {a.stride};
+                    (val.val, a.len, a.type_, stride)
+                }
+                Type::Slice(s) => {
+                    let blob = val.val.into_value(16, &mut context.memory)?;
+                    (AddrOrValueBlob::Addr(blob.get_usize_prefix()), blob.get_usize_at(8)?, s.type_, unsafe {(*s.type_).calculate_size()})
+                }
+                Type::Array(_) => return err!(TypeMismatch, "can't iterate over array of unknown length"),
+                _ => return err!(TypeMismatch, "can't iterate over {}", t.t.kind_name()),
+            };
+            for idx in 0..len {
+                let v = match &base {
+                    &AddrOrValueBlob::Addr(addr) => AddrOrValueBlob::Addr(addr + idx * stride),
+                    AddrOrValueBlob::Blob(blob) => AddrOrValueBlob::Blob(blob.bit_range(idx * stride * 8, stride * 8)?),
+                };
+                if !eval_for_loop_iteration(name, Value {val: v, type_: element_type, flags: val.flags.inherit()}, expr, body, state, context)? {
+                    break;
+                }
+            }
+            Ok(unit_value(state))
+        }
+        AST::Continue => {
+            state.control_flow = Some(ControlFlow::Continue);
+            err!(Runtime, "'continue' outside of a loop")
+        }
+        AST::Break => {
+            state.control_flow = Some(ControlFlow::Break);
+            err!(Runtime, "'break' outside of a loop")
+        }
+        AST::Return => {
+            let val = match node.children.first() {
+                Some(&child) => eval_expression(expr, child, state, context, false)?,
+                None => unit_value(state),
+            };
+            state.control_flow = Some(ControlFlow::Return(val));
+            err!(Runtime, "'return' outside of a function")
+        }
+        AST::FunctionDefinition {name, ..} => {
+            if ["typeof", "var", "try", "type"].contains(&&name[..]) {
+                return err!(Syntax, "can't redefine builtin function '{}'", name);
+            }
+            state.definitions.functions.insert(name.clone(), ScriptDefinition {expr: Rc::new(expr.clone()), node: node_idx});
+            Ok(unit_value(state))
+        }
+        AST::StructDefinition {name, ..} => {
+            state.definitions.structs.insert(name.clone(), ScriptDefinition {expr: Rc::new(expr.clone()), node: node_idx});
+            state.script_types.remove(name);
+            let type_ = get_script_struct_type(name, state, context)?.unwrap();
+            Ok(Value {val: AddrOrValueBlob::Blob(ValueBlob::new(type_ as usize)), type_: state.types.types_arena.add(TypeInfo {name: "type", size: 8, flags: TypeFlags::SIZE_KNOWN, t: Type::MetaType, ..Default::default()}), flags: ValueFlags::empty()})
         }
         AST::Type {..} | AST::PointerType | AST::ArrayType(_) => panic!("unexpected type AST"),
-        _ => return err!(NotImplemented, "not implemented"),
     }
 }
 
+// Index in state.local_variables of the innermost visible `let` variable with this name.
+fn find_local_variable(state: &EvalState, name: &str) -> Option<usize> {
+    state.local_variables[state.local_variables_start..].iter().rposition(|(n, _)| n == name).map(|i| i + state.local_variables_start)
+}
+
+// Value of statements like `let` and loops, and of empty blocks: empty tuple.
+fn unit_value(state: &mut EvalState) -> Value {
+    StructBuilder::default().finish("()", ValueFlags::empty(), &mut state.types)
+}
+
+// Read the value from memory if needed, for putting it into a struct or array built by the script.
+fn materialize_value(mut val: Value, memory: &mut CachedMemReader) -> Result<Value> {
+    let size = unsafe {(*val.type_).calculate_size()};
+    val.val = AddrOrValueBlob::Blob(val.val.into_value(size, memory)?);
+    Ok(val)
+}
+
+fn eval_condition(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, context: &mut EvalContext) -> Result<bool> {
+    let mut val = eval_expression(expr, node_idx, state, context, false)?;
+    follow_references_and_prettify(&mut val, None, false, state, context)?;
+    Ok(to_basic(&val, &mut context.memory, "branch on")?.cast_to_usize() != 0)
+}
+
+// Returns false if the loop should stop because of `break`.
+fn eval_loop_body(expr: &Expression, body: ASTIdx, state: &mut EvalState, context: &mut EvalContext) -> Result<bool> {
+    match eval_expression(expr, body, state, context, false) {
+        Ok(_) => Ok(true),
+        Err(e) => match state.control_flow.take() {
+            Some(ControlFlow::Break) => Ok(false),
+            Some(ControlFlow::Continue) => Ok(true),
+            cf => {
+                state.control_flow = cf;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn eval_for_loop_iteration(name: &str, val: Value, expr: &Expression, body: ASTIdx, state: &mut EvalState, context: &mut EvalContext) -> Result<bool> {
+    let scope_start = state.local_variables.len();
+    state.local_variables.push((name.to_string(), val));
+    let r = eval_loop_body(expr, body, state, context);
+    state.local_variables.truncate(scope_start);
+    r
+}
+
+fn call_script_function(def: &ScriptDefinition, expr: &Expression, node: &ASTNode, state: &mut EvalState, context: &mut EvalContext) -> Result<Value> {
+    let fexpr = &*def.expr;
+    let fnode = &fexpr.ast[def.node.0];
+    let (name, arg_names, has_return_type) = match &fnode.a {
+        AST::FunctionDefinition {name, arg_names, has_return_type} => (name, arg_names, *has_return_type),
+        _ => panic!("not a function definition"),
+    };
+    if node.children.len() != arg_names.len() {
+        return err!(TypeMismatch, "{}() expects {} arguments, got {}", name, arg_names.len(), node.children.len());
+    }
+    if state.call_depth >= MAX_CALL_DEPTH {
+        return err!(TooLong, "function calls nested more than {} deep (infinite recursion?)", MAX_CALL_DEPTH);
+    }
+
+    // Arguments are evaluated in the caller's scope, then converted to the declared types.
+    let mut args: Vec<(String, Value)> = Vec::new();
+    for (i, &child) in node.children.iter().enumerate() {
+        let mut val = eval_expression(expr, child, state, context, false)?;
+        follow_references_and_prettify(&mut val, None, false, state, context)?;
+        let type_ = eval_type(fexpr, fnode.children[i], state, context)?;
+        args.push((arg_names[i].clone(), cast_value(val, type_, state, context)?));
+    }
+    let return_type = if has_return_type {Some(eval_type(fexpr, fnode.children[arg_names.len()], state, context)?)} else {None};
+
+    // New frame: the caller's locals are not visible.
+    let saved_start = state.local_variables_start;
+    state.local_variables_start = state.local_variables.len();
+    state.local_variables.extend(args);
+    state.call_depth += 1;
+    let r = eval_expression(fexpr, *fnode.children.last().unwrap(), state, context, false);
+    state.call_depth -= 1;
+    state.local_variables.truncate(state.local_variables_start);
+    state.local_variables_start = saved_start;
+
+    let mut val = match r {
+        Ok(v) => v,
+        Err(e) => match state.control_flow.take() {
+            Some(ControlFlow::Return(v)) => v,
+            // `break` or `continue` outside of a loop in this function; report it as an error instead of breaking the caller's loop.
+            _ => return Err(e),
+        }
+    };
+    if let Some(type_) = return_type {
+        follow_references_and_prettify(&mut val, None, false, state, context)?;
+        val = cast_value(val, type_, state, context)?;
+    }
+    Ok(val)
+}
+
+// For compound assignments, makes the already evaluated lhs available to the AST::CompoundAssignmentLhs node in the rhs.
+fn eval_assignment_rhs(expr: &Expression, node: &ASTNode, compound_lhs: &Option<Value>, state: &mut EvalState, context: &mut EvalContext) -> Result<Value> {
+    let lhs = match compound_lhs {
+        None => return eval_expression(expr, node.children[1], state, context, false),
+        Some(v) => v.clone() };
+    state.compound_assignment_lhs.push(lhs);
+    let r = eval_expression(expr, node.children[1], state, context, false);
+    state.compound_assignment_lhs.pop();
+    r
+}
+
+// Calls a function in the debuggee. `this` is the object address for method calls.
+fn call_debuggee_function(function: &DebuggeeFunction, this: Option<usize>, expr: &Expression, arg_nodes: &[ASTIdx], state: &mut EvalState, context: &mut EvalContext) -> Result<Value> {
+    let mut args: Vec<FunctionCallArg> = Vec::new();
+    if let Some(addr) = this {
+        args.push(FunctionCallArg::Int(addr as u64));
+    }
+    for &child in arg_nodes {
+        let val = eval_expression(expr, child, state, context, false)?;
+        let param_type = function.param_types.as_ref().map(|p| p[args.len()]);
+        args.push(function_call_arg(val, param_type, state, context)?);
+    }
+    // Without debug info, assume the function returns an integer or pointer.
+    let type_ = function.return_type.unwrap_or(state.builtin_types.u64_);
+    if let Type::Struct(_) = unsafe {&(*type_).t} {
+        // We don't pass the hidden pointer for structs returned in memory.
+        return err!(NotImplemented, "returning structs from debuggee functions is not supported");
+    }
+    let r = context.call_function(function.addr, &args, &mut state.function_call_time_left)?;
+    state.make_return_value(type_, &r, context)
+}
+
+fn function_call_arg(mut val: Value, param_type: Option<*const TypeInfo>, state: &mut EvalState, context: &mut EvalContext) -> Result<FunctionCallArg> {
+    let by_reference = param_type.is_some_and(|t| matches!(unsafe {&(*t).t}, Type::Pointer(p) if p.flags.contains(PointerFlags::REFERENCE)));
+    if by_reference && unsafe {(*val.type_).t.as_pointer().is_none()} {
+        return match val.val {
+            AddrOrValueBlob::Addr(a) => Ok(FunctionCallArg::Int(a as u64)),
+            AddrOrValueBlob::Blob(_) => err!(Runtime, "can't pass a value that's not in memory by reference"),
+        };
+    }
+    if !by_reference {
+        val.flags.insert(ValueFlags::RAW);
+        follow_references_and_prettify(&mut val, None, /*pointers_too*/ false, state, context)?;
+    }
+    match unsafe {&(*val.type_).t} {
+        // Arrays decay to pointers.
+        Type::Array(_) => return match val.val {
+            AddrOrValueBlob::Addr(a) => Ok(FunctionCallArg::Int(a as u64)),
+            AddrOrValueBlob::Blob(_) => err!(Runtime, "can't pass an array that's not in memory"),
+        },
+        Type::Struct(_) => return err!(NotImplemented, "passing structs by value to debuggee functions is not supported"),
+        _ => (),
+    }
+    if let Some(t) = param_type {
+        val = cast_value(val, t, state, context)?;
+    }
+    let mut type_ = val.type_;
+    while let Type::Enum(e) = unsafe {&(*type_).t} {
+        type_ = e.type_;
+    }
+    let size = unsafe {(*type_).calculate_size()};
+    Ok(match to_basic(&val, &mut context.memory, "pass")? {
+        BasicValue::F(x) if size == 4 => FunctionCallArg::Float(f32::to_bits(x as f32) as u64),
+        BasicValue::F(x) => FunctionCallArg::Float(f64::to_bits(x)),
+        b => FunctionCallArg::Int(b.transmute_to_usize() as u64),
+    })
+}
+
+// Returns None if there's no script struct with this name. Instantiates the struct's TypeInfo on first use.
+fn get_script_struct_type(name: &str, state: &mut EvalState, context: &mut EvalContext) -> Result<Option<*const TypeInfo>> {
+    if let Some(&t) = state.script_types.get(name) {
+        return Ok(Some(t));
+    }
+    let def = match state.definitions.structs.get(name) {
+        Some(d) => d.clone(),
+        None => return Ok(None),
+    };
+    let snode = &def.expr.ast[def.node.0];
+    let field_names = match &snode.a {
+        AST::StructDefinition {field_names, ..} => field_names,
+        _ => panic!("not a struct definition"),
+    };
+    // Register the type before resolving field types, to allow pointers to itself, e.g. `struct Node {next: *Node, x: i64}`.
+    let type_name = state.types.misc_arena.add_str(name);
+    let type_: *mut TypeInfo = state.types.types_arena.add_mut(TypeInfo {name: type_name, flags: TypeFlags::SIZE_KNOWN, t: Type::Struct(StructType::default()), ..Default::default()});
+    state.script_types.insert(name.to_string(), type_);
+
+    let r = lay_out_script_struct(name, type_, field_names, snode, &def.expr, state, context);
+    if r.is_err() {
+        state.script_types.remove(name);
+    }
+    r?;
+    Ok(Some(type_))
+}
+
+// Fills out fields and size of a struct defined in the script. Lays out the fields like C would.
+fn lay_out_script_struct(name: &str, type_: *mut TypeInfo, field_names: &[String], snode: &ASTNode, expr: &Expression, state: &mut EvalState, context: &mut EvalContext) -> Result<()> {
+    let mut struct_type = StructType::default();
+    let mut offset = 0usize;
+    let mut struct_alignment = 1usize;
+    for (field_name, &child) in field_names.iter().zip(snode.children.iter()) {
+        let field_type = eval_type(expr, child, state, context)?;
+        if std::ptr::eq(field_type, type_) {
+            return err!(TypeMismatch, "struct {} contains itself", name);
+        }
+        let size = unsafe {(*field_type).calculate_size()};
+        let alignment = guess_type_alignment(field_type);
+        struct_alignment = struct_alignment.max(alignment);
+        offset = offset.next_multiple_of(alignment);
+        let field_name = state.types.misc_arena.add_str(field_name);
+        state.types.add_field(&mut struct_type, StructField {name: field_name, flags: FieldFlags::empty(), bit_offset: offset * 8, bit_size: size * 8, type_: field_type, discr_value: 0});
+        offset += size;
+    }
+    unsafe {
+        (*type_).size = offset.next_multiple_of(struct_alignment);
+        (*type_).t = Type::Struct(struct_type);
+    }
+    Ok(())
+}
+
+// Debug info doesn't say type alignment. Guess it from the sizes of primitive types inside.
+fn guess_type_alignment(t: *const TypeInfo) -> usize {
+    let t = unsafe {&*t};
+    match &t.t {
+        Type::Struct(s) => s.fields().iter().map(|f| guess_type_alignment(f.type_)).max().unwrap_or(1),
+        Type::Array(a) => guess_type_alignment(a.type_),
+        _ => {
+            let size = t.calculate_size();
+            if size.is_power_of_two() {size.min(16)} else {1}
+        }
+    }
+}
+
+// `val` should have references already followed.
+fn cast_value(mut val: Value, type_: *const TypeInfo, state: &mut EvalState, context: &mut EvalContext) -> Result<Value> {
+    let is_reinterpretable = |type_: *const TypeInfo, to: bool| -> bool {
+        match unsafe {&(*type_).t} {
+            Type::Primitive(_) | Type::Pointer(_) | Type::PointerToMember(_) | Type::Struct(_) | Type::Enum(_) => true,
+            Type::Array(a) => a.flags.contains(ArrayFlags::LEN_KNOWN) || to,
+            Type::Slice(_) => !to,
+            _ => false,
+        }
+    };
+    if !is_reinterpretable(val.type_, false) {
+        return err!(TypeMismatch, "can't cast from {}", unsafe {(*val.type_).t.kind_name()});
+    }
+    if !is_reinterpretable(type_, true) {
+        return err!(TypeMismatch, "can't cast to {}", unsafe {(*type_).t.kind_name()});
+    }
+
+    // Special casts for numeric types, e.g. int to float.
+    match to_basic(&val, &mut context.memory, "cast") {
+        Ok(b) => {
+            if let Some(v) = from_basic(b, type_)? {
+                val.val = v;
+                val.type_ = type_;
+                val.flags.remove(ValueFlags::BIG_ENDIAN); // to_basic converted it to little-endian
+                return Ok(val);
+            }
+        }
+        Err(e) if e.is_type_mismatch() => (),
+        Err(e) => return Err(e),
+    }
+
+    // Reinterpret cast for everything else, e.g. array to struct (by value).
+    // (Casting by value is useful when a value has no address, e.g. casting a simd register from [u64; 8] to [u32; 16].)
+    // Currently we're extra permissive and allow casts even when sizes don't match, and even when the value is a reference (so could be cast through pointer instead);
+    // if some of this turns out too error-prone in practice, we can add some constraints.
+    let from_t = unsafe {&*val.type_};
+    let (from_size, mut from_val) = match &from_t.t {
+        Type::Slice(s) => {
+            // Cast slice as if it were an array. Useful for e.g. casting pretty-printed vectors to string (v as [char8]).
+            let slice_val = mem::take(&mut val.val).into_value(16, &mut context.memory)?;
+            let addr = slice_val.get_usize_at(0).unwrap();
+            let len = slice_val.get_usize_at(8).unwrap();
+            let inner_type = unsafe {&*s.type_};
+            let inner_size = inner_type.calculate_size();
+            (inner_size * len, AddrOrValueBlob::Addr(addr))
+        }
+        _ => {
+            (unsafe {(*val.type_).calculate_size()}, mem::take(&mut val.val))
+        }
+    };
+    let t = unsafe {&*type_};
+    match &t.t {
+        Type::Array(a) if !a.flags.contains(ArrayFlags::LEN_KNOWN) => {
+            // Special cast to unsized array. Type of the result is a sized array of the ~same size as the left hand side.
+            // E.g. `ymm0 as [u8]` is the same as `ymm0 as [u8; 32]`.
+            let element_size = unsafe {(*a.type_).calculate_size()};
+            let n = from_size / element_size;
+            let sized_array = state.types.add_array(a.type_, Some(n), ArrayFlags::empty());
+            if let AddrOrValueBlob::Blob(blob) = &mut from_val {
+                blob.resize(n * element_size);
+            }
+            val.val = from_val;
+            val.type_ = sized_array;
+            return Ok(val);
+        }
+        _ => (),
+    }
+    let to_size = t.calculate_size();
+    let v = match from_val {
+        AddrOrValueBlob::Addr(addr) if to_size <= from_size => AddrOrValueBlob::Addr(addr),
+        AddrOrValueBlob::Addr(addr) => AddrOrValueBlob::Blob(from_val.into_value(to_size, &mut context.memory)?),
+        AddrOrValueBlob::Blob(mut blob) => {
+            blob.resize(to_size);
+            AddrOrValueBlob::Blob(blob)
+        }
+    };
+    val.val = v;
+    val.type_ = type_;
+    Ok(val)
+}
+
 fn eval_type(expr: &Expression, node_idx: ASTIdx, state: &mut EvalState, context: &mut EvalContext) -> Result<*const TypeInfo> {
     let node = &expr.ast[node_idx.0];
     match &node.a {
-        AST::Type {name, quoted} => state.get_type(context, name),
+        AST::Type {name, quoted} => {
+            if let Some(t) = get_script_struct_type(name, state, context)? {
+                return Ok(t);
+            }
+            state.get_type(context, name)
+        }
         AST::PointerType => {
             let inner = eval_type(expr, node.children[0], state, context)?;
             Ok(state.types.add_pointer(inner, PointerFlags::empty()))
@@ -874,7 +1479,7 @@ fn follow_references_and_prettify(val: &mut Value, mut additional_values: Option
         let type_ = unsafe {&*val.type_};
         match &type_.t {
             Type::Pointer(p) if p.flags.contains(PointerFlags::REFERENCE) || pointers_too => {
-                let addr = val.val.clone().into_value(8, &mut context.memory)?.get_usize()?;
+                let addr = val.val.clone().into_value(type_.calculate_size(), &mut context.memory)?.get_usize()?;
                 val.val = AddrOrValueBlob::Addr(addr);
                 val.type_ = p.type_;
             }
@@ -1033,6 +1638,7 @@ enum BinaryOperator {
 struct ASTIdx(usize);
 
 // Syntax and names are partially stolen from https://doc.rust-lang.org/reference/expressions.html
+#[derive(Clone)]
 enum AST {
     Literal(LiteralValue), // 42.69, "foo"
     Variable {name: String, quoted: bool, from_any_frame: bool}, // foo::bar::v, or `v`, or ^v
@@ -1049,6 +1655,7 @@ enum AST {
     TupleIndexing(usize), // t.0
     StructExpression(Vec<String>), // struct { x: 42, y: 69 }
     Call(String), // f(1, "foo")
+    MethodCall(String), // v.f(1, "foo"); children: object, then arguments
     Continue, // continue
     Break, // break
     Return, // return
@@ -1064,6 +1671,8 @@ enum AST {
         has_type: bool,
     },
 
+    CompoundAssignmentLhs, // the `a` in `a + b` in `a = a + b` produced from `a += b`; refers to the already evaluated lhs of the enclosing assignment
+
     TypeInfo, // type(i64)  TODO: For sizeof and offsetof implement .#size and .#offset on types and fields. Maybe add sizeof()/offsetof() syntax too.
 
     FunctionDefinition { // children: arg types, return type (if has), body Block
@@ -1079,8 +1688,10 @@ enum AST {
 
 bitflags! { struct ASTNodeFlags: u8 {
     const HAS_PARENS = 0x1; // is this node directly wrapped in any number of parens
+    const COMPOUND_ASSIGNMENT = 0x2; // `a += b`, see AST::CompoundAssignmentLhs
 }}
 
+#[derive(Clone)]
 struct ASTNode {
     range: Range<usize>,
     a: AST,
@@ -1213,7 +1824,9 @@ impl<'a> Lexer<'a> {
                             int_s = self.input.eat_digits(radix);
                         }
                     }
-                    let (has_dot, frac_s) = if !previous_dot && self.input.eat_if_eq('.') {
+                    // (`1..5` is a range, not a float followed by '.'.)
+                    let range_follows = self.input.input[self.input.pos..].starts_with("..");
+                    let (has_dot, frac_s) = if !previous_dot && !range_follows && self.input.eat_if_eq('.') {
                         (true, self.input.eat_digits(radix))
                     } else {
                         (false, "")
@@ -1230,7 +1843,7 @@ impl<'a> Lexer<'a> {
                     if let Some(c) = self.input.peek() {
                         match c {
                             '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' => return err!(Syntax, "unexpected suffix after number at {}", self.input.pos),
-                            '.' if !previous_dot => return err!(Syntax, "unexpected '.' after number at {}", self.input.pos),
+                            '.' if !previous_dot && !range_follows => return err!(Syntax, "unexpected '.' after number at {}", self.input.pos),
                             _ => (),
                         }
                     }
@@ -1387,6 +2000,11 @@ impl Token {
 
 fn parse_block(lex: &mut Lexer, expr: &mut Expression) -> Result<ASTIdx> {
     let (range, _) = lex.expect("'{'", |t| t.is_char('{'))?;
+    parse_block_after_brace(lex, expr, range)
+}
+
+// `range` is the range of the '{' token, which was already eaten.
+fn parse_block_after_brace(lex: &mut Lexer, expr: &mut Expression, range: Range<usize>) -> Result<ASTIdx> {
     let mut node = ASTNode {range, children: Vec::new(), a: AST::Block, flags: ASTNodeFlags::empty()};
     loop {
         if let Some((range, _)) = lex.eat_if(|t| t.is_char('}'))? {
@@ -1469,7 +2087,7 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
                                         _ => return err!(Syntax, "expected field name, got {:?} at {}", t, r.start),
                                     };
                                     lex.expect("':'", |t| t.is_char(':'))?;
-                                    let ex = parse_expression(lex, expr, Precedence::Weakest)?;
+                                    let ex = parse_expression(lex, expr, Precedence::Comma)?;
                                     field_names.push(name);
                                     node.children.push(ex);
 
@@ -1561,6 +2179,9 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
                             lex.expect("':'", |t| t.is_char(':'))?;
                             node.children.push(parse_type(lex, expr)?);
                             arg_names.push(arg_name);
+                            if lex.eat_if(|t| t.is_binary_operator(BinaryOperator::Comma))?.is_none() {
+                                lex.peek_expect("')'", |t| t.is_char(')'))?;
+                            }
                         }
                         let has_return_type = lex.eat_if(|t| t.is_arrow())?.is_some();
                         if has_return_type {
@@ -1610,7 +2231,7 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
             x => panic!("unexpected lexer output: {:?}", x),
         }
         Token::Char('{') => {
-            let i = parse_block(lex, expr)?;
+            let i = parse_block_after_brace(lex, expr, range.clone())?;
             replace = Some(i);
             AST::Tuple // ignored
         }
@@ -1630,7 +2251,7 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
         }
         Token::Char('[') => {
             while lex.eat_if(|t| t.is_char(']'))?.is_none() {
-                node.children.push(parse_expression(lex, expr, Precedence::Weakest)?);
+                node.children.push(parse_expression(lex, expr, Precedence::Comma)?);
                 if lex.eat_if(|t| t.is_binary_operator(BinaryOperator::Comma))?.is_none() {
                     lex.peek_expect("']'", |t| t.is_char(']'))?;
                 }
@@ -1657,8 +2278,17 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
                 }
                 lex.eat(1)?;
                 let rhs = parse_expression(lex, expr, precedence)?;
-                let node = ASTNode {range: expr.ast[node_idx.0].range.start..expr.ast[rhs.0].range.end, children: vec![node_idx, rhs], a: AST::BinaryOperator(op), flags: ASTNodeFlags::empty()};
-                expr.ast.push(node);
+                let range = expr.ast[node_idx.0].range.start..expr.ast[rhs.0].range.end;
+                if let Some(base_op) = compound_assignment_base_operator(op) {
+                    // Turn `a += b` into `a = <a> + b`, where <a> is the value of `a` evaluated by the assignment. So `a` is evaluated only once, e.g. `arr[f()] += 1` calls f() once.
+                    expr.ast.push(ASTNode {range: expr.ast[node_idx.0].range.clone(), children: Vec::new(), a: AST::CompoundAssignmentLhs, flags: ASTNodeFlags::empty()});
+                    let lhs_value_idx = ASTIdx(expr.ast.len()-1);
+                    expr.ast.push(ASTNode {range: range.clone(), children: vec![lhs_value_idx, rhs], a: AST::BinaryOperator(base_op), flags: ASTNodeFlags::empty()});
+                    let value_idx = ASTIdx(expr.ast.len()-1);
+                    expr.ast.push(ASTNode {range, children: vec![node_idx, value_idx], a: AST::BinaryOperator(BinaryOperator::Assign), flags: ASTNodeFlags::COMPOUND_ASSIGNMENT});
+                } else {
+                    expr.ast.push(ASTNode {range, children: vec![node_idx, rhs], a: AST::BinaryOperator(op), flags: ASTNodeFlags::empty()});
+                }
                 node_idx = ASTIdx(expr.ast.len()-1);
             }
             Token::Char('(') => {
@@ -1675,7 +2305,7 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
                             expr.ast[node_idx.0].a = AST::TypeInfo;
                         } else {
                             while !lex.peek(1)?.1.is_char(')') {
-                                let ex = parse_expression(lex, expr, Precedence::Weakest)?;
+                                let ex = parse_expression(lex, expr, Precedence::Comma)?;
                                 expr.ast[node_idx.0].children.push(ex);
                                 if lex.eat_if(|t| t.is_binary_operator(BinaryOperator::Comma))?.is_none() {
                                     lex.peek_expect("')'", |t| t.is_char(')'))?;
@@ -1686,6 +2316,19 @@ fn parse_expression(lex: &mut Lexer, expr: &mut Expression, outer_precedence: Pr
                         let (r, _) = lex.expect("')'", |t| t.is_char(')'))?;
                         expr.ast[node_idx.0].range.end = r.end;
                     }
+                    AST::Field {name, quoted} if !quoted && !name.starts_with('#') => {
+                        let name = name.clone();
+                        while !lex.peek(1)?.1.is_char(')') {
+                            let ex = parse_expression(lex, expr, Precedence::Comma)?;
+                            expr.ast[node_idx.0].children.push(ex);
+                            if lex.eat_if(|t| t.is_binary_operator(BinaryOperator::Comma))?.is_none() {
+                                lex.peek_expect("')'", |t| t.is_char(')'))?;
+                            }
+                        }
+                        expr.ast[node_idx.0].a = AST::MethodCall(name);
+                        let (r, _) = lex.expect("')'", |t| t.is_char(')'))?;
+                        expr.ast[node_idx.0].range.end = r.end;
+                    }
                     _ => return err!(Syntax, "expression can't be called at {}", range.start),
                 }
             }
@@ -1789,6 +2432,22 @@ fn binary_operator_precedence(op: BinaryOperator) -> Precedence {
     }
 }
 
+fn compound_assignment_base_operator(op: BinaryOperator) -> Option<BinaryOperator> {
+    Some(match op {
+        BinaryOperator::AddAssign => BinaryOperator::Add,
+        BinaryOperator::SubAssign => BinaryOperator::Sub,
+        BinaryOperator::MulAssign => BinaryOperator::Mul,
+        BinaryOperator::DivAssign => BinaryOperator::Div,
+        BinaryOperator::RemAssign => BinaryOperator::Rem,
+        BinaryOperator::AndAssign => BinaryOperator::BitAnd,
+        BinaryOperator::OrAssign => BinaryOperator::BitOr,
+        BinaryOperator::XorAssign => BinaryOperator::BitXor,
+        BinaryOperator::ShlAssign => BinaryOperator::Shl,
+        BinaryOperator::ShrAssign => BinaryOperator::Shr,
+        _ => return None,
+    })
+}
+
 fn unary_operator_precedence(_op: UnaryOperator) -> Precedence {
     Precedence::Unary
 }
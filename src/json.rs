@@ -0,0 +1,325 @@
+use crate::{*, error::*};
+use std::{fmt, fmt::Write, str};
+
+// Minimal JSON, just enough for the debug adapter protocol (see dap.rs).
+// Objects preserve key order; lookup is linear, which is fine for the small messages we deal with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+static NULL: JsonValue = JsonValue::Null;
+
+impl JsonValue {
+    pub fn parse(s: &str) -> Result<JsonValue> {
+        let mut p = JsonParser {s: s.as_bytes(), pos: 0};
+        let v = p.parse_value(0)?;
+        p.skip_whitespace();
+        if p.pos != p.s.len() {
+            return err!(Format, "unexpected trailing characters in json at position {}", p.pos);
+        }
+        Ok(v)
+    }
+
+    pub fn object(fields: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    // Returns Null if there's no such field or if this is not an object.
+    pub fn get(&self, key: &str) -> &JsonValue {
+        match self {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    pub fn is_null(&self) -> bool { matches!(self, Self::Null) }
+    pub fn as_str(&self) -> Option<&str> { match self { Self::String(s) => Some(s), _ => None } }
+    pub fn as_bool(&self) -> Option<bool> { match self { Self::Bool(b) => Some(*b), _ => None } }
+    pub fn as_array(&self) -> &[JsonValue] { match self { Self::Array(a) => a, _ => &[] } }
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            &Self::Number(x) if x.fract() == 0.0 && x.abs() < 9e15 => Some(x as i64),
+            _ => None,
+        }
+    }
+
+    pub fn write(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(b) => out.push_str(if *b {"true"} else {"false"}),
+            &Self::Number(x) if !x.is_finite() => out.push_str("null"),
+            &Self::Number(x) if x.fract() == 0.0 && x.abs() < 9e15 => write!(out, "{}", x as i64).unwrap(),
+            Self::Number(x) => write!(out, "{}", x).unwrap(),
+            Self::String(s) => write_json_string(s, out),
+            Self::Array(a) => {
+                out.push('[');
+                for (i, v) in a.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    v.write(out);
+                }
+                out.push(']');
+            }
+            Self::Object(fields) => {
+                out.push('{');
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    write_json_string(k, out);
+                    out.push(':');
+                    v.write(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        self.write(&mut s);
+        f.write_str(&s)
+    }
+}
+
+impl From<bool> for JsonValue { fn from(x: bool) -> Self { Self::Bool(x) } }
+impl From<i64> for JsonValue { fn from(x: i64) -> Self { Self::Number(x as f64) } }
+impl From<usize> for JsonValue { fn from(x: usize) -> Self { Self::Number(x as f64) } }
+impl From<i32> for JsonValue { fn from(x: i32) -> Self { Self::Number(x as f64) } }
+impl From<&str> for JsonValue { fn from(x: &str) -> Self { Self::String(x.to_string()) } }
+impl From<String> for JsonValue { fn from(x: String) -> Self { Self::String(x) } }
+impl From<Vec<JsonValue>> for JsonValue { fn from(x: Vec<JsonValue>) -> Self { Self::Array(x) } }
+
+fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct JsonParser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.s.len() && b" \t\r\n".contains(&self.s[self.pos]) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        self.skip_whitespace();
+        if self.pos < self.s.len() && self.s[self.pos] == c {
+            self.pos += 1;
+            Ok(())
+        } else {
+            err!(Format, "expected '{}' in json at position {}", c as char, self.pos)
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue> {
+        if depth > 200 {
+            return err!(Format, "json nested too deeply");
+        }
+        self.skip_whitespace();
+        let c = match self.s.get(self.pos) {
+            None => return err!(Format, "unexpected end of json"),
+            Some(c) => *c };
+        match c {
+            b'{' => {
+                self.pos += 1;
+                let mut fields: Vec<(String, JsonValue)> = Vec::new();
+                self.skip_whitespace();
+                if self.s.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.parse_string()?;
+                    self.expect(b':')?;
+                    let value = self.parse_value(depth + 1)?;
+                    fields.push((key, value));
+                    self.skip_whitespace();
+                    match self.s.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Object(fields));
+                        }
+                        _ => return err!(Format, "expected ',' or '}}' in json at position {}", self.pos),
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items: Vec<JsonValue> = Vec::new();
+                self.skip_whitespace();
+                if self.s.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.s.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return err!(Format, "expected ',' or ']' in json at position {}", self.pos),
+                    }
+                }
+            }
+            b'"' => Ok(JsonValue::String(self.parse_string()?)),
+            b't' | b'f' | b'n' => {
+                for (word, value) in [("true", JsonValue::Bool(true)), ("false", JsonValue::Bool(false)), ("null", JsonValue::Null)] {
+                    if self.s[self.pos..].starts_with(word.as_bytes()) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                err!(Format, "unexpected token in json at position {}", self.pos)
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.pos;
+                while self.pos < self.s.len() && (self.s[self.pos].is_ascii_digit() || b"+-.eE".contains(&self.s[self.pos])) {
+                    self.pos += 1;
+                }
+                let text = str::from_utf8(&self.s[start..self.pos]).unwrap();
+                match text.parse::<f64>() {
+                    Ok(x) => Ok(JsonValue::Number(x)),
+                    Err(_) => err!(Format, "invalid number in json: {}", text),
+                }
+            }
+            _ => err!(Format, "unexpected character in json at position {}", self.pos),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        if self.s.get(self.pos) != Some(&b'"') {
+            return err!(Format, "expected string in json at position {}", self.pos);
+        }
+        self.pos += 1;
+        let mut bytes: Vec<u8> = Vec::new();
+        loop {
+            let c = match self.s.get(self.pos) {
+                None => return err!(Format, "unterminated string in json"),
+                Some(c) => *c };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = match self.s.get(self.pos) {
+                        None => return err!(Format, "unterminated string in json"),
+                        Some(e) => *e };
+                    self.pos += 1;
+                    match e {
+                        b'"' | b'\\' | b'/' => bytes.push(e),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'b' => bytes.push(8),
+                        b'f' => bytes.push(12),
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xd800..0xdc00).contains(&code) && self.s[self.pos..].starts_with(b"\\u") {
+                                // Surrogate pair.
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            let c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                            let mut buf = [0u8; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        }
+                        _ => return err!(Format, "invalid escape sequence in json at position {}", self.pos),
+                    }
+                }
+                _ => bytes.push(c),
+            }
+        }
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => err!(Format, "invalid utf8 in json string"),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        if self.pos + 4 > self.s.len() {
+            return err!(Format, "unterminated \\u escape in json");
+        }
+        let text = str::from_utf8(&self.s[self.pos..self.pos+4]).map_err(|_| error!(Format, "invalid \\u escape in json"))?;
+        let code = u32::from_str_radix(text, 16).map_err(|_| error!(Format, "invalid \\u escape in json"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let text = r#" {"seq": 3, "type":"request", "arguments": {"lines": [1, 2.5, -7e2], "ok": true, "none": null, "s": "a\"b\\c\né😀"}} "#;
+        let v = JsonValue::parse(text).unwrap();
+        assert_eq!(v.get("seq").as_i64(), Some(3));
+        assert_eq!(v.get("type").as_str(), Some("request"));
+        let args = v.get("arguments");
+        assert_eq!(args.get("lines").as_array().len(), 3);
+        assert_eq!(args.get("lines").as_array()[2].as_i64(), Some(-700));
+        assert_eq!(args.get("ok").as_bool(), Some(true));
+        assert!(args.get("none").is_null());
+        assert!(args.get("missing").is_null());
+        assert_eq!(args.get("s").as_str(), Some("a\"b\\c\n\u{e9}\u{1f600}"));
+
+        let written = v.to_string();
+        assert_eq!(written, r#"{"seq":3,"type":"request","arguments":{"lines":[1,2.5,-700],"ok":true,"none":null,"s":"a\"b\\c\né😀"}}"#);
+        assert_eq!(JsonValue::parse(&written).unwrap(), v);
+
+        assert!(JsonValue::parse("{\"a\": 1,}").is_err());
+        assert!(JsonValue::parse("[1 2]").is_err());
+        assert!(JsonValue::parse("\"abc").is_err());
+    }
+
+    #[test]
+    fn escapes_and_numbers() {
+        assert_eq!(JsonValue::parse(r#""\ud83d\ude00 \u00e9\t\/\b\f""#).unwrap().as_str(), Some("\u{1f600} \u{e9}\t/\u{8}\u{c}"));
+        // Lone high surrogate.
+        assert_eq!(JsonValue::parse(r#""\ud83d""#).unwrap().as_str(), Some("\u{fffd}"));
+        assert_eq!(JsonValue::from("\u{1}\r").to_string(), r#""\u0001\r""#);
+
+        assert_eq!(JsonValue::parse("0").unwrap().as_i64(), Some(0));
+        assert_eq!(JsonValue::parse("-0.5").unwrap(), JsonValue::Number(-0.5));
+        assert_eq!(JsonValue::parse("1E3").unwrap().as_i64(), Some(1000));
+        assert_eq!(JsonValue::parse("1e300").unwrap().as_i64(), None);
+        assert_eq!(JsonValue::Number(f64::NAN).to_string(), "null");
+        assert_eq!(JsonValue::from(1i64 << 40).to_string(), "1099511627776");
+
+        for bad in ["", "-", "1.2.3", "tru", "nul", "1 2", "[", r#"{"a" 1}"#, "{1: 2}", r#""\x""#, r#""\u12""#] {
+            assert!(JsonValue::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
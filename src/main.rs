@@ -20,6 +20,7 @@ pub mod procfs;
 pub mod unwind;
 pub mod range_index;
 pub mod registers;
+pub mod arch;
 pub mod disassembly;
 pub mod pool;
 pub mod layout;
@@ -44,9 +45,12 @@ pub mod os;
 pub mod license;
 pub mod term_emu;
 pub mod syntax_highlight;
+pub mod json;
+pub mod dap;
+pub mod batch;
 
-use crate::{elf::*, error::*, debugger::*, util::*, ui::*, log::*, process_info::*, symbols::*, symbols_registry::*, procfs::*, unwind::*, range_index::*, settings::*, context::*, executor::*, persistent::*, doc::*, terminal::*, common_ui::*, core_dumper::*, os::*, registers::*};
-use std::{rc::Rc, mem, str, fs, os::fd::{FromRawFd}, io::Read, io, io::Write, panic, process, thread, thread::ThreadId, cell::UnsafeCell, ptr, pin::Pin, sync::Arc, str::FromStr, path::PathBuf, collections::HashSet};
+use crate::{elf::*, error::*, debugger::*, util::*, ui::*, log::*, process_info::*, symbols::*, symbols_registry::*, procfs::*, unwind::*, range_index::*, settings::*, context::*, executor::*, persistent::*, doc::*, terminal::*, common_ui::*, core_dumper::*, os::*, registers::*, dap::*, batch::*};
+use std::{rc::Rc, mem, str, fs, os::fd::{FromRawFd, IntoRawFd}, io::Read, io, io::Write, panic, process, thread, thread::ThreadId, cell::UnsafeCell, ptr, pin::Pin, sync::Arc, str::FromStr, path::PathBuf, collections::HashSet, time::Duration};
 use libc::{self, STDIN_FILENO, pid_t};
 #[cfg(target_env = "musl")]
 use tikv_jemallocator::Jemalloc;
@@ -136,6 +140,12 @@ fn main() {
     let mut core_dumper_buffer_size = 1usize << 20;
     let mut core_dumper_mode = CoreDumperMode::Fork;
     let mut mouse_mode_changed = false;
+    // None - TUI, Some(None) - DAP over stdin/stdout, Some(Some(port)) - DAP over TCP.
+    let mut dap_mode: Option<Option<u16>> = None;
+    // None - interactive, Some(None) - batch mode without a script, Some(Some(path)) - batch mode with script ("-" for stdin).
+    let mut batch_script: Option<Option<String>> = None;
+    let mut batch_json = false;
+    let mut batch_symbols_timeout: Option<Duration> = None;
     while !args.is_empty() && args[0].starts_with("-") {
         if let Some(v) = parse_arg(&mut args, &mut seen_args, "--pid", "-p", false, false) {
             attach_pid = match pid_t::from_str(&v) {
@@ -149,6 +159,14 @@ fn main() {
             settings.use_tty = false;
         } else if let Some(v) = parse_arg(&mut args, &mut seen_args, "--external-tty", "-t", false, false) {
             tty_file = Some(v);
+        } else if let Some(v) = parse_arg(&mut args, &mut seen_args, "--ui-tty", "", false, false) {
+            settings.ui_tty = Some(v);
+        } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--ui-here", "", true, false) {
+            match run_ui_here_tool() {
+                Ok(()) => (),
+                Err(e) => eprintln!("error: {}", e),
+            }
+            return;
         } else if let Some(v) = parse_arg(&mut args, &mut seen_args, "--stdin", "", false, false) {
             settings.stdin_file = Some(v);
         } else if let Some(v) = parse_arg(&mut args, &mut seen_args, "--stdout", "", false, false) {
@@ -241,23 +259,80 @@ fn main() {
             use_default_debuginfod_urls = true;
         } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--aslr", "", true, false) {
             settings.disable_aslr = false;
+        } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--non-stop", "", true, false) {
+            settings.non_stop = true;
+        } else if let Some(m) = parse_arg(&mut args, &mut seen_args, "--follow-fork", "", false, false) {
+            settings.follow_fork = match &m.to_lowercase()[..] {
+                "parent" => FollowFork::Parent,
+                "child" => FollowFork::Child,
+                "both" => FollowFork::Both,
+                _ => {
+                    eprintln!("unrecognized --follow-fork mode: '{}'; expected one of: parent, child, both", m);
+                    process::exit(1);
+                }
+            };
         } else if let Some(s) = parse_arg(&mut args, &mut seen_args, "--breakpoint", "", false, true) {
-            // This allows the path to contain ':' characters.
-            let parts: Vec<&str> = s.rsplitn(2, ':').collect();
-            if parts.len() != 2 {
-                eprintln!("invalid --breakpoint format: '{}', expected path:line", s);
+            match LineBreakpoint::parse(&s) {
+                Some(bp) => settings.initial_breakpoints.push(BreakpointOn::Line(bp)),
+                // Anything that doesn't look like path:line is a function name or pattern.
+                None => match FunctionBreakpoint::parse(&s) {
+                    Ok(f) => settings.initial_breakpoints.push(BreakpointOn::Function(f)),
+                    Err(e) => {
+                        eprintln!("invalid --breakpoint: '{}': {}; expected path:line or function name", s, e);
+                        process::exit(1);
+                    }
+                }
+            }
+        } else if &args[0][..] == "--batch" && (args.len() == 1 || (args[1].starts_with("-") && &args[1][..] != "-")) {
+            // --batch without a script.
+            if batch_script.is_some() {
+                eprintln!("--batch can't be specified multiple times");
+                process::exit(1);
+            }
+            batch_script = Some(None);
+            args = &args[1..];
+        } else if let Some(path) = parse_arg(&mut args, &mut seen_args, "--batch", "", false, false) {
+            if batch_script.is_some() {
+                eprintln!("--batch can't be specified multiple times");
+                process::exit(1);
+            }
+            batch_script = Some(Some(path));
+        } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--json", "", true, false) {
+            batch_json = true;
+        } else if let Some(s) = parse_arg(&mut args, &mut seen_args, "--symbols-timeout", "", false, false) {
+            batch_symbols_timeout = match s.parse::<f64>() {
+                Ok(t) if (0.0..=4e9).contains(&t) => Some(Duration::from_secs_f64(t)),
+                _ => {
+                    eprintln!("invalid --symbols-timeout: '{}', expected real number in [0, 4e9] (in seconds)", s);
+                    process::exit(1);
+                }
+            };
+        } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--dap", "", true, false) {
+            if dap_mode.is_some() {
+                eprintln!("--dap and --dap-port can't be used together");
+                process::exit(1);
+            }
+            dap_mode = Some(None);
+        } else if let Some(s) = parse_arg(&mut args, &mut seen_args, "--dap-port", "", false, false) {
+            if dap_mode.is_some() {
+                eprintln!("--dap and --dap-port can't be used together");
                 process::exit(1);
             }
-            let line = match parts[0].parse::<usize>() {
+            dap_mode = Some(Some(match u16::from_str(&s) {
+                Ok(p) => p,
+                Err(_) => {
+                    eprintln!("invalid --dap-port: '{}', expected port number", s);
+                    process::exit(1);
+                }
+            }));
+        } else if let Some(v) = parse_arg(&mut args, &mut seen_args, "--snapshots", "", false, false) {
+            settings.snapshots = match usize::from_str(&v) {
                 Err(_) => {
-                    eprintln!("invalid --breakpoint line number: '{}', expected nonnegative integer", parts[0]);
+                    eprintln!("invalid --snapshots (expected nonnegative integer): {}", v);
                     process::exit(1);
                 }
-                Ok(l) => l,
+                Ok(n) => n,
             };
-            let path = parts[1].to_string();
-
-            settings.initial_breakpoints.push(LineBreakpoint {path: path.into(), line: line, adjusted_line: None});
         } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--no-syntax-highlight", "", true, false) {
             settings.syntax_highlighting = false;
         } else if let Some(_) = parse_arg(&mut args, &mut seen_args, "--version", "", true, false) {
@@ -318,13 +393,38 @@ fn main() {
         command_line = Some(cmd);
     }
 
+    let tty_file_given = tty_file.is_some();
     if let Some(v) = tty_file {
         if settings.stdin_file.is_none() { settings.stdin_file = Some(v.clone()); }
         if settings.stdout_file.is_none() { settings.stdout_file = Some(v.clone()); }
         if settings.stderr_file.is_none() { settings.stderr_file = Some(v.clone()); }
     }
 
-    if attach_pid.is_none() && command_line.is_none() && core_dump_path.is_none() {
+    if (batch_json || batch_symbols_timeout.is_some()) && batch_script.is_none() {
+        eprintln!("{} requires --batch", if batch_json {"--json"} else {"--symbols-timeout"});
+        process::exit(1);
+    }
+    if batch_script.is_some() && dap_mode.is_some() {
+        eprintln!("--batch and --dap can't be used together");
+        process::exit(1);
+    }
+    if settings.non_stop && dap_mode.is_some() {
+        eprintln!("--non-stop is not supported with --dap");
+        process::exit(1);
+    }
+    if batch_script.is_some() {
+        // Our stdout is for the batch mode output.
+        settings.use_tty = false;
+    }
+
+    if dap_mode.is_some() {
+        if attach_pid.is_some() || core_dump_path.is_some() {
+            eprintln!("--pid and --core are not supported in DAP mode; use attach request instead of --pid");
+            process::exit(1);
+        }
+        // The child can't inherit our stdin/stdout, they're used for the protocol. Its output goes to files in the session directory instead (unless --stdout/--stderr are given).
+        settings.use_tty = false;
+    } else if attach_pid.is_none() && command_line.is_none() && core_dump_path.is_none() {
         eprintln!("usage: {} (-p pid | executable_path [args..] | [-c] core_dump_path [[--exe] executable_path] | --help)", all_args[0]);
         process::exit(1);
     }
@@ -332,11 +432,37 @@ fn main() {
         eprintln!("must have exactly one of: --pid, --core, command line");
         process::exit(1);
     }
+    if settings.ui_tty.is_some() {
+        if command_line.is_none() || batch_script.is_some() || dap_mode.is_some() || tty_file_given {
+            eprintln!("--ui-tty is only supported when running a program in the interactive mode, without --external-tty");
+            process::exit(1);
+        }
+        // The program gets our terminal, the UI goes to the other one.
+        let path = match current_tty_path() {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("--ui-tty: {}", e);
+                process::exit(1);
+            }
+        };
+        if settings.stdin_file.is_none() { settings.stdin_file = Some(path.clone()); }
+        if settings.stdout_file.is_none() { settings.stdout_file = Some(path.clone()); }
+        if settings.stderr_file.is_none() { settings.stderr_file = Some(path.clone()); }
+        settings.use_tty = false;
+    }
     if command_line.is_none() && (settings.stdin_file.is_some() || settings.stdout_file.is_some() || settings.stderr_file.is_some()) {
         eprintln!("--stdin/--stdout/--stderr/--tty are not allowed with --pid or --core");
         process::exit(1);
     }
 
+    // (Before redirecting stderr, so that errors and the "waiting for UI terminal" message are printed in the current terminal.)
+    if let Some(path) = &settings.ui_tty {
+        if let Err(e) = take_over_ui_terminal(path) {
+            eprintln!("--ui-tty: {}", e);
+            process::exit(1);
+        }
+    }
+
     // This redirects stderr to the log file, so we have to do it early.
     let persistent = match PersistentState::init(&settings) {
         Ok(x) => x,
@@ -412,7 +538,12 @@ fn main() {
         }));
     }
 
-    match run(settings, attach_pid, core_dump_path, command_line, persistent, supplementary_binaries) {
+    let result = match (dap_mode, batch_script) {
+        (Some(port), _) => run_dap(settings, port, command_line, persistent, supplementary_binaries),
+        (None, Some(script)) => run_batch(settings, script, batch_json, batch_symbols_timeout.unwrap_or(Duration::from_secs(300)), attach_pid, core_dump_path, command_line, persistent, supplementary_binaries),
+        (None, None) => run(settings, attach_pid, core_dump_path, command_line, persistent, supplementary_binaries),
+    };
+    match result {
         Ok(()) => (),
         Err(e) => {
             eprintln!("fatal: {}", e);
@@ -436,17 +567,13 @@ fn calculate_num_threads(num_threads: &Option<usize>) -> usize {
     }.max(1)
 }
 
-fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<String>, command_line: Option<Vec<String>>, persistent: PersistentState, supplementary_binaries: SupplementaryBinaries) -> Result<()> {
-    let num_threads = calculate_num_threads(&settings.num_threads);
-    let context = Arc::new(Context {settings, executor: Executor::new(num_threads), wake_main_thread: Arc::new(EventFD::new())});
-
-    let epoll = Epoll::new()?;
-
-    // Forward some signals into pipes that wake up the main loop.
-    // SIGCHLD is raised when we need to frob ptrace.
-    // (I also tried using pidfd, but it turned out to be currenlty broken/annoying in Linux in multiple ways: (1) it's not reported to the tracer if this process is itself ptraced (e.g. if you strace the debugger), (2) the signal needs to be blocked in all threads individually, (3) the fd is inherited by forked processes and needs to be closed manually.)
+// Forward some signals into pipes that wake up the main loop.
+// SIGCHLD is raised when we need to frob ptrace.
+// (I also tried using pidfd, but it turned out to be currenlty broken/annoying in Linux in multiple ways: (1) it's not reported to the tracer if this process is itself ptraced (e.g. if you strace the debugger), (2) the signal needs to be blocked in all threads individually, (3) the fd is inherited by forked processes and needs to be closed manually.)
+// Returns read ends of the pipes, indexed by signal number.
+fn forward_signals_to_pipes(epoll: &Epoll, signals: &[i32]) -> Result<[i32; 32]> {
     let mut signal_pipes_read = [-1i32; 32];
-    for sig in [libc::SIGCHLD, libc::SIGWINCH] {
+    for &sig in signals {
         unsafe {
             // Create a pipe.
             let mut pipe = [0i32; 2];
@@ -468,6 +595,16 @@ fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<Str
             epoll.add(pipe[0], libc::EPOLLIN, pipe[0] as u64)?;
         }
     }
+    Ok(signal_pipes_read)
+}
+
+fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<String>, command_line: Option<Vec<String>>, persistent: PersistentState, supplementary_binaries: SupplementaryBinaries) -> Result<()> {
+    let num_threads = calculate_num_threads(&settings.num_threads);
+    let context = Arc::new(Context {settings, executor: Executor::new(num_threads), wake_main_thread: Arc::new(EventFD::new())});
+
+    let epoll = Epoll::new()?;
+
+    let signal_pipes_read = forward_signals_to_pipes(&epoll, &[libc::SIGCHLD, libc::SIGWINCH])?;
 
     let frame_ns = (1e9 / context.settings.fps) as usize;
     let render_timer = TimerFD::new();
@@ -527,12 +664,16 @@ fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<Str
         render_timer.set(1, frame_ns);
     }
 
-    for line_breakpoint in &context.settings.initial_breakpoints {
-        let existing_id = debugger.find_line_breakpoint_fuzzy(&line_breakpoint);
+    for on in &context.settings.initial_breakpoints {
+        let existing_id = match on {
+            BreakpointOn::Line(line_breakpoint) => debugger.find_line_breakpoint_fuzzy(line_breakpoint),
+            BreakpointOn::Function(f) => debugger.find_function_breakpoint(&f.pattern),
+            _ => None,
+        };
         if let Some(id) = existing_id {
             let _ = debugger.set_breakpoint_enabled(id, true);
         } else {
-            let _ = debugger.add_breakpoint(BreakpointOn::Line(line_breakpoint.clone()));
+            let _ = debugger.add_breakpoint(on.clone());
         }
     }
 
@@ -646,13 +787,7 @@ fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<Str
                 //    (We could separate it out (in Layout::build()), it's not a big deal, but that would be more code and some risk of bugs where the two layout code paths don't quite match.)
 
                 // Apply initial_step only the first time we start the program. For subsequent starts, the user can use steps instead (e.g. 's' to start and run to main()).
-                let initial_step = if context.settings.stop_on_initial_exec {
-                    Some(BreakpointOn::InitialExec)
-                } else if context.settings.stop_on_main {
-                    Some(BreakpointOn::PointOfInterest(PointOfInterest::MainFunction))
-                } else {
-                    None
-                };
+                let initial_step = BreakpointOn::initial_step_from_settings(&context.settings);
                 should_start_child = false;
                 debugger.start_child(initial_step)?;
             }
@@ -678,3 +813,193 @@ fn run(settings: Settings, attach_pid: Option<pid_t>, core_dump_path: Option<Str
         }
     }
 }
+
+// Debug Adapter Protocol server instead of the TUI, see dap.rs.
+fn run_dap(settings: Settings, port: Option<u16>, command_line: Option<Vec<String>>, persistent: PersistentState, supplementary_binaries: SupplementaryBinaries) -> Result<()> {
+    let num_threads = calculate_num_threads(&settings.num_threads);
+    let context = Arc::new(Context {settings, executor: Executor::new(num_threads), wake_main_thread: Arc::new(EventFD::new())});
+
+    let epoll = Epoll::new()?;
+    let signal_pipes_read = forward_signals_to_pipes(&epoll, &[libc::SIGCHLD])?;
+
+    let (in_fd, out_fd) = match port {
+        None => (STDIN_FILENO, libc::STDOUT_FILENO),
+        Some(port) => {
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("info: waiting for DAP client on port {}", port);
+            let (stream, addr) = listener.accept()?;
+            eprintln!("info: DAP client connected from {}", addr);
+            let fd = stream.into_raw_fd();
+            (fd, fd)
+        }
+    };
+    epoll.add(in_fd, libc::EPOLLIN, in_fd as u64)?;
+
+    let periodic_timer = TimerFD::new();
+    periodic_timer.set(1, context.settings.periodic_timer_ns);
+    epoll.add(periodic_timer.fd, libc::EPOLLIN, periodic_timer.fd as u64)?;
+
+    let misc_wakeup_fd = context.wake_main_thread.clone();
+    epoll.add(misc_wakeup_fd.fd, libc::EPOLLIN, misc_wakeup_fd.fd as u64)?;
+
+    let mut server = DapServer::new(context.clone(), persistent, supplementary_binaries, command_line, out_fd);
+    defer! {
+        #[allow(static_mut_refs)]
+        unsafe {*DEBUGGER_TO_DROP_ON_PANIC.get() = DebuggerPtr(ptr::null_mut());}
+    }
+
+    // Created when the Debugger is created, by launch or attach request.
+    let mut symbols_event_fd: Option<Arc<EventFD>> = None;
+    let mut have_debugger_events = false;
+
+    loop {
+        let mut events: [libc::epoll_event; 32] = unsafe { mem::zeroed() };
+        let n = epoll.wait(&mut events)?;
+        for event in &events[..n] {
+            let fd = event.u64 as i32;
+            if fd == signal_pipes_read[libc::SIGCHLD as usize] {
+                drain_signal_pipe(fd);
+                have_debugger_events = true;
+            } else if fd == misc_wakeup_fd.fd {
+                misc_wakeup_fd.read();
+                have_debugger_events = true;
+            } else if fd == in_fd {
+                server.read_input(in_fd)?;
+            } else if fd == periodic_timer.fd {
+                periodic_timer.read();
+                if let Some(debugger) = &mut server.debugger {
+                    debugger.refresh_all_resource_stats();
+                }
+            } else if symbols_event_fd.as_ref().is_some_and(|f| f.fd == fd) {
+                let debugger = server.debugger.as_mut().unwrap();
+                let drop_caches = debugger.symbols.process_events();
+                if drop_caches {
+                    debugger.drop_caches()?;
+                }
+            } else {
+                eprintln!("warning: epoll returned unexpected data: {}", fd);
+            }
+        }
+
+        if let Some(debugger) = &mut server.debugger {
+            if symbols_event_fd.is_none() {
+                let f = debugger.symbols.event_fd();
+                epoll.add(f.fd, libc::EPOLLIN, f.fd as u64)?;
+                symbols_event_fd = Some(f);
+                #[allow(static_mut_refs)]
+                unsafe { *DEBUGGER_TO_DROP_ON_PANIC.get() = DebuggerPtr(&mut **debugger); }
+                // The process may have been started or attached while handling the request.
+                have_debugger_events = true;
+            }
+
+            if have_debugger_events {
+                let drop_caches;
+                (have_debugger_events, drop_caches) = debugger.process_events()?;
+                if drop_caches {
+                    debugger.drop_caches()?;
+                }
+                if have_debugger_events {
+                    debugger.context.wake_main_thread.write(1);
+                }
+            }
+        }
+
+        server.report_state_changes();
+
+        if server.should_quit {
+            if let Some(debugger) = &mut server.debugger {
+                debugger.shutdown();
+            }
+            LogTimestampInDestructor("shutdown complete");
+            std::process::exit(0);
+        }
+    }
+}
+
+// Non-interactive mode, see batch.rs.
+fn run_batch(settings: Settings, script: Option<String>, json: bool, symbols_timeout: Duration, attach_pid: Option<pid_t>, core_dump_path: Option<String>, command_line: Option<Vec<String>>, persistent: PersistentState, supplementary_binaries: SupplementaryBinaries) -> Result<()> {
+    let mut runner = BatchRunner::new(script.as_deref(), json, symbols_timeout)?;
+
+    let num_threads = calculate_num_threads(&settings.num_threads);
+    let context = Arc::new(Context {settings, executor: Executor::new(num_threads), wake_main_thread: Arc::new(EventFD::new())});
+
+    let epoll = Epoll::new()?;
+    let signal_pipes_read = forward_signals_to_pipes(&epoll, &[libc::SIGCHLD])?;
+
+    let mut debugger: Pin<Box<Debugger>>;
+    if let &Some(pid) = &attach_pid {
+        debugger = Pin::new(Box::new(Debugger::attach(pid, context.clone(), persistent, supplementary_binaries)?));
+        #[allow(static_mut_refs)]
+        unsafe { *DEBUGGER_TO_DROP_ON_PANIC.get() = DebuggerPtr(&mut *debugger); }
+    } else if let Some(path) = &core_dump_path {
+        debugger = Pin::new(Box::new(Debugger::open_core_dump(path, context.clone(), persistent, supplementary_binaries)?));
+    } else {
+        debugger = Pin::new(Box::new(Debugger::from_command_line(&command_line.unwrap(), context.clone(), persistent, supplementary_binaries)));
+        if script.is_none() {
+            runner.prepend_run_command();
+        }
+    }
+    defer! {
+        #[allow(static_mut_refs)]
+        unsafe {*DEBUGGER_TO_DROP_ON_PANIC.get() = DebuggerPtr(ptr::null_mut());}
+    }
+    PersistentState::load_pretty_printers(&mut debugger);
+
+    let symbols_event_fd = debugger.symbols.event_fd();
+    epoll.add(symbols_event_fd.fd, libc::EPOLLIN, symbols_event_fd.fd as u64)?;
+    let misc_wakeup_fd = debugger.context.wake_main_thread.clone();
+    epoll.add(misc_wakeup_fd.fd, libc::EPOLLIN, misc_wakeup_fd.fd as u64)?;
+    // For the symbols loading timeout.
+    let periodic_timer = TimerFD::new();
+    periodic_timer.set(1, context.settings.periodic_timer_ns);
+    epoll.add(periodic_timer.fd, libc::EPOLLIN, periodic_timer.fd as u64)?;
+
+    for on in &context.settings.initial_breakpoints {
+        let _ = debugger.add_breakpoint(on.clone());
+    }
+
+    // Start executing the script right away.
+    let mut have_debugger_events = true;
+
+    loop {
+        if have_debugger_events {
+            let drop_caches;
+            (have_debugger_events, drop_caches) = debugger.process_events()?;
+            if drop_caches {
+                debugger.drop_caches()?;
+            }
+            if have_debugger_events {
+                debugger.context.wake_main_thread.write(1);
+            }
+        }
+
+        runner.run_commands(&mut debugger);
+        if let Some(code) = runner.exit_code {
+            debugger.shutdown();
+            LogTimestampInDestructor("shutdown complete");
+            std::process::exit(code);
+        }
+
+        let mut events: [libc::epoll_event; 32] = unsafe { mem::zeroed() };
+        let n = epoll.wait(&mut events)?;
+        for event in &events[..n] {
+            let fd = event.u64 as i32;
+            if fd == signal_pipes_read[libc::SIGCHLD as usize] {
+                drain_signal_pipe(fd);
+                have_debugger_events = true;
+            } else if fd == misc_wakeup_fd.fd {
+                misc_wakeup_fd.read();
+                have_debugger_events = true;
+            } else if fd == symbols_event_fd.fd {
+                let drop_caches = debugger.symbols.process_events();
+                if drop_caches {
+                    debugger.drop_caches()?;
+                }
+            } else if fd == periodic_timer.fd {
+                periodic_timer.read();
+            } else {
+                eprintln!("warning: epoll returned unexpected data: {}", fd);
+            }
+        }
+    }
+}
@@ -80,8 +80,12 @@ pub const NT_X86_XSTATE: u32 = 0x202;
 
 pub const ELFCOMPRESS_ZLIB: u32 = 1;
 
+// siginfo.si_code for SIGSEGV: page is mapped but the access is not allowed by its protection. (Missing from libc crate.)
+pub const SEGV_ACCERR: i32 = 2;
+
 // Uuuugh.
 const SIGNAL_NAMES: [&str; 32] = ["[unknown signal number]", "SIGHUP", "SIGINT", "SIGQUIT", "SIGILL", "SIGTRAP", "SIGABRT", "SIGBUS", "SIGFPE", "SIGKILL", "SIGUSR1", "SIGSEGV", "SIGUSR2", "SIGPIPE", "SIGALRM", "SIGTERM", "SIGSTKFLT", "SIGCHLD", "SIGCONT", "SIGSTOP", "SIGTSTP", "SIGTTIN", "SIGTTOU", "SIGURG", "SIGXCPU", "SIGXFSZ", "SIGVTALRM", "SIGPROF", "SIGWINCH", "SIGIO", "SIGPWR", "SIGSYS"];
+const SYSCALL_NAMES: [&str; 451] = ["read", "write", "open", "close", "stat", "fstat", "lstat", "poll", "lseek", "mmap", "mprotect", "munmap", "brk", "rt_sigaction", "rt_sigprocmask", "rt_sigreturn", "ioctl", "pread64", "pwrite64", "readv", "writev", "access", "pipe", "select", "sched_yield", "mremap", "msync", "mincore", "madvise", "shmget", "shmat", "shmctl", "dup", "dup2", "pause", "nanosleep", "getitimer", "alarm", "setitimer", "getpid", "sendfile", "socket", "connect", "accept", "sendto", "recvfrom", "sendmsg", "recvmsg", "shutdown", "bind", "listen", "getsockname", "getpeername", "socketpair", "setsockopt", "getsockopt", "clone", "fork", "vfork", "execve", "exit", "wait4", "kill", "uname", "semget", "semop", "semctl", "shmdt", "msgget", "msgsnd", "msgrcv", "msgctl", "fcntl", "flock", "fsync", "fdatasync", "truncate", "ftruncate", "getdents", "getcwd", "chdir", "fchdir", "rename", "mkdir", "rmdir", "creat", "link", "unlink", "symlink", "readlink", "chmod", "fchmod", "chown", "fchown", "lchown", "umask", "gettimeofday", "getrlimit", "getrusage", "sysinfo", "times", "ptrace", "getuid", "syslog", "getgid", "setuid", "setgid", "geteuid", "getegid", "setpgid", "getppid", "getpgrp", "setsid", "setreuid", "setregid", "getgroups", "setgroups", "setresuid", "getresuid", "setresgid", "getresgid", "getpgid", "setfsuid", "setfsgid", "getsid", "capget", "capset", "rt_sigpending", "rt_sigtimedwait", "rt_sigqueueinfo", "rt_sigsuspend", "sigaltstack", "utime", "mknod", "uselib", "personality", "ustat", "statfs", "fstatfs", "sysfs", "getpriority", "setpriority", "sched_setparam", "sched_getparam", "sched_setscheduler", "sched_getscheduler", "sched_get_priority_max", "sched_get_priority_min", "sched_rr_get_interval", "mlock", "munlock", "mlockall", "munlockall", "vhangup", "modify_ldt", "pivot_root", "_sysctl", "prctl", "arch_prctl", "adjtimex", "setrlimit", "chroot", "sync", "acct", "settimeofday", "mount", "umount2", "swapon", "swapoff", "reboot", "sethostname", "setdomainname", "iopl", "ioperm", "create_module", "init_module", "delete_module", "get_kernel_syms", "query_module", "quotactl", "nfsservctl", "getpmsg", "putpmsg", "afs_syscall", "tuxcall", "security", "gettid", "readahead", "setxattr", "lsetxattr", "fsetxattr", "getxattr", "lgetxattr", "fgetxattr", "listxattr", "llistxattr", "flistxattr", "removexattr", "lremovexattr", "fremovexattr", "tkill", "time", "futex", "sched_setaffinity", "sched_getaffinity", "set_thread_area", "io_setup", "io_destroy", "io_getevents", "io_submit", "io_cancel", "get_thread_area", "lookup_dcookie", "epoll_create", "epoll_ctl_old", "epoll_wait_old", "remap_file_pages", "getdents64", "set_tid_address", "restart_syscall", "semtimedop", "fadvise64", "timer_create", "timer_settime", "timer_gettime", "timer_getoverrun", "timer_delete", "clock_settime", "clock_gettime", "clock_getres", "clock_nanosleep", "exit_group", "epoll_wait", "epoll_ctl", "tgkill", "utimes", "vserver", "mbind", "set_mempolicy", "get_mempolicy", "mq_open", "mq_unlink", "mq_timedsend", "mq_timedreceive", "mq_notify", "mq_getsetattr", "kexec_load", "waitid", "add_key", "request_key", "keyctl", "ioprio_set", "ioprio_get", "inotify_init", "inotify_add_watch", "inotify_rm_watch", "migrate_pages", "openat", "mkdirat", "mknodat", "fchownat", "futimesat", "newfstatat", "unlinkat", "renameat", "linkat", "symlinkat", "readlinkat", "fchmodat", "faccessat", "pselect6", "ppoll", "unshare", "set_robust_list", "get_robust_list", "splice", "tee", "sync_file_range", "vmsplice", "move_pages", "utimensat", "epoll_pwait", "signalfd", "timerfd_create", "eventfd", "fallocate", "timerfd_settime", "timerfd_gettime", "accept4", "signalfd4", "eventfd2", "epoll_create1", "dup3", "pipe2", "inotify_init1", "preadv", "pwritev", "rt_tgsigqueueinfo", "perf_event_open", "recvmmsg", "fanotify_init", "fanotify_mark", "prlimit64", "name_to_handle_at", "open_by_handle_at", "clock_adjtime", "syncfs", "sendmmsg", "setns", "getcpu", "process_vm_readv", "process_vm_writev", "kcmp", "finit_module", "sched_setattr", "sched_getattr", "renameat2", "seccomp", "getrandom", "memfd_create", "kexec_file_load", "bpf", "execveat", "userfaultfd", "membarrier", "mlock2", "copy_file_range", "preadv2", "pwritev2", "pkey_mprotect", "pkey_alloc", "pkey_free", "statx", "io_pgetevents", "rseq", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "pidfd_send_signal", "io_uring_setup", "io_uring_enter", "io_uring_register", "open_tree", "move_mount", "fsopen", "fsconfig", "fsmount", "fspick", "pidfd_open", "clone3", "close_range", "openat2", "pidfd_getfd", "faccessat2", "process_madvise", "epoll_pwait2", "mount_setattr", "quotactl_fd", "landlock_create_ruleset", "landlock_add_rule", "landlock_restrict_self", "memfd_secret", "process_mrelease", "futex_waitv", "set_mempolicy_home_node"];
 const ERRNO_NAMES: [&str; 134] = ["[success]", "EPERM", "ENOENT", "ESRCH", "EINTR", "EIO", "ENXIO", "E2BIG", "ENOEXEC", "EBADF", "ECHILD", "EAGAIN", "ENOMEM", "EACCES", "EFAULT", "ENOTBLK", "EBUSY", "EEXIST", "EXDEV", "ENODEV", "ENOTDIR", "EISDIR", "EINVAL", "ENFILE", "EMFILE", "ENOTTY", "ETXTBSY", "EFBIG", "ENOSPC", "ESPIPE", "EROFS", "EMLINK", "EPIPE", "EDOM", "ERANGE", "EDEADLK", "ENAMETOOLONG", "ENOLCK", "ENOSYS", "ENOTEMPTY", "ELOOP", "[unknown errno]", "ENOMSG", "EIDRM", "ECHRNG", "EL2NSYNC", "EL3HLT", "EL3RST", "ELNRNG", "EUNATCH", "ENOCSI", "EL2HLT", "EBADE", "EBADR", "EXFULL", "ENOANO", "EBADRQC", "EBADSLT", "[unknown errno]", "EBFONT", "ENOSTR", "ENODATA", "ETIME", "ENOSR", "ENONET", "ENOPKG", "EREMOTE", "ENOLINK", "EADV", "ESRMNT", "ECOMM", "EPROTO", "EMULTIHOP", "EDOTDOT", "EBADMSG", "EOVERFLOW", "ENOTUNIQ", "EBADFD", "EREMCHG", "ELIBACC", "ELIBBAD", "ELIBSCN", "ELIBMAX", "ELIBEXEC", "EILSEQ", "ERESTART", "ESTRPIPE", "EUSERS", "ENOTSOCK", "EDESTADDRREQ", "EMSGSIZE", "EPROTOTYPE", "ENOPROTOOPT", "EPROTONOSUPPORT", "ESOCKTNOSUPPORT", "EOPNOTSUPP", "EPFNOSUPPORT", "EAFNOSUPPORT", "EADDRINUSE", "EADDRNOTAVAIL", "ENETDOWN", "ENETUNREACH", "ENETRESET", "ECONNABORTED", "ECONNRESET", "ENOBUFS", "EISCONN", "ENOTCONN", "ESHUTDOWN", "ETOOMANYREFS", "ETIMEDOUT", "ECONNREFUSED", "EHOSTDOWN", "EHOSTUNREACH", "EALREADY", "EINPROGRESS", "ESTALE", "EUCLEAN", "ENOTNAM", "ENAVAIL", "EISNAM", "EREMOTEIO", "EDQUOT", "ENOMEDIUM", "EMEDIUMTYPE", "ECANCELED", "ENOKEY", "EKEYEXPIRED", "EKEYREVOKED", "EKEYREJECTED", "EOWNERDEAD", "ENOTRECOVERABLE", "ERFKILL", "EHWPOISON"];
 
 pub fn signal_name(sig: i32) -> &'static str {
@@ -90,6 +94,31 @@ pub fn signal_name(sig: i32) -> &'static str {
     SIGNAL_NAMES[if sig >= SIGNAL_NAMES.len() {0} else {sig}]
 }
 
+// Accepts "SIGUSR1", "usr1", or a number (including realtime signals, 32-64).
+pub fn signal_from_name(s: &str) -> Option<i32> {
+    if let Ok(n) = s.parse::<i32>() {
+        return if (1..=64).contains(&n) {Some(n)} else {None};
+    }
+    let s = s.to_ascii_uppercase();
+    let s = s.strip_prefix("SIG").unwrap_or(&s);
+    SIGNAL_NAMES.iter().skip(1).position(|n| &n[3..] == s).map(|i| i as i32 + 1)
+}
+
+pub fn syscall_name(nr: usize) -> &'static str {
+    match SYSCALL_NAMES.get(nr) {
+        Some(&s) if !s.is_empty() => s,
+        _ => "[unknown syscall]",
+    }
+}
+
+// Accepts name or number.
+pub fn syscall_from_name(s: &str) -> Option<usize> {
+    if let Ok(n) = s.parse::<usize>() {
+        return Some(n);
+    }
+    SYSCALL_NAMES.iter().position(|n| !n.is_empty() && *n == s)
+}
+
 pub fn errno_name(errno: i32) -> &'static str {
     // There's no errno -> name (not message) function that's consistenly available in C standard library on Linux.
     let errno = errno as usize;
@@ -154,6 +183,26 @@ pub struct elf_prstatus {
     pub pr_reg: libc::user_regs_struct, // GP registers
     pub pr_fpvalid: i32, // True if math co-processor being used.
 }
+// Beginning of elf_prstatus of 32-bit processes (i386 and x32), up to pr_reg. The size of pr_reg depends on the architecture.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct compat_elf_prstatus_common {
+	pub si_signo: i32,
+	pub si_code_but_actually_it_is_zero: i32,
+	pub si_errno_but_actually_it_is_zero: i32,
+
+    pub pr_cursig: i16,
+    pub pr_sigpend: u32,
+    pub pr_sighold: u32,
+    pub pr_pid: pid_t,
+    pub pr_ppid: pid_t,
+    pub pr_pgrp: pid_t,
+    pub pr_sid: pid_t,
+    pub pr_utime: [i32; 2], // compat_timeval
+    pub pr_stime: [i32; 2],
+    pub pr_cutime: [i32; 2],
+    pub pr_cstime: [i32; 2],
+}
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct elf_prpsinfo {
@@ -242,3 +291,127 @@ pub const PTRACE_O_TRACEEXIT: u64 = 1 << PTRACE_EVENT_EXIT;
 pub const PTRACE_O_TRACESECCOMP: u64 = 1 << PTRACE_EVENT_SECCOMP;
 pub const PTRACE_O_EXITKILL: u64 = 1 << 20;
 pub const PTRACE_O_SUSPEND_SECCOMP: u64 = 1 << 21;
+
+pub const PTRACE_SYSCALL_INFO_NONE: u8 = 0;
+// ptrace_syscall_info.arch values (from linux/audit.h).
+pub const AUDIT_ARCH_X86_64: u32 = 0xc000003e;
+// Set in the syscall number for syscalls made by x32 processes. The rest of the number mostly matches x86-64 numbering.
+pub const X32_SYSCALL_BIT: u64 = 0x40000000;
+
+pub const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+pub const PTRACE_SYSCALL_INFO_EXIT: u8 = 2;
+pub const PTRACE_SYSCALL_INFO_SECCOMP: u8 = 3;
+
+// struct ptrace_syscall_info, with the union flattened. For PTRACE_SYSCALL_INFO_EXIT, `nr` is the return value and args[0] is is_error.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct ptrace_syscall_info {
+    pub op: u8,
+    pub pad: [u8; 3],
+    pub arch: u32,
+    pub instruction_pointer: u64,
+    pub stack_pointer: u64,
+    pub nr: u64,
+    pub args: [u64; 6],
+    pub ret_data: u32,
+    pub pad2: u32,
+}
+
+// Syscall number and arguments, as seen at syscall-enter-stop.
+#[derive(Clone, Copy, Debug)]
+pub struct SyscallStop {
+    pub nr: usize,
+    pub args: [u64; 6],
+}
+
+// How to show a syscall argument in the watches window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyscallArg {
+    Int, // C int, e.g. file descriptor
+    UInt, // e.g. mode_t
+    Long,
+    Size, // size_t and other 64-bit unsigned
+    Ptr,
+    Str, // const char*, null-terminated
+    DirFd, // fd or AT_FDCWD
+    OpenFlags,
+    Prot,
+    MapFlags,
+    Signal,
+}
+
+pub const AT_FDCWD: i32 = -100;
+pub const OPEN_FLAG_NAMES: &[(&str, usize)] = &[("O_RDONLY", 0), ("O_WRONLY", 0o1), ("O_RDWR", 0o2), ("O_CREAT", 0o100), ("O_EXCL", 0o200), ("O_NOCTTY", 0o400), ("O_TRUNC", 0o1000), ("O_APPEND", 0o2000), ("O_NONBLOCK", 0o4000), ("O_DSYNC", 0o10000), ("O_ASYNC", 0o20000), ("O_DIRECT", 0o40000), ("O_LARGEFILE", 0o100000), ("O_DIRECTORY", 0o200000), ("O_NOFOLLOW", 0o400000), ("O_NOATIME", 0o1000000), ("O_CLOEXEC", 0o2000000), ("O_SYNC", 0o4010000), ("O_PATH", 0o10000000), ("O_TMPFILE", 0o20200000)];
+pub const PROT_FLAG_NAMES: &[(&str, usize)] = &[("PROT_NONE", 0), ("PROT_READ", 1), ("PROT_WRITE", 2), ("PROT_EXEC", 4)];
+pub const MAP_FLAG_NAMES: &[(&str, usize)] = &[("MAP_SHARED", 0x1), ("MAP_PRIVATE", 0x2), ("MAP_SHARED_VALIDATE", 0x3), ("MAP_FIXED", 0x10), ("MAP_ANONYMOUS", 0x20), ("MAP_32BIT", 0x40), ("MAP_GROWSDOWN", 0x100), ("MAP_DENYWRITE", 0x800), ("MAP_EXECUTABLE", 0x1000), ("MAP_LOCKED", 0x2000), ("MAP_NORESERVE", 0x4000), ("MAP_POPULATE", 0x8000), ("MAP_NONBLOCK", 0x10000), ("MAP_STACK", 0x20000), ("MAP_HUGETLB", 0x40000), ("MAP_SYNC", 0x80000), ("MAP_FIXED_NOREPLACE", 0x100000)];
+
+// Names and types of arguments of common syscalls. Other syscalls get generic arg0..arg5.
+pub fn syscall_args(nr: usize) -> &'static [(&'static str, SyscallArg)] {
+    use SyscallArg::*;
+    match syscall_name(nr) {
+        "read" | "write" => &[("fd", Int), ("buf", Ptr), ("count", Size)],
+        "pread64" | "pwrite64" => &[("fd", Int), ("buf", Ptr), ("count", Size), ("offset", Long)],
+        "open" => &[("path", Str), ("flags", OpenFlags), ("mode", UInt)],
+        "openat" => &[("dirfd", DirFd), ("path", Str), ("flags", OpenFlags), ("mode", UInt)],
+        "creat" | "mkdir" | "chmod" => &[("path", Str), ("mode", UInt)],
+        "mkdirat" | "fchmodat" => &[("dirfd", DirFd), ("path", Str), ("mode", UInt)],
+        "close" | "dup" | "fsync" | "fdatasync" | "fchdir" => &[("fd", Int)],
+        "dup2" => &[("oldfd", Int), ("newfd", Int)],
+        "dup3" => &[("oldfd", Int), ("newfd", Int), ("flags", OpenFlags)],
+        "stat" | "lstat" => &[("path", Str), ("statbuf", Ptr)],
+        "fstat" => &[("fd", Int), ("statbuf", Ptr)],
+        "newfstatat" => &[("dirfd", DirFd), ("path", Str), ("statbuf", Ptr), ("flags", Int)],
+        "statx" => &[("dirfd", DirFd), ("path", Str), ("flags", Int), ("mask", Size), ("statxbuf", Ptr)],
+        "lseek" => &[("fd", Int), ("offset", Long), ("whence", Int)],
+        "mmap" => &[("addr", Ptr), ("length", Size), ("prot", Prot), ("flags", MapFlags), ("fd", Int), ("offset", Size)],
+        "mprotect" => &[("addr", Ptr), ("length", Size), ("prot", Prot)],
+        "munmap" => &[("addr", Ptr), ("length", Size)],
+        "madvise" => &[("addr", Ptr), ("length", Size), ("advice", Int)],
+        "brk" => &[("addr", Ptr)],
+        "rt_sigaction" => &[("signum", Signal), ("act", Ptr), ("oldact", Ptr), ("sigsetsize", Size)],
+        "rt_sigprocmask" => &[("how", Int), ("set", Ptr), ("oldset", Ptr), ("sigsetsize", Size)],
+        "ioctl" => &[("fd", Int), ("request", Size), ("arg", Ptr)],
+        "fcntl" => &[("fd", Int), ("cmd", Int), ("arg", Size)],
+        "access" => &[("path", Str), ("mode", Int)],
+        "faccessat" | "faccessat2" => &[("dirfd", DirFd), ("path", Str), ("mode", Int), ("flags", Int)],
+        "pipe" => &[("pipefd", Ptr)],
+        "pipe2" => &[("pipefd", Ptr), ("flags", OpenFlags)],
+        "poll" => &[("fds", Ptr), ("nfds", Size), ("timeout", Int)],
+        "nanosleep" => &[("req", Ptr), ("rem", Ptr)],
+        "clock_nanosleep" => &[("clockid", Int), ("flags", Int), ("req", Ptr), ("rem", Ptr)],
+        "socket" => &[("domain", Int), ("type", Int), ("protocol", Int)],
+        "connect" | "bind" => &[("sockfd", Int), ("addr", Ptr), ("addrlen", Size)],
+        "accept" => &[("sockfd", Int), ("addr", Ptr), ("addrlen", Ptr)],
+        "accept4" => &[("sockfd", Int), ("addr", Ptr), ("addrlen", Ptr), ("flags", Int)],
+        "listen" => &[("sockfd", Int), ("backlog", Int)],
+        "sendto" => &[("sockfd", Int), ("buf", Ptr), ("len", Size), ("flags", Int), ("dest_addr", Ptr), ("addrlen", Size)],
+        "recvfrom" => &[("sockfd", Int), ("buf", Ptr), ("len", Size), ("flags", Int), ("src_addr", Ptr), ("addrlen", Ptr)],
+        "sendmsg" | "recvmsg" => &[("sockfd", Int), ("msg", Ptr), ("flags", Int)],
+        "shutdown" => &[("sockfd", Int), ("how", Int)],
+        "clone" => &[("flags", Size), ("stack", Ptr), ("parent_tid", Ptr), ("child_tid", Ptr), ("tls", Ptr)],
+        "clone3" => &[("args", Ptr), ("size", Size)],
+        "execve" => &[("path", Str), ("argv", Ptr), ("envp", Ptr)],
+        "execveat" => &[("dirfd", DirFd), ("path", Str), ("argv", Ptr), ("envp", Ptr), ("flags", Int)],
+        "exit" | "exit_group" => &[("status", Int)],
+        "wait4" => &[("pid", Int), ("wstatus", Ptr), ("options", Int), ("rusage", Ptr)],
+        "kill" => &[("pid", Int), ("sig", Signal)],
+        "tkill" => &[("tid", Int), ("sig", Signal)],
+        "tgkill" => &[("tgid", Int), ("tid", Int), ("sig", Signal)],
+        "unlink" | "rmdir" | "chdir" => &[("path", Str)],
+        "unlinkat" => &[("dirfd", DirFd), ("path", Str), ("flags", Int)],
+        "rename" | "link" | "symlink" => &[("oldpath", Str), ("newpath", Str)],
+        "renameat" | "renameat2" => &[("olddirfd", DirFd), ("oldpath", Str), ("newdirfd", DirFd), ("newpath", Str), ("flags", Int)],
+        "readlink" => &[("path", Str), ("buf", Ptr), ("bufsiz", Size)],
+        "readlinkat" => &[("dirfd", DirFd), ("path", Str), ("buf", Ptr), ("bufsiz", Size)],
+        "truncate" => &[("path", Str), ("length", Long)],
+        "ftruncate" => &[("fd", Int), ("length", Long)],
+        "futex" => &[("uaddr", Ptr), ("futex_op", Int), ("val", Int), ("timeout", Ptr), ("uaddr2", Ptr), ("val3", Int)],
+        "epoll_wait" => &[("epfd", Int), ("events", Ptr), ("maxevents", Int), ("timeout", Int)],
+        "epoll_ctl" => &[("epfd", Int), ("op", Int), ("fd", Int), ("event", Ptr)],
+        "prctl" => &[("option", Int), ("arg2", Size), ("arg3", Size), ("arg4", Size), ("arg5", Size)],
+        "getrandom" => &[("buf", Ptr), ("buflen", Size), ("flags", Int)],
+        "close_range" => &[("first", Int), ("last", Int), ("flags", Int)],
+        "getpid" | "gettid" | "getppid" | "sched_yield" | "fork" | "vfork" => &[],
+        _ => &[("arg0", Size), ("arg1", Size), ("arg2", Size), ("arg3", Size), ("arg4", Size), ("arg5", Size)],
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{*, error::*, debugger::*, ui::*, util::*, settings::*, log::*};
+use crate::{*, error::*, debugger::*, ui::*, util::*, settings::*, log::*, pretty::*};
 use std::{fs, os::fd::{OwnedFd, RawFd, AsRawFd, FromRawFd}, os::unix::ffi::OsStrExt, ffi::CString, io, io::{Read, Write}, path::{Path, PathBuf}, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
 
 pub struct PersistentState {
@@ -16,8 +16,9 @@ pub struct PersistentState {
     state_hash: u64,
     save_failures: usize,
     keys_config_reload_count: usize,
+    pretty_printers_config_reload_count: usize,
 }
-impl Default for PersistentState { fn default() -> Self { Self {path: err!(Internal, "state is empty"), configs_path: None, debuginfod_cache_path: None, config_change_fd: None, dir: None, lock: None, state_hash: 0, save_failures: 0, log_file_path: None, original_stderr_fd: None, keys_config_reload_count: 0} } }
+impl Default for PersistentState { fn default() -> Self { Self {path: err!(Internal, "state is empty"), configs_path: None, debuginfod_cache_path: None, config_change_fd: None, dir: None, lock: None, state_hash: 0, save_failures: 0, log_file_path: None, original_stderr_fd: None, keys_config_reload_count: 0, pretty_printers_config_reload_count: 0} } }
 impl PersistentState {
     // Finds/creates a directory ~/.nnd/0, and flock()s ~/.nnd/0/lock to prevent other debugger processes from using this directory.
     // If ~/.nnd/0 is already locked, tries ~/.nnd/1, etc. The lock is released when debugger exits or dies.
@@ -119,6 +120,7 @@ impl PersistentState {
         let mut buf: Vec<u8> = Vec::new();
 
         buf.write_usize(STATE_FILE_MAGIC_NUMBER)?;
+        buf.write_usize(STATE_FILE_VERSION)?;
         debugger.save_state(&mut buf)?;
         ui.save_state(&mut buf)?;
 
@@ -144,6 +146,16 @@ impl PersistentState {
         Ok(())
     }
 
+    // For the DAP frontend (dap.rs), which has no UI state or key binds.
+    pub fn load_pretty_printers(debugger: &mut Debugger) {
+        if debugger.persistent.configs_path.is_none() {
+            return;
+        }
+        if let Some(printers) = Self::read_pretty_printers_config(debugger) {
+            debugger.pretty_printers = printers;
+        }
+    }
+
     pub fn load_state_and_configs(debugger: &mut Debugger, ui: &mut DebuggerUI) -> Option</*config_change_fd*/ i32> {
         if debugger.persistent.dir.is_none() {
             return None;
@@ -162,6 +174,9 @@ impl PersistentState {
             if let Some(binds) = Self::read_keys_config(debugger, ui) {
                 ui.ui.key_binds = binds;
             }
+            if let Some(printers) = Self::read_pretty_printers_config(debugger) {
+                debugger.pretty_printers = printers;
+            }
         }
         match Self::load_state(debugger, ui) {
             Ok(()) => (),
@@ -176,10 +191,11 @@ impl PersistentState {
 
     pub fn process_events(debugger: &mut Debugger, ui: &mut DebuggerUI) {
         if let Some(fd) = &debugger.persistent.config_change_fd {
-            let (mut keys_changed, mut _colors_changed) = (false, false);
+            let (mut keys_changed, mut _colors_changed, mut pretty_printers_changed) = (false, false, false);
             for (ev, name) in fd.read() {
                 keys_changed |= &name == b"keys";
                 _colors_changed |= &name == b"colors";
+                pretty_printers_changed |= &name == b"pretty_printers";
             }
             if keys_changed {
                 if let Some(binds) = Self::read_keys_config(debugger, ui) {
@@ -190,6 +206,14 @@ impl PersistentState {
                     }
                 }
             }
+            if pretty_printers_changed {
+                if let Some(printers) = Self::read_pretty_printers_config(debugger) {
+                    debugger.pretty_printers = printers;
+                    debugger.persistent.pretty_printers_config_reload_count += 1;
+                    log!(debugger.log, "reloaded pretty printers config ({})", debugger.persistent.pretty_printers_config_reload_count);
+                    ui.drop_caches(); // re-evaluate watches
+                }
+            }
             /* uncomment when we actually read colors config here
             if colors_changed {
                 ui.drop_caches(); // there are colors in cached disassembly and code
@@ -219,11 +243,16 @@ impl PersistentState {
 
         let mut inp: &[u8] = &buf;
         let magic = inp.read_usize()?;
-        if magic != STATE_FILE_MAGIC_NUMBER {
-            return err!(Environment, "bad magic number");
+        let version = match magic {
+            STATE_FILE_MAGIC_NUMBER => inp.read_usize()?,
+            STATE_FILE_MAGIC_NUMBER_V0 => 0,
+            _ => return err!(Environment, "bad magic number"),
+        };
+        if version > STATE_FILE_VERSION {
+            return err!(Environment, "save file is from a newer version of nnd (format version {}, expected at most {})", version, STATE_FILE_VERSION);
         }
 
-        debugger.load_state(&mut inp)?;
+        debugger.load_state(&mut inp, version)?;
         ui.load_state(&mut inp)?;
 
         if !inp.is_empty() {
@@ -272,6 +301,11 @@ impl PersistentState {
 
         Self::write_shared_file(keys_str.as_bytes(), keys_default, configs_path, dir)?;
 
+        let mut pretty_str = String::new();
+        let pretty_default = Path::new("pretty_printers.default");
+        CustomPrettyPrinters::write_config_example(&mut pretty_str, &configs_path.join("pretty_printers"), &configs_path.join(pretty_default), &configs_path.join("pretty_printers.error"))?;
+        Self::write_shared_file(pretty_str.as_bytes(), pretty_default, configs_path, dir)?;
+
         Ok(())
     }
 
@@ -310,9 +344,42 @@ impl PersistentState {
             }
         }
     }
+
+    fn read_pretty_printers_config(debugger: &mut Debugger) -> Option<CustomPrettyPrinters> {
+        let configs_path = debugger.persistent.configs_path.as_ref().unwrap();
+        let path = configs_path.join("pretty_printers");
+        let text = match fs::read_to_string(&path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Some(CustomPrettyPrinters::default()),
+            Err(e) => {
+                eprintln!("warning: failed to read pretty printers config file at {}: {}", path.display(), e);
+                log!(debugger.log, "pretty printers config read error: {}", e);
+                return None;
+            }
+        };
+        let error_file_name = Path::new("pretty_printers.error");
+        let mut error_line_number = 0usize;
+        match CustomPrettyPrinters::parse_config(&text, &mut error_line_number) {
+            Ok(c) => {
+                let _ = fs::remove_file(configs_path.join(error_file_name));
+                Some(c)
+            }
+            Err(e) => {
+                eprintln!("warning: pretty printers config error on line {}: {}", error_line_number, e);
+                log!(debugger.log, "pretty printers config error on line {}: {}", error_line_number, e);
+                let _ = Self::write_shared_file(format!("line {}: {}\n", error_line_number, e).as_bytes(), error_file_name, configs_path, debugger.persistent.dir.as_ref().unwrap());
+                None
+            }
+        }
+    }
 }
 
-const STATE_FILE_MAGIC_NUMBER: usize = 0xe4b84e6353eb8214;
+// Files with this magic number are followed by STATE_FILE_VERSION. Bump the version when changing the format, and keep loading the old versions.
+const STATE_FILE_MAGIC_NUMBER: usize = 0x6e0d52b8f17ac395;
+// Before the version number was added. Old nnd versions reject files with the new magic number instead of misparsing them.
+const STATE_FILE_MAGIC_NUMBER_V0: usize = 0x6e0d52b8f17ac394;
+// 1 - breakpoint log message, ignore count, disable-after-hit; data breakpoint value type; signal, syscall and function breakpoints.
+const STATE_FILE_VERSION: usize = 1;
 
 pub fn open_dev_null() -> Result<fs::File> {
     let fd = unsafe {libc::open("/dev/null\0".as_ptr() as *const i8, libc::O_RDWR, libc::O_CLOEXEC)};
@@ -1,7 +1,130 @@
-use crate::{*, types::*, error::*, expr::*, util::*, settings::*, common_ui::*, procfs::*, symbols::*};
-use std::{mem, mem::MaybeUninit, ptr, fmt::Write, borrow::Cow, io::Write as ioWrite, ops::Range};
+use crate::{*, types::*, error::*, expr::*, util::*, settings::*, common_ui::*, procfs::*, symbols::*, interp::*};
+use std::{mem, mem::MaybeUninit, ptr, fmt, fmt::Write, borrow::Cow, io::Write as ioWrite, ops::Range, path::Path};
 use bitflags::*;
 
+// Pretty-printers written by the user in the watch expression language, loaded from a config file. Applied before the built-in ones.
+#[derive(Default)]
+pub struct CustomPrettyPrinters {
+    pub printers: Vec<CustomPrettyPrinter>,
+}
+
+pub struct CustomPrettyPrinter {
+    // Type name, with '*' matching any sequence of characters.
+    pub pattern: String,
+    // Turns value `self` into the pretty value.
+    pub expr: Expression,
+}
+
+impl CustomPrettyPrinters {
+    pub fn find(&self, type_name: &str) -> Option<&CustomPrettyPrinter> {
+        self.printers.iter().find(|p| name_matches_pattern(type_name, &p.pattern))
+    }
+
+    pub fn parse_config(text: &str, error_line_number: &mut usize) -> Result<Self> {
+        let mut res = Self::default();
+        // (pattern, line number of the header, expression text)
+        let mut current: Option<(String, usize, String)> = None;
+        let mut finish = |current: Option<(String, usize, String)>, error_line_number: &mut usize| -> Result<()> {
+            if let Some((pattern, line_number, text)) = current {
+                *error_line_number = line_number;
+                if text.trim().is_empty() {
+                    return err!(Usage, "printer for '{}' has no expression", pattern);
+                }
+                let expr = match parse_watch_expression(&text) {
+                    Ok(x) => x,
+                    Err(e) => return err!(Usage, "printer for '{}': {}", pattern, e),
+                };
+                res.printers.push(CustomPrettyPrinter {pattern, expr});
+            }
+            Ok(())
+        };
+        for (idx, line) in text.lines().enumerate() {
+            *error_line_number = idx + 1;
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(pattern) = line.strip_prefix("printer ") {
+                let pattern = pattern.trim();
+                if pattern.is_empty() {
+                    return err!(Usage, "expected type name pattern after 'printer'");
+                }
+                finish(current.take(), error_line_number)?;
+                current = Some((pattern.to_string(), idx + 1, String::new()));
+                continue;
+            }
+            match &mut current {
+                Some((_, _, text)) => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+                None if line.trim().is_empty() => (),
+                None => return err!(Usage, "expected 'printer <type name pattern>' line"),
+            }
+        }
+        finish(current.take(), error_line_number)?;
+        Ok(res)
+    }
+
+    pub fn write_config_example(out: &mut String, real_config_path: &Path, example_path: &Path, error_file_path: &Path) -> std::result::Result<(), fmt::Error> {
+        write!(out, r###"# Example custom pretty-printers configuration.
+
+# This file is autogenerated and overwritten on debugger startup. Don't edit it.
+# The real config file should be placed at: {0}
+# You may `cp {1} {0}` and edit {0}
+#
+# If the debugger fails to parse the config file, a file with error message will be created at: {2}
+# The config is reloaded on the fly, no restart needed, just save the file.
+#
+# Each printer starts with a line "printer <type name pattern>", followed by an expression in the watch expression language
+# (see the help page for the watches window), possibly spanning multiple lines, up to the next "printer" line.
+# The expression turns the value, available as variable 'self', into another value to show instead, usually a slice/array, a struct, or a string.
+#
+# In the pattern, '*' matches any sequence of characters. The first matching printer applies. Custom printers take precedence over built-in ones.
+# Fields of the original struct are still accessible in watch expressions after pretty-printing, and '.#r' disables pretty-printing as usual.
+#
+# Lines starting with '#' are comments. All lines here are commented out. Uncomment them in the real config to apply.
+
+# printer my::SmallVector<*>
+# self.data.[self.size]
+
+# printer my::String
+# if self.is_inline {{(&self.inline_buf as *char8).[self.len]}} else {{self.heap_ptr.[self.len] as [char8]}}
+
+# printer my::IntrusiveList<*>
+# {{
+#     let n = 0;
+#     let p = self.head;
+#     while p != 0 {{n += 1; p = p.next}};
+#     struct {{len: n, head: self.head}}
+# }}
+"###,
+               real_config_path.display(), example_path.display(), error_file_path.display())
+    }
+}
+
+fn name_matches_pattern(name: &str, pattern: &str) -> bool {
+    // Glob matching with backtracking to the last '*'.
+    let (name, pattern) = (name.as_bytes(), pattern.as_bytes());
+    let (mut n, mut p) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None; // (pattern position after '*', name position it currently matches up to)
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            p += 1;
+            star = Some((p, n));
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((sp, sn)) = star {
+            p = sp;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
 // Apply pretty-printers and other transformations to a value. Used both for printing and expression evaluaion.
 // E.g. this function may turn an std::vector<int> into a *[int; 123], making it appear as an array both when printed whole
 // and when used in expression like v[10] (will index the array) or v._M_begin (will fail even if std::vector has field _M_begin).
@@ -46,6 +169,25 @@ pub fn prettify_value(val: &mut Cow<Value>, warning: &mut Option<Error>, state:
             }
         }
 
+        if !val.flags.contains(ValueFlags::NO_CUSTOM_PRETTY_PRINTER) {
+            let printers = context.pretty_printers;
+            if let Some(printer) = printers.find(type_.name) {
+                let mut arg = val.as_ref().clone();
+                arg.flags.insert(ValueFlags::NO_CUSTOM_PRETTY_PRINTER);
+                match eval_custom_pretty_printer(&printer.expr, arg, state, context) {
+                    Ok(mut new_val) => {
+                        if new_val.type_ == val.type_ {
+                            // The printer returned a value of the same type, don't apply it again.
+                            new_val.flags.insert(ValueFlags::NO_CUSTOM_PRETTY_PRINTER);
+                        }
+                        *val = Cow::Owned(new_val);
+                        continue;
+                    }
+                    Err(e) => *warning = Some(e),
+                }
+            }
+        }
+
         let mut substruct = Substruct::new(struct_, type_);
         unravel_struct(&mut substruct);
 
@@ -1452,3 +1594,112 @@ fn recognize_cpp_optional(substruct: &mut Substruct, val: &mut Cow<Value>, state
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{pretty::*, expr::tests::eval_script_with_printers};
+
+    #[test]
+    fn type_name_patterns() {
+        assert!(name_matches_pattern("my::Foo", "my::Foo"));
+        assert!(!name_matches_pattern("my::Foo", "my::Fo"));
+        assert!(!name_matches_pattern("my::Fo", "my::Foo"));
+        assert!(!name_matches_pattern("other::my::Foo", "my::Foo"));
+
+        // Templates.
+        assert!(name_matches_pattern("my::SmallVector<int, 16>", "my::SmallVector<*>"));
+        assert!(name_matches_pattern("my::SmallVector<std::pair<int, my::SmallVector<char, 4> >, 16>", "my::SmallVector<*>"));
+        assert!(!name_matches_pattern("my::SmallVectorBase<int>", "my::SmallVector<*>"));
+        assert!(!name_matches_pattern("my::SmallVector", "my::SmallVector<*>"));
+        assert!(name_matches_pattern("std::map<int, my::Foo, std::less<int> >", "std::map<*, my::*>"));
+        assert!(!name_matches_pattern("std::map<my::Foo, int>", "std::map<*, my::*>"));
+
+        // Namespaces.
+        assert!(name_matches_pattern("a::b::Foo", "*::Foo"));
+        assert!(name_matches_pattern("Foo", "*Foo"));
+        assert!(!name_matches_pattern("Foo", "*::Foo"));
+        assert!(!name_matches_pattern("a::FooBar", "*::Foo"));
+        assert!(name_matches_pattern("a::FooBar", "*::Foo*"));
+        assert!(name_matches_pattern("std::__1::basic_string<char>", "std::*::basic_string<*>"));
+
+        assert!(name_matches_pattern("", ""));
+        assert!(name_matches_pattern("", "*"));
+        assert!(!name_matches_pattern("a", ""));
+        assert!(name_matches_pattern("anything", "**"));
+    }
+
+    #[test]
+    fn parse_config() {
+        let mut line = 0;
+        let text = "# comment\n\nprinter my::Vec<*>\nself.data.[self.len]\n\nprinter Pair\n# comment inside\n{\n    let s = self.a + self.b;\n    s\n}\n";
+        let p = CustomPrettyPrinters::parse_config(text, &mut line).unwrap();
+        assert_eq!(p.printers.iter().map(|p| &p.pattern[..]).collect::<Vec<_>>(), ["my::Vec<*>", "Pair"]);
+        assert!(p.find("my::Vec<int>").is_some());
+        assert_eq!(p.find("Pair").unwrap().pattern, "Pair");
+        assert!(p.find("Pairs").is_none());
+
+        // First match wins.
+        let p = CustomPrettyPrinters::parse_config("printer my::*\n1\nprinter my::Foo\n2\n", &mut line).unwrap();
+        assert_eq!(p.find("my::Foo").unwrap().pattern, "my::*");
+
+        assert!(CustomPrettyPrinters::parse_config("", &mut line).unwrap().printers.is_empty());
+
+        // Malformed configs, with the line number to report.
+        let check_error = |text: &str, expected_line: usize| {
+            let mut line = 0;
+            assert!(CustomPrettyPrinters::parse_config(text, &mut line).is_err(), "{}", text);
+            assert_eq!(line, expected_line, "{}", text);
+        };
+        check_error("self.x\n", 1); // expression without 'printer' line
+        check_error("# ok\nprinter Foo\nself.x\nprinter \nself.y\n", 4); // empty pattern
+        check_error("printer Foo\n\nprinter Bar\nself.x\n", 1); // no expression
+        check_error("printer Foo\nself.x\nprinter Bar\nself.(\n", 3); // syntax error, reported at the printer's header
+    }
+
+    #[test]
+    fn custom_printers() {
+        let mut line = 0;
+        let config = "
+printer Pair
+[self.a, self.b]
+
+printer Point
+struct {x: self.a, sum: self.a + self.b}
+
+printer Name
+if self.a == 0 {\"empty\"} else {\"nonempty\"}
+
+printer Loop
+self
+";
+        let printers = CustomPrettyPrinters::parse_config(config, &mut line).unwrap();
+        let r = eval_script_with_printers(&[
+            "struct Pair {a: u32, b: u32}",
+            "struct Point {a: u32, b: u32}",
+            "struct Name {a: u32}",
+            "struct Loop {a: u32, b: u8}",
+            "struct Other {a: u32, b: u8}",
+            "{let p: Pair; p}",
+            "{let p: Point; p}",
+            "{let p: Name; p}",
+            "{let p: Loop; p}",
+            "{let p: Other; p}",
+            "{let p: Pair; p.#r}",
+            "{let p: Pair; p[1]}",
+        ], &printers);
+        let r: Vec<String> = r.into_iter().map(|x| x.unwrap()).collect();
+        // Array.
+        assert_eq!(r[5], "[0, 0]");
+        // Struct.
+        assert_eq!(r[6], "{x: 0, sum: 0}");
+        // String.
+        assert_eq!(r[7], "\"empty\"");
+        // Printer returning the same type isn't applied again.
+        assert_eq!(r[8], "{a: 0, b: 0}");
+        // No printer.
+        assert_eq!(r[9], "{a: 0, b: 0}");
+        // '.#r' disables it, and indexing applies to the printed value.
+        assert_eq!(r[10], "{a: 0, b: 0}");
+        assert_eq!(r[11], "0");
+    }
+}
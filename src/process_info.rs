@@ -1,5 +1,5 @@
-use crate::{*, debugger::*, error::*, util::*, symbols::*, symbols_registry::*, procfs::*, unwind::*, registers::*, log::*, settings::*, elf::*, os::*};
-use std::{collections::{HashMap, hash_map::Entry}, time::Instant, fs, os::unix::fs::MetadataExt, sync::Arc, ops::Range, str};
+use crate::{*, debugger::*, error::*, util::*, symbols::*, symbols_registry::*, procfs::*, unwind::*, registers::*, log::*, settings::*, elf::*, os::*, arch::*};
+use std::{collections::{HashMap, hash_map::Entry}, time::{Instant, Duration}, fs, os::unix::fs::MetadataExt, sync::Arc, ops::Range, str};
 use std::mem;
 use libc::pid_t;
 
@@ -364,7 +364,52 @@ pub fn refresh_maps_and_binaries_info(debugger: &mut Debugger) -> /*binaries_add
     false
 }
 
+// Same as refresh_maps_and_binaries_info(), but for debugger.forked_processes[idx] (--follow-fork=both): re-reads its /proc/<pid>/maps and recalculates where binaries are mapped in it.
+// Binaries mapped only in this process (e.g. it dlopen()ed a library after fork) are added to the registry, so that its stack traces can be symbolized,
+// but breakpoints are placed only in binaries that the main process has mapped.
+pub fn refresh_forked_process_maps(debugger: &mut Debugger, idx: usize) -> /*binaries_added*/ bool {
+    let pid = debugger.forked_processes[idx].pid;
+    let mut maps = match MemMapsInfo::read_proc_maps(pid) {
+        Err(e) => {
+            eprintln!("error: failed to read maps of process {}: {}", pid, e);
+            return false;
+        }
+        Ok(m) => m
+    };
+    let memory = MemReader::Pid(PidMemReader::new(pid));
+    let mut addr_maps: HashMap<usize, AddrMap> = HashMap::new();
+    let mut binaries_added = false;
+    for map in maps.maps.iter_mut() {
+        let locator = match &map.binary_locator {
+            None => continue,
+            Some(b) => b,
+        };
+        let bin = match debugger.symbols.locator_to_id.get(locator) {
+            Some(id) => debugger.symbols.get_mut(*id).unwrap(),
+            None => {
+                let build_id = if map.offset == 0 {extract_build_id_from_mapped_elf(&memory, map.start, map.len).ok()} else {None};
+                binaries_added = true;
+                debugger.symbols.add(locator.clone(), &memory, None, build_id, /*is_main_binary*/ false, None)
+            }
+        };
+        map.binary_id = Some(bin.id);
+        if let Ok(elves) = &bin.elves {
+            addr_maps.entry(bin.id).or_default().update(map, &elves[0], &locator.path);
+        }
+    }
+    if binaries_added {
+        debugger.symbols.update_priority_order();
+    }
+    let p = &mut debugger.forked_processes[idx];
+    p.maps = maps;
+    p.addr_maps = addr_maps;
+    binaries_added
+}
+
 fn parse_r_debug(ptr_addr: usize, memory: &MemReader, elf: &ElfFile) -> Result<Option<RDebug>> {
+    if elf.is_32_bit() {
+        return err!(NotImplemented, "TLS not supported for {}", elf.arch.name());
+    }
     let ptr = memory.read_u64(ptr_addr)? as usize;
     if ptr == 0 {
         return Ok(None);
@@ -401,7 +446,7 @@ pub fn refresh_thread_info(pid: pid_t, t: &mut Thread, prof: &mut ProfileBucket,
         return;
     }
     if !t.exiting {
-        let s = ProcStat::parse(&format!("/proc/{}/task/{}/stat", pid, t.tid), prof);
+        let s = ProcStat::parse(&format!("/proc/{}/task/{}/stat", t.pid, t.tid), prof);
         t.info.resource_stats.update(s, Instant::now(), t.state == ThreadState::Suspended, settings.periodic_timer_ns);
     }
 
@@ -433,7 +478,7 @@ pub fn refresh_all_resource_stats(pid: pid_t, my_stats: &mut ResourceStats, debu
 
         for (tid, t) in threads {
             if !t.exiting {
-                let s = ProcStat::parse(&format!("/proc/{}/task/{}/stat", pid, tid), prof);
+                let s = ProcStat::parse(&format!("/proc/{}/task/{}/stat", t.pid, tid), prof);
                 t.info.resource_stats.update(s, Instant::now(), t.state == ThreadState::Suspended, settings.periodic_timer_ns);
                 any_error = any_error.or_else(|| t.info.resource_stats.error.clone());
             }
@@ -451,6 +496,16 @@ pub fn ptrace_getregs(tid: pid_t) -> Result<Registers> {
     }
 }
 
+pub fn ptrace_setregs(tid: pid_t, regs: &Registers) -> Result<()> {
+    unsafe {
+        let mut s: libc::user_regs_struct = mem::zeroed();
+        ptrace(PTRACE_GETREGS, tid, 0, &mut s as *mut _ as u64)?;
+        regs.to_ptrace(&mut s);
+        ptrace(PTRACE_SETREGS, tid, 0, &mut s as *mut _ as u64)?;
+        Ok(())
+    }
+}
+
 pub fn ptrace_get_extra_regs(tid: pid_t) -> ExtraRegisters {
     unsafe {
         let mut buf = [0u8; XSAVE_SIZE_UPPER_BOUND];
@@ -461,3 +516,142 @@ pub fn ptrace_get_extra_regs(tid: pid_t) -> ExtraRegisters {
         ExtraRegisters::from_xsave(&buf[..iov.iov_len])
     }
 }
+
+// The whole XSAVE area as is, for saving and restoring all SSE/AVX registers around a function call.
+// Unlike XSAVE_SIZE_UPPER_BOUND, this includes big components like AMX tiles: PTRACE_SETREGSET requires the full size.
+pub fn ptrace_get_xstate(tid: pid_t) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; 1 << 16];
+    let mut iov = libc::iovec {iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len()};
+    unsafe {ptrace(PTRACE_GETREGSET, tid, NT_X86_XSTATE as u64, &mut iov as *mut libc::iovec as u64)}?;
+    buf.truncate(iov.iov_len);
+    Ok(buf)
+}
+
+pub fn ptrace_set_xstate(tid: pid_t, xstate: &[u8]) -> Result<()> {
+    let mut iov = libc::iovec {iov_base: xstate.as_ptr() as *mut libc::c_void, iov_len: xstate.len()};
+    unsafe {ptrace(PTRACE_SETREGSET, tid, NT_X86_XSTATE as u64, &mut iov as *mut libc::iovec as u64)}?;
+    Ok(())
+}
+
+// Things that happened to the debuggee during function calls from watch expressions, which the Debugger needs to know about.
+#[derive(Default)]
+pub struct FunctionCallEvents {
+    // Threads spawned by the called function, and the threads that spawned them. They'll report their initial stop to the Debugger's event loop as usual.
+    pub new_threads: Vec<(pid_t, /*parent tid*/ pid_t)>,
+    // waitpid() results that we couldn't handle, e.g. the thread exited. Should be processed as if the event loop received them.
+    pub wait_events: Vec<(pid_t, i32)>,
+}
+
+// Makes a suspended thread execute one syscall. Temporarily writes `syscall; int3` (or `int 0x80; int3` for i386 debuggees) at `code_addr`
+// (see SymbolsRegistry::main_binary_entry_point()), runs it, then restores the code and registers. Other threads stay suspended.
+// `nr` is the syscall number for x86-64 and for i386, as the numbering differs. Returns the syscall's return value (negative errno on failure).
+pub fn ptrace_inject_syscall(tid: pid_t, code_addr: usize, nr: (/*x86-64*/ i64, /*i386*/ i64), args: &[u64], events: &mut FunctionCallEvents) -> Result<i64> {
+    const ARG_REGISTERS: [RegisterIdx; 6] = [RegisterIdx::Rdi, RegisterIdx::Rsi, RegisterIdx::Rdx, RegisterIdx::R10, RegisterIdx::R8, RegisterIdx::R9];
+    const I386_ARG_REGISTERS: [RegisterIdx; 6] = [RegisterIdx::Rbx, RegisterIdx::Rcx, RegisterIdx::Rdx, RegisterIdx::Rsi, RegisterIdx::Rdi, RegisterIdx::Rbp];
+    assert!(args.len() <= ARG_REGISTERS.len());
+    let saved_regs = ptrace_getregs(tid)?;
+    let (opcode, nr, arg_registers) = match saved_regs.arch {
+        Arch::I386 => (0xcc80cd, nr.1, &I386_ARG_REGISTERS), // int 0x80; int3
+        Arch::X86_64 | Arch::X32 => (0xcc050f, nr.0, &ARG_REGISTERS), // syscall; int3
+        Arch::Aarch64 => return err!(NotImplemented, "syscall injection is not supported on aarch64"),
+    };
+    let original_word = unsafe {ptrace(PTRACE_PEEKDATA, tid, code_addr as u64, 0)}? as u64;
+    let code = (original_word & !0xffffff) | opcode;
+    unsafe {ptrace(PTRACE_POKEDATA, tid, code_addr as u64, code)}?;
+
+    let mut regs = saved_regs.clone();
+    regs.set(RegisterIdx::Rip, code_addr as u64, false);
+    regs.set(RegisterIdx::Rax, nr as u64, false);
+    for (i, &x) in args.iter().enumerate() {
+        regs.set(arg_registers[i], x, false);
+    }
+    // Same as for function calls: don't restart the interrupted syscall, don't single-step.
+    regs.set(RegisterIdx::OrigRax, u64::MAX, false);
+    regs.set(RegisterIdx::Flags, saved_regs.get(RegisterIdx::Flags)?.0 & !0x100, false);
+    let r = ptrace_setregs(tid, &regs).and_then(|()| ptrace_run_function_call(tid, code_addr + 2, Duration::from_secs(1), events));
+
+    if !events.wait_events.iter().any(|(t, _)| *t == tid) {
+        let restored = ptrace_setregs(tid, &saved_regs);
+        let unpatched = unsafe {ptrace(PTRACE_POKEDATA, tid, code_addr as u64, original_word)};
+        restored.and(unpatched.map(|_| ()))?;
+    }
+    let ret = r?.get(RegisterIdx::Rax)?.0;
+    // For i386 only the low 32 bits of eax are meaningful.
+    Ok(if saved_regs.arch == Arch::I386 {ret as u32 as i32 as i64} else {ret as i64})
+}
+
+// Resumes a suspended thread whose registers were set up to call a function, and waits until the function returns to `return_trap_addr`,
+// where the caller placed an int3. Other threads stay suspended. Returns registers at the point of return.
+// On error, the thread is stopped somewhere inside the called function, and the caller should restore its registers;
+// unless the thread exited or exec-ed, in which case its event is added to `events.wait_events`.
+pub fn ptrace_run_function_call(tid: pid_t, return_trap_addr: usize, timeout: Duration, events: &mut FunctionCallEvents) -> Result<Registers> {
+    unsafe {ptrace(PTRACE_CONT, tid, 0, 0)}?;
+    let deadline = Instant::now() + timeout;
+    let mut interrupted = false;
+    // We can't block in waitpid() because it has no timeout, and SIGCHLD goes to the main loop's pipe. Poll with exponential backoff instead:
+    // short calls return quickly, long calls don't spin the CPU.
+    let mut sleep_duration = Duration::from_micros(10);
+    loop {
+        let mut wstatus = 0i32;
+        let r = unsafe {libc::waitpid(tid, &mut wstatus, libc::__WALL | if interrupted {0} else {libc::WNOHANG})};
+        if r < 0 {
+            return errno_err!("waitpid() failed");
+        }
+        if r == 0 {
+            if Instant::now() >= deadline {
+                unsafe {ptrace(PTRACE_INTERRUPT, tid, 0, 0)}?;
+                interrupted = true;
+            } else {
+                std::thread::sleep(sleep_duration.min(deadline.saturating_duration_since(Instant::now())));
+                sleep_duration = (sleep_duration * 2).min(Duration::from_millis(10));
+            }
+            continue;
+        }
+        if libc::WIFEXITED(wstatus) || libc::WIFSIGNALED(wstatus) {
+            events.wait_events.push((tid, wstatus));
+            return err!(ProcessState, "thread exited during function call");
+        }
+        if !libc::WIFSTOPPED(wstatus) {
+            return err!(Internal, "waitpid() returned unexpected status: {}", wstatus);
+        }
+        let signal = libc::WSTOPSIG(wstatus);
+        let event = wstatus >> 16;
+        if interrupted {
+            // Whatever stop this is, we can restore registers from here. If it's not the stop from PTRACE_INTERRUPT (e.g. a signal arrived first),
+            // the interrupt will produce a spurious stop later, which the event loop will ignore.
+            return err!(TooLong, "function call didn't return in {} ms", timeout.as_millis());
+        }
+        match event {
+            0 => (),
+            PTRACE_EVENT_STOP => { // group-stop
+                unsafe {ptrace(PTRACE_CONT, tid, 0, 0)}?;
+                continue;
+            }
+            PTRACE_EVENT_CLONE => {
+                let mut new_tid: pid_t = 0;
+                unsafe {ptrace(PTRACE_GETEVENTMSG, tid, 0, &mut new_tid as *mut pid_t as u64)}?;
+                events.new_threads.push((new_tid, tid));
+                unsafe {ptrace(PTRACE_CONT, tid, 0, 0)}?;
+                continue;
+            }
+            _ => {
+                // Exit or exec. Nothing to return to.
+                events.wait_events.push((tid, wstatus));
+                return err!(ProcessState, "thread exited or exec-ed during function call");
+            }
+        }
+        let regs = ptrace_getregs(tid)?;
+        let rip = regs.get(RegisterIdx::Rip)?.0 as usize;
+        if signal == libc::SIGTRAP {
+            if rip == return_trap_addr + 1 {
+                return Ok(regs);
+            }
+            return err!(Runtime, "function call hit a breakpoint at 0x{:x}", rip);
+        }
+        if [libc::SIGSEGV, libc::SIGABRT, libc::SIGILL, libc::SIGFPE, libc::SIGBUS].contains(&signal) {
+            return err!(Runtime, "function call got {} at 0x{:x}", signal_name(signal), rip);
+        }
+        // Benign signal, deliver it (same as the event loop does).
+        unsafe {ptrace(PTRACE_CONT, tid, 0, signal as u64)}?;
+    }
+}
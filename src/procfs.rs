@@ -1,6 +1,6 @@
 use crate::{*, error::*, elf::*, util::*, log::*, os::*};
 use std::io::{BufReader, BufRead};
-use std::{fs, fs::File, os::fd::{OwnedFd, AsRawFd}, str::FromStr, ops::Range, cmp::Ordering, collections::HashSet, mem::MaybeUninit, sync::Arc};
+use std::{fs, fs::File, os::fd::{OwnedFd, AsRawFd}, str::FromStr, ops::Range, cmp::Ordering, collections::{HashSet, HashMap}, mem::MaybeUninit, sync::{Arc, Mutex}};
 use bitflags::*;
 use libc::{pid_t, c_void};
 
@@ -280,6 +280,7 @@ pub enum MemReader {
     Invalid,
     Pid(PidMemReader),
     CoreDump(Arc<CoreDumpMemReader>),
+    Snapshot(Arc<SnapshotMemReader>),
 }
 impl MemReader {
     pub fn check_valid(&self) -> Result<()> { match &self {Self::Invalid => err!(ProcessState, "no process"), _ => Ok(())} }
@@ -289,6 +290,7 @@ impl MemReader {
             Self::Invalid => return err!(ProcessState, "no process"),
             Self::Pid(r) => return r.read_uninit(offset, buf),
             Self::CoreDump(r) => return r.read_uninit(offset, buf),
+            Self::Snapshot(r) => return r.read_uninit(offset, buf),
         }
     }
 
@@ -308,6 +310,16 @@ impl MemReader {
         self.read(offset, &mut buf)?;
         Ok(buf[0])
     }
+
+    // `any_suspended_tid` is needed for the PTRACE_POKEDATA fallback, see PidMemReader::write().
+    pub fn write(&self, offset: usize, buf: &[u8], any_suspended_tid: pid_t) -> Result<()> {
+        match self {
+            Self::Invalid => err!(ProcessState, "no process"),
+            Self::Pid(r) => r.write(offset, buf, any_suspended_tid),
+            Self::CoreDump(_) => err!(NotImplemented, "can't modify core dump"),
+            Self::Snapshot(r) => r.write(offset, buf, any_suspended_tid),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -336,10 +348,129 @@ impl PidMemReader {
             Ok(std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len()))
         }
     }
+
+    pub fn write(&self, addr: usize, buf: &[u8], any_suspended_tid: pid_t) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if buf.len() > usize::MAX - addr {
+            return err!(Runtime, "bad memory range: 0x{:x} + 0x{:x}", addr, buf.len());
+        }
+        unsafe {
+            let local_iov = libc::iovec {iov_base: buf.as_ptr() as *mut c_void, iov_len: buf.len()};
+            let remote_iov = libc::iovec {iov_base: addr as *mut c_void, iov_len: buf.len()};
+            let r = libc::process_vm_writev(self.pid, &local_iov as *const libc::iovec, 1, &remote_iov as *const libc::iovec, 1, 0);
+            if r == buf.len() as isize {
+                return Ok(());
+            }
+            if r >= 0 {
+                return err!(ProcessState, "partial write to mem @{:x}:0x{:x}", addr, buf.len());
+            }
+            if *libc::__errno_location() != libc::EFAULT {
+                return errno_err!("process_vm_writev failed");
+            }
+        }
+
+        // process_vm_writev() respects page protection, so it fails on read-only memory (e.g. .rodata or .text). PTRACE_POKEDATA doesn't.
+        // It writes whole 8-byte words, so do read-modify-write at the ends. (It also fails on unmapped addresses, so no need to check that separately.)
+        let start = addr & !7;
+        let end = (addr + buf.len() + 7) & !7;
+        for word_addr in (start..end).step_by(8) {
+            let mut word = [0u8; 8];
+            let lo = addr.max(word_addr);
+            let hi = (addr + buf.len()).min(word_addr + 8);
+            if lo != word_addr || hi != word_addr + 8 {
+                self.read_uninit(word_addr, unsafe {std::slice::from_raw_parts_mut(word.as_mut_ptr() as *mut MaybeUninit<u8>, 8)})?;
+            }
+            word[lo - word_addr..hi - word_addr].copy_from_slice(&buf[lo - addr..hi - addr]);
+            unsafe { ptrace(PTRACE_POKEDATA, any_suspended_tid, word_addr as u64, u64::from_le_bytes(word))?; }
+        }
+        Ok(())
+    }
 }
 
 const PAGE_SIZE: usize = 4096;
 
+// Memory of the process as of some past stop (see Snapshots in debugger.rs).
+// While the stop is current, reads go to the process, and each touched page is copied into the snapshot (up to max_pages).
+// After freeze(), only the copied pages can be read. So a snapshot contains whatever memory was looked at (stack unwinding, watches, etc) while the process was stopped there.
+pub struct SnapshotMemReader {
+    max_pages: usize,
+    state: Mutex<SnapshotMemState>,
+}
+struct SnapshotMemState {
+    live: Option<PidMemReader>,
+    // None if the page couldn't be read.
+    pages: HashMap<usize, Option<Box<[u8]>>>,
+}
+impl SnapshotMemReader {
+    pub fn new(live: PidMemReader, max_pages: usize) -> Self { Self {max_pages, state: Mutex::new(SnapshotMemState {live: Some(live), pages: HashMap::new()})} }
+
+    pub fn freeze(&self) { self.state.lock().unwrap().live = None; }
+    pub fn is_frozen(&self) -> bool { self.state.lock().unwrap().live.is_none() }
+    pub fn num_pages(&self) -> usize { self.state.lock().unwrap().pages.len() }
+
+    pub fn read_uninit<'a>(&self, offset: usize, buf: &'a mut [MaybeUninit<u8>]) -> Result<&'a mut [u8]> {
+        if buf.len() > usize::MAX - offset {
+            return err!(Runtime, "bad memory range: 0x{:x} + 0x{:x}", offset, buf.len());
+        }
+        let mut state = self.state.lock().unwrap();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let addr = offset + pos;
+            let page_addr = addr & !(PAGE_SIZE - 1);
+            let start = addr - page_addr;
+            let len = (PAGE_SIZE - start).min(buf.len() - pos);
+            let chunk = &mut buf[pos..pos+len];
+            match state.pages.get(&page_addr) {
+                Some(Some(page)) => {
+                    for (d, s) in chunk.iter_mut().zip(&page[start..start+len]) {
+                        d.write(*s);
+                    }
+                }
+                Some(None) => return err!(ProcessState, "failed to read memory at 0x{:x}", addr),
+                None => {
+                    let live = match &state.live {
+                        None => return err!(ProcessState, "memory at 0x{:x} was not captured in snapshot", addr),
+                        Some(r) => r.clone() };
+                    if state.pages.len() >= self.max_pages {
+                        live.read_uninit(addr, chunk)?;
+                    } else {
+                        let mut page = vec![0u8; PAGE_SIZE].into_boxed_slice();
+                        let r = unsafe {live.read_uninit(page_addr, std::slice::from_raw_parts_mut(page.as_mut_ptr() as *mut MaybeUninit<u8>, PAGE_SIZE))};
+                        if let Err(e) = r {
+                            state.pages.insert(page_addr, None);
+                            return Err(e);
+                        }
+                        for (d, s) in chunk.iter_mut().zip(&page[start..start+len]) {
+                            d.write(*s);
+                        }
+                        state.pages.insert(page_addr, Some(page));
+                    }
+                }
+            }
+            pos += len;
+        }
+        Ok(unsafe {std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len())})
+    }
+
+    pub fn write(&self, offset: usize, buf: &[u8], any_suspended_tid: pid_t) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let live = match &state.live {
+            None => return err!(NotImplemented, "can't modify snapshot of a past stop"),
+            Some(r) => r.clone() };
+        // Drop the copied pages, they'll be re-read with the new contents.
+        if !buf.is_empty() {
+            let mut page_addr = offset & !(PAGE_SIZE - 1);
+            while page_addr < offset + buf.len() {
+                state.pages.remove(&page_addr);
+                page_addr += PAGE_SIZE;
+            }
+        }
+        live.write(offset, buf, any_suspended_tid)
+    }
+}
+
 // Reads debuggee's memory, caches last read page. Good for sequential small reads.
 pub struct CachedMemReader {
     pub mem: MemReader,
@@ -385,6 +516,11 @@ impl CachedMemReader {
         Ok(())
     }
 
+    // Must be called after writing to debuggee's memory, otherwise we may keep returning the old contents of the cached page.
+    pub fn invalidate(&mut self) {
+        self.addr = None;
+    }
+
     pub fn read_u8(&mut self, offset: usize) -> Result<u8> {
         if &self.addr == &Some(offset & !(PAGE_SIZE - 1)) {
             // Fast path.
@@ -409,6 +545,12 @@ impl CachedMemReader {
         self.read(offset, &mut buf)?;
         Ok(usize::from_le_bytes(buf))
     }
+    // Little-endian unsigned integer of `size` <= 8 bytes, e.g. a pointer in a 32-bit process.
+    pub fn read_uint(&mut self, offset: usize, size: usize) -> Result<usize> {
+        let mut buf = [0u8; 8];
+        self.read(offset, &mut buf[..size])?;
+        Ok(usize::from_le_bytes(buf))
+    }
 
     pub fn eat_uleb128(&mut self, offset: &mut usize) -> Result<usize> {
         Ok(self.leb128_impl(offset)?.0)
@@ -1,10 +1,11 @@
 use std::{mem, fmt, cell::{Cell, UnsafeCell}};
-use crate::{*, error::*, process_info::ptrace_get_extra_regs};
+use crate::{*, error::*, arch::*, process_info::ptrace_get_extra_regs};
 use libc::pid_t;
 
 // Registers stored with each stack trace frame.
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Registers {
+    pub arch: Arch,
     pub ints: [u64; RegisterIdx::COUNT],
 
     // Bitmask saying which values are populated in the array(s) above (including dubious values).
@@ -12,6 +13,7 @@ pub struct Registers {
     // Which of the values are just guesses.
     pub dubious_mask: u64,
 }
+impl Default for Registers { fn default() -> Self { Self {arch: Arch::default(), ints: [0; RegisterIdx::COUNT], mask: 0, dubious_mask: 0} } }
 
 // Registers that are not in user_regs_struct, mostly simd. They're bulky and never callee-saved and ~never appear in unwind information.
 // So we store them once per thread rather than once per stack frame.
@@ -64,10 +66,18 @@ pub enum RegisterIdx {
     // Other things for unwinding. Not actually registers.
     Cfa = 25, // Canonical Frame Address from .eh_frame
     Ret = 26, // return address according to .eh_frame
+
+    // aarch64 registers. (Only for core dumps for now.) X29 is the frame pointer, X30 is the link register.
+    X0 = 27, X1 = 28, X2 = 29, X3 = 30, X4 = 31, X5 = 32, X6 = 33, X7 = 34, X8 = 35, X9 = 36, X10 = 37, X11 = 38, X12 = 39, X13 = 40, X14 = 41, X15 = 42,
+    X16 = 43, X17 = 44, X18 = 45, X19 = 46, X20 = 47, X21 = 48, X22 = 49, X23 = 50, X24 = 51, X25 = 52, X26 = 53, X27 = 54, X28 = 55, X29 = 56, X30 = 57,
+    Sp = 58,
+    Pc = 59,
+    Pstate = 60,
 }
 impl RegisterIdx {
-    pub const COUNT: usize = RegisterIdx::Ret as usize + 1;
+    pub const COUNT: usize = RegisterIdx::Pstate as usize + 1;
 
+    // x86-64 DWARF register numbering. Use Arch::register_from_dwarf() if the architecture is not known to be x86-64.
     pub fn from_dwarf(r: gimli::Register) -> Option<RegisterIdx> {
         match r.0 {
             0..=16 => Some(unsafe {mem::transmute(r.0 as u8)}),
@@ -86,6 +96,39 @@ impl RegisterIdx {
         }
     }
 
+    // i386 DWARF register numbering, mapped onto the corresponding x86-64 registers.
+    pub fn from_dwarf_i386(r: gimli::Register) -> Option<RegisterIdx> {
+        match r.0 {
+            0 => Some(RegisterIdx::Rax),
+            1 => Some(RegisterIdx::Rcx),
+            2 => Some(RegisterIdx::Rdx),
+            3 => Some(RegisterIdx::Rbx),
+            4 => Some(RegisterIdx::Rsp),
+            5 => Some(RegisterIdx::Rbp),
+            6 => Some(RegisterIdx::Rsi),
+            7 => Some(RegisterIdx::Rdi),
+            8 => Some(RegisterIdx::Rip),
+            9 => Some(RegisterIdx::Flags),
+            // 11-18 - st0-st7, 21-28 - xmm0-xmm7, 29-36 - mm0-mm7, 39 - mxcsr, 40 - es
+            41 => Some(RegisterIdx::Cs),
+            42 => Some(RegisterIdx::Ss),
+            // 43 - ds
+            44 => Some(RegisterIdx::Fs),
+            45 => Some(RegisterIdx::Gs),
+            _ => None,
+        }
+    }
+
+    pub fn from_dwarf_aarch64(r: gimli::Register) -> Option<RegisterIdx> {
+        match r.0 {
+            0..=30 => Some(unsafe {mem::transmute::<u8, RegisterIdx>(RegisterIdx::X0 as u8 + r.0 as u8)}),
+            31 => Some(RegisterIdx::Sp),
+            32 => Some(RegisterIdx::Pc),
+            // 33 - ELR_mode, 34 - RA_SIGN_STATE (pointer authentication), 64-95 - v0-v31
+            _ => None,
+        }
+    }
+
     pub fn all() -> &'static [RegisterIdx] {
         &REGISTER_IDXS
     }
@@ -199,6 +242,15 @@ impl ExtraRegisterIdx {
         }
     }
 
+    pub fn from_dwarf_i386(r: gimli::Register) -> Option<ExtraRegisterIdx> {
+        let n = r.0 as usize;
+        match n {
+            21..=28 => Some(ExtraRegisterIdx::zmm(n - 21)),
+            39 => Some(ExtraRegisterIdx::mxcsr()),
+            _ => None,
+        }
+    }
+
     pub fn parse_ignore_case(s: &str) -> Option<Self> {
         if !s.is_ascii() {
             return None;
@@ -246,8 +298,8 @@ impl fmt::Display for ExtraRegisterIdx {
     }
 }
 
-pub const REGISTER_IDXS: [RegisterIdx; RegisterIdx::COUNT] =  [RegisterIdx::Rax, RegisterIdx::Rdx, RegisterIdx::Rcx, RegisterIdx::Rbx, RegisterIdx::Rsi, RegisterIdx::Rdi, RegisterIdx::Rbp, RegisterIdx::Rsp, RegisterIdx::R8, RegisterIdx::R9, RegisterIdx::R10, RegisterIdx::R11, RegisterIdx::R12, RegisterIdx::R13, RegisterIdx::R14, RegisterIdx::R15, RegisterIdx::Rip, RegisterIdx::Cs, RegisterIdx::Ss, RegisterIdx::Fs, RegisterIdx::Gs, RegisterIdx::FsBase, RegisterIdx::GsBase, RegisterIdx::Flags, RegisterIdx::OrigRax, RegisterIdx::Cfa, RegisterIdx::Ret]; // rust
-pub const REGISTER_NAMES: [&'static str; RegisterIdx::COUNT] = ["rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rip", "cs", "ss", "fs", "gs", "fs_base", "gs_base", "flags", "orig_rax", "cfa", "ret"];
+pub const REGISTER_IDXS: [RegisterIdx; RegisterIdx::COUNT] =  [RegisterIdx::Rax, RegisterIdx::Rdx, RegisterIdx::Rcx, RegisterIdx::Rbx, RegisterIdx::Rsi, RegisterIdx::Rdi, RegisterIdx::Rbp, RegisterIdx::Rsp, RegisterIdx::R8, RegisterIdx::R9, RegisterIdx::R10, RegisterIdx::R11, RegisterIdx::R12, RegisterIdx::R13, RegisterIdx::R14, RegisterIdx::R15, RegisterIdx::Rip, RegisterIdx::Cs, RegisterIdx::Ss, RegisterIdx::Fs, RegisterIdx::Gs, RegisterIdx::FsBase, RegisterIdx::GsBase, RegisterIdx::Flags, RegisterIdx::OrigRax, RegisterIdx::Cfa, RegisterIdx::Ret, RegisterIdx::X0, RegisterIdx::X1, RegisterIdx::X2, RegisterIdx::X3, RegisterIdx::X4, RegisterIdx::X5, RegisterIdx::X6, RegisterIdx::X7, RegisterIdx::X8, RegisterIdx::X9, RegisterIdx::X10, RegisterIdx::X11, RegisterIdx::X12, RegisterIdx::X13, RegisterIdx::X14, RegisterIdx::X15, RegisterIdx::X16, RegisterIdx::X17, RegisterIdx::X18, RegisterIdx::X19, RegisterIdx::X20, RegisterIdx::X21, RegisterIdx::X22, RegisterIdx::X23, RegisterIdx::X24, RegisterIdx::X25, RegisterIdx::X26, RegisterIdx::X27, RegisterIdx::X28, RegisterIdx::X29, RegisterIdx::X30, RegisterIdx::Sp, RegisterIdx::Pc, RegisterIdx::Pstate]; // rust
+pub const REGISTER_NAMES: [&'static str; RegisterIdx::COUNT] = ["rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "rip", "cs", "ss", "fs", "gs", "fs_base", "gs_base", "flags", "orig_rax", "cfa", "ret", "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30", "sp", "pc", "pstate"];
 // Generated with: '", "'.join([f'xmm{i}' for i in range(32)] + [f'ymm{i}' for i in range(32)] + [f'zmm{i}' for i in range(32)])
 pub const EXTRA_REGISTER_NAMES_MM: [[&'static str; 32]; 3] = [["xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15", "xmm16", "xmm17", "xmm18", "xmm19", "xmm20", "xmm21", "xmm22", "xmm23", "xmm24", "xmm25", "xmm26", "xmm27", "xmm28", "xmm29", "xmm30", "xmm31"], ["ymm0", "ymm1", "ymm2", "ymm3", "ymm4", "ymm5", "ymm6", "ymm7", "ymm8", "ymm9", "ymm10", "ymm11", "ymm12", "ymm13", "ymm14", "ymm15", "ymm16", "ymm17", "ymm18", "ymm19", "ymm20", "ymm21", "ymm22", "ymm23", "ymm24", "ymm25", "ymm26", "ymm27", "ymm28", "ymm29", "ymm30", "ymm31"], ["zmm0", "zmm1", "zmm2", "zmm3", "zmm4", "zmm5", "zmm6", "zmm7", "zmm8", "zmm9", "zmm10", "zmm11", "zmm12", "zmm13", "zmm14", "zmm15", "zmm16", "zmm17", "zmm18", "zmm19", "zmm20", "zmm21", "zmm22", "zmm23", "zmm24", "zmm25", "zmm26", "zmm27", "zmm28", "zmm29", "zmm30", "zmm31"]];
 pub const EXTRA_REGISTER_NAMES_OPMASK: [&'static str; 8] = ["opmask0", "opmask1", "opmask2", "opmask3", "opmask4", "opmask5", "opmask6", "opmask7"];
@@ -255,20 +307,65 @@ pub const EXTRA_REGISTERS_ZMM: [ExtraRegisterIdx; 32] = [ExtraRegisterIdx::zmm(0
 pub const EXTRA_REGISTERS_EXCEPT_ZMM: [ExtraRegisterIdx; 2] = [ExtraRegisterIdx::mxcsr(), ExtraRegisterIdx::opmask()];
 
 impl Registers {
+    // If the thread is a 32-bit process, we (as a 64-bit tracer) still get the 64-bit user_regs_struct, with 32-bit registers zero-extended.
+    // We recognize this case by the code segment selector (__USER32_CS).
     pub fn from_ptrace(s: &libc::user_regs_struct) -> Self {
         Registers {
-            ints: [s.rax, s.rdx, s.rcx, s.rbx, s.rsi, s.rdi, s.rbp, s.rsp, s.r8, s.r9, s.r10, s.r11, s.r12, s.r13, s.r14, s.r15, s.rip, s.cs, s.ss, s.fs, s.gs, s.fs_base, s.gs_base, s.eflags, s.orig_rax, 0, 0],
+            arch: if s.cs == 0x23 {Arch::I386} else {Arch::X86_64},
+            ints: Self::ints_with_prefix(&[s.rax, s.rdx, s.rcx, s.rbx, s.rsi, s.rdi, s.rbp, s.rsp, s.r8, s.r9, s.r10, s.r11, s.r12, s.r13, s.r14, s.r15, s.rip, s.cs, s.ss, s.fs, s.gs, s.fs_base, s.gs_base, s.eflags, s.orig_rax]),
             mask: (1u64 << (RegisterIdx::OrigRax as u32 + 1)) - 1,
             dubious_mask: 0,
         }
     }
 
+    // From aarch64 user_pt_regs, as found in NT_PRSTATUS in core dumps: x0-x30, sp, pc, pstate.
+    pub fn from_aarch64_user_regs(r: &[u64; 34]) -> Self {
+        let mut regs = Self::new(Arch::Aarch64);
+        for (i, &v) in r[..31].iter().enumerate() {
+            regs.set(RegisterIdx::from_dwarf_aarch64(gimli::Register(i as u16)).unwrap(), v, false);
+        }
+        regs.set(RegisterIdx::Sp, r[31], false);
+        regs.set(RegisterIdx::Pc, r[32], false);
+        regs.set(RegisterIdx::Pstate, r[33], false);
+        regs
+    }
+
+    // From i386 user_regs_struct, as found in NT_PRSTATUS in core dumps of 32-bit processes.
+    pub fn from_i386_user_regs(r: &[u32; 17]) -> Self {
+        let mut regs = Self::new(Arch::I386);
+        for (reg, i) in [(RegisterIdx::Rbx, 0), (RegisterIdx::Rcx, 1), (RegisterIdx::Rdx, 2), (RegisterIdx::Rsi, 3), (RegisterIdx::Rdi, 4), (RegisterIdx::Rbp, 5), (RegisterIdx::Rax, 6), (RegisterIdx::Fs, 9), (RegisterIdx::Gs, 10), (RegisterIdx::OrigRax, 11), (RegisterIdx::Rip, 12), (RegisterIdx::Cs, 13), (RegisterIdx::Flags, 14), (RegisterIdx::Rsp, 15), (RegisterIdx::Ss, 16)] {
+            regs.set(reg, r[i] as u64, false);
+        }
+        regs
+    }
+
+    pub fn new(arch: Arch) -> Self {
+        Registers {arch, ..Default::default()}
+    }
+
+    fn ints_with_prefix(prefix: &[u64]) -> [u64; RegisterIdx::COUNT] {
+        let mut r = [0u64; RegisterIdx::COUNT];
+        r[..prefix.len()].copy_from_slice(prefix);
+        r
+    }
+
+    // Inverse of from_ptrace(). Leaves fields for unpopulated registers unchanged.
+    pub fn to_ptrace(&self, s: &mut libc::user_regs_struct) {
+        let fields: [&mut u64; RegisterIdx::OrigRax as usize + 1] = [&mut s.rax, &mut s.rdx, &mut s.rcx, &mut s.rbx, &mut s.rsi, &mut s.rdi, &mut s.rbp, &mut s.rsp, &mut s.r8, &mut s.r9, &mut s.r10, &mut s.r11, &mut s.r12, &mut s.r13, &mut s.r14, &mut s.r15, &mut s.rip, &mut s.cs, &mut s.ss, &mut s.fs, &mut s.gs, &mut s.fs_base, &mut s.gs_base, &mut s.eflags, &mut s.orig_rax];
+        for (i, f) in fields.into_iter().enumerate() {
+            if self.mask & (1u64 << i) != 0 {
+                *f = self.ints[i];
+            }
+        }
+    }
+
     // From sigcontext_64, aka libc::mcontext_t.gregs
     pub fn from_context(r: &[i64; 23]) -> Self {
         // TODO: Take FsBase and GsBase from fpstate, it's used for TLS.
         Registers {
-            ints: [r[13] as u64, r[12] as u64, r[14] as u64, r[11] as u64, r[9] as u64, r[8] as u64, r[10] as u64, r[15] as u64, r[0] as u64, r[1] as u64, r[2] as u64, r[3] as u64, r[4] as u64, r[5] as u64, r[6] as u64, r[7] as u64, r[16] as u64,
-                   r[18] as u64 & 0xffff, (r[18] as u64) >> 48, ((r[18] as u64) >> 32) & 0xffff, ((r[18] as u64) >> 16) & 0xffff, 0, 0, r[17] as u64, 0, 0, 0],
+            arch: Arch::X86_64,
+            ints: Self::ints_with_prefix(&[r[13] as u64, r[12] as u64, r[14] as u64, r[11] as u64, r[9] as u64, r[8] as u64, r[10] as u64, r[15] as u64, r[0] as u64, r[1] as u64, r[2] as u64, r[3] as u64, r[4] as u64, r[5] as u64, r[6] as u64, r[7] as u64, r[16] as u64,
+                   r[18] as u64 & 0xffff, (r[18] as u64) >> 48, ((r[18] as u64) >> 32) & 0xffff, ((r[18] as u64) >> 16) & 0xffff, 0, 0, r[17] as u64]),
             mask: ((1u64 << (RegisterIdx::Flags as u32 + 1)) - 1) & !(1 << RegisterIdx::FsBase as u32) & !(1 << RegisterIdx::GsBase as u32),
             dubious_mask: 0,
         }
@@ -499,6 +596,27 @@ impl ExtraRegisters {
     pub fn from_error(e: Error) -> Self { Self {error: Some(e), ..Self::default()} }
 }
 
+// Low 128 bits of xmm register `i` in an XSAVE area. Zero if the SSE state is in its initial configuration (not saved).
+pub fn xsave_get_xmm(xsave: &[u8], i: usize) -> [u64; 2] {
+    if xsave.len() < XSAVE_PREFIX_SIZE || xsave[512] & (1 << XFEATURE_SSE) == 0 {
+        return [0, 0];
+    }
+    let off = XSAVE_XMM_OFFSET + i*16;
+    [u64::from_le_bytes(xsave[off..off+8].try_into().unwrap()), u64::from_le_bytes(xsave[off+8..off+16].try_into().unwrap())]
+}
+
+// Inverse of xsave_get_xmm(). Marks the SSE state as present, otherwise the kernel would ignore the new value.
+pub fn xsave_set_xmm(xsave: &mut [u8], i: usize, val: [u64; 2]) -> Result<()> {
+    if xsave.len() < XSAVE_PREFIX_SIZE {
+        return err!(ProcessState, "XSAVE state too short: {}", xsave.len());
+    }
+    let off = XSAVE_XMM_OFFSET + i*16;
+    xsave[off..off+8].copy_from_slice(&val[0].to_le_bytes());
+    xsave[off+8..off+16].copy_from_slice(&val[1].to_le_bytes());
+    xsave[512] |= 1 << XFEATURE_SSE;
+    Ok(())
+}
+
 impl LazyExtraRegisters {
     pub fn reset_with_tid(&mut self, tid: pid_t) {
         self.tid.replace(Some(tid));
@@ -1,4 +1,4 @@
-use crate::{*, symbols::*, arena::*, procfs::*, symbols_registry::*, util::*, context::*, executor::*, types::*};
+use crate::{*, symbols::*, arena::*, procfs::*, symbols_registry::*, util::*, context::*, executor::*, types::*, debugger::*};
 use std::{mem, path::PathBuf, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}}, collections::HashSet, cmp, ops::Range, os::unix::ffi::OsStrExt, path::Path, str};
 use std::arch::x86_64::*;
 use rand::{random, distributions::Alphanumeric, thread_rng, Rng};
@@ -419,6 +419,32 @@ impl Searcher for FunctionSearcher {
     fn properties(&self) -> SearcherProperties { SearcherProperties {have_names: true, have_files: true, have_mangled_names: true, can_go_to_line: false, parallel: true} }
 }
 
+// Function breakpoint resolution: finds functions whose demangled name matches the pattern, in one shard (same sharding as FunctionSearcher).
+// Returns static entry addresses. Demangling is slow, so this runs on the thread pool, see SymbolsRegistry::functions_matching_breakpoint().
+pub fn find_functions_matching_breakpoint(symbols: &Symbols, shard_idx: usize, bp: &FunctionBreakpoint, cancel: &AtomicBool) -> Vec<usize> {
+    let range = (symbols.functions.len()*shard_idx/symbols.shards.len())..(symbols.functions.len()*(shard_idx+1)/symbols.shards.len());
+    let mut res: Vec<usize> = Vec::new();
+    for idx in range {
+        if idx & 127 == 0 && cancel.load(Ordering::Relaxed) {
+            return Vec::new();
+        }
+        let function = &symbols.functions[idx];
+        if function.flags.contains(FunctionFlags::SENTINEL) || !bp.may_match_mangled_name(function.mangled_name()) {
+            continue;
+        }
+        let entry_addr = match function.entry_addr() {
+            None => continue,
+            Some(x) => x };
+        let name = function.demangle_name();
+        let mut name: &str = &name;
+        rust_remove_function_name_hash_suffix(&mut name);
+        if bp.matches(name) {
+            res.push(entry_addr);
+        }
+    }
+    res
+}
+
 fn modify_query_for_mangled_search(query: &SearchQuery) -> SearchQuery {
     let s: String = query.s.get().chars().filter(|&c| c.is_ascii_alphanumeric() || c == '_').collect();
     SearchQuery::parse(&s, /*can_have_file*/ false, /*can_go_to_line*/ false)
@@ -804,4 +830,34 @@ mod tests {
             assert_eq!(expected, found, "{} {} {} {}", i, hay, needle, case_sensitive);
         }
     }
+
+    #[test]
+    fn test_function_breakpoint_patterns() {
+        // (mangled name, demangled name as shown by Function::demangle_name())
+        let functions = [
+            ("_ZN2ns3FoopLERKS0_", "ns::Foo::operator+="),
+            ("_ZN2ns3FooplERKS0_", "ns::Foo::operator+"),
+            ("_ZNK2ns3FoocvbEv", "ns::Foo::operator bool"),
+            ("_ZN2ns3Foo3barEv", "ns::Foo::bar"),
+            ("_ZN2ns3Foo6barbazEi", "ns::Foo::barbaz"),
+            ("_ZN3qux3barEv", "qux::bar"),
+        ];
+        let find = |pattern: &str| -> Vec<&str> {
+            let bp = FunctionBreakpoint::parse(pattern).unwrap();
+            functions.iter().filter(|(m, d)| bp.may_match_mangled_name(m.as_bytes()) && bp.matches(d)).map(|(_, d)| *d).collect()
+        };
+
+        assert_eq!(find("ns::Foo::operator+"), vec!["ns::Foo::operator+"]);
+        assert_eq!(find("Foo::operator+="), vec!["ns::Foo::operator+="]);
+        assert_eq!(find("operator bool"), vec!["ns::Foo::operator bool"]);
+        assert_eq!(find("*operator+*"), vec!["ns::Foo::operator+=", "ns::Foo::operator+"]);
+        assert_eq!(find("bar"), vec!["ns::Foo::bar", "qux::bar"]);
+        assert_eq!(find("*Foo::bar*"), vec!["ns::Foo::bar", "ns::Foo::barbaz"]);
+
+        assert_eq!(find("/^ns::Foo::bar/"), vec!["ns::Foo::bar", "ns::Foo::barbaz"]);
+        assert_eq!(find("/::bar$/"), vec!["ns::Foo::bar", "qux::bar"]);
+        assert_eq!(find("/operator[+-]$/"), vec!["ns::Foo::operator+"]);
+        assert_eq!(find("/^q/"), vec!["qux::bar"]);
+        assert!(FunctionBreakpoint::parse("/(/").is_err());
+    }
 }
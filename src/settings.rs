@@ -1,4 +1,4 @@
-use crate::{*, terminal::*, common_ui::*, error::*, debugger::LineBreakpoint};
+use crate::{*, terminal::*, common_ui::*, error::*, debugger::BreakpointOn};
 use std::{collections::{HashMap, hash_map::Entry}, path::PathBuf, fmt, fmt::Write as fmtWrite, path::Path, mem, env};
 
 pub struct Settings {
@@ -38,13 +38,24 @@ pub struct Settings {
 
     pub use_tty: bool,
     pub tty_scrollback: usize,
+    // Reverse tty mode: the UI is drawn in this other terminal ("-" if lent by `nnd --ui-here`), the program runs in ours.
+    pub ui_tty: Option<String>,
 
     pub disable_aslr: bool,
 
+    pub follow_fork: FollowFork,
+
+    // When a thread hits a breakpoint, finishes a step, or crashes, suspend only that thread and let the others keep running.
+    // Useful for programs that have e.g. heartbeat threads that must keep running to not lose some connection.
+    pub non_stop: bool,
+
+    // How many recent stops to remember (registers and the memory that was looked at), to browse them later. 0 to disable.
+    pub snapshots: usize,
+
     pub fixed_fps: bool, // render `fps` times per second even if nothing changes
     pub trace_logging: bool, // verbose logging, e.g. log every signal passed-through to the process
 
-    pub initial_breakpoints: Vec<LineBreakpoint>,
+    pub initial_breakpoints: Vec<BreakpointOn>,
 
     pub syntax_highlighting: bool,
 }
@@ -73,9 +84,15 @@ impl Default for Settings {
 
         use_tty: true,
         tty_scrollback: 1000,
+        ui_tty: None,
 
         disable_aslr: true,
 
+        follow_fork: FollowFork::Parent,
+        non_stop: false,
+
+        snapshots: 0,
+
         fixed_fps: false,
         trace_logging: false,
 
@@ -102,6 +119,14 @@ impl SessionName {
     pub fn is_name(&self) -> bool { match self {Self::Name(_) => true, _ => false} }
 }
 
+// Which process to keep debugging when the debuggee calls fork() or vfork(). With Parent or Child, the other process is detached (with breakpoints removed from its memory).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowFork {
+    Parent,
+    Child,
+    Both,
+}
+
 pub fn get_debuginfod_urls(default: bool) -> Vec<String> {
     if default {
         // This is a "federated" server that queries ~10 other servers (debuginfod.ubuntu.com, debuginfod.fedoraproject.org, etc), see https://sourceware.org/elfutils/Debuginfod.html
@@ -378,6 +403,9 @@ pub enum KeyAction {
     Suspend,
     Kill,
     SendSigint,
+    SuspendThread,
+    ResumeThread,
+    LockBreakpointsToThread,
 
     StepIntoLine,
     StepIntoInstruction,
@@ -387,6 +415,7 @@ pub enum KeyAction {
     StepOut,
     StepOutNoInline,
     StepToCursor,
+    SetNextStatement,
 
     WindowUp,
     WindowDown,
@@ -424,6 +453,8 @@ pub enum KeyAction {
     PreviousStackFrame,
     NextThread,
     PreviousThread,
+    NextSnapshot,
+    PreviousSnapshot,
     NextLocation,
     PreviousLocation,
     NextMatch,
@@ -440,6 +471,12 @@ pub enum KeyAction {
     DataReadWriteBreakpoint,
     ConditionalDataWriteBreakpoint,
     ConditionalDataReadWriteBreakpoint,
+    DataChangeBreakpoint,
+    SignalBreakpoint,
+    EditBreakpointThread,
+    EditBreakpointLog,
+    EditBreakpointIgnoreCount,
+    ToggleBreakpointDisableAfterHit,
 
     DuplicateRow,
     AddValueRefWatch,
@@ -698,6 +735,9 @@ impl Default for KeyBinds {
             (Key::Char('c').plain(), KeyAction::Continue),
             (Key::Char('p').plain(), KeyAction::Suspend),
             (Key::Char('c').ctrl(), KeyAction::Suspend),
+            (Key::Char('p').alt(), KeyAction::SuspendThread),
+            (Key::Char('c').alt(), KeyAction::ResumeThread),
+            (Key::Char('L').plain(), KeyAction::LockBreakpointsToThread),
             (Key::Char('k').ctrl(), KeyAction::Kill),
             (Key::Char('k').alt(), KeyAction::SendSigint),
             (Key::Up.alt(), KeyAction::WindowUp),
@@ -738,6 +778,7 @@ impl Default for KeyBinds {
             (Key::Char('f').plain(), KeyAction::StepOut),
             (Key::Char('F').plain(), KeyAction::StepOutNoInline),
             (Key::Char('C').plain(), KeyAction::StepToCursor),
+            (Key::Char('J').plain(), KeyAction::SetNextStatement),
             // (Ctrl+tab and ctrl+shift+tab are unrepresentable in ansi escape codes.)
             (Key::Char('t').ctrl(), KeyAction::NextTab),
             (Key::Char('t').alt(), KeyAction::PreviousTab),
@@ -747,6 +788,8 @@ impl Default for KeyBinds {
             (Key::Char('[').plain(), KeyAction::PreviousStackFrame),
             (Key::Char('}').plain(), KeyAction::NextThread),
             (Key::Char('{').plain(), KeyAction::PreviousThread),
+            (Key::Char('>').plain(), KeyAction::NextSnapshot),
+            (Key::Char('<').plain(), KeyAction::PreviousSnapshot),
             (Key::Char('.').plain(), KeyAction::NextLocation),
             (Key::Char(',').plain(), KeyAction::PreviousLocation),
             (Key::Char('g').alt(), KeyAction::NextMatch),
@@ -772,6 +815,12 @@ impl Default for KeyBinds {
             (Key::Char('B').plain(), KeyAction::DataReadWriteBreakpoint),
             (Key::Char('b').alt(), KeyAction::ConditionalDataWriteBreakpoint),
             (Key::Char('B').alt(), KeyAction::ConditionalDataReadWriteBreakpoint),
+            (Key::Char('V').plain(), KeyAction::DataChangeBreakpoint),
+            (Key::Char('a').plain(), KeyAction::SignalBreakpoint),
+            (Key::Char('T').plain(), KeyAction::EditBreakpointThread),
+            (Key::Char('l').plain(), KeyAction::EditBreakpointLog),
+            (Key::Char('i').plain(), KeyAction::EditBreakpointIgnoreCount),
+            (Key::Char('O').plain(), KeyAction::ToggleBreakpointDisableAfterHit),
         ]);
         let text_input = KeyMap::new(&[
             (Key::Char('7').ctrl(), KeyAction::Undo), // ctrl+/ is indistinguishable from ctrl+7
@@ -150,10 +150,16 @@ pub struct Symbols {
     // .debug_info, .debug_line, etc - sections describing things in the source code (functions, line numbers, structs and their fields, etc) and how they map to address ranges.
     // If some or all sections are missing, we treat them as empty, and no special handling is needed because empty sections parse as valid DWARF debug info with 0 units.
     pub dwarf: Dwarf<DwarfSlice>,
+    // .dwo or .dwp files with split DWARF (see load_split_units()), and the supplementary file (see DieOffset). Their sections are referenced from `units`.
+    pub split_elves: Vec<Arc<ElfFile>>,
 
     // Units in .debug_info, sorted by DieOffset (not to be confused with address).
     // Expect tens of thousands of these.
     pub units: Vec<DwarfUnit>,
+    // Type signature -> DIE, for DW_FORM_ref_sig8.
+    pub type_units: HashMap<DebugTypeSignature, DieOffset>,
+    // Where the supplementary file's units start, for DW_FORM_GNU_ref_alt and DW_FORM_ref_sup*. None if there's no supplementary file.
+    pub supplementary_offset_shift: Option<usize>,
 
     // Indexes to map addresses (in debuggee's virtual address space) to stuff.
     // Keep in mind that these assume segment addresses as defined in ELF file, which are different from addresses at runtime.
@@ -303,6 +309,7 @@ unsafe impl Sync for FunctionInfo {}
 impl FunctionInfo {
     pub fn mangled_name(&self) -> &'static [u8] { unsafe {slice::from_raw_parts(self.mangled_name, self.mangled_name_len as usize)} }
     pub fn debug_info_offset(&self) -> Option<DieOffset> { if self.die.0 == usize::MAX { None } else { Some(self.die) } }
+    pub fn entry_addr(&self) -> Option<usize> { if self.entry_addr == 0 { None } else { Some(self.entry_addr) } }
     // 0 means this function has no machine code or comes from .symtab, 1 means it has no inline functions.
     pub fn num_levels(&self) -> usize { self.subfunction_levels.len().saturating_sub(1) }
     pub fn shard_idx(&self) -> usize { self.shard_idx as usize }
@@ -326,6 +333,27 @@ impl FunctionInfo {
     }
 }
 
+// Rust function names in debug info look like this: nnd::pretty::prettify_value::hf586ae10d8ed2bb6
+// Remove that last suffix.
+pub fn rust_remove_function_name_hash_suffix(name: &mut &str) {
+    if name.len() < 19 {
+        return;
+    }
+    let i = name.len() - 19;
+    if !name.is_char_boundary(i) {
+        return;
+    }
+    let suf = &name[i..];
+    if !suf.starts_with("::h") {
+        return;
+    }
+    let suf = &suf[3..];
+    if suf.chars().any(|c| !c.is_ascii_hexdigit()) {
+        return;
+    }
+    *name = &name[..i];
+}
+
 // Identifying information about a function, suitable for writing to the save file.
 // We want both more specific and less specific information here.
 // Specific (e.g. address) to be able to find the exactly correct function if the binary hasn't changed.
@@ -994,7 +1022,8 @@ unsafe impl Sync for GlobalVariableNameMessage {}
 
 // Usage: call new(), then alternate between single-threaded calls to prepare_stage(i) and multi-threaded calls to run(i, shard_idx) (for all shard_idx in parallel); when prepare_stage() returns Ok(false), call into_result().
 impl SymbolsLoader {
-    pub fn new(elves: Vec<Arc<ElfFile>>, binary_id: usize, max_shards: usize, status: Arc<SymbolsLoadingStatus>) -> Result<Self> {
+    // `supplementary_elf` is the file from .gnu_debugaltlink or .debug_sup (e.g. produced by `dwz -m`), with debug info shared by multiple binaries.
+    pub fn new(elves: Vec<Arc<ElfFile>>, supplementary_elf: Option<Arc<ElfFile>>, binary_id: usize, max_shards: usize, status: Arc<SymbolsLoadingStatus>) -> Result<Self> {
         assert!(!elves.is_empty());
         let start_time = Instant::now();
 
@@ -1017,13 +1046,19 @@ impl SymbolsLoader {
         };
 
         let mut dwarf = Dwarf::load(load_section)?;
+        let supplementary_dwarf: Option<Arc<Dwarf<DwarfSlice>>> = match &supplementary_elf {
+            None => None,
+            Some(elf) => Some(Arc::new(Dwarf::load(|id| -> Result<DwarfSlice> {
+                match elf.section_by_name.get(id.name()) {
+                    Some(&idx) => Ok(DwarfSlice::new(unsafe {mem::transmute::<&[u8], &'static [u8]>(elf.section_data(idx)?)})),
+                    None => Ok(DwarfSlice::new(&[0u8;0][..])),
+                }
+            })?)),
+        };
+        // (Before load_split_units() because .dwo files inherit it.)
+        dwarf.sup = supplementary_dwarf.clone();
         *status.stage.lock().unwrap() = "listing units".to_string();
 
-        if !dwarf.debug_types.reader().is_empty() {
-            // This was removed in DWARF 5, so probably not worth supporting.
-            return err!(Dwarf, ".debug_types not supported");
-        }
-
         let code_addr_range = match find_section(".text")? {
             Some((data, addr)) => addr..addr+data.len(),
             None => 0..0,
@@ -1037,7 +1072,47 @@ impl SymbolsLoader {
             }
         }
 
-        let binary_size: usize = elves.iter().map(|e| e.decompressed_size()).sum();
+        let layouts = AllAttributeStructLayouts::new(
+            vec![
+                (vec![DW_TAG_namespace], NamespaceAttributes::layout()),
+                (vec![DW_TAG_variable, DW_TAG_formal_parameter], VariableAttributes::layout()),
+                (vec![DW_TAG_subprogram], SubprogramAttributes::layout()),
+                (vec![DW_TAG_base_type, DW_TAG_unspecified_type, DW_TAG_structure_type, DW_TAG_class_type, DW_TAG_union_type, DW_TAG_enumeration_type, DW_TAG_pointer_type, DW_TAG_reference_type, DW_TAG_rvalue_reference_type, DW_TAG_array_type, DW_TAG_const_type, DW_TAG_restrict_type, DW_TAG_volatile_type, DW_TAG_atomic_type, DW_TAG_typedef, DW_TAG_subroutine_type, DW_TAG_ptr_to_member_type], TypeAttributes::layout()),
+                (vec![DW_TAG_inheritance, DW_TAG_member, DW_TAG_enumerator], FieldAttributes::layout()),
+                (vec![DW_TAG_variant_part], VariantPartAttributes::layout()),
+                (vec![DW_TAG_variant], VariantAttributes::layout()),
+                (vec![DW_TAG_subrange_type], SubrangeTypeAttributes::layout()),
+                (vec![DW_TAG_lexical_block], LexicalBlockAttributes::layout()),
+                (vec![DW_TAG_inlined_subroutine], InlinedSubroutineAttributes::layout()),
+                (vec![DW_TAG_template_type_parameter], TemplateTypeParameterAttributes::layout()),
+                (vec![DW_TAG_template_value_parameter], TemplateValueParameterAttributes::layout()),
+            ],
+            CommonAttributes::layout(),
+            vec![DW_TAG_label, DW_TAG_variable, DW_TAG_formal_parameter, DW_TAG_subprogram, DW_TAG_inlined_subroutine]);
+
+        let mut abbreviations_shared = AbbreviationsSharedData::new(layouts);
+        abbreviations_shared.has_supplementary_file = supplementary_dwarf.is_some();
+        let mut units = list_units(&dwarf, &elves[0].name, /*offset_shift*/ 0, &mut abbreviations_shared)?;
+        let mut split_elves: Vec<Arc<ElfFile>> = Vec::new();
+        let mut debug_info_len = load_split_units(&dwarf, &mut units, &elves, &mut abbreviations_shared, &mut split_elves)?;
+
+        // The supplementary file's units go last. They're mostly partial units with types, referenced from the main file's units.
+        let mut supplementary_offset_shift: Option<usize> = None;
+        if let (Some(elf), Some(sup_dwarf)) = (&supplementary_elf, &supplementary_dwarf) {
+            abbreviations_shared.supplementary_offset_shift = debug_info_len;
+            supplementary_offset_shift = Some(debug_info_len);
+            match list_units(sup_dwarf, &elf.name, debug_info_len, &mut abbreviations_shared) {
+                Ok(sup_units) => for mut unit in sup_units {
+                    unit.split_dwarf = Some(sup_dwarf.clone());
+                    units.push(unit);
+                }
+                Err(e) => eprintln!("warning: failed to list units in {}: {}", elf.name, e),
+            }
+            debug_info_len += unit_sections_len(sup_dwarf);
+            split_elves.push(elf.clone());
+        }
+
+        let binary_size: usize = elves.iter().chain(split_elves.iter()).map(|e| e.decompressed_size()).sum();
         let num_shards = max_shards.min(binary_size >> 20).max(1);
         // How should we assign units to shards?
         // For maximum balance, we'd want to shuffle the units and assign them round-robin. But that's bad for locality of file reads.
@@ -1046,7 +1121,7 @@ impl SymbolsLoader {
         // and maybe they have smaller DIEs, making them slower to process for the same size.)
         // So we do something hybrid: take ranges of 4 MB consecutive units and assign them to shards round-robin.
         // I haven't tested if this actually helps or not.
-        let unit_distribution_granularity = (1usize << 22).min(dwarf.debug_info.reader().len() / num_shards).max(1);
+        let unit_distribution_granularity = (1usize << 22).min(debug_info_len / num_shards).max(1);
 
         // Estimates of how long different stages take relative to each other, for progress indication.
         let progress_fraction = [
@@ -1085,28 +1160,8 @@ impl SymbolsLoader {
         let mut round_robin_shard = 0usize;
         let mut round_robin_offset = 0usize;
 
-        let debug_info_section = dwarf.debug_info.reader().slice();
         let mut reported_progress_offset = 0usize;
 
-        let layouts = AllAttributeStructLayouts::new(
-            vec![
-                (vec![DW_TAG_namespace], NamespaceAttributes::layout()),
-                (vec![DW_TAG_variable, DW_TAG_formal_parameter], VariableAttributes::layout()),
-                (vec![DW_TAG_subprogram], SubprogramAttributes::layout()),
-                (vec![DW_TAG_base_type, DW_TAG_unspecified_type, DW_TAG_structure_type, DW_TAG_class_type, DW_TAG_union_type, DW_TAG_enumeration_type, DW_TAG_pointer_type, DW_TAG_reference_type, DW_TAG_rvalue_reference_type, DW_TAG_array_type, DW_TAG_const_type, DW_TAG_restrict_type, DW_TAG_volatile_type, DW_TAG_atomic_type, DW_TAG_typedef, DW_TAG_subroutine_type, DW_TAG_ptr_to_member_type], TypeAttributes::layout()),
-                (vec![DW_TAG_inheritance, DW_TAG_member, DW_TAG_enumerator], FieldAttributes::layout()),
-                (vec![DW_TAG_variant_part], VariantPartAttributes::layout()),
-                (vec![DW_TAG_variant], VariantAttributes::layout()),
-                (vec![DW_TAG_subrange_type], SubrangeTypeAttributes::layout()),
-                (vec![DW_TAG_lexical_block], LexicalBlockAttributes::layout()),
-                (vec![DW_TAG_inlined_subroutine], InlinedSubroutineAttributes::layout()),
-                (vec![DW_TAG_template_type_parameter], TemplateTypeParameterAttributes::layout()),
-                (vec![DW_TAG_template_value_parameter], TemplateValueParameterAttributes::layout()),
-            ],
-            CommonAttributes::layout(),
-            vec![DW_TAG_label, DW_TAG_variable, DW_TAG_formal_parameter, DW_TAG_subprogram, DW_TAG_inlined_subroutine]);
-
-        let (mut units, abbreviations_shared) = list_units(&mut dwarf, &elves[0].name, layouts)?;
         for (i, unit) in units.iter_mut().enumerate() {
             if unit.offset.0 > round_robin_offset + unit_distribution_granularity {
                 round_robin_offset = unit.offset.0;
@@ -1117,12 +1172,12 @@ impl SymbolsLoader {
 
             if unit.offset.0 > reported_progress_offset + 10000000 {
                 reported_progress_offset = unit.offset.0;
-                status.progress_ppm.store((unit.offset.0 as f64 / debug_info_section.len() as f64 * progress_per_stage[0][1].0 * 1e6) as usize, Ordering::Relaxed);
+                status.progress_ppm.store((unit.offset.0 as f64 / debug_info_len as f64 * progress_per_stage[0][1].0 * 1e6) as usize, Ordering::Relaxed);
             }
         }
 
         for [_, symtab] in &strtab_symtab {
-            let entry_size = mem::size_of::<libc::Elf64_Sym>();
+            let entry_size = elf_symbol_size(elves[0].is_32_bit());
             if symtab.len() % entry_size != 0 {
                 return err!(Dwarf, ".symtab or .dynsym length is not divisible by {}: {}", entry_size, symtab.len());
             }
@@ -1138,7 +1193,7 @@ impl SymbolsLoader {
 
         prepare_time_per_stage_ns[0] = start_time.elapsed().as_nanos() as usize;
         Ok(SymbolsLoader {
-            num_shards: shards.len(), binary_id, sym: Symbols {elves, dwarf, units, files: Vec::new(), file_paths: StringTable::new(), path_to_used_file: HashMap::new(), functions: BigVec::new(), shards: Vec::new(), builtin_types: BuiltinTypes::invalid(), base_types: Vec::new(), vtables: Vec::new(), points_of_interest: HashMap::new(), loading_duration_ns: 0, loading_memory_usage: None, code_addr_range, unsupported_features: DwarfUnsupportedFeatures::empty()},
+            num_shards: shards.len(), binary_id, sym: Symbols {elves, split_elves, dwarf, units, type_units: HashMap::new(), supplementary_offset_shift, files: Vec::new(), file_paths: StringTable::new(), path_to_used_file: HashMap::new(), functions: BigVec::new(), shards: Vec::new(), builtin_types: BuiltinTypes::invalid(), base_types: Vec::new(), vtables: Vec::new(), points_of_interest: HashMap::new(), loading_duration_ns: 0, loading_memory_usage: None, code_addr_range, unsupported_features: DwarfUnsupportedFeatures::empty()},
             shards: shards.into_iter().map(|s| SyncUnsafeCell::new(CachePadded::new(s))).collect(), die_to_function_shards: (0..num_shards).map(|_| SyncUnsafeCell::new(CachePadded::new(Vec::new()))).collect(), types: types_loader, send_global_variable_names, strtab_symtab, status, progress_per_stage,
             abbreviations_shared, prepare_time_per_stage_ns, run_time_per_stage_ns, shard_progress_ppm: (0..num_shards).map(|_| CachePadded::new(AtomicUsize::new(0))).collect(), stage: 0, types_before_dedup: 0, type_offsets: 0, type_offset_maps_bytes: 0, type_dedup_maps_bytes: 0})
     }
@@ -1230,6 +1285,7 @@ impl SymbolsLoader {
 
     pub fn into_result(mut self) -> Symbols {
         self.sym.unsupported_features = self.abbreviations_shared.unsupported_features;
+        self.sym.type_units = mem::take(&mut self.abbreviations_shared.type_units);
         for shard in self.shards.into_iter() {
             let shard = shard.into_inner().into_inner();
             self.sym.unsupported_features.insert(shard.unsupported_features);
@@ -1268,12 +1324,13 @@ impl SymbolsLoader {
                 }
                 let comp_unit = &self.sym.units[unit_idx];
                 let unit = &comp_unit.unit;
+                let dwarf = comp_unit.line_program_dwarf(&self.sym.dwarf);
                 for (idx, file) in entries.iter().enumerate() {
                     let directory = Path::new(OsStr::from_bytes(match file.directory(header) {
                         None => &[],
-                        Some(d) => self.sym.dwarf.attr_string(unit, d)?.slice(),
+                        Some(d) => dwarf.attr_string(unit, d)?.slice(),
                     }));
-                    let filename = Path::new(OsStr::from_bytes(self.sym.dwarf.attr_string(unit, file.path_name())?.slice()));
+                    let filename = Path::new(OsStr::from_bytes(dwarf.attr_string(unit, file.path_name())?.slice()));
                     let f = FileInfo {unit_comp_dir: Path::new(comp_unit.comp_dir), directory, filename, path: /*assigned after dedup*/ Path::new(""), used_lines: 0,
                                           version: FileVersionInfo {timestamp: file.timestamp(), size: file.size(), md5: if program.header().file_has_md5() { Some(file.md5().clone()) } else { None } }};
                     f.concat_path(&mut temp_path);
@@ -1537,14 +1594,11 @@ impl SymbolsLoader {
     fn parse_symtab(&self, shard_idx: usize, shard: &mut SymbolsLoaderShard) -> Result<()> {
         for (section_idx, ranges) in shard.symtab_ranges.iter().enumerate() {
             let [strtab, symtab] = &self.strtab_symtab[section_idx];
-            let entry_size = mem::size_of::<libc::Elf64_Sym>();
+            let is_32_bit = self.sym.elves[0].is_32_bit();
+            let entry_size = elf_symbol_size(is_32_bit);
             for range in ranges {
                 for offset in range.clone().step_by(entry_size) {
-                    let mut sym: libc::Elf64_Sym;
-                    unsafe {
-                        sym = mem::zeroed();
-                        ptr::copy_nonoverlapping::<u8>(symtab[offset..offset+entry_size].as_ptr(), mem::transmute(&mut sym), entry_size);
-                    }
+                    let sym = parse_elf_symbol(&symtab[offset..offset+entry_size], is_32_bit)?;
                     if sym.st_value == 0 || sym.st_shndx == SHN_UNDEF {
                         continue;
                     }
@@ -1924,7 +1978,6 @@ struct DwarfLoader<'a> {
     // abbrev.has_children() tells whether current entry has children.
     // These two pieces of information are enough to keep track of depth.
     loader: &'a SymbolsLoader,
-    section_slice: DwarfSlice,
     shard_idx: usize,
     shard: &'a mut SymbolsLoaderShard,
     unit: &'a DwarfUnit,
@@ -1973,6 +2026,8 @@ struct LoaderTempStorage {
     // Used by finish_function(). Reused to avoid allocations (they showed up in profiling).
     stack: Vec<(/*range_idx*/ usize, /*active*/ usize)>,
     levels: Vec<Vec<Subfunction>>,
+    // Named type declarations in the current unit, sorted by offset. For looking up DW_AT_specification on type definitions.
+    declaration_names: Vec<(DieOffset, &'static str)>,
 }
 
 // While DFSing the tree of DIEs we maintain a few stacks: main stack that has all DIEs we're currently inside of (pushed/popped very frequently, needs to be fast), and smaller stacks for specific kinds of DIEs, e.g. functions.
@@ -2142,6 +2197,7 @@ dwarf_struct!{ TypeAttributes {
     encoding: usize, DW_AT_encoding, Unsigned;
     byte_stride: usize, DW_AT_byte_stride, Unsigned;
     bit_stride: usize, DW_AT_bit_stride, Unsigned;
+    signature: /*DieOffset*/ usize, DW_AT_signature, DebugInfoOffset;
 }}
 
 dwarf_struct!{ FieldAttributes {
@@ -2211,8 +2267,7 @@ enum DwarfVariableLocation {
 impl<'a> DwarfLoader<'a> {
     fn new(loader: &'a SymbolsLoader, shard: &'a mut SymbolsLoaderShard, unit_idx: usize, temp: LoaderTempStorage, stacks: DwarfLoaderStacks) -> Self {
         let unit = &loader.sym.units[unit_idx];
-        let section_slice = *loader.sym.dwarf.debug_info.reader();
-        Self {loader, section_slice, shard_idx: unit.shard_idx, shard, unit, scope_name: String::new(), temp, stacks}
+        Self {loader, shard_idx: unit.shard_idx, shard, unit, scope_name: String::new(), temp, stacks}
     }
 
     fn append_namespace_to_scope_name(&mut self, s: &str, linkable: bool) {
@@ -2264,7 +2319,7 @@ impl<'a> DwarfLoader<'a> {
                 // Different locations for different address ranges. Add multiple local variables.
                 // Or it's a static variable inside a function - then the range from location list is much wider
                 // than the containing function (e.g. whole .text section).
-                let mut locs_iter = self.loader.sym.dwarf.locations(&self.unit.unit, LocationListsOffset(off))?;
+                let mut locs_iter = self.unit.dwarf(&self.loader.sym.dwarf).locations(&self.unit.unit, LocationListsOffset(off))?;
                 custom_ranges = true;
                 while let Some(entry) = locs_iter.next()? {
                     var.location = PackedVariableLocation::expr(entry.data);
@@ -2363,7 +2418,7 @@ impl<'a> DwarfLoader<'a> {
                 let unit_idx = loader.sym.units.partition_point(|u| u.offset.0 <= specification_or_origin.offset);
                 let u = &loader.sym.units[unit_idx.saturating_sub(1)];
                 let Some(off) = DebugInfoOffset(specification_or_origin.offset).to_unit_offset(&u.unit.header) else {return err!(Dwarf, "specification/abstract_origin entry offset out of unit bounds")};
-                attribute_context.to_mut().switch_unit(u);
+                attribute_context.to_mut().switch_unit(u, &loader.sym.dwarf);
                 off
             } else {
                 UnitOffset(specification_or_origin.offset)
@@ -2424,15 +2479,20 @@ impl<'a> DwarfLoader<'a> {
             *self.stacks.ranges.push_uninit(f) = RangesStackEntry::default();
             *self.stacks.subfunction.push_uninit(f) = SubfunctionStackEntry {subfunction_idx: u32::MAX, ..Default::default()};
         }
+        self.temp.declaration_names.clear();
 
         // Iterate over DIEs in depth-first order.
-        let mut cursor = SliceReader::new(self.unit.unit.header.range_from(UnitOffset(self.unit.unit.header.header_size())..)?.slice());
-        let attribute_context = AttributeContext {unit: self.unit, dwarf: &self.loader.sym.dwarf, shared: &self.loader.abbreviations_shared};
+        let entries_offset = self.unit.unit.header.header_size();
+        let entries = self.unit.unit.header.range_from(UnitOffset(entries_offset)..)?.slice();
+        // (Not using the start of .debug_info as base because split units come from different sections, with offsets shifted to not overlap.)
+        let entries_offset = self.unit.offset.0 + entries_offset;
+        let mut cursor = SliceReader::new(entries);
+        let attribute_context = AttributeContext {unit: self.unit, dwarf: self.unit.dwarf(&self.loader.sym.dwarf), shared: &self.loader.abbreviations_shared};
         let encoding = self.unit.unit.encoding();
         let mut prev_has_children = true;
         let mut skip_subtree = usize::MAX;
         loop {
-            let offset = DebugInfoOffset(cursor.offset_from(self.section_slice.slice()));
+            let offset = DebugInfoOffset(entries_offset + cursor.offset_from(entries));
 
             // Pop from the stack if needed.
             if !prev_has_children {
@@ -2509,12 +2569,13 @@ impl<'a> DwarfLoader<'a> {
                 DW_TAG_compile_unit | DW_TAG_partial_unit | DW_TAG_type_unit => {
                     cursor.skip_attributes(abbrev, &attribute_context)?;
                     let ranges_top = self.stacks.ranges.push_uninit(&mut self.stacks.main.top_mut().flags);
-                    parse_dwarf_ranges(&self.loader.sym.dwarf, &self.unit.unit, &self.unit.ranges, self.unit.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, /*out_entry_pc*/ &mut 0, &mut self.shard.warn)?;
+                    // (The unit's ranges come from the executable's sections even for split units, since they're inherited from the skeleton.)
+                    parse_dwarf_ranges(self.unit.line_program_dwarf(&self.loader.sym.dwarf), &self.unit.unit, &self.unit.ranges, self.unit.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, /*out_entry_pc*/ &mut 0, &mut self.shard.warn)?;
                     self.stacks.main.top_mut().flags.remove(StackFlags::IS_FUNCTION_SCOPE | StackFlags::IS_TYPE_SCOPE); // this shouldn't do anything since this tag is always root, but just in case
                 }
 
-                // This tag means that the debug info is split into a separate file using DWARF's complicated mechanism for that.
-                // Not sure if this is worth supporting, since simpler ways of splitting are available: debuglink, or just providing an unstripped executable on the side.
+                // This tag means that the debug info is split into a separate .dwo file. We replace skeleton units with the units from .dwo files
+                // in load_split_units(), so we get here only if the .dwo file wasn't found.
                 DW_TAG_skeleton_unit => {
                     skip_subtree = self.stacks.main.len;
                     cursor.skip_attributes(abbrev, &attribute_context)?;
@@ -2593,7 +2654,7 @@ impl<'a> DwarfLoader<'a> {
                     let ranges_stack_idx = self.stacks.ranges.len;
                     let ranges_top = self.stacks.ranges.push_uninit(&mut self.stacks.main.top_mut().flags);
                     let mut entry_pc = 0usize;
-                    parse_dwarf_ranges(&self.loader.sym.dwarf, &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, &mut entry_pc, &mut self.shard.warn)?;
+                    parse_dwarf_ranges(self.unit.dwarf(&self.loader.sym.dwarf), &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, &mut entry_pc, &mut self.shard.warn)?;
                     self.stacks.main.top_mut().flags.remove(StackFlags::IS_ANY_SCOPE);
                     self.stacks.main.top_mut().flags.insert(StackFlags::IS_FUNCTION_SCOPE);
 
@@ -2720,7 +2781,7 @@ impl<'a> DwarfLoader<'a> {
                     // Useful: DECL, name, bit_size, byte_size, declaration, type, containing_type, signature, alignment
                     // For base types: encoding
                     // For arrays and enums used as array index: bit_stride, byte_stride, ordering, rank
-                    // Possible in theory, but I haven't seen these on types: specification, abstract_origin
+                    // Specification: seen in type units, see below. Possible in theory, but I haven't seen these on types: abstract_origin
                     // Other: data_bit_offset, data_location
                     // Other: binary_scale, decimal_scale, decimal_sign, digit_count, picture_string, small
                     // Other: allocated, associated, enum_class, accessibility, visibility, start_scope, export_symbols, endianity, calling_convention, address_class
@@ -2751,14 +2812,27 @@ impl<'a> DwarfLoader<'a> {
                         info.line = Self::parse_line_info(&mut self.shard, &self.loader, self.unit, &attrs.decl);
                     }
 
+                    // GCC's type units (-fdebug-types-section) define types at the top level, with DW_AT_specification pointing to a declaration inside the namespace.
+                    // Take the fully qualified name from the declaration.
+                    let mut specification_name = "";
+                    if attrs.fields & DwarfReference::HAS_SPECIFICATION_OR_ABSTRACT_ORIGIN != 0 && attrs.fields & DwarfReference::GLOBAL == 0 && self.scope_name.is_empty() {
+                        let decl_offset = self.unit.offset.0 + attrs.specification_or_abstract_origin.offset;
+                        if let Ok(i) = self.temp.declaration_names.binary_search_by_key(&decl_offset, |(o, _)| o.0) {
+                            specification_name = self.temp.declaration_names[i].1;
+                        }
+                    }
+
                     if attrs.name.is_empty() {
                         self.append_namespace_to_scope_name("_", false);
                     } else {
-                        self.append_namespace_to_scope_name(attrs.name, true);
+                        self.append_namespace_to_scope_name(if specification_name.is_empty() {attrs.name} else {specification_name}, true);
                         info.name = self.shard.types.temp_types.unsorted_type_names.add_str(&self.scope_name, 0);
                         if self.stacks.scope.top().scope_name_is_linkable {
                             info.flags.insert(TypeFlags::LINKABLE_NAME);
                         }
+                        if attrs.declaration {
+                            self.temp.declaration_names.push((offset, info.name));
+                        }
                     }
 
                     if attrs.fields & TypeAttributes::byte_size != 0 {
@@ -2790,7 +2864,8 @@ impl<'a> DwarfLoader<'a> {
                         info.flags.insert(TypeFlags::DECLARATION);
                     }
 
-                    if attrs.fields & DwarfReference::HAS_SPECIFICATION_OR_ABSTRACT_ORIGIN != 0 {
+                    // (Anonymous types in type units have DW_AT_specification too, but there's no name to take from the declaration.)
+                    if attrs.fields & DwarfReference::HAS_SPECIFICATION_OR_ABSTRACT_ORIGIN != 0 && specification_name.is_empty() && !(attrs.name.is_empty() && attrs.fields & DwarfReference::GLOBAL == 0) {
                         if self.shard.warn.check(line!()) { eprintln!("warning: DW_AT_specification/DW_AT_abstract_origin on {} is not supported (@0x{:x})", abbrev.tag, offset.0); }
                     }
 
@@ -2903,6 +2978,13 @@ impl<'a> DwarfLoader<'a> {
                         self.shard.base_types.push((offset.0 as u64) << 8 | type_enum as u64);
                     }
 
+                    if attrs.fields & TypeAttributes::signature != 0 {
+                        // Stub for a type defined in a type unit. Either nameless without other attributes, or a declaration with some member function declarations.
+                        is_alias = true;
+                        info.t = Type::Pointer(PointerType {flags: PointerFlags::empty(), type_: attrs.signature as *const TypeInfo});
+                        info.name = "";
+                    }
+
                     let prev_type_stack_len = self.stacks.type_.len;
                     let ti;
                     if is_alias {
@@ -3140,7 +3222,7 @@ impl<'a> DwarfLoader<'a> {
                     unsafe {cursor.read_attributes(abbrev, /*which_layout*/ 0, &attribute_context, &raw mut attrs as *mut u8)}?;
                     if attrs.fields & (DwarfRanges::RANGES | DwarfRanges::LOW_PC) != 0 {
                         let ranges_top = self.stacks.ranges.push_uninit(&mut self.stacks.main.top_mut().flags);
-                        parse_dwarf_ranges(&self.loader.sym.dwarf, &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, /*out_entry_pc*/ &mut 0, &mut self.shard.warn)?;
+                        parse_dwarf_ranges(self.unit.dwarf(&self.loader.sym.dwarf), &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, /*out_entry_pc*/ &mut 0, &mut self.shard.warn)?;
                     }
                 }
 
@@ -3160,7 +3242,7 @@ impl<'a> DwarfLoader<'a> {
                     unsafe {cursor.read_attributes(abbrev, /*which_layout*/ 0, &attribute_context, &raw mut attrs as *mut u8)}?;
                     let ranges_top = self.stacks.ranges.push_uninit(&mut self.stacks.main.top_mut().flags);
                     let mut entry_pc = 0usize;
-                    parse_dwarf_ranges(&self.loader.sym.dwarf, &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, &mut entry_pc, &mut self.shard.warn)?;
+                    parse_dwarf_ranges(self.unit.dwarf(&self.loader.sym.dwarf), &self.unit.unit, &attrs.ranges, attrs.fields, offset, self.loader.sym.code_addr_range.start, &mut ranges_top.pc_ranges, &mut entry_pc, &mut self.shard.warn)?;
 
                     let call_line = Self::parse_line_info(&mut self.shard, &self.loader, self.unit, &attrs.call);
                     let callee_die = if attrs.fields & InlinedSubroutineAttributes::abstract_origin != 0 {
@@ -3368,13 +3450,187 @@ impl<'a> DwarfLoader<'a> {
     }
 }
 
+// Split DWARF (-gsplit-dwarf): the executable has only skeleton units, while the DIEs are in .dwo files (one per compilation unit, found through DW_AT_dwo_name and DW_AT_comp_dir),
+// or packed into one <executable>.dwp file. We find them and replace each skeleton unit with the full unit, so the rest of the loader mostly doesn't need to know about this.
+// Type units from .dwo/.dwp files are added to `units` too.
+// Units from .dwo files get DIE offsets past the end of the executable's .debug_info and .debug_types, and `units` is re-sorted by offset.
+// Returns the end of the DIE offset range, i.e. the total size of all .debug_info and .debug_types sections.
+fn load_split_units(dwarf: &Dwarf<DwarfSlice>, units: &mut Vec<DwarfUnit>, elves: &[Arc<ElfFile>], shared: &mut AbbreviationsSharedData, out_elves: &mut Vec<Arc<ElfFile>>) -> Result<usize> {
+    let mut offset_shift = unit_sections_len(dwarf);
+    if !units.iter().any(|u| u.unit.dwo_id.is_some()) {
+        return Ok(offset_shift);
+    }
+    let _prof = ProfileScope::with_threshold(0.01, format!("loading split units ({})", elves[0].name));
+
+    let open_file = |path: &Path| -> Result<Arc<ElfFile>> {
+        let file = File::open(path)?;
+        Ok(Arc::new(ElfFile::from_file(path.to_string_lossy().into_owned(), &file, file.metadata()?.len())?))
+    };
+    let load_dwo_section = |elf: &ElfFile, id: SectionId| -> Result<DwarfSlice> {
+        match id.dwo_name().and_then(|name| elf.section_by_name.get(name)) {
+            Some(&idx) => Ok(DwarfSlice::new(unsafe {mem::transmute::<&[u8], &'static [u8]>(elf.section_data(idx)?)})),
+            None => Ok(DwarfSlice::new(&[0u8;0][..])),
+        }
+    };
+
+    let mut dwp: Option<(DwarfPackage<DwarfSlice>, String)> = None;
+    for elf in elves {
+        let path = PathBuf::from(format!("{}.dwp", elf.name));
+        if !path.exists() {
+            continue;
+        }
+        match open_file(&path).and_then(|f| {
+            let package = DwarfPackage::load(|id| load_dwo_section(&f, id), DwarfSlice::new(&[0u8;0][..]))?;
+            out_elves.push(f);
+            Ok(package)
+        }) {
+            Ok(package) => {
+                dwp = Some((package, path.to_string_lossy().into_owned()));
+                break;
+            }
+            Err(e) => eprintln!("warning: failed to load {}: {}", path.display(), e),
+        }
+    }
+
+    let mut type_units: Vec<DwarfUnit> = Vec::new();
+    let (mut num_loaded, mut num_missing) = (0usize, 0usize);
+    let mut first_missing = String::new();
+    for unit in units.iter_mut() {
+        let Some(dwo_id) = unit.unit.dwo_id else {continue};
+        let res = (|| -> Result<Option<(Vec<Dwarf<DwarfSlice>>, String)>> {
+            if let Some((package, name)) = &dwp {
+                if let Some(d) = package.find_cu(dwo_id, dwarf)? {
+                    return Ok(Some((vec![d], name.clone())));
+                }
+            }
+
+            let skeleton = &*unit;
+            let dwo_name = Path::new(skeleton.dwo_name);
+            if skeleton.dwo_name.is_empty() {
+                return Ok(None);
+            }
+            // Look in the compilation directory first, then next to the executable (in case the build directory was moved).
+            let mut candidates = vec![Path::new(skeleton.comp_dir).join(dwo_name)];
+            for elf in elves {
+                if let Some(dir) = Path::new(&elf.name).parent() {
+                    candidates.push(dir.join(dwo_name));
+                    if let Some(file_name) = dwo_name.file_name() {
+                        candidates.push(dir.join(file_name));
+                    }
+                }
+            }
+            let Some(path) = candidates.into_iter().find(|p| p.exists()) else {return Ok(None)};
+            let f = open_file(&path)?;
+            let mut d = Dwarf::load(|id| load_dwo_section(&f, id))?;
+            d.make_dwo(dwarf);
+            let mut res = vec![d];
+            // Each type unit is in its own section (COMDAT group): .debug_types.dwo in DWARF 4, .debug_info.dwo in DWARF 5. section_by_name has only one of them.
+            // Make a Dwarf for each of the other ones.
+            for section in &f.sections {
+                let section_id = match section.name.as_str() {
+                    ".debug_info.dwo" => SectionId::DebugInfo,
+                    ".debug_types.dwo" => SectionId::DebugTypes,
+                    _ => continue,
+                };
+                if f.section_by_name.get(&section.name) == Some(&section.idx) {
+                    continue;
+                }
+                let mut d = Dwarf::load(|id| match id {
+                    _ if id == section_id => Ok(DwarfSlice::new(unsafe {mem::transmute::<&[u8], &'static [u8]>(f.section_data(section.idx)?)})),
+                    SectionId::DebugInfo | SectionId::DebugTypes => Ok(DwarfSlice::new(&[0u8;0][..])),
+                    _ => load_dwo_section(&f, id),
+                })?;
+                d.make_dwo(dwarf);
+                res.push(d);
+            }
+            let name = f.name.clone();
+            out_elves.push(f);
+            Ok(Some((res, name)))
+        })();
+
+        let (split_dwarfs, file_name) = match res {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                if num_missing == 0 {
+                    first_missing = unit.dwo_name.to_string();
+                }
+                num_missing += 1;
+                continue;
+            }
+            Err(e) => {
+                eprintln!("warning: failed to load split unit {}: {}", unit.dwo_name, e);
+                num_missing += 1;
+                continue;
+            }
+        };
+
+        let mut found = false;
+        for split_dwarf in split_dwarfs {
+            let split_dwarf = Arc::new(split_dwarf);
+            let split_units = match list_units(&split_dwarf, &file_name, offset_shift, shared) {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("warning: failed to list units in {}: {}", file_name, e);
+                    continue;
+                }
+            };
+            offset_shift += unit_sections_len(&split_dwarf);
+            for mut split_unit in split_units {
+                if !found && split_unit.unit.dwo_id == Some(dwo_id) {
+                    found = true;
+                    let skeleton = mem::replace(unit, split_unit);
+                    unit.inherit_from_skeleton(skeleton, split_dwarf.clone());
+                } else if let UnitType::Type {..} | UnitType::SplitType {..} = split_unit.unit.header.type_() {
+                    split_unit.split_dwarf = Some(split_dwarf.clone());
+                    type_units.push(split_unit);
+                }
+            }
+        }
+        if !found {
+            eprintln!("warning: no unit with dwo id 0x{:x} in {}", dwo_id.0, file_name);
+            num_missing += 1;
+            continue;
+        }
+        num_loaded += 1;
+    }
+
+    // In .dwp files, type units are indexed separately from compilation units, so find_cu() above didn't see them.
+    if let Some((package, file_name)) = &dwp {
+        for row in 1..=package.tu_index.unit_count() {
+            let split_dwarf = match package.tu_sections(row, dwarf) {
+                Ok(x) => Arc::new(x),
+                Err(e) => {
+                    eprintln!("warning: failed to load type unit {} in {}: {}", row, file_name, e);
+                    continue;
+                }
+            };
+            match list_units(&split_dwarf, file_name, offset_shift, shared) {
+                Ok(split_units) => for mut split_unit in split_units {
+                    split_unit.split_dwarf = Some(split_dwarf.clone());
+                    type_units.push(split_unit);
+                }
+                Err(e) => eprintln!("warning: failed to list type units in {}: {}", file_name, e),
+            }
+            offset_shift += unit_sections_len(&split_dwarf);
+        }
+    }
+
+    if num_missing != 0 {
+        eprintln!("warning: {} of {} split units not found{} ({})", num_missing, num_loaded + num_missing, if first_missing.is_empty() {String::new()} else {format!(", e.g. {}", first_missing)}, elves[0].name);
+        shared.unsupported_features.insert(DwarfUnsupportedFeatures::SKELETON_UNITS);
+    }
+    units.append(&mut type_units);
+    units.sort_unstable_by_key(|u| u.offset);
+    Ok(offset_shift)
+}
+
 fn parse_dwarf_ranges(dwarf: &Dwarf<DwarfSlice>, unit: &Unit<DwarfSlice>, ranges: &DwarfRanges, fields: u32, offset: DieOffset, code_start: usize, out_ranges: &mut Vec<gimli::Range>, out_entry_pc: &mut usize, warn: &mut Limiter) -> Result<()> {
     out_ranges.clear();
     *out_entry_pc = 0;
     // Sometimes both low_pc and ranges are present on compilation units. In this case ranges take precedence, and low_pc acts as "base address" for all range lists in the unit (which gimli takes care of automatically).
     if fields & DwarfRanges::RANGES != 0 {
-        // (Compatibility: in DWARF 4 we'd need to add DW_AT_GNU_ranges_base.)
-        let mut it = dwarf.ranges(unit, RangeListsOffset(ranges.ranges))?;
+        // (In pre-DWARF-5 .dwo files this adds DW_AT_GNU_ranges_base. DW_FORM_rnglistx was already converted to offset, and this is a no-op for it.)
+        let mut it = dwarf.ranges(unit, dwarf.ranges_offset_from_raw(unit, RawRangeListsOffset(ranges.ranges)))?;
         while let Some(range) = it.next()? {
             // In practice ranges often contain garbage near-zero addresses, even in debug builds without LTO. Presumably linker relocations get lost at some point, maybe because of compiler optimizations.
             // If the bad range is empty, I guess it's intended to be skipped. But sometimes these ranges are not empty, sometimes they don't start at 0, and sometimes none of the bad ranges in the list start at 0.
@@ -3459,7 +3715,7 @@ pub fn run_load_symbols_tool(path: String, num_threads: usize) -> Result<()> {
         {
             let start_time = Instant::now();
 
-            loader = Arc::new(SyncUnsafeCell::new(SymbolsLoader::new(vec![elf], 1, num_threads, status.clone())?));
+            loader = Arc::new(SyncUnsafeCell::new(SymbolsLoader::new(vec![elf], None, 1, num_threads, status.clone())?));
 
             stage_names = unsafe {&*loader.get()}.progress_per_stage.clone();
             println!("stage 0 prepare ({}) took {}", stage_names[0][0].1, PrettyDuration(start_time.elapsed().as_secs_f64()));
@@ -1,5 +1,5 @@
-use crate::{*, error::*, elf::*, symbols::*, procfs::*, util::*, unwind::*, log::*, context::*, settings::*};
-use std::{fs::{File, OpenOptions}, collections::{HashMap, HashSet, hash_map::Entry, VecDeque}, rc::Rc, sync::{Arc, Mutex, Condvar, Weak}, sync::atomic::{AtomicBool, AtomicUsize, Ordering}, thread::{self, JoinHandle}, mem, path::Path, io, str, ops::Range, os::unix::{fs::OpenOptionsExt, ffi::OsStrExt}, os::fd::AsRawFd, ptr, fmt::Write as fmtWrite, io::{Read, Write}, time::{Duration, Instant}};
+use crate::{*, error::*, elf::*, symbols::*, procfs::*, util::*, unwind::*, log::*, context::*, settings::*, debugger::*, search::*};
+use std::{fs::{File, OpenOptions}, collections::{HashMap, HashSet, hash_map::Entry, VecDeque}, rc::Rc, sync::{Arc, Mutex, Condvar, Weak}, sync::atomic::{AtomicBool, AtomicUsize, Ordering}, thread::{self, JoinHandle}, mem, path::{Path, PathBuf}, io, str, ops::Range, os::unix::{fs::OpenOptionsExt, ffi::OsStrExt}, os::fd::AsRawFd, ptr, fmt::Write as fmtWrite, io::{Read, Write}, time::{Duration, Instant}};
 use std::os::unix::fs::MetadataExt;
 
 #[derive(Clone)]
@@ -29,6 +29,9 @@ pub struct Binary {
     pub mmap_idx: usize,
     // Index in `priority_order`.
     pub priority_idx: usize,
+
+    // Function breakpoint pattern -> static entry addresses of matching functions, see functions_matching_breakpoint(). None while the search is in progress.
+    pub function_breakpoint_matches: HashMap<String, Option<Arc<Vec<usize>>>>,
 }
 impl Binary {
     pub fn symbols_loaded(&self) -> bool {
@@ -111,6 +114,18 @@ impl SymbolsRegistry {
         self.priority_order.iter().map(|id| &self.binaries[*id].as_ref().unwrap().0)
     }
 
+    // The main executable's entry point (_start). It runs only once, at startup, so it's unlikely to be hit by other threads,
+    // which makes it a good place for temporary code injected into the debuggee (function call return trap, injected syscalls).
+    pub fn main_binary_entry_point(&self) -> Result<usize> {
+        for binary in self.iter() {
+            if binary.is_main_binary && binary.is_mapped {
+                let elves = binary.elves.as_ref_clone_error()?;
+                return Ok(binary.addr_map.static_to_dynamic(elves[0].entry_point));
+            }
+        }
+        err!(ProcessState, "main executable is not mapped")
+    }
+
     pub fn add(&mut self, locator: BinaryLocator, memory: &MemReader, custom_path: Option<String>, build_id: Option<Vec<u8>>, is_main_binary: bool, reconstruction: Option<BinaryReconstructionInput>) -> &mut Binary {
         let id = self.binaries.len();
         let mut elf_contents_maybe: Result<Vec<u8>> = err!(Internal, "no contents");
@@ -125,7 +140,7 @@ impl SymbolsRegistry {
             }
         }
 
-        let mut binary = Binary {locator: locator.clone(), build_id: build_id.clone(), id, elves: err!(Loading, "loading symbols"), symbols: err!(Loading, "loading symbols"), unwind: err!(Loading, "loading symbols"), is_main_binary, is_mapped: false, addr_map: AddrMap::default(), tls_offset: err!(Loading, "TLS offset not known"), mmap_idx: 0, priority_idx: 0, notices: Vec::new(), warnings: Vec::new()};
+        let mut binary = Binary {locator: locator.clone(), build_id: build_id.clone(), id, elves: err!(Loading, "loading symbols"), symbols: err!(Loading, "loading symbols"), unwind: err!(Loading, "loading symbols"), is_main_binary, is_mapped: false, addr_map: AddrMap::default(), tls_offset: err!(Loading, "TLS offset not known"), mmap_idx: 0, priority_idx: 0, notices: Vec::new(), warnings: Vec::new(), function_breakpoint_matches: HashMap::new()};
 
         if let Some(id) = &build_id {
             binary.notices.push(format!("build id: {}", hexdump(id, 1000)));
@@ -144,6 +159,32 @@ impl SymbolsRegistry {
         &mut self.binaries.last_mut().unwrap().as_mut().unwrap().0
     }
 
+    // Finds functions matching a function breakpoint's pattern, see find_functions_matching_breakpoint(). Returns static entry addresses.
+    // The search runs on the thread pool, one task per shard; until it's done, returns Err(Loading), and process_events() reports its completion like symbols loading.
+    pub fn functions_matching_breakpoint(&mut self, id: usize, bp: &FunctionBreakpoint) -> Result<Arc<Vec<usize>>> {
+        let (binary, status) = match self.binaries.get_mut(id) {
+            Some(Some(x)) => x,
+            _ => return err!(Internal, "no binary {}", id) };
+        let symbols = binary.symbols.as_ref_clone_error()?.clone();
+        match binary.function_breakpoint_matches.get(&bp.pattern) {
+            Some(Some(addrs)) => return Ok(addrs.clone()),
+            Some(None) => return err!(Loading, "searching for functions matching {}", bp.pattern),
+            None => (),
+        }
+        binary.function_breakpoint_matches.insert(bp.pattern.clone(), None);
+        let state = Arc::new(FunctionMatchingState {bp: bp.clone(), addrs: Mutex::new(Vec::new()), tasks_remaining: AtomicUsize::new(symbols.shards.len())});
+        for shard_idx in 0..symbols.shards.len() {
+            let (shared, state, symbols, status) = (self.shared.clone(), state.clone(), symbols.clone(), status.clone());
+            self.shared.context.executor.add(move || task_match_functions(shared, id, state, symbols, shard_idx, status));
+        }
+        err!(Loading, "searching for functions matching {}", bp.pattern)
+    }
+
+    // If any function breakpoint searches are running, returns id of a binary being searched.
+    pub fn function_breakpoint_search_in_progress(&self) -> Option<usize> {
+        self.iter().find(|b| b.function_breakpoint_matches.values().any(|m| m.is_none())).map(|b| b.id)
+    }
+
     pub fn mark_all_as_unmapped(&mut self) -> HashMap</*binary_id*/ usize, /*addr_map.diff*/ usize> {
        let mut prev_mapped: HashMap<usize, usize> = HashMap::new();
         for (_, id) in &self.locator_to_id {
@@ -208,6 +249,9 @@ impl SymbolsRegistry {
                 Message::Unwind {id, unwind} => if let Some((bin, _)) = &mut self.binaries[id] {
                     bin.unwind = unwind.map(|x| Arc::new(x));
                 }
+                Message::FunctionMatches {id, pattern, addrs} => if let Some((bin, _)) = &mut self.binaries[id] {
+                    bin.function_breakpoint_matches.insert(pattern, Some(Arc::new(addrs)));
+                }
             }
         }
         true
@@ -285,6 +329,24 @@ enum Message {
     Elf {id: usize, elves: Result<Vec<Arc<ElfFile>>>, notices: Vec<String>, warnings: Vec<String>},
     Symbols {id: usize, symbols: Result<Symbols>, notices: Vec<String>, warnings: Vec<String>},
     Unwind {id: usize, unwind: Result<UnwindInfo>},
+    FunctionMatches {id: usize, pattern: String, addrs: Vec<usize>},
+}
+
+struct FunctionMatchingState {
+    bp: FunctionBreakpoint,
+    addrs: Mutex<Vec<usize>>,
+    tasks_remaining: AtomicUsize,
+}
+
+fn task_match_functions(shared: Arc<Shared>, id: usize, state: Arc<FunctionMatchingState>, symbols: Arc<Symbols>, shard_idx: usize, status: Arc<SymbolsLoadingStatus>) {
+    let mut addrs = find_functions_matching_breakpoint(&symbols, shard_idx, &state.bp, &status.cancel);
+    state.addrs.lock().unwrap().append(&mut addrs);
+    if state.tasks_remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+        let addrs = mem::take(&mut *state.addrs.lock().unwrap());
+        let mut lock = shared.to_main_thread.lock().unwrap();
+        lock.push_back(Message::FunctionMatches {id, pattern: state.bp.pattern.clone(), addrs});
+        shared.wake_main_thread.write(1);
+    }
 }
 
 fn task_load_elf(shared: Arc<Shared>, locator: BinaryLocator, id: usize, status: Arc<SymbolsLoadingStatus>, elf_contents_maybe: Result<Vec<u8>>, custom_path: Option<String>, build_id: Option<Vec<u8>>, mut elves: Vec<Arc<ElfFile>>, reconstruction: Option<BinaryReconstructionInput>) {
@@ -402,6 +464,18 @@ fn task_load_elf(shared: Arc<Shared>, locator: BinaryLocator, id: usize, status:
         Ok(elves)
     };
 
+    let mut supplementary_elf: Option<Arc<ElfFile>> = None;
+    if let Ok(elves) = &elves {
+        match find_supplementary_file(elves, &shared.context.settings, &status) {
+            Ok(None) => (),
+            Ok(Some(elf)) => {
+                notices.push(format!("supplementary debug info: {}", elf.name));
+                supplementary_elf = Some(elf);
+            }
+            Err(e) => warnings.push(format!("supplementary debug info: {}", e)),
+        }
+    }
+
     {
         let mut lock = shared.to_main_thread.lock().unwrap();
         lock.push_back(Message::Elf {id, elves: elves.clone().into(), notices, warnings});
@@ -411,7 +485,95 @@ fn task_load_elf(shared: Arc<Shared>, locator: BinaryLocator, id: usize, status:
     if let Ok(elves) = elves {
         let (shared_clone, elves_clone) = (shared.clone(), elves.clone());
         shared.context.executor.add(move || task_load_unwind(shared_clone, id, elves_clone));
-        LoadScheduler::start(id, elves, shared, status);
+        LoadScheduler::start(id, elves, supplementary_elf, shared, status);
+    }
+}
+
+// DWZ (`dwz -m`, used by distro debuginfo packages) moves debug info that's common to multiple binaries into a separate file,
+// referenced through section .gnu_debugaltlink (GNU extension) or .debug_sup (DWARF 5). Both contain the file's path and build id.
+// The path is often relative to the debug file, or absolute but only valid on the machine where the package was built, so we also look it up by build id.
+fn find_supplementary_file(elves: &[Arc<ElfFile>], settings: &Settings, status: &SymbolsLoadingStatus) -> Result<Option<Arc<ElfFile>>> {
+    let mut link: Option<(&ElfFile, &[u8], &[u8])> = None;
+    for elf in elves {
+        if let Some(&idx) = elf.section_by_name.get(".gnu_debugaltlink") {
+            // Null-terminated path, then build id.
+            let data = elf.section_data(idx)?;
+            let Some(n) = data.iter().position(|&c| c == b'\0') else {return err!(Dwarf, ".gnu_debugaltlink is not null-terminated")};
+            link = Some((elf, &data[..n], &data[n+1..]));
+            break;
+        }
+        if let Some(&idx) = elf.section_by_name.get(".debug_sup") {
+            // u16 version, u8 is_supplementary, null-terminated path, uleb128 checksum length, checksum (build id).
+            let data = elf.section_data(idx)?;
+            if data.len() < 4 {
+                return err!(Dwarf, ".debug_sup is too short: {}", data.len());
+            }
+            if data[2] != 0 {
+                // This is the supplementary file itself.
+                continue;
+            }
+            let data = &data[3..];
+            let Some(n) = data.iter().position(|&c| c == b'\0') else {return err!(Dwarf, ".debug_sup is not null-terminated")};
+            let mut reader = gimli::EndianSlice::new(&data[n+1..], gimli::LittleEndian);
+            let len = gimli::leb128::read::unsigned(&mut reader)? as usize;
+            if len > reader.len() {
+                return err!(Dwarf, ".debug_sup checksum out of bounds");
+            }
+            link = Some((elf, &data[..n], &reader.slice()[..len]));
+            break;
+        }
+    }
+    let Some((elf, path, build_id)) = link else {return Ok(None)};
+    let path_str = str::from_utf8(path)?;
+
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if path_str.starts_with('/') {
+        candidates.push(PathBuf::from(path_str));
+    } else if let Some(dir) = Path::new(&elf.name).parent() {
+        candidates.push(dir.join(path_str));
+    }
+    // E.g. "../../.dwz/zsh-5.9-4.fc39.x86_64" (Fedora) or "/usr/lib/debug/.dwz/x86_64-linux-gnu/zsh.debug" (Debian).
+    if let Some(i) = path_str.find(".dwz/") {
+        candidates.push(Path::new("/usr/lib/debug/.dwz").join(&path_str[i+5..]));
+    }
+    if build_id.len() > 1 {
+        candidates.push(PathBuf::from(format!("/usr/lib/debug/.build-id/{:02x}/{}.debug", build_id[0], hexdump(&build_id[1..], 1000))));
+    }
+
+    let mut first_error: Option<Error> = None;
+    for candidate in candidates {
+        let file = match File::open(&candidate) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                first_error.get_or_insert(e.into());
+                continue;
+            }
+        };
+        let res = match ElfFile::from_file(candidate.to_string_lossy().into_owned(), &file, file.metadata()?.len()) {
+            Ok(x) => x,
+            Err(e) => {
+                first_error.get_or_insert(e);
+                continue;
+            }
+        };
+        if !build_id.is_empty() && res.build_id.as_deref() != Some(build_id) {
+            first_error.get_or_insert(error!(MissingSymbols, "build id mismatch in {}: expected {}, found {}", res.name, hexdump(build_id, 1000), res.build_id.as_ref().map_or("none".to_string(), |x| hexdump(x, 1000))));
+            continue;
+        }
+        return Ok(Some(Arc::new(res)));
+    }
+
+    if let (false, false, Some(debuginfod_cache_path)) = (build_id.is_empty(), settings.debuginfod_urls.is_empty(), &settings.debuginfod_cache_path) {
+        match try_to_download_from_debuginfod(build_id, &settings.debuginfod_urls, "debuginfo", debuginfod_cache_path, status) {
+            Ok((res, _)) => return Ok(Some(Arc::new(res))),
+            Err(e) => {first_error.get_or_insert(e);}
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => err!(MissingSymbols, "{} not found", path_str),
     }
 }
 
@@ -603,10 +765,10 @@ struct LoadScheduler {
 }
 
 impl LoadScheduler {
-    fn start(id: usize, elves: Vec<Arc<ElfFile>>, shared: Arc<Shared>, status: Arc<SymbolsLoadingStatus>) {
+    fn start(id: usize, elves: Vec<Arc<ElfFile>>, supplementary_elf: Option<Arc<ElfFile>>, shared: Arc<Shared>, status: Arc<SymbolsLoadingStatus>) {
         let mut scheduler = LoadScheduler {id, name: elves[0].name.clone(), loader: SyncUnsafeCell::new(None), num_shards: 0, shared: shared.clone(), status: status.clone(), failed: AtomicBool::new(false), stage: AtomicUsize::new(0), tasks_left: AtomicUsize::new(1)};
         let max_shards = (shared.context.executor.num_threads - 1).max(1);
-        let loader = match SymbolsLoader::new(elves, id, max_shards, status) {
+        let loader = match SymbolsLoader::new(elves, supplementary_elf, id, max_shards, status) {
             Ok(l) => l,
             Err(e) => {
                 scheduler.handle_fail(e);
@@ -1,5 +1,5 @@
 use crate::{*, common_ui::*, error::*, log::*, util::*, os::*};
-use std::{result, io, mem, sync::atomic::{AtomicBool, Ordering}, cell::UnsafeCell, io::Write, str, str::FromStr, fmt, fmt::{Display, Formatter}};
+use std::{result, io, mem, fs, ptr, os::fd::{OwnedFd, AsRawFd, FromRawFd, IntoRawFd}, sync::atomic::{AtomicBool, AtomicI32, Ordering}, cell::UnsafeCell, io::Write, str, str::FromStr, fmt, fmt::{Display, Formatter}};
 use bitflags::*;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
@@ -220,6 +220,180 @@ pub fn restore_terminal() {
 pub struct TerminalRestorer;
 impl Drop for TerminalRestorer { fn drop(&mut self) { restore_terminal(); } }
 
+// Reverse tty mode (--ui-tty): the debugged program gets the terminal nnd was started in, and the UI is drawn in another terminal.
+// The other terminal is either given as a path (then the user has to pacify its shell, e.g. with `sleep inf`), or lent to us by
+// `nnd --ui-here` running in it, which sends us its stdin fd over a unix socket and then sleeps until we exit.
+
+fn ui_tty_socket_address() -> (libc::sockaddr_un, libc::socklen_t) {
+    // Abstract socket namespace, so there's no file to clean up. Abstract sockets have no permissions, and another user may bind this name first,
+    // so both sides check the peer's uid (see check_ui_tty_peer()).
+    let name = format!("\0nnd-ui-tty-{}", unsafe {libc::getuid()});
+    let mut addr: libc::sockaddr_un = unsafe {mem::zeroed()};
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (i, b) in name.bytes().enumerate() {
+        addr.sun_path[i] = b as libc::c_char;
+    }
+    (addr, (mem::size_of::<libc::sa_family_t>() + name.len()) as libc::socklen_t)
+}
+
+// Path of the terminal on our stdin, e.g. "/dev/pts/2".
+pub fn current_tty_path() -> Result<String> {
+    if unsafe {libc::isatty(libc::STDIN_FILENO)} != 1 {
+        return err!(Usage, "stdin is not a terminal");
+    }
+    Ok(fs::read_link("/proc/self/fd/0")?.to_string_lossy().into_owned())
+}
+
+// Replaces our stdin and stdout with the UI terminal. `path` is a tty path, or "-" to wait for `nnd --ui-here`.
+// Called before the terminal is configured and before stderr is redirected to the log file.
+pub fn take_over_ui_terminal(path: &str) -> Result<()> {
+    let fd = if path == "-" {
+        receive_ui_terminal()?
+    } else {
+        let c_path = format!("{}\0", path);
+        let fd = unsafe {libc::open(c_path.as_ptr() as *const libc::c_char, libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC)};
+        if fd < 0 { return errno_err!("failed to open '{}'", path); }
+        if unsafe {libc::isatty(fd)} != 1 {
+            unsafe {libc::close(fd)};
+            return err!(Usage, "'{}' is not a terminal", path);
+        }
+        fd
+    };
+    unsafe {
+        if libc::dup2(fd, libc::STDIN_FILENO) < 0 || libc::dup2(fd, libc::STDOUT_FILENO) < 0 { return errno_err!("dup2() failed"); }
+        libc::close(fd);
+
+        // Keyboard signals from the program's terminal go to its whole process group, which includes us. Ignore them, but with a handler
+        // rather than SIG_IGN, so that exec() resets them to default in the debuggee.
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = noop_signal_handler as *const () as libc::sighandler_t;
+        for sig in [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP] {
+            if libc::sigaction(sig, &action, ptr::null_mut()) != 0 { return errno_err!("sigaction() failed"); }
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn noop_signal_handler(_: i32) {}
+
+// Returns the peer's pid, or error if the peer is another user's process. Otherwise another user could lend us their terminal or, worse, receive ours.
+fn check_ui_tty_peer(sock: &OwnedFd) -> Result<libc::pid_t> {
+    unsafe {
+        let mut cred: libc::ucred = mem::zeroed();
+        let mut cred_len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+        if libc::getsockopt(sock.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut libc::c_void, &mut cred_len) != 0 { return errno_err!("getsockopt(SO_PEERCRED) failed"); }
+        if cred.uid != libc::getuid() {
+            return err!(Usage, "UI terminal socket peer belongs to another user (uid {}, pid {})", cred.uid, cred.pid);
+        }
+        Ok(cred.pid)
+    }
+}
+
+fn receive_ui_terminal() -> Result<i32> {
+    unsafe {
+        let sock = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if sock < 0 { return errno_err!("socket() failed"); }
+        let sock = OwnedFd::from_raw_fd(sock);
+        let (addr, len) = ui_tty_socket_address();
+        if libc::bind(sock.as_raw_fd(), &addr as *const libc::sockaddr_un as *const libc::sockaddr, len) != 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::EADDRINUSE) {
+                return err!(Usage, "another nnd is already waiting for a UI terminal");
+            }
+            return err!(Environment, "bind() failed: {}", e);
+        }
+        if libc::listen(sock.as_raw_fd(), 1) != 0 { return errno_err!("listen() failed"); }
+
+        eprintln!("waiting for UI terminal: run `nnd --ui-here` in another terminal");
+        let conn = loop {
+            let c = libc::accept4(sock.as_raw_fd(), ptr::null_mut(), ptr::null_mut(), libc::SOCK_CLOEXEC);
+            if c < 0 {
+                if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) { return errno_err!("accept() failed"); }
+                continue;
+            }
+            let c = OwnedFd::from_raw_fd(c);
+            match check_ui_tty_peer(&c) {
+                Ok(_) => break c,
+                Err(e) => eprintln!("warning: {}", e), // keep waiting for the right peer
+            }
+        };
+        drop(sock);
+
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {iov_base: &mut byte as *mut u8 as *mut libc::c_void, iov_len: 1};
+        let mut cmsg_buf = [0u64; 8];
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<i32>() as u32) as _;
+        if libc::recvmsg(conn.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) <= 0 { return errno_err!("failed to receive terminal from nnd --ui-here"); }
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_level != libc::SOL_SOCKET || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            return err!(ProcessState, "nnd --ui-here didn't send a terminal");
+        }
+        let fd = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const i32);
+
+        // Leave `conn` open until we exit; `nnd --ui-here` waits for it to close.
+        let _ = conn.into_raw_fd();
+        Ok(fd)
+    }
+}
+
+static UI_HERE_DEBUGGER_PID: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward_sigwinch_handler(_: i32) {
+    let pid = UI_HERE_DEBUGGER_PID.load(Ordering::Relaxed);
+    if pid > 0 {
+        unsafe {libc::kill(pid, libc::SIGWINCH)};
+    }
+}
+
+// `nnd --ui-here`: lend this terminal to an `nnd --ui-tty -` waiting in another terminal, and wait for it to exit.
+// We stay in the foreground of this terminal, so we get its SIGWINCH and forward it to the debugger.
+pub fn run_ui_here_tool() -> Result<()> {
+    if unsafe {libc::isatty(libc::STDIN_FILENO)} != 1 {
+        return err!(Usage, "stdin is not a terminal");
+    }
+    unsafe {
+        let sock = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if sock < 0 { return errno_err!("socket() failed"); }
+        let sock = OwnedFd::from_raw_fd(sock);
+        let (addr, len) = ui_tty_socket_address();
+        if libc::connect(sock.as_raw_fd(), &addr as *const libc::sockaddr_un as *const libc::sockaddr, len) != 0 {
+            return err!(Usage, "no nnd is waiting for a UI terminal (start one with --ui-tty - first): {}", io::Error::last_os_error());
+        }
+
+        let pid = check_ui_tty_peer(&sock)?;
+        UI_HERE_DEBUGGER_PID.store(pid, Ordering::Relaxed);
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = forward_sigwinch_handler as *const () as libc::sighandler_t;
+        if libc::sigaction(libc::SIGWINCH, &action, ptr::null_mut()) != 0 { return errno_err!("sigaction() failed"); }
+
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {iov_base: &mut byte as *mut u8 as *mut libc::c_void, iov_len: 1};
+        let mut cmsg_buf = [0u64; 8];
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<i32>() as u32) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<i32>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut i32, libc::STDIN_FILENO);
+        if libc::sendmsg(sock.as_raw_fd(), &msg, 0) != 1 { return errno_err!("failed to send terminal"); }
+
+        // Wait for the debugger to exit (EOF). Its input comes from our terminal directly, we just need to not read it.
+        loop {
+            let r = libc::read(sock.as_raw_fd(), &mut byte as *mut u8 as *mut libc::c_void, 1);
+            if r >= 0 || io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) { break; }
+        }
+    }
+    Ok(())
+}
+
 pub struct Terminal {
     prev_buffer: ScreenBuffer,
     temp_buffer: ScreenBuffer, // reuse buffer across frames
@@ -338,6 +338,22 @@ impl DebuggerUI {
                     let r = debugger.suspend();
                     report_result(&mut self.state, &r);
                 }
+                Some(KeyAction::SuspendThread) => {
+                    let r = debugger.suspend_one_thread(self.state.selected_thread);
+                    report_result(&mut self.state, &r);
+                }
+                Some(KeyAction::ResumeThread) => {
+                    let r = debugger.resume_one_thread(self.state.selected_thread);
+                    report_result(&mut self.state, &r);
+                }
+                Some(KeyAction::LockBreakpointsToThread) => {
+                    let tid = self.state.selected_thread;
+                    match debugger.lock_breakpoints_to_thread(tid) {
+                        Ok(true) => log!(debugger.log, "breakpoints locked to thread {}", tid),
+                        Ok(false) => log!(debugger.log, "breakpoints unlocked from thread {}", tid),
+                        r => report_result(&mut self.state, &r),
+                    }
+                }
                 Some(KeyAction::Kill) => {
                     let r = debugger.murder(libc::SIGKILL);
                     report_result(&mut self.state, &r);
@@ -350,6 +366,12 @@ impl DebuggerUI {
                         report_result(&mut self.state, &r);
                     }
                 }
+                Some(action @ (KeyAction::PreviousSnapshot | KeyAction::NextSnapshot)) => {
+                    let r = if *action == KeyAction::PreviousSnapshot {debugger.view_previous_snapshot()} else {debugger.view_next_snapshot()};
+                    report_result(&mut self.state, &r);
+                    // Everything that depends on registers or memory needs to be recalculated.
+                    self.should_drop_caches = true;
+                }
                 Some(KeyAction::Help) => open_help = true,
 
                 Some(KeyAction::DropCaches) => {
@@ -408,10 +430,16 @@ impl DebuggerUI {
         hints.push(KeyHint::key(KeyAction::ToggleProfiler, "profiler").hide());
         hints.push(KeyHint::key(KeyAction::DropCaches, "drop caches and redraw").hide());
         let state = debugger.target_state;
+        // Some threads are suspended while others run, e.g. in non-stop mode.
+        let any_held = state == ProcessState::Running && debugger.threads.values().any(|t| t.held);
+        let selected_held = state == ProcessState::Running && debugger.threads.get(&self.state.selected_thread).is_some_and(|t| t.held);
         hints.push(KeyHint::key(KeyAction::Suspend, "suspend").conditional(state == ProcessState::Running || state == ProcessState::Stepping, "if running or stepping"));
-        hints.push(KeyHint::key(KeyAction::Continue, "continue").conditional(state == ProcessState::Suspended || state == ProcessState::Stepping, "if suspended or stepping"));
-        hints.push(KeyHint::keys(&[KeyAction::StepIntoLine, KeyAction::StepOverLine, KeyAction::StepOut, KeyAction::StepOverColumn], "step into/over/out/column").conditional(state == ProcessState::Suspended, "if suspended"));
-        hints.push(KeyHint::keys(&[KeyAction::StepIntoInstruction, KeyAction::StepOverInstruction], "step into/over instruction").conditional(state == ProcessState::Suspended, "if suspended"));
+        hints.push(KeyHint::key(KeyAction::Continue, "continue").conditional(state == ProcessState::Suspended || state == ProcessState::Stepping || any_held, "if suspended or stepping"));
+        hints.push(KeyHint::keys(&[KeyAction::SuspendThread, KeyAction::ResumeThread], "suspend/resume thread").conditional(state.process_ready() && (debugger.context.settings.non_stop || any_held), "if process exists"));
+        hints.push(KeyHint::key(KeyAction::LockBreakpointsToThread, "lock/unlock breakpoints to thread").conditional(state.process_ready(), "if process exists"));
+        hints.push(KeyHint::keys(&[KeyAction::StepIntoLine, KeyAction::StepOverLine, KeyAction::StepOut, KeyAction::StepOverColumn], "step into/over/out/column").conditional(state == ProcessState::Suspended || selected_held, "if suspended"));
+        hints.push(KeyHint::keys(&[KeyAction::StepIntoInstruction, KeyAction::StepOverInstruction], "step into/over instruction").conditional(state == ProcessState::Suspended || selected_held, "if suspended"));
+        hints.push(KeyHint::keys(&[KeyAction::PreviousSnapshot, KeyAction::NextSnapshot], "view earlier stops").conditional(state == ProcessState::Suspended && debugger.context.settings.snapshots != 0, "if suspended"));
         let startable = state == ProcessState::NoProcess && debugger.mode == RunMode::Run;
         let startable_text = "if no process";
         hints.push(KeyHint::key(KeyAction::Run, "start").conditional(startable, startable_text));
@@ -525,6 +553,13 @@ fn report_result<R>(state: &mut UIState, r: &Result<R>) {
     }
 }
 
+fn report_next_statement_result(state: &mut UIState, r: Result<Option<String>>) {
+    match r {
+        Ok(Some(warning)) => state.last_error = warning,
+        r => report_result(state, &r),
+    }
+}
+
 fn get_breakpoint_icon(enabled: bool, active: bool, secondary: bool, conditional: bool, data: bool, stop_on_read: bool, palette: &Palette) -> (&'static str, Style) {
     if data {
         if stop_on_read {
@@ -705,7 +740,7 @@ struct WatchesWindow {
 
     eval_state: EvalState,
 
-    data_breakpoints: HashMap<(/*addr*/ usize, /*size*/ u8), (/*enabled*/ bool, /*active*/ bool, /*stop_on_read*/ bool, /*conditional*/ bool, /*hit*/ bool)>,
+    data_breakpoints: HashMap<(/*addr*/ usize, /*size*/ usize), (/*enabled*/ bool, /*active*/ bool, /*stop_on_read*/ bool, /*conditional*/ bool, /*hit*/ bool)>,
 
     variable_search_dialog: Option<SearchDialog>,
     type_search_dialog: Option<SearchDialog>,
@@ -725,6 +760,18 @@ impl WatchesWindow {
     const COLUMN_SPACING: usize = 3;
     
     fn eval_locals(&mut self, context: &mut EvalContext, parent: ValueTreeNodeIdx, palette: &Palette) {
+        if let Some(syscall) = &context.syscall {
+            // Stopped at a syscall breakpoint, show the decoded arguments first.
+            let value = self.eval_state.make_syscall_value(syscall);
+            let l = styled_writeln!(self.tree.text, palette.default, "#syscall");
+            self.tree.add(ValueTreeNode {name: l..l+1, value: Ok(value), identity: hash(&"#syscall"), parent, ..Default::default()});
+        }
+        if let Some(r) = context.function_return {
+            // Just stepped out of a function, show what it returned.
+            let value = self.eval_state.make_function_return_value(&r, context);
+            let l = styled_writeln!(self.tree.text, palette.default, "$ret");
+            self.tree.add(ValueTreeNode {name: l..l+1, value, identity: hash(&"$ret"), parent, ..Default::default()});
+        }
         let selected_subframe = context.selected_subframe;
         let subframe = &context.stack.subframes[selected_subframe];
         let pseudo_addr = context.stack.frames[subframe.frame_idx].pseudo_addr;
@@ -763,7 +810,7 @@ impl WatchesWindow {
             let regs = &context.stack.frames[sf.frame_idx].regs;
             for reg in RegisterIdx::all() {
                 if let Ok((v, dubious)) = regs.get(*reg) {
-                    let l = styled_writeln!(self.tree.text, palette.default, "{}", reg);
+                    let l = styled_writeln!(self.tree.text, palette.default, "{}", regs.arch.register_name(*reg));
                     let value = Value {val: AddrOrValueBlob::Blob(ValueBlob::new(v as usize)), type_: self.eval_state.builtin_types.u64_, flags: ValueFlags::HEX};
                     self.tree.add(ValueTreeNode {name: l..l+1, value: Ok(value), dubious, identity: hash(&reg), parent, ..D!()});
                 }
@@ -1099,7 +1146,7 @@ impl WatchesWindow {
                     }
                     ui.add(w);
 
-                    if let Ok(Some((addr, size))) = Self::get_value_addr_and_size_for_data_breakpoint(&node.value, Ok(context)) {
+                    if let Ok(Some((addr, size, _))) = Self::get_value_addr_and_size_for_data_breakpoint(&node.value, Ok(context)) {
                         if let Some(&(enabled, active, stop_on_read, conditional, hit)) = self.data_breakpoints.get(&(addr, size)) {
                             let l = if hit {
                                 ui_writeln!(ui, instruction_pointer, "{}", "⮕ ")
@@ -1309,25 +1356,28 @@ impl WatchesWindow {
         Ok(Some((addr, t)))
     }
 
-    fn get_value_addr_and_size_for_data_breakpoint(value: &Result<Value>, eval_context: std::result::Result<&mut EvalContext, &mut Debugger>) -> Result<Option<(usize, u8)>> {
+    fn get_value_addr_and_size_for_data_breakpoint(value: &Result<Value>, eval_context: std::result::Result<&mut EvalContext, &mut Debugger>) -> Result<Option<(usize, usize, *const TypeInfo)>> {
         match Self::get_value_addr_and_type(value, eval_context) {
             Err(e) => Err(e),
             Ok(None) => Ok(None),
             Ok(Some((addr, t))) => {
                 let size = unsafe {(*t).calculate_size()};
-                if [1, 2, 4, 8].contains(&size) {
-                    Ok(Some((addr, size as u8)))
-                } else {
-                    return err!(Usage, "unsupported size: {} bytes (must be 1, 2, 4, or 8)", size);
+                if size == 0 {
+                    return err!(Usage, "can't watch a 0-byte value");
                 }
+                Ok(Some((addr, size, t)))
             }
         }
     }
     
-    fn toggle_data_breakpoint(value: &Result<Value>, stop_on_read: bool, edit_condition: bool, state: &mut UIState, debugger: &mut Debugger, ui: &mut UI) -> Result<()> {
-        let (addr, size) = match Self::get_value_addr_and_size_for_data_breakpoint(value, Err(debugger))? {
+    // If edit_condition is true, stop_on_change of an existing breakpoint is kept as is.
+    fn toggle_data_breakpoint(value: &Result<Value>, stop_on_read: bool, stop_on_change: bool, edit_condition: bool, state: &mut UIState, debugger: &mut Debugger, ui: &mut UI) -> Result<()> {
+        let (addr, size, t) = match Self::get_value_addr_and_size_for_data_breakpoint(value, Err(debugger))? {
             Some(x) => x,
             None => return Ok(()) };
+        // For typing `old` and `new` in the condition.
+        let mut type_name = String::new();
+        let type_name = print_type_in_watch_language_syntax(t, &mut type_name, 0).ok().map(|()| type_name);
 
         ui.should_redraw = true;
 
@@ -1335,21 +1385,26 @@ impl WatchesWindow {
         for (id, breakpoint) in debugger.breakpoints.iter() {
             if let BreakpointOn::Data(d) = &breakpoint.on {
                 if d.addr == addr && d.size == size {
-                    existing = Some((id, d.stop_on_read));
+                    existing = Some((id, d.stop_on_read, d.stop_on_change));
                     break;
                 }
             }
         }
-        let id = if let Some((id, existing_stop_on_read)) = existing {
-            if stop_on_read != existing_stop_on_read {
+        let id = if let Some((id, existing_stop_on_read, existing_stop_on_change)) = existing {
+            let stop_on_change = if edit_condition {existing_stop_on_change} else {stop_on_change};
+            if let BreakpointOn::Data(d) = &mut debugger.breakpoints.get_mut(id).on {
+                d.type_name = type_name;
+            }
+            if stop_on_read != existing_stop_on_read || stop_on_change != existing_stop_on_change {
                 debugger.set_data_breakpoint_stop_on_read(id, stop_on_read)?;
+                debugger.set_data_breakpoint_stop_on_change(id, stop_on_change)?;
             } else if !edit_condition {
                 debugger.remove_breakpoint(id);
                 return Ok(());
             }
             id
         } else {
-            debugger.add_breakpoint(BreakpointOn::Data(DataBreakpoint {addr, size, stop_on_read}))?
+            debugger.add_breakpoint(BreakpointOn::Data(DataBreakpoint {addr, size, stop_on_read, stop_on_change, type_name, value: None, old_value: None}))?
         };
         if edit_condition {
             state.should_edit_breakpoint_condition = Some(id);
@@ -1417,7 +1472,7 @@ impl WindowContent for WatchesWindow {
         // Keyboard input.
         let mut refresh_data = self.tree.roots.is_empty();
         let (mut open_variable_search, mut open_type_search) = (false, false);
-        for action in ui.check_keys(&[KeyAction::Cancel, KeyAction::CursorRight, KeyAction::CursorLeft, KeyAction::Enter, KeyAction::DeleteRow, KeyAction::DuplicateRow, KeyAction::AddValueRefWatch, KeyAction::ReorderRowUp, KeyAction::ReorderRowDown, KeyAction::Open, KeyAction::FindType, KeyAction::DataWriteBreakpoint, KeyAction::DataReadWriteBreakpoint, KeyAction::ConditionalDataWriteBreakpoint, KeyAction::ConditionalDataReadWriteBreakpoint]) {
+        for action in ui.check_keys(&[KeyAction::Cancel, KeyAction::CursorRight, KeyAction::CursorLeft, KeyAction::Enter, KeyAction::DeleteRow, KeyAction::DuplicateRow, KeyAction::AddValueRefWatch, KeyAction::ReorderRowUp, KeyAction::ReorderRowDown, KeyAction::Open, KeyAction::FindType, KeyAction::DataWriteBreakpoint, KeyAction::DataReadWriteBreakpoint, KeyAction::ConditionalDataWriteBreakpoint, KeyAction::ConditionalDataReadWriteBreakpoint, KeyAction::DataChangeBreakpoint]) {
             self.scroll_to_cursor = true;
 
             if action == KeyAction::Cancel {
@@ -1506,10 +1561,11 @@ impl WindowContent for WatchesWindow {
                         self.cursor_path = vec![self.expressions[i.min(self.expressions.len()-1)].identity];
                     }
                 }
-                KeyAction::DataWriteBreakpoint | KeyAction::DataReadWriteBreakpoint | KeyAction::ConditionalDataWriteBreakpoint | KeyAction::ConditionalDataReadWriteBreakpoint => {
+                KeyAction::DataWriteBreakpoint | KeyAction::DataReadWriteBreakpoint | KeyAction::ConditionalDataWriteBreakpoint | KeyAction::ConditionalDataReadWriteBreakpoint | KeyAction::DataChangeBreakpoint => {
                     let stop_on_read = action == KeyAction::DataReadWriteBreakpoint || action == KeyAction::ConditionalDataReadWriteBreakpoint;
                     let conditional = action == KeyAction::ConditionalDataWriteBreakpoint || action == KeyAction::ConditionalDataReadWriteBreakpoint;
-                    let r = Self::toggle_data_breakpoint(&node.value, stop_on_read, conditional, state, debugger, ui);
+                    let stop_on_change = action == KeyAction::DataChangeBreakpoint;
+                    let r = Self::toggle_data_breakpoint(&node.value, stop_on_read, stop_on_change, conditional, state, debugger, ui);
                     report_result(state, &r);
                 }
                 KeyAction::Open => open_variable_search = true,
@@ -1679,6 +1735,18 @@ impl WindowContent for WatchesWindow {
             }
         }
 
+        if eval_context.modified_debuggee {
+            // An assignment wrote to debuggee's memory or registers. Registers and stack trace may have changed, redraw other windows.
+            // (Don't re-evaluate the watches, the assignment would be executed again.)
+            let events = mem::take(&mut eval_context.function_call_events);
+            debugger.refresh_after_modifying_debuggee(events);
+            ui.should_redraw = true;
+        }
+        if refresh_data {
+            // Let breakpoint conditions call functions defined in watches.
+            debugger.script_definitions = self.eval_state.definitions.clone();
+        }
+
         if let &Some((identity, _, _)) = &self.text_input {
             // (The second condition is not required because the text input will lose focus on next frame if cursor_path moved away from it. But it saves one frame of latency + one unnecessary redraw.)
             if !self.text_input_built || self.cursor_path.last() != Some(&identity) {
@@ -1708,6 +1776,7 @@ impl WindowContent for WatchesWindow {
             KeyHint::keys(&[KeyAction::Open, KeyAction::FindType], "find variable/type"),
             KeyHint::key(KeyAction::Tooltip, "tooltip"),
             KeyHint::keys(&[KeyAction::DataWriteBreakpoint, KeyAction::DataReadWriteBreakpoint, KeyAction::ConditionalDataWriteBreakpoint, KeyAction::ConditionalDataReadWriteBreakpoint], "data breakpoint w/rw/cond"),
+            KeyHint::key(KeyAction::DataChangeBreakpoint, "data breakpoint on value change"),
         ]);
     }
 
@@ -2275,8 +2344,13 @@ impl DisassemblyWindow {
                 state.selected_addr = Some((binary.id, function_idx, cursor_addr));
             }
 
-            for action in ui.check_keys(&[KeyAction::Enter, KeyAction::DeleteRow, KeyAction::EditCondition, KeyAction::StepToCursor, KeyAction::PreviousLocation, KeyAction::NextLocation]) {
+            for action in ui.check_keys(&[KeyAction::Enter, KeyAction::DeleteRow, KeyAction::EditCondition, KeyAction::StepToCursor, KeyAction::SetNextStatement, KeyAction::PreviousLocation, KeyAction::NextLocation]) {
                 match action {
+                    KeyAction::SetNextStatement if cursor_addr != usize::MAX => {
+                        ui.should_redraw = true;
+                        let r = debugger.set_next_statement(state.selected_thread, BreakpointOn::Instruction(InstructionBreakpoint {function: None, addr: cursor_addr, subfunction_level: SUBFUNCTION_LEVEL_MAX}));
+                        report_next_statement_result(state, r);
+                    }
                     KeyAction::Enter | KeyAction::DeleteRow | KeyAction::EditCondition | KeyAction::StepToCursor if cursor_addr != usize::MAX => {
                         let offset = cursor_static_addr - function.addr.0;
                         // TODO: For function entry breakpoints use entry_pc instead of start of first range. Figure out how to indicate it in the UI nicely.
@@ -2638,6 +2712,7 @@ impl WindowContent for DisassemblyWindow {
             KeyHint::keys(&[KeyAction::ReorderRowUp, KeyAction::ReorderRowDown], "reorder tabs"),
             KeyHint::keys(&[KeyAction::Enter, KeyAction::DeleteRow, KeyAction::EditCondition], "breakpoint").if_not_core_dump(),
             KeyHint::key(KeyAction::StepToCursor, "run to cursor").if_not_core_dump(),
+            KeyHint::key(KeyAction::SetNextStatement, "jump to cursor").if_not_core_dump(),
         ]);
     }
 
@@ -2724,21 +2799,33 @@ impl WindowContent for StatusWindow {
             ProcessState::Stepping if debugger.stopped_until_symbols_are_loaded.is_some() => ui_writeln!(ui, state_other, "loading symbols ({}%), then stepping", symbols_progress_pct),
             ProcessState::Stepping => ui_writeln!(ui, state_other, "stepping"),
             ProcessState::Running if debugger.stopped_until_symbols_are_loaded.is_some() => ui_writeln!(ui, state_other, "loading symbols ({}%), then running", symbols_progress_pct),
-            ProcessState::Running => ui_writeln!(ui, state_running, "running"),
+            ProcessState::Running => match debugger.threads.values().filter(|t| t.held).count() {
+                0 => ui_writeln!(ui, state_running, "running"),
+                n => ui_writeln!(ui, state_running, "running ({} of {} threads suspended)", n, debugger.threads.len()),
+            }
             ProcessState::Suspended | ProcessState::CoreDump => {
                 let mut stop_reason: (isize, Option<StopReason>) = (-1, None);
-                for (tid, thread) in &debugger.threads {
-                    for reason in &thread.stop_reasons {
+                let mut snapshot_label = String::new();
+                let mut consider_reasons = |reasons: &Vec<StopReason>| {
+                    for reason in reasons {
                         let p = reason.priority();
                         if p > stop_reason.0 {
                             stop_reason = (p, Some(reason.clone()));
                         }
                     }
+                };
+                match debugger.snapshots.viewing {
+                    Some(idx) => {
+                        snapshot_label = format!("snapshot {}/{} (read-only)", idx + 1, debugger.snapshots.list.len());
+                        debugger.snapshots.list[idx].threads.values().for_each(|t| consider_reasons(&t.stop_reasons));
+                    }
+                    None => debugger.threads.values().for_each(|t| consider_reasons(&t.stop_reasons)),
                 }
-                let state = if debugger.target_state == ProcessState::Suspended {"suspended"} else {"core dump"};
+                let state = if !snapshot_label.is_empty() {&snapshot_label} else if debugger.target_state == ProcessState::Suspended {"suspended"} else {"core dump"};
                 match stop_reason.1 {
                     None => ui_writeln!(ui, state_suspended, "{}", state),
                     Some(StopReason::Breakpoint(_)) => ui_writeln!(ui, state_suspended, "{} (hit breakpoint)", state),
+                    Some(StopReason::DataChange(_, old, new)) => ui_writeln!(ui, state_suspended, "{} (value changed: {})", state, format_data_change(&old, &new)),
                     Some(StopReason::DebugTrap) => ui_writeln!(ui, state_suspended, "{} (debug trap)", state),
                     Some(StopReason::Step) => ui_writeln!(ui, state_suspended, "{} (stepped)", state),
                     Some(StopReason::Exception) => ui_writeln!(ui, state_suspended, "{} (exception during step)", state),
@@ -2755,6 +2842,9 @@ impl WindowContent for StatusWindow {
             _ => {
                 ui_write!(ui, default_dim, "pid: ");
                 ui_write!(ui, default, "{}", debugger.pid);
+                if !debugger.forked_processes.is_empty() {
+                    ui_write!(ui, default_dim, " +{} forked", debugger.forked_processes.len());
+                }
                 ui_writeln!(ui, default_dim, " cpu {:.0}% mem {}", debugger.info.total_resource_stats.cpu_percentage(debugger.context.settings.periodic_timer_ns), PrettySize(debugger.info.total_resource_stats.latest.rss_bytes()));
             }
         }
@@ -3331,10 +3421,12 @@ impl WindowContent for ThreadsWindow {
         ui.layout_children(Axis::Y);
 
         let have_stats = debugger.mode != RunMode::CoreDump;
+        let multiple_processes = !debugger.forked_processes.is_empty();
         let mut table = with_parent!(ui, table_widget, {
             Table::new(mem::take(&mut self.table_state), ui, vec![
                 Column::new("idx", AutoSize::Fixed(5), true),
                 Column::new("tid", AutoSize::Fixed(10), true),
+                Column::new("pid", AutoSize::Fixed(10), true).with_hidden(!multiple_processes),
                 Column::new("name", AutoSize::Fixed(15), true),
                 Column::new("s", AutoSize::Fixed(2), true).with_hidden(!have_stats),
                 Column::new("cpu", AutoSize::Fixed(4), true).with_hidden(!have_stats),
@@ -3358,14 +3450,16 @@ impl WindowContent for ThreadsWindow {
                 (0, true ) => threads.sort_unstable_by_key(|t| !t.idx),
                 (1, false) => threads.sort_unstable_by_key(|t|  t.tid),
                 (1, true ) => threads.sort_unstable_by_key(|t| !t.tid),
-                (2, false) => threads.sort_unstable_by_key(|t| (                  t.info.resource_stats.latest.comm() , t.idx)),
-                (2, true ) => threads.sort_unstable_by_key(|t| (std::cmp::Reverse(t.info.resource_stats.latest.comm()), t.idx)),
-                (3, false) => threads.sort_unstable_by_key(|t| (                  t.info.resource_stats.latest.state , t.tid)),
-                (3, true ) => threads.sort_unstable_by_key(|t| (std::cmp::Reverse(t.info.resource_stats.latest.state), t.tid)),
-                (4, false) => threads.sort_unstable_by_key(|t| ( (t.info.resource_stats.cpu_percentage(debugger.context.settings.periodic_timer_ns) * 1000.0) as isize, t.idx)),
-                (4, true ) => threads.sort_unstable_by_key(|t| (-(t.info.resource_stats.cpu_percentage(debugger.context.settings.periodic_timer_ns) * 1000.0) as isize, t.idx)),
-                (6, false) => threads.sort_unstable_by_key(|t| ( t.info.regs.get(RegisterIdx::Rip).map_or(0, |(r, _)| r), t.idx)),
-                (6, true ) => threads.sort_unstable_by_key(|t| (!t.info.regs.get(RegisterIdx::Rip).map_or(0, |(r, _)| r), t.idx)),
+                (2, false) => threads.sort_unstable_by_key(|t| ( t.pid, t.idx)),
+                (2, true ) => threads.sort_unstable_by_key(|t| (!t.pid, t.idx)),
+                (3, false) => threads.sort_unstable_by_key(|t| (                  t.info.resource_stats.latest.comm() , t.idx)),
+                (3, true ) => threads.sort_unstable_by_key(|t| (std::cmp::Reverse(t.info.resource_stats.latest.comm()), t.idx)),
+                (4, false) => threads.sort_unstable_by_key(|t| (                  t.info.resource_stats.latest.state , t.tid)),
+                (4, true ) => threads.sort_unstable_by_key(|t| (std::cmp::Reverse(t.info.resource_stats.latest.state), t.tid)),
+                (5, false) => threads.sort_unstable_by_key(|t| ( (t.info.resource_stats.cpu_percentage(debugger.context.settings.periodic_timer_ns) * 1000.0) as isize, t.idx)),
+                (5, true ) => threads.sort_unstable_by_key(|t| (-(t.info.resource_stats.cpu_percentage(debugger.context.settings.periodic_timer_ns) * 1000.0) as isize, t.idx)),
+                (7, false) => threads.sort_unstable_by_key(|t| ( t.info.regs.get(t.info.regs.arch.pc()).map_or(0, |(r, _)| r), t.idx)),
+                (7, true ) => threads.sort_unstable_by_key(|t| (!t.info.regs.get(t.info.regs.arch.pc()).map_or(0, |(r, _)| r), t.idx)),
                 _ => (),
             }
         }
@@ -3446,6 +3540,9 @@ impl WindowContent for ThreadsWindow {
             ui_writeln!(ui, default_dim, "{}", t.tid);
             table.text_cell(ui);
 
+            ui_writeln!(ui, default_dim, "{}", t.pid);
+            table.text_cell(ui);
+
             if let Some(e) = &t.info.resource_stats.error {
                 ui_writeln!(ui, error, "{}", e);
             } else {
@@ -3481,6 +3578,10 @@ impl WindowContent for ThreadsWindow {
                 ThreadState::Suspended if !stack.frames.is_empty() => {
                     let f = &stack.frames[0];
                     let sf = &stack.subframes[0];
+                    if matches!(debugger.target_state, ProcessState::Running | ProcessState::Stepping) {
+                        // The other threads are running (e.g. non-stop mode), make it clear which ones aren't.
+                        ui_write!(ui, state_suspended, "[suspended] ");
+                    }
                     if sf.function_idx.is_ok() {
                         ui_writeln!(ui, function_name, "{}", sf.function_name);
                     } else {
@@ -3519,7 +3620,7 @@ impl WindowContent for ThreadsWindow {
                         style_adjustment = ui.palette.thread_crash;
                         break;
                     }
-                    StopReason::Breakpoint(_) | StopReason::DebugTrap => style_adjustment = ui.palette.thread_breakpoint_hit,
+                    StopReason::Breakpoint(_) | StopReason::DataChange(..) | StopReason::DebugTrap => style_adjustment = ui.palette.thread_breakpoint_hit,
                     StopReason::Step | StopReason::Exception => (),
                 }
             }
@@ -3786,27 +3887,6 @@ fn rust_fake_path_to_url(path: &str) -> Option<String> {
     res.push_str(&path[7..]);
     Some(res)
 }
-// Rust function names in debug info look like this: nnd::pretty::prettify_value::hf586ae10d8ed2bb6
-// Remove that last suffix.
-fn rust_remove_function_name_hash_suffix(name: &mut &str) {
-    if name.len() < 19 {
-        return;
-    }
-    let i = name.len() - 19;
-    if !name.is_char_boundary(i) {
-        return;
-    }
-    let suf = &name[i..];
-    if !suf.starts_with("::h") {
-        return;
-    }
-    let suf = &suf[3..];
-    if suf.chars().any(|c| !c.is_ascii_hexdigit()) {
-        return;
-    }
-    *name = &name[..i];
-}
-
 #[derive(Default)]
 struct CodeTab {
     title: String,
@@ -4204,6 +4284,15 @@ impl CodeWindow {
         report_result(state, &r);
     }
 
+    fn set_next_statement(tab: &CodeTab, state: &mut UIState, debugger: &mut Debugger, ui: &mut UI) {
+        if tab.path_in_symbols.as_os_str().is_empty() {
+            return;
+        }
+        ui.should_redraw = true;
+        let r = debugger.set_next_statement(state.selected_thread, BreakpointOn::Line(Self::make_breakpoint_for_current_line(tab)));
+        report_next_statement_result(state, r);
+    }
+
     fn make_breakpoint_for_current_line(tab: &CodeTab) -> LineBreakpoint {
         LineBreakpoint {path: tab.path_in_symbols.clone(), line: tab.area_state.cursor + 1, adjusted_line: None}
     }
@@ -4386,12 +4475,13 @@ impl CodeWindow {
         let file = Self::find_or_open_file(&mut self.file_cache, &tab.path_in_symbols, &tab.version_in_symbols, debugger, &ui.palette);
 
         let mut select_disassembly_address: isize = 0;
-        for action in ui.check_keys(&[KeyAction::Enter, KeyAction::EditCondition, KeyAction::DeleteRow, KeyAction::PreviousLocation, KeyAction::NextLocation, KeyAction::StepToCursor]) {
+        for action in ui.check_keys(&[KeyAction::Enter, KeyAction::EditCondition, KeyAction::DeleteRow, KeyAction::PreviousLocation, KeyAction::NextLocation, KeyAction::StepToCursor, KeyAction::SetNextStatement]) {
             match action {
                 KeyAction::PreviousLocation => select_disassembly_address -= 1,
                 KeyAction::NextLocation => select_disassembly_address += 1,
                 KeyAction::Enter | KeyAction::DeleteRow | KeyAction::EditCondition => Self::toggle_breakpoint(tab, action == KeyAction::DeleteRow, action == KeyAction::EditCondition, state, debugger, ui),
                 KeyAction::StepToCursor => Self::step_to_cursor(tab, state, debugger, ui),
+                KeyAction::SetNextStatement => Self::set_next_statement(tab, state, debugger, ui),
                 _ => (),
             }
         }
@@ -4485,7 +4575,7 @@ impl CodeWindow {
         let mut breakpoint_lines: Vec<BreakpointLine> = Vec::new();
         for (id, breakpoint) in debugger.breakpoints.iter() {
             match &breakpoint.on {
-                BreakpointOn::Line(bp) if bp.path == tab.path_in_symbols => {
+                BreakpointOn::Line(bp) if bp.matches_path(&tab.path_in_symbols) => {
                     let conditional = breakpoint.condition.is_some();
                     breakpoint_lines.push(BreakpointLine {line: bp.line, id, enabled: breakpoint.enabled, has_locations: false, active: breakpoint.active, adjusted: false, conditional});
                     if let Some(&adj) = bp.adjusted_line.as_ref() {
@@ -4667,6 +4757,7 @@ impl WindowContent for CodeWindow {
             KeyHint::keys(&[KeyAction::Enter, KeyAction::DeleteRow, KeyAction::EditCondition], "toggle/delete/edit breakpoint").if_not_core_dump(),
             KeyHint::keys(&[KeyAction::ReorderRowUp, KeyAction::ReorderRowDown], "reorder tabs"),
             KeyHint::key(KeyAction::StepToCursor, "run to cursor").if_not_core_dump(),
+            KeyHint::key(KeyAction::SetNextStatement, "jump to cursor").if_not_core_dump(),
         ]);
     }
 
@@ -4700,11 +4791,27 @@ impl WindowContent for CodeWindow {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BreakpointTextInput {
+    Condition,
+    Signals,
+    Thread,
+    Log,
+    IgnoreCount,
+}
+
 #[derive(Default)]
 struct BreakpointsWindow {
     table_state: TableState,
     selected_breakpoint: Option<BreakpointId>,
     condition_input: Option<(BreakpointId, TextInput)>,
+    // Editing a signal breakpoint's signals and policy, a syscall breakpoint's syscalls, or a function breakpoint's pattern. None id means adding a new one, in an extra row at the end of the table.
+    signal_input: Option<(Option<BreakpointId>, TextInput)>,
+    // Editing the thread filter: tid or #index.
+    thread_input: Option<(BreakpointId, TextInput)>,
+    // Editing the format string of a log-only breakpoint.
+    log_input: Option<(BreakpointId, TextInput)>,
+    ignore_input: Option<(BreakpointId, TextInput)>,
     initialized: bool,
 }
 impl WindowContent for BreakpointsWindow {
@@ -4712,6 +4819,12 @@ impl WindowContent for BreakpointsWindow {
         out.extend([
             KeyHint::key(KeyAction::DeleteRow, "delete breakpoint"),
             KeyHint::keys(&[KeyAction::Enter, KeyAction::EditCondition], "enable/disable/edit breakpoint"),
+            KeyHint::key(KeyAction::SignalBreakpoint, "add/edit signal/syscall/function breakpoint"),
+            KeyHint::key(KeyAction::EditBreakpointThread, "restrict to thread"),
+            KeyHint::key(KeyAction::EditBreakpointLog, "log instead of stopping"),
+            KeyHint::key(KeyAction::EditBreakpointIgnoreCount, "ignore next N hits"),
+            KeyHint::key(KeyAction::ToggleBreakpointDisableAfterHit, "disable after hit"),
+            KeyHint::key(KeyAction::DataChangeBreakpoint, "data: stop only on value change"),
             KeyHint::key(KeyAction::Tooltip, "tooltip"),
         ]);
     }
@@ -4729,7 +4842,7 @@ impl WindowContent for BreakpointsWindow {
         state.hit_breakpoints.clear();
         for (tid, thread) in &debugger.threads {
             for reason in &thread.stop_reasons {
-                if let &StopReason::Breakpoint(id) = reason {
+                if let Some(id) = reason.breakpoint_id() {
                     state.hit_breakpoints.push(id);
                 }
             }
@@ -4752,9 +4865,38 @@ impl WindowContent for BreakpointsWindow {
             start_editing_condition = true;
         }
 
-        for action in ui.check_keys(&[KeyAction::DeleteRow, KeyAction::Enter, KeyAction::EditCondition, KeyAction::Cancel]) {
+        let mut start_editing_signals = false;
+        let mut start_editing_thread = false;
+        let mut start_editing_log = false;
+        let mut start_editing_ignore_count = false;
+        for action in ui.check_keys(&[KeyAction::DeleteRow, KeyAction::Enter, KeyAction::EditCondition, KeyAction::Cancel, KeyAction::SignalBreakpoint, KeyAction::EditBreakpointThread, KeyAction::EditBreakpointLog, KeyAction::EditBreakpointIgnoreCount, KeyAction::ToggleBreakpointDisableAfterHit, KeyAction::DataChangeBreakpoint]) {
             if action == KeyAction::Cancel {
                 self.condition_input = None;
+                self.signal_input = None;
+                self.thread_input = None;
+                self.log_input = None;
+                self.ignore_input = None;
+            }
+            if action == KeyAction::SignalBreakpoint {
+                start_editing_signals = true;
+                continue;
+            }
+            if action == KeyAction::Enter && self.signal_input.as_ref().is_some_and(|(target, _)| *target == self.selected_breakpoint) {
+                let (target, input) = mem::take(&mut self.signal_input).unwrap();
+                match BreakpointOn::parse_typed(&input.text) {
+                    Ok(on) => {
+                        let r = match target {
+                            None => debugger.add_breakpoint(on).map(|id| self.selected_breakpoint = Some(id)),
+                            Some(id) => debugger.set_typed_breakpoint(id, on),
+                        };
+                        report_result(state, &r);
+                    }
+                    Err(e) => {
+                        report_result(state, &Err::<(), Error>(e));
+                        self.signal_input = Some((target, input));
+                    }
+                }
+                continue;
             }
             let id = match &self.selected_breakpoint {
                 None => continue,
@@ -4772,7 +4914,53 @@ impl WindowContent for BreakpointsWindow {
                     let condition = if text.is_empty() {None} else {Some(text)};
                     debugger.set_breakpoint_condition(id, condition);
                 }
-                KeyAction::EditCondition => start_editing_condition = true,
+                KeyAction::Enter if self.thread_input.as_ref().is_some_and(|(idd, _)| *idd == id) => {
+                    let input = mem::take(&mut self.thread_input).unwrap().1;
+                    match debugger.parse_thread_filter(&input.text) {
+                        Ok(thread) => {
+                            let r = debugger.set_breakpoint_thread(id, thread);
+                            report_result(state, &r);
+                        }
+                        Err(e) => {
+                            report_result(state, &Err::<(), Error>(e));
+                            self.thread_input = Some((id, input));
+                        }
+                    }
+                }
+                KeyAction::Enter if self.log_input.as_ref().is_some_and(|(idd, _)| *idd == id) => {
+                    let text = mem::take(&mut self.log_input).unwrap().1.text;
+                    debugger.set_breakpoint_log(id, if text.is_empty() {None} else {Some(text)});
+                }
+                KeyAction::Enter if self.ignore_input.as_ref().is_some_and(|(idd, _)| *idd == id) => {
+                    let input = mem::take(&mut self.ignore_input).unwrap().1;
+                    let text = input.text.trim();
+                    match if text.is_empty() {Ok(0)} else {text.parse::<usize>()} {
+                        Ok(n) => debugger.set_breakpoint_ignore_count(id, n),
+                        Err(_) => {
+                            let r: Result<()> = err!(Usage, "expected number of hits to ignore, got '{}'", text);
+                            report_result(state, &r);
+                            self.ignore_input = Some((id, input));
+                        }
+                    }
+                }
+                KeyAction::EditCondition => {
+                    start_editing_condition = true;
+                    self.signal_input = None;
+                    self.thread_input = None;
+                    self.log_input = None;
+                    self.ignore_input = None;
+                }
+                KeyAction::EditBreakpointThread => start_editing_thread = true,
+                KeyAction::EditBreakpointLog => start_editing_log = true,
+                KeyAction::EditBreakpointIgnoreCount => start_editing_ignore_count = true,
+                KeyAction::ToggleBreakpointDisableAfterHit => {
+                    let disable = !breakpoint.disable_after_hit;
+                    debugger.set_breakpoint_disable_after_hit(id, disable);
+                }
+                KeyAction::DataChangeBreakpoint => if let BreakpointOn::Data(d) = &breakpoint.on {
+                    let r = debugger.set_data_breakpoint_stop_on_change(id, !d.stop_on_change);
+                    report_result(state, &r);
+                }
                 KeyAction::Enter => {
                     let enable = !breakpoint.enabled;
                     let r = debugger.set_breakpoint_enabled(id, enable);
@@ -4790,12 +4978,71 @@ impl WindowContent for BreakpointsWindow {
                 }
             }
         }
+        if start_editing_thread {
+            if let &Some(id) = &self.selected_breakpoint {
+                if let Some(breakpoint) = debugger.breakpoints.try_get(id) {
+                    let text = breakpoint.thread.map_or(String::new(), |t| format!("{}", t));
+                    self.thread_input = Some((id, TextInput::new_with_text(text)));
+                    self.condition_input = None;
+                    self.signal_input = None;
+                    self.log_input = None;
+                    self.ignore_input = None;
+                }
+            }
+        }
+        if start_editing_log {
+            if let &Some(id) = &self.selected_breakpoint {
+                if let Some(breakpoint) = debugger.breakpoints.try_get(id) {
+                    let text = breakpoint.log.as_ref().map_or(String::new(), |(s, _)| s.clone());
+                    self.log_input = Some((id, TextInput::new_with_text(text)));
+                    self.condition_input = None;
+                    self.signal_input = None;
+                    self.thread_input = None;
+                    self.ignore_input = None;
+                }
+            }
+        }
+        if start_editing_ignore_count {
+            if let &Some(id) = &self.selected_breakpoint {
+                if let Some(breakpoint) = debugger.breakpoints.try_get(id) {
+                    let text = if breakpoint.ignore_count == 0 {String::new()} else {format!("{}", breakpoint.ignore_count)};
+                    self.ignore_input = Some((id, TextInput::new_with_text(text)));
+                    self.condition_input = None;
+                    self.signal_input = None;
+                    self.thread_input = None;
+                    self.log_input = None;
+                }
+            }
+        }
+        if start_editing_signals {
+            self.condition_input = None;
+            self.thread_input = None;
+            self.log_input = None;
+            self.ignore_input = None;
+            // Edit the selected signal or syscall breakpoint, or add a new one.
+            let existing = self.selected_breakpoint.and_then(|id| match &debugger.breakpoints.try_get(id)?.on {
+                BreakpointOn::Signal(s) => Some((id, format!("{} {}", s.signals_str(), s.policy_str()))),
+                BreakpointOn::Syscall(s) => Some((id, format!("syscall {}", s.syscalls_str()))),
+                BreakpointOn::Function(f) => Some((id, format!("function {}", f.pattern))),
+                _ => None });
+            self.signal_input = Some(match existing {
+                Some((id, text)) => (Some(id), TextInput::new_with_text(text)),
+                None => {
+                    self.selected_breakpoint = None;
+                    (None, TextInput::new_with_text(String::new()))
+                }
+            });
+        }
+        let adding_signal_breakpoint = self.signal_input.as_ref().is_some_and(|(target, _)| target.is_none());
 
         // (This has to be after the potential remove_breakpoint() above.)
         let mut breakpoints: Vec<BreakpointId> = debugger.breakpoints.iter().filter(|(_, b)| !b.hidden).map(|p| p.0).collect();
         breakpoints.sort_by_key(|id| id.seqno);
 
-        if let &Some(id) = &self.selected_breakpoint {
+        if adding_signal_breakpoint {
+            table.state.cursor = breakpoints.len();
+            table.state.scroll_to_cursor |= start_editing_signals;
+        } else if let &Some(id) = &self.selected_breakpoint {
             if let Some(idx) = breakpoints.iter().position(|b| b == &id) {
                 table.state.cursor = idx;
             }
@@ -4811,7 +5058,7 @@ impl WindowContent for BreakpointsWindow {
         }
         locations.sort_unstable_by_key(|(id, _, _)| *id);
 
-        let mut text_input_parent_widget = None;
+        let mut text_input_parent_widget: Option<(WidgetIdx, BreakpointTextInput)> = None;
         for &id in &breakpoints {
             let b = debugger.breakpoints.get(id);
             let is_hit = state.hit_breakpoints.binary_search(&id).is_ok();
@@ -4834,7 +5081,8 @@ impl WindowContent for BreakpointsWindow {
                 }
             }
             let error = error;
-            let error_is_benign = b.builtin && error.as_ref().is_some_and(|e| e.is_no_function());
+            let is_pending = error.as_ref().is_some_and(|e| e.is_pending());
+            let error_is_benign = (b.builtin && error.as_ref().is_some_and(|e| e.is_no_function())) || is_pending;
 
             let row_widget = table.start_row(id.seqno, ui);
 
@@ -4875,7 +5123,39 @@ impl WindowContent for BreakpointsWindow {
                 }
                 BreakpointOn::InitialExec => ui_write!(ui, default, "early during startup"),
                 BreakpointOn::PointOfInterest(point) => ui_write!(ui, default, "{}", point.name_for_ui()),
-                BreakpointOn::Data(on) => ui_write!(ui, default, "0x{:x} ({}, {} bytes)", on.addr, if on.stop_on_read {"rw"} else {"w"}, on.size),
+                BreakpointOn::Data(on) => {
+                    ui_write!(ui, default, "0x{:x} ({}, {} bytes)", on.addr, if on.stop_on_read {"rw"} else {"w"}, on.size);
+                    if on.stop_on_change {
+                        ui_write!(ui, default_dim, " [on change]");
+                    }
+                    if debugger.is_data_breakpoint_page_trapped(id) {
+                        ui_write!(ui, default_dim, " [page protection]");
+                    }
+                }
+                BreakpointOn::Signal(on) => {
+                    ui_write!(ui, default, "signal {}", on.signals_str());
+                    ui_write!(ui, default_dim, " ({})", on.policy_str());
+                }
+                BreakpointOn::Syscall(on) => ui_write!(ui, default, "syscall {}", on.syscalls_str()),
+                BreakpointOn::Function(on) => {
+                    ui_write!(ui, default, "function ");
+                    ui_write!(ui, function_name, "{}", on.pattern);
+                }
+            }
+            if let &Some(tid) = &b.thread {
+                match debugger.threads.get(&tid) {
+                    Some(t) => ui_write!(ui, default_dim, " [thread {} #{}]", tid, t.idx),
+                    None => ui_write!(ui, error, " [thread {} exited]", tid),
+                }
+            }
+            if b.ignore_count > 0 {
+                ui_write!(ui, default_dim, " [ignore {}]", b.ignore_count);
+            }
+            if b.disable_after_hit {
+                ui_write!(ui, default_dim, " [once]");
+            }
+            if is_pending {
+                ui_write!(ui, warning, " [pending]");
             }
             let row_is_focused = ui.check_focus();
             let l = ui.text.close_line();
@@ -4884,8 +5164,34 @@ impl WindowContent for BreakpointsWindow {
 
                 ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::TEXT_TRUNCATION_ALIGN_RIGHT));
 
+                if (row_is_focused || start_editing_signals) && self.signal_input.as_ref().is_some_and(|(idd, _)| *idd == Some(id)) {
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::Signals));
+                }
+                if (row_is_focused || start_editing_thread) && self.thread_input.as_ref().is_some_and(|(idd, _)| *idd == id) {
+                    let l = ui_writeln!(ui, default_dim, "thread: tid or #index, empty for any thread");
+                    ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::Thread));
+                }
+                if (row_is_focused || start_editing_ignore_count) && self.ignore_input.as_ref().is_some_and(|(idd, _)| *idd == id) {
+                    let l = ui_writeln!(ui, default_dim, "ignore: number of hits to skip, empty for none");
+                    ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::IgnoreCount));
+                }
+                if (row_is_focused || start_editing_log) && self.log_input.as_ref().is_some_and(|(idd, _)| *idd == id) {
+                    let l = ui_writeln!(ui, default_dim, "log: text with {{expressions}}, empty to stop instead");
+                    ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::Log));
+                } else if let Some((text, parts)) = &b.log {
+                    ui_write!(ui, default_dim, "log: ");
+                    let l = ui_writeln!(ui, default, "{}", text);
+                    ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                    if let Err(e) = parts {
+                        let l = ui_writeln!(ui, error, "{}", e);
+                        ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                    }
+                }
                 if (row_is_focused || start_editing_condition) && self.condition_input.as_ref().is_some_and(|(idd, _)| *idd == id) {
-                    text_input_parent_widget = Some(ui.cur_parent);
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::Condition));
                 } else if let Some((text, expr, err)) = &b.condition {
                     let l = ui_writeln!(ui, default, "{}", text);
                     let lines = ui.text.split_by_newline_character(l, None);
@@ -4912,7 +5218,7 @@ impl WindowContent for BreakpointsWindow {
                 }
             });
 
-            if b.enabled && !b.on.is_data() {
+            if b.enabled && !b.on.is_data() && !b.on.is_signal() && !b.on.is_syscall() {
                 ui_writeln!(ui, default_dim, "{}", locs_end - locs_begin);
             } else {
                 ui.text.close_line();
@@ -4931,18 +5237,60 @@ impl WindowContent for BreakpointsWindow {
             }
         }
 
+        if adding_signal_breakpoint {
+            table.start_row(usize::MAX, ui);
+            ui.text.close_line();
+            table.text_cell(ui);
+            ui.text.close_line();
+            table.text_cell(ui);
+            let row_is_focused = ui.check_focus();
+            with_parent!(ui, table.start_cell(ui), {
+                ui.cur_mut().set_vstack();
+                let l = ui_writeln!(ui, default_dim, "e.g. SIGUSR1 SIGPIPE nostop log pass, or: syscall openat mmap, or: function foo::bar (also *substring* or /regex/), or: foo.cpp:42");
+                ui.add(widget!().height(AutoSize::Text).text(l).flags(WidgetFlags::LINE_WRAP));
+                if row_is_focused || start_editing_signals {
+                    text_input_parent_widget = Some((ui.cur_parent, BreakpointTextInput::Signals));
+                }
+            });
+            ui.text.close_line();
+            table.text_cell(ui);
+            ui.text.close_line();
+            table.text_cell(ui);
+        }
+
         table.finish_horizontal_layout(ui);
-        if let Some(input_parent_widget) = text_input_parent_widget {
+        if let Some((input_parent_widget, which)) = text_input_parent_widget {
             // Build the text input once we know the table column width.
             let input_widget = ui.add(widget!().parent(input_parent_widget).identity(&'i').height(AutoSize::Children));
             with_parent!(ui, input_widget, {
-                let input = &mut self.condition_input.as_mut().unwrap().1;
+                let input = match which {
+                    BreakpointTextInput::Condition => &mut self.condition_input.as_mut().unwrap().1,
+                    BreakpointTextInput::Signals => &mut self.signal_input.as_mut().unwrap().1,
+                    BreakpointTextInput::Thread => &mut self.thread_input.as_mut().unwrap().1,
+                    BreakpointTextInput::Log => &mut self.log_input.as_mut().unwrap().1,
+                    BreakpointTextInput::IgnoreCount => &mut self.ignore_input.as_mut().unwrap().1,
+                };
                 let changed = input.build(ui);
                 table.state.scroll_to_cursor |= changed;
             });
-        } else {
+        }
+        // Drop the inputs that are not shown.
+        let which = text_input_parent_widget.map(|(_, w)| w);
+        if which != Some(BreakpointTextInput::Condition) {
             self.condition_input = None;
         }
+        if which != Some(BreakpointTextInput::Signals) {
+            self.signal_input = None;
+        }
+        if which != Some(BreakpointTextInput::Thread) {
+            self.thread_input = None;
+        }
+        if which != Some(BreakpointTextInput::Log) {
+            self.log_input = None;
+        }
+        if which != Some(BreakpointTextInput::IgnoreCount) {
+            self.ignore_input = None;
+        }
 
         self.table_state = table.finish(ui);
 
@@ -4952,7 +5300,8 @@ impl WindowContent for BreakpointsWindow {
             if let &Some(id) = &self.selected_breakpoint {
                 match &debugger.breakpoints.get(id).on {
                     BreakpointOn::Line(on) => {
-                        state.should_scroll_source = Some((Some(SourceScrollTarget {path: on.path.clone(), version: None, line: on.line, cascade: true}), false));
+                        let path = debugger.find_line_breakpoint_file(on).unwrap_or_else(|| on.path.clone());
+                        state.should_scroll_source = Some((Some(SourceScrollTarget {path, version: None, line: on.line, cascade: true}), false));
                     }
                     BreakpointOn::Instruction(on) => {
                         if let Some((locator, offset)) = &on.function {
@@ -4989,7 +5338,7 @@ impl WindowContent for BreakpointsWindow {
                             }
                         }
                     }
-                    BreakpointOn::InitialExec | BreakpointOn::Data(_) => (),
+                    BreakpointOn::InitialExec | BreakpointOn::Data(_) | BreakpointOn::Signal(_) | BreakpointOn::Syscall(_) | BreakpointOn::Function(_) => (),
                 }
             }
         }
@@ -5088,7 +5437,9 @@ impl TerminalWindow {
     }
 
     fn is_tty_disabled(debugger: &Debugger) -> Option<String> {
-        if !debugger.context.settings.use_tty {
+        if debugger.context.settings.ui_tty.is_some() {
+            Some("with --ui-tty (the program runs in the terminal where nnd was started)".to_string())
+        } else if !debugger.context.settings.use_tty {
             Some("with --no-pty".to_string())
         } else if debugger.mode != RunMode::Run {
             Some(format!("in {} mode", debugger.mode.human_string()))
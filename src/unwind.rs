@@ -1,4 +1,4 @@
-use crate::{*, error::{*, Error, Result}, range_index::*, util::*, elf::*, procfs::*, registers::*, symbols::*, expr::*, dwarf::*, symbols_registry::Binary};
+use crate::{*, error::{*, Error, Result}, range_index::*, util::*, elf::*, procfs::*, registers::*, arch::*, symbols::*, expr::*, dwarf::*, symbols_registry::Binary};
 use gimli::*;
 use std::{sync::Arc, rc::Rc, mem, path::PathBuf, ops::Range, mem::MaybeUninit};
 
@@ -210,12 +210,18 @@ impl UnwindInfo {
         };
 
         let mut res = Self {elves: elves.clone(), eh_frame: None, debug_frame: None};
+        // (Absolute pointers in these sections are 4 bytes in 32-bit binaries.)
+        let address_size = elves[0].arch.pointer_size() as u8;
 
         if let Some((data, addr)) = load_section(".eh_frame")? {
-            res.eh_frame = Some(UnwindSectionIndex::load(data, static_base_addresses.clone().set_eh_frame(addr), EhFrame::from(DwarfSlice::new(data)))?);
+            let mut section = EhFrame::from(DwarfSlice::new(data));
+            section.set_address_size(address_size);
+            res.eh_frame = Some(UnwindSectionIndex::load(data, static_base_addresses.clone().set_eh_frame(addr), section)?);
         }
         if let Some((data, addr)) = load_section(".debug_frame")? {
-            res.debug_frame = Some(UnwindSectionIndex::load(data, static_base_addresses, DebugFrame::from(DwarfSlice::new(data)))?);
+            let mut section = DebugFrame::from(DwarfSlice::new(data));
+            section.set_address_size(address_size);
+            res.debug_frame = Some(UnwindSectionIndex::load(data, static_base_addresses, section)?);
         }
         if res.eh_frame.is_none() && res.debug_frame.is_none() {
             return err!(MissingSymbols, "no .eh_frame or .debug_frame section");
@@ -278,8 +284,10 @@ impl UnwindInfo {
 
     // Assigns cfa and return address in current frame and returns registers for next frame.
     pub fn step(memory: &mut CachedMemReader, binary: Option<&Binary>, scratch: &mut UnwindScratchBuffer, pseudo_addr: usize, frame: &mut StackFrame) -> Result<(Registers, /*is_signal_trampoline*/ bool)> {
-        let addr = frame.regs.get(RegisterIdx::Rip)?.0 as usize;
-        let special = Self::recognize_special_location(memory, binary.clone(), addr);
+        let arch = frame.regs.arch;
+        let addr = frame.regs.get(arch.pc())?.0 as usize;
+        // (The machine code patterns are x86-specific.)
+        let special = if arch.x86_bitness().is_some() {Self::recognize_special_location(memory, binary, addr, arch)} else {SpecialUnwindLocation::None};
 
         if special == SpecialUnwindLocation::SigReturn {
             let new_regs = Self::step_through_sig_return(memory, &mut frame.regs)?;
@@ -300,18 +308,30 @@ impl UnwindInfo {
             }
         }
 
-        let r = Self::step_using_frame_pointer(special, memory, frame)?;
+        let r = match arch {
+            Arch::X86_64 | Arch::X32 | Arch::I386 => Self::step_using_frame_pointer(special, memory, frame)?,
+            Arch::Aarch64 => Self::step_using_frame_pointer_aarch64(memory, frame)?,
+        };
         frame.unwind_source = UnwindInfoSource::FramePointer;
         Ok((r, false))
     }
 
     pub fn step_from_bad_function_call(memory: &mut CachedMemReader, frame: &mut StackFrame) -> Result<(Registers, /*is_signal_trampoline*/ bool)> {
+        if frame.regs.arch == Arch::Aarch64 {
+            // Same for `blr` on aarch64, except the return address is in the link register rather than on the stack.
+            let lr = frame.regs.get(RegisterIdx::X30)?.0;
+            let mut new_regs = frame.regs.clone();
+            new_regs.set(RegisterIdx::Pc, Arch::Aarch64.strip_return_address(lr), /*dubious*/ true);
+            frame.unwind_source = UnwindInfoSource::BadFunctionCall;
+            return Ok((new_regs, false));
+        }
         // Assume the code did a `call` on a bad (e.g. null) address. I.e. they pushed return address on the stack and jumped, and no code executed in this thread since then.
+        let word = frame.regs.arch.register_size();
         let rsp = frame.regs.get(RegisterIdx::Rsp)?.0 as usize;
-        let prev_rip = memory.read_usize(rsp)?;
+        let prev_rip = memory.read_uint(rsp, word)?;
         let mut new_regs = frame.regs.clone();
         new_regs.set(RegisterIdx::Rip, prev_rip as u64, /*dubious*/ true);
-        new_regs.set(RegisterIdx::Rsp, rsp as u64 + 8, /*dubious*/ true);
+        new_regs.set(RegisterIdx::Rsp, (rsp + word) as u64, /*dubious*/ true);
         frame.unwind_source = UnwindInfoSource::BadFunctionCall;
         Ok((new_regs, false))
     }
@@ -322,29 +342,35 @@ impl UnwindInfo {
         // [function body]
         // pop rbp
         // ret              EpilogueMiddle
+        //
+        // Same on i386, but with 4-byte stack slots.
 
+        let arch = frame.regs.arch;
+        let word = arch.register_size();
+        // i386 ABI used to only require 4-byte stack alignment, and some code still assumes that.
+        let alignment = if word == 4 {4} else {16};
         let rbp = frame.regs.get(RegisterIdx::Rbp)?.0 as usize;
         let rsp = frame.regs.get(RegisterIdx::Rsp)?.0 as usize;
         let prev_rsp = match special {
-            SpecialUnwindLocation::None => rbp.saturating_add(16),
-            SpecialUnwindLocation::PreludeMiddle => rsp.saturating_add(16),
-            SpecialUnwindLocation::PreludeStart | SpecialUnwindLocation::EpilogueMiddle => rsp.saturating_add(8),
+            SpecialUnwindLocation::None => rbp.saturating_add(word * 2),
+            SpecialUnwindLocation::PreludeMiddle => rsp.saturating_add(word * 2),
+            SpecialUnwindLocation::PreludeStart | SpecialUnwindLocation::EpilogueMiddle => rsp.saturating_add(word),
             SpecialUnwindLocation::SigReturn => panic!("huh"),
         };
-        if prev_rsp % 16 != 0 || prev_rsp < 16 {
+        if prev_rsp % alignment != 0 || prev_rsp < word * 2 {
             return err!(ProcessState, "rsp not aligned");
         }
-        let cfa = prev_rsp - 16; // effective rbp
+        let cfa = prev_rsp - word * 2; // effective rbp
         frame.regs.set(RegisterIdx::Cfa, cfa as u64, /*dubious*/ true);
 
         let prev_rbp = match special {
-            SpecialUnwindLocation::None | SpecialUnwindLocation::PreludeMiddle => memory.read_usize(cfa)?,
+            SpecialUnwindLocation::None | SpecialUnwindLocation::PreludeMiddle => memory.read_uint(cfa, word)?,
             SpecialUnwindLocation::PreludeStart | SpecialUnwindLocation::EpilogueMiddle => rbp,
             SpecialUnwindLocation::SigReturn => panic!("huh"),
         };
 
-        let mut new_regs = Registers::default();
-        let prev_rip = memory.read_usize(cfa + 8)?;
+        let mut new_regs = Registers::new(arch);
+        let prev_rip = memory.read_uint(cfa + word, word)?;
         frame.regs.set(RegisterIdx::Ret, prev_rip as u64, /*dubious*/ true);
         new_regs.set(RegisterIdx::Rip, prev_rip as u64, /*dubious*/ true);
         new_regs.set(RegisterIdx::Rsp, prev_rsp as u64, /*dubious*/ true);
@@ -354,6 +380,37 @@ impl UnwindInfo {
         Ok(new_regs)
     }
 
+    fn step_using_frame_pointer_aarch64(memory: &mut CachedMemReader, frame: &mut StackFrame) -> Result<Registers> {
+        // stp x29, x30, [sp, #-16]!
+        // mov x29, sp
+        // [function body]
+        // ldp x29, x30, [sp], #16
+        // ret
+        //
+        // I.e. x29 points to a frame record {previous x29, return address}. We don't try to recognize the prelude and epilogue, so the top frame may be wrong if we're stopped there.
+        let fp = frame.regs.get(RegisterIdx::X29)?.0 as usize;
+        if fp == 0 {
+            // The outermost frame record is zero, by convention. This is how stacks end.
+            return Ok(Registers::new(Arch::Aarch64));
+        }
+        if !fp.is_multiple_of(16) {
+            return err!(ProcessState, "fp not aligned");
+        }
+        let cfa = fp + 16;
+        frame.regs.set(RegisterIdx::Cfa, cfa as u64, /*dubious*/ true);
+
+        let prev_fp = memory.read_usize(fp)?;
+        let prev_pc = Arch::Aarch64.strip_return_address(memory.read_usize(fp + 8)? as u64);
+        let mut new_regs = Registers::new(Arch::Aarch64);
+        frame.regs.set(RegisterIdx::Ret, prev_pc, /*dubious*/ true);
+        new_regs.set(RegisterIdx::Pc, prev_pc, /*dubious*/ true);
+        new_regs.set(RegisterIdx::Sp, cfa as u64, /*dubious*/ true);
+        if prev_fp > fp || prev_fp == 0 { // don't get stuck in infinite loop if unwinding through garbage
+            new_regs.set(RegisterIdx::X29, prev_fp as u64, /*dubious*/ true);
+        }
+        Ok(new_regs)
+    }
+
     fn step_using_dwarf(&self, memory: &mut CachedMemReader, binary: &Binary, scratch: &mut UnwindScratchBuffer, pseudo_addr: usize, frame: &mut StackFrame) -> Result<Option<(Registers, /*is_signal_trampoline*/ bool)>> {
         let Some((fde, row, cfa, cfa_dubious, section, unwind_source)) = self.find_row_and_eval_cfa(memory, binary, scratch, pseudo_addr, &frame.regs)? else { return Ok(None) };
         frame.regs.set(RegisterIdx::Cfa, cfa as u64, cfa_dubious);
@@ -361,13 +418,14 @@ impl UnwindInfo {
         frame.lsda = fde.lsda().clone();
         frame.unwind_source = unwind_source;
 
-        let mut new_regs = Registers::default();
-        let mut seen_regs = 0usize; // to distinguish registers that were explicitly set to RegisterRule::Undefined from ones that weren't specified
+        let arch = frame.regs.arch;
+        let mut new_regs = Registers::new(arch);
+        let mut seen_regs = 0u64; // to distinguish registers that were explicitly set to RegisterRule::Undefined from ones that weren't specified
         let mut found_return_address = false;
         for (reg_num, rule) in row.registers() {
-            let reg = RegisterIdx::from_dwarf(*reg_num);
+            let reg = arch.register_from_dwarf(*reg_num);
             if let &Some(r) = &reg {
-                seen_regs |= 1usize << r as u32;
+                seen_regs |= 1u64 << r as u32;
             }
 
             let val = eval_register_rule(reg.clone(), rule, fde.cie().encoding(), section, &frame.regs, memory, binary)?;
@@ -410,30 +468,46 @@ impl UnwindInfo {
         // (Except for RBP - don't mark it as dubious. I saw omitted RBP recovery rule for the first instruction of a function,
         //  before the 'push rbp' happens, where clealy RegisterRule::SameValue behavior is expected. So I guess this is
         //  the general convention at least for this register.)
-        for reg in [RegisterIdx::Rbp, RegisterIdx::Rbx, RegisterIdx::R12, RegisterIdx::R13, RegisterIdx::R14, RegisterIdx::R15] {
-            if seen_regs & 1usize << reg as u32 == 0 && frame.regs.has(reg) {
-                let dubious = reg != RegisterIdx::Rbp;
+        for &reg in arch.callee_saved_registers() {
+            if seen_regs & 1u64 << reg as u32 == 0 && frame.regs.has(reg) {
+                let dubious = reg != arch.frame_pointer();
                 new_regs.set(reg, frame.regs.get(reg).unwrap().0, dubious);
             }
         }
-        if seen_regs & 1usize << RegisterIdx::Rsp as u32 == 0 {
-            new_regs.set(RegisterIdx::Rsp, cfa as u64, false);
+        if seen_regs & 1u64 << arch.sp() as u32 == 0 {
+            new_regs.set(arch.sp(), cfa as u64, false);
         }
         // Pass through fs_base, it's a property of a thread rather than stack frame. Maybe we should move it to ExtraRegisters (or maybe .eh_frame dwarf expressions may use it, idk).
-        if seen_regs & 1usize << RegisterIdx::FsBase as u32 == 0 && frame.regs.has(RegisterIdx::FsBase) {
+        if seen_regs & 1u64 << RegisterIdx::FsBase as u32 == 0 && frame.regs.has(RegisterIdx::FsBase) {
             let (v, dub) = frame.regs.get(RegisterIdx::FsBase).unwrap();
             new_regs.set(RegisterIdx::FsBase, v, dub);
         }
 
         if !found_return_address {
             // According to libunwind: "Leaf function keeps the return address in register and there is no explicit intructions how to restore it"
-            let reg = match RegisterIdx::from_dwarf(fde.cie().return_address_register()) {
+            let reg = match arch.register_from_dwarf(fde.cie().return_address_register()) {
                 None => return err!(Dwarf, "unrecognized return address register: {:?}", fde.cie().return_address_register()),
                 Some(r) => r };
             // Typically the return address register is just RIP, and for the root stack frame there's no RIP in new_regs.
             if new_regs.has(reg) {
                 let (v, dubious) = new_regs.get(reg).unwrap();
                 frame.regs.set(RegisterIdx::Ret, v, dubious);
+            } else if reg != arch.pc() && frame.regs.has(reg) {
+                // On aarch64 the return address register is the link register (x30), which is a normal register rather than pc.
+                // In a leaf function it just keeps the value from the call.
+                // But gimli doesn't distinguish missing rule from explicit DW_CFA_undefined (which e.g. _start uses to terminate the stack), so check that
+                // the link register doesn't point into the current function call, which is what it looks like in non-top frames after the callee restored it.
+                let (v, dubious) = frame.regs.get(reg).unwrap();
+                if arch.strip_return_address(v) != frame.regs.get(arch.pc())?.0 {
+                    new_regs.set(reg, v, dubious);
+                    frame.regs.set(RegisterIdx::Ret, v, dubious);
+                }
+            }
+        }
+        if arch == Arch::Aarch64 {
+            // Unlike x86, the return address register is not the instruction pointer, so we have to assign the next frame's pc explicitly.
+            if let Some((v, dubious)) = frame.regs.get_option(RegisterIdx::Ret) {
+                new_regs.set(arch.pc(), arch.strip_return_address(v), dubious);
             }
         }
 
@@ -441,7 +515,7 @@ impl UnwindInfo {
     }
 
     // Look at machine code near the instruction pointer and recognize a few common patterns.
-    fn recognize_special_location(memory: &mut CachedMemReader, binary: Option<&Binary>, addr: usize) -> SpecialUnwindLocation {
+    fn recognize_special_location(memory: &mut CachedMemReader, binary: Option<&Binary>, addr: usize, arch: Arch) -> SpecialUnwindLocation {
         // Recognize signal trampoline machine code, like GDB does. Because some libc implementations (musl) don't have correct DWARF unwind information for this function.
         const SIG_RETURN_CODE: [u8; 9] = [
             0x48, 0xc7, 0xc0, 0x0f, 0x00, 0x00, 0x00, // mov rax, 15
@@ -460,6 +534,9 @@ impl UnwindInfo {
             // PreludeMiddle
             0x48, 0x8b, 0xec // mov rbp, rsp
         ];
+        // Without the REX.W prefix on i386.
+        const PRELUDE_CODE_A_I386: [u8; 3] = [0x55, 0x89, 0xe5];
+        const PRELUDE_CODE_B_I386: [u8; 3] = [0x55, 0x8b, 0xec];
         const PRELUDE_MID: usize = 1;
 
         const EPILOGUE_CODE: [u8; 2] = [
@@ -506,12 +583,16 @@ impl UnwindInfo {
             }
         };
 
+        // (Signal trampoline is different on i386 and x32, we rely on DWARF there.)
+        let sig_return = arch == Arch::X86_64;
+        let (prelude_a, prelude_b): (&[u8], &[u8]) = if arch == Arch::I386 {(&PRELUDE_CODE_A_I386, &PRELUDE_CODE_B_I386)} else {(&PRELUDE_CODE_A, &PRELUDE_CODE_B)};
+
         match data[offset] {
             // (Compare first byte first for speed.)
-            x if x == SIG_RETURN_CODE[0] && data[offset..].starts_with(&SIG_RETURN_CODE) => SpecialUnwindLocation::SigReturn,
-            x if x == SIG_RETURN_CODE[SIG_RETURN_MID] && offset >= SIG_RETURN_MID && data[offset-SIG_RETURN_MID..].starts_with(&SIG_RETURN_CODE) => SpecialUnwindLocation::SigReturn,
-            x if x == PRELUDE_CODE_A[0] && (data[offset..].starts_with(&PRELUDE_CODE_A) || data[offset..].starts_with(&PRELUDE_CODE_B)) => SpecialUnwindLocation::PreludeStart,
-            x if x == PRELUDE_CODE_A[PRELUDE_MID] && offset >= PRELUDE_MID && (data[offset-PRELUDE_MID..].starts_with(&PRELUDE_CODE_A) || data[offset-PRELUDE_MID..].starts_with(&PRELUDE_CODE_B)) => SpecialUnwindLocation::PreludeMiddle,
+            x if sig_return && x == SIG_RETURN_CODE[0] && data[offset..].starts_with(&SIG_RETURN_CODE) => SpecialUnwindLocation::SigReturn,
+            x if sig_return && x == SIG_RETURN_CODE[SIG_RETURN_MID] && offset >= SIG_RETURN_MID && data[offset-SIG_RETURN_MID..].starts_with(&SIG_RETURN_CODE) => SpecialUnwindLocation::SigReturn,
+            x if x == prelude_a[0] && (data[offset..].starts_with(prelude_a) || data[offset..].starts_with(prelude_b)) => SpecialUnwindLocation::PreludeStart,
+            x if (x == prelude_a[PRELUDE_MID] || x == prelude_b[PRELUDE_MID]) && offset >= PRELUDE_MID && (data[offset-PRELUDE_MID..].starts_with(prelude_a) || data[offset-PRELUDE_MID..].starts_with(prelude_b)) => SpecialUnwindLocation::PreludeMiddle,
             x if x == EPILOGUE_CODE[EPILOGUE_MID] && offset >= EPILOGUE_MID && data[offset-EPILOGUE_MID..].starts_with(&EPILOGUE_CODE) => SpecialUnwindLocation::EpilogueMiddle,
             _ => SpecialUnwindLocation::None,
         }
@@ -550,7 +631,7 @@ fn eval_dwarf_expression_as_u64(expression: Expression<DwarfSlice>, encoding: En
             AddrOrValueBlob::Blob(v) => v.get_usize()? as u64,
         }
     } else {
-        val.into_value(8, memory)?.get_usize()? as u64
+        val.into_value(regs.arch.register_size(), memory)?.get_usize()? as u64
     };
     Ok((val, dubious))
 
@@ -563,7 +644,7 @@ fn eval_cfa_rule(rule: &CfaRule<usize>, encoding: Encoding, section: DwarfSlice,
             eval_dwarf_expression_as_u64(e, encoding, registers, memory, binary, /*skip_final_dereference*/ true)?
         }
         CfaRule::RegisterAndOffset {register: reg, offset} => {
-            let reg = match RegisterIdx::from_dwarf(*reg) {
+            let reg = match registers.arch.register_from_dwarf(*reg) {
                 None => return err!(Dwarf, "unsupported register in cfa: {:?}", reg),
                 Some(r) => r,
             };
@@ -588,7 +669,7 @@ fn eval_register_rule(reg: Option<RegisterIdx>, rule: &RegisterRule<usize>, enco
             }
         }
         RegisterRule::Register(reg) => {
-            let reg = match RegisterIdx::from_dwarf(*reg) {
+            let reg = match registers.arch.register_from_dwarf(*reg) {
                 None => return err!(NotImplemented, "unsupported register (in reg): {:?}", reg),
                 Some(r) => r,
             };
@@ -597,12 +678,12 @@ fn eval_register_rule(reg: Option<RegisterIdx>, rule: &RegisterRule<usize>, enco
                 Ok(x) => x,
             }
         }
-        RegisterRule::Offset(offset) => (memory.read_usize(cfa.wrapping_add(*offset as u64) as usize)? as u64, cfa_dubious),
+        RegisterRule::Offset(offset) => (memory.read_uint(cfa.wrapping_add(*offset as u64) as usize, registers.arch.register_size())? as u64, cfa_dubious),
         RegisterRule::ValOffset(offset) => (cfa.wrapping_add(*offset as u64), cfa_dubious),
         RegisterRule::Expression(e) => {
             let e = Expression(DwarfSlice::new(&section.slice()[e.offset..e.offset+e.length]));
             let (addr, dubious) = eval_dwarf_expression_as_u64(e, encoding, registers, memory, binary, /*skip_final_dereference*/ true)?;
-            (memory.read_usize(addr as usize)? as u64, dubious)
+            (memory.read_uint(addr as usize, registers.arch.register_size())? as u64, dubious)
         }
         RegisterRule::ValExpression(e) => {
             let e = Expression(DwarfSlice::new(&section.slice()[e.offset..e.offset+e.length]));
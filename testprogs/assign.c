@@ -0,0 +1,9 @@
+// Used by tests/batch.rs: modifies the program's memory from expressions.
+int arr[3] = {10, 20, 30};
+int counter = 0;
+
+__attribute__((noinline)) int next_index(void) { return counter++; }
+
+int main() {
+    return arr[0] + counter; // line 8
+}
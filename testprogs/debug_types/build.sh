@@ -0,0 +1,26 @@
+#!/bin/sh
+# Builds the C and C++ programs with types in type units (-fdebug-types-section), each variant in its own subdirectory of the output directory (default: next to this script):
+#   c4, cpp4   - DWARF 4, type units in .debug_types
+#   c5, cpp5   - DWARF 5, type units in .debug_info
+#   c, cpp     - without type units, for comparison
+set -e
+
+BASEDIR=$(realpath "$(dirname "$0")")
+OUT=$(realpath "${1:-$BASEDIR}")
+
+build() {
+    local dir="$OUT/$1"
+    local compiler="$2"
+    local ext="$3"
+    local other="$4"
+    shift 4
+    mkdir -p "$dir"
+    $compiler -g -O0 "$@" -o "$dir/prog" "$BASEDIR/main.$ext" "$BASEDIR/$other.$ext"
+}
+
+build c gcc c shapes
+build c4 gcc c shapes -gdwarf-4 -fdebug-types-section
+build c5 gcc c shapes -gdwarf-5 -fdebug-types-section
+build cpp g++ cpp widget
+build cpp4 g++ cpp widget -gdwarf-4 -fdebug-types-section
+build cpp5 g++ cpp widget -gdwarf-5 -fdebug-types-section
@@ -0,0 +1,7 @@
+#include "shapes.h"
+
+int main() {
+    struct Rect r = {{1, 2}, {4, 6}, 7};
+    int a = area(&r);
+    return a == 12 ? 0 : 1;
+}
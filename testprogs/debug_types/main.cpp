@@ -0,0 +1,6 @@
+#include "widget.hpp"
+
+int main() {
+    ns::Widget w {3, {1.5, 10}, {20, 11}};
+    return w.total() == 24 ? 0 : 1;
+}
@@ -0,0 +1,7 @@
+#include "shapes.h"
+
+int area(const struct Rect *r) {
+    int w = r->max.x - r->min.x;
+    int h = r->max.y - r->min.y;
+    return w * h; // line 6
+}
@@ -0,0 +1,12 @@
+struct Point {
+    int x;
+    int y;
+};
+
+struct Rect {
+    struct Point min;
+    struct Point max;
+    int color;
+};
+
+int area(const struct Rect *r);
@@ -0,0 +1,6 @@
+#include "widget.hpp"
+
+int ns::Widget::total() const {
+    int sum = id + (int)box.value + (int)counter.value;
+    return sum; // line 5
+}
@@ -0,0 +1,16 @@
+namespace ns {
+    template<typename T>
+    struct Box {
+        T value;
+        int tag;
+    };
+
+    class Widget {
+    public:
+        int id;
+        Box<double> box;
+        Box<long> counter;
+
+        int total() const;
+    };
+}
@@ -0,0 +1,80 @@
+# Hand-written equivalent of a supplementary file produced by `dwz -m`: a partial unit with `struct Pair {int a; int b;}`, and the name "g_pair" in .debug_str.
+# pair_alt.s refers into this file by offset, the .if-s below check that the offsets didn't move.
+
+    .section .note.gnu.build-id,"a",@note
+    .long 4, 20, 3 # name size, desc size, NT_GNU_BUILD_ID
+    .string "GNU"
+    .byte 0xd2,0x5a,0x11,0x7e,0x00,0x01,0x02,0x03,0x04,0x05,0x06,0x07,0x08,0x09,0x0a,0x0b,0x0c,0x0d,0x0e,0x0f
+
+    .section .debug_abbrev,"",@progbits
+    .uleb128 1, 0x3c # DW_TAG_partial_unit
+    .byte 1
+    .uleb128 0x13, 0x0b # DW_AT_language, DW_FORM_data1
+    .byte 0, 0
+    .uleb128 2, 0x13 # DW_TAG_structure_type
+    .byte 1
+    .uleb128 0x03, 0x0e # DW_AT_name, DW_FORM_strp
+    .uleb128 0x0b, 0x0b # DW_AT_byte_size, DW_FORM_data1
+    .byte 0, 0
+    .uleb128 3, 0x0d # DW_TAG_member
+    .byte 0
+    .uleb128 0x03, 0x0e # DW_AT_name, DW_FORM_strp
+    .uleb128 0x49, 0x13 # DW_AT_type, DW_FORM_ref4
+    .uleb128 0x38, 0x0b # DW_AT_data_member_location, DW_FORM_data1
+    .byte 0, 0
+    .uleb128 4, 0x24 # DW_TAG_base_type
+    .byte 0
+    .uleb128 0x03, 0x0e # DW_AT_name, DW_FORM_strp
+    .uleb128 0x0b, 0x0b # DW_AT_byte_size, DW_FORM_data1
+    .uleb128 0x3e, 0x0b # DW_AT_encoding, DW_FORM_data1
+    .byte 0, 0
+    .byte 0
+
+    .section .debug_info,"",@progbits
+.Lunit:
+    .long .Lunit_end - .Lunit_version
+.Lunit_version:
+    .value 4
+    .long 0 # abbrev offset
+    .byte 8
+.Lpartial_unit:
+    .uleb128 1
+    .byte 0x0c # DW_LANG_C99
+.Lpair:
+    .uleb128 2
+    .long .Lstr_Pair - .Lstr
+    .byte 8
+    .uleb128 3
+    .long .Lstr_a - .Lstr
+    .long .Lint - .Lunit
+    .byte 0
+    .uleb128 3
+    .long .Lstr_b - .Lstr
+    .long .Lint - .Lunit
+    .byte 4
+    .byte 0
+.Lint:
+    .uleb128 4
+    .long .Lstr_int - .Lstr
+    .byte 4
+    .byte 5 # DW_ATE_signed
+    .byte 0
+.Lunit_end:
+
+    .section .debug_str,"MS",@progbits,1
+.Lstr:
+.Lstr_g_pair:
+    .string "g_pair"
+.Lstr_Pair:
+    .string "Pair"
+.Lstr_a:
+    .string "a"
+.Lstr_b:
+    .string "b"
+.Lstr_int:
+    .string "int"
+
+    # Offsets used in pair_alt.s.
+    .if (.Lpartial_unit - .Lunit) != 11 || (.Lpair - .Lunit) != 13 || (.Lstr_g_pair - .Lstr) != 0
+    .error "alt.s layout doesn't match the offsets in pair_alt.s"
+    .endif
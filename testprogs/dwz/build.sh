@@ -0,0 +1,19 @@
+#!/bin/sh
+# Builds the program with debug info partly moved to a DWZ supplementary file (.gnu_debugaltlink), each variant in its own subdirectory of the output directory (default: next to this script):
+#   dwz      - two copies of the program processed with `dwz -m alt.debug`, which moves struct Pair and the common strings to alt.debug (skipped if dwz is not installed)
+#   handmade - the same layout written by hand in assembly (alt.s, pair_alt.s), for when dwz is not available
+set -e
+
+BASEDIR=$(realpath "$(dirname "$0")")
+OUT=$(realpath "${1:-$BASEDIR}")
+
+mkdir -p "$OUT/handmade"
+(cd "$OUT/handmade" && as -o alt.debug "$BASEDIR/alt.s" && as -o pair_alt.o "$BASEDIR/pair_alt.s" && gcc -g -O0 -c -o main.o "$BASEDIR/main.c" && gcc -o prog main.o pair_alt.o)
+
+if command -v dwz > /dev/null; then
+    mkdir -p "$OUT/dwz"
+    # (dwz -m only moves debug info that's duplicated across the files, hence the second copy of the program.)
+    (cd "$OUT/dwz" && gcc -g -O0 -gdwarf-4 -o prog "$BASEDIR/main.c" "$BASEDIR/pair.c" && cp prog prog2 && dwz -m alt.debug prog prog2)
+else
+    echo "dwz not found, skipping the dwz variant" >&2
+fi
@@ -0,0 +1,4 @@
+int main() {
+    volatile int x = 0;
+    return x; // line 3
+}
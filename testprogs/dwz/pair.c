@@ -0,0 +1,3 @@
+#include "pair.h"
+
+struct Pair g_pair = {3, 4};
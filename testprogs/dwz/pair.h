@@ -0,0 +1,4 @@
+struct Pair {
+    int a;
+    int b;
+};
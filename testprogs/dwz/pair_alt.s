@@ -0,0 +1,56 @@
+# Hand-written equivalent of pair.c after `dwz -m alt.debug`: the variable's type and name are in the supplementary file alt.debug (see alt.s), referenced with DW_FORM_GNU_ref_alt and DW_FORM_GNU_strp_alt.
+
+    .data
+    .globl g_pair
+    .type g_pair, @object
+    .size g_pair, 8
+    .align 4
+g_pair:
+    .long 3, 4
+
+    .section .gnu_debugaltlink,"",@progbits
+    .string "alt.debug"
+    .byte 0xd2,0x5a,0x11,0x7e,0x00,0x01,0x02,0x03,0x04,0x05,0x06,0x07,0x08,0x09,0x0a,0x0b,0x0c,0x0d,0x0e,0x0f
+
+    .section .debug_abbrev,"",@progbits
+.Labbrev:
+    .uleb128 1, 0x11 # DW_TAG_compile_unit
+    .byte 1
+    .uleb128 0x03, 0x08 # DW_AT_name, DW_FORM_string
+    .uleb128 0x13, 0x0b # DW_AT_language, DW_FORM_data1
+    .byte 0, 0
+    .uleb128 2, 0x3d # DW_TAG_imported_unit
+    .byte 0
+    .uleb128 0x18, 0x1f20 # DW_AT_import, DW_FORM_GNU_ref_alt
+    .byte 0, 0
+    .uleb128 3, 0x34 # DW_TAG_variable
+    .byte 0
+    .uleb128 0x03, 0x1f21 # DW_AT_name, DW_FORM_GNU_strp_alt
+    .uleb128 0x49, 0x1f20 # DW_AT_type, DW_FORM_GNU_ref_alt
+    .uleb128 0x3f, 0x19 # DW_AT_external, DW_FORM_flag_present
+    .uleb128 0x02, 0x18 # DW_AT_location, DW_FORM_exprloc
+    .byte 0, 0
+    .byte 0
+
+    .section .debug_info,"",@progbits
+.Lunit:
+    .long .Lunit_end - .Lunit_version
+.Lunit_version:
+    .value 4
+    .long .Labbrev
+    .byte 8
+    .uleb128 1
+    .string "pair.c"
+    .byte 0x0c # DW_LANG_C99
+    .uleb128 2
+    .long 11 # partial unit in alt.debug
+    .uleb128 3
+    .long 0 # "g_pair" in alt.debug
+    .long 13 # struct Pair in alt.debug
+    .uleb128 9
+    .byte 0x03 # DW_OP_addr
+    .quad g_pair
+    .byte 0
+.Lunit_end:
+
+    .section .note.GNU-stack,"",@progbits
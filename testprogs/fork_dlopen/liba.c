@@ -0,0 +1,3 @@
+int lib_fn(int x) {
+    return x + 1;
+}
@@ -0,0 +1,4 @@
+// Just takes up address space.
+int libb_fn(void) {
+    return 0;
+}
@@ -0,0 +1,33 @@
+// Used by tests/batch.rs with --follow-fork=both: after fork, the child loads libraries in a different order than the parent,
+// so the same library ends up at different addresses in the two processes. Usage: main path/to/liba.so path/to/libb.so
+#include <dlfcn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <unistd.h>
+#include <sys/wait.h>
+
+static void *load(const char *path) {
+    void *h = dlopen(path, RTLD_NOW);
+    if (!h) {
+        fprintf(stderr, "%s\n", dlerror());
+        exit(1);
+    }
+    return h;
+}
+
+static int call_lib_fn(void *h, int x) {
+    int (*f)(int) = (int (*)(int))dlsym(h, "lib_fn");
+    return f(x);
+}
+
+int main(int argc, char **argv) {
+    pid_t child = fork();
+    if (child == 0) {
+        usleep(200000); // let the parent load liba.so first
+        load(argv[2]); // takes the address where the parent has liba.so
+        return call_lib_fn(load(argv[1]), 2) == 3 ? 0 : 1;
+    }
+    void *h = load(argv[1]);
+    waitpid(child, 0, 0);
+    return call_lib_fn(h, 1) == 2 ? 0 : 1;
+}
@@ -0,0 +1,19 @@
+// Used by tests/batch.rs: calls debuggee functions from expressions and breakpoint conditions, with and without another thread running.
+#include <pthread.h>
+#include <unistd.h>
+
+__attribute__((noinline)) int twice(int x) { return x * 2; }
+
+void *spin(void *arg) {
+    for (int i = 0; i < 100; i++) usleep(10000);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    pthread_t t;
+    if (argc > 1) pthread_create(&t, 0, spin, 0);
+    for (int i = 0; i < 3; i++)
+        twice(i); // line 16
+    if (argc > 1) pthread_join(t, 0);
+    return 0;
+}
@@ -0,0 +1,22 @@
+// Used by tests/batch.rs: with --non-stop, the main thread steps over a line while another thread hits a breakpoint.
+#include <pthread.h>
+#include <unistd.h>
+
+volatile int go = 0;
+
+__attribute__((noinline)) void hit_in_other_thread(void) { __asm__ volatile(""); }
+
+void *other_thread(void *arg) {
+    while (!go) usleep(1000);
+    hit_in_other_thread();
+    return 0;
+}
+
+int main() {
+    pthread_t t;
+    pthread_create(&t, 0, other_thread, 0);
+    usleep(10000);
+    go = 1; usleep(500000); // line 19: stepped over, the other thread hits its breakpoint meanwhile
+    pthread_join(t, 0);
+    return 0;
+}
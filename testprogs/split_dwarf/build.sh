@@ -0,0 +1,24 @@
+#!/bin/sh
+# Builds the program with split DWARF in a few variants, each in its own subdirectory of the output directory (default: next to this script):
+#   dwo4, dwo5 - DWARF 4 and 5, debug info in .dwo files next to the object files
+#   dwp4       - DWARF 4, packed into prog.dwp with `dwp`, and the .dwo files deleted
+#                (No DWARF 5 .dwp: binutils dwp produces an empty package for DWARF 5 units.)
+#   plain      - without split DWARF, for comparison
+set -e
+
+BASEDIR=$(realpath "$(dirname "$0")")
+OUT=$(realpath "${1:-$BASEDIR}")
+
+build() {
+    local dir="$OUT/$1"
+    shift
+    mkdir -p "$dir"
+    # (Compile in the output directory, so that DW_AT_comp_dir points to where the .dwo files are.)
+    (cd "$dir" && gcc -g -O0 "$@" -c -o shapes.o "$BASEDIR/shapes.c" && gcc -g -O0 "$@" -c -o main.o "$BASEDIR/main.c" && gcc -o prog main.o shapes.o)
+}
+
+build plain
+build dwo4 -gdwarf-4 -gsplit-dwarf
+build dwo5 -gdwarf-5 -gsplit-dwarf
+build dwp4 -gdwarf-4 -gsplit-dwarf
+(cd "$OUT/dwp4" && dwp -e prog -o prog.dwp && rm main.dwo shapes.dwo)
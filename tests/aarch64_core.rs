@@ -0,0 +1,31 @@
+// Opens a synthetic aarch64 core dump (tests/fixtures/aarch64_core, see mk.py there) in --batch mode and checks the stack trace and registers.
+// The innermost frames are unwound using .eh_frame, and the return address into _start has a pointer authentication signature that must be stripped.
+
+use std::{process::Command, path::Path};
+
+#[test]
+fn unwind() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/aarch64_core");
+    let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join("aarch64_core_unwind");
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    let script = home.join("script.nnd");
+    std::fs::write(&script, "bt\np x19\nf 1\np x19\nf 2\np x19\np pc\np sp\n").unwrap();
+
+    // The core refers to the executable by relative path.
+    let output = Command::new(env!("CARGO_BIN_EXE_nnd")).current_dir(&fixture).env("HOME", &home).arg("--batch").arg(&script).args(["-c", "core"]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}\nstderr: {}", stdout, String::from_utf8_lossy(&output.stderr));
+
+    let lines: Vec<&str> = stdout.lines().map(|l| l.trim()).collect();
+    let frames: Vec<&str> = lines.iter().copied().filter(|l| l.starts_with('#')).collect();
+    assert_eq!(frames.len(), 3, "{}", stdout);
+    assert!(frames[0].starts_with("#0") && frames[0].contains("0x400130 leaf_fn"), "{}", stdout);
+    assert!(frames[1].starts_with("#1") && frames[1].contains("0x400120 outer_fn"), "{}", stdout);
+    // Return address 0x0012000000400108 with the PAC bits stripped.
+    assert!(frames[2].starts_with("#2") && frames[2].contains("0x400108 "), "{}", stdout);
+
+    // x19 is callee-saved: 42 in leaf_fn and outer_fn, restored from outer_fn's stack frame in _start.
+    let values: Vec<&str> = lines.iter().copied().filter(|l| l.contains(" = ")).collect();
+    assert_eq!(values, ["x19 = 42", "x19 = 42", "x19 = 7", &format!("pc = {}", 0x400108), &format!("sp = {}", 0x7ff0f00)], "{}", stdout);
+}
@@ -0,0 +1,232 @@
+// Runs `nnd --batch` scripts against small programs from testprogs/, compiled with cc. Tests are skipped if there's no C compiler.
+
+use std::{process::Command, path::{Path, PathBuf}};
+
+// Creates an empty directory to use as HOME for nnd and to put the compiled program in.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn compile(dir: &Path, source: &str, output: &str, flags: &[&str]) -> Option<PathBuf> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("testprogs").join(source);
+    let exe = dir.join(output);
+    match Command::new("cc").args(flags).arg("-o").arg(&exe).arg(&source).status() {
+        Ok(s) if s.success() => Some(exe),
+        _ => {
+            eprintln!("skipping: couldn't compile {} with cc", source.display());
+            None
+        }
+    }
+}
+
+// Runs a build script from testprogs/ (for programs that need more than one cc command), with the output directory as argument.
+fn build_with_script(dir: &Path, script: &str) -> bool {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("testprogs").join(script);
+    match Command::new("sh").arg(&script).arg(dir).status() {
+        Ok(s) if s.success() => true,
+        _ => {
+            eprintln!("skipping: {} failed", script.display());
+            false
+        }
+    }
+}
+
+// Replaces hex numbers (addresses) with "0x_", for comparing output of differently built programs. Stack addresses depend on e.g. the length of the executable's path.
+fn mask_addresses(line: &str) -> String {
+    let mut res = String::new();
+    let mut rest = line;
+    while let Some(i) = rest.find("0x") {
+        res.push_str(&rest[..i]);
+        res.push_str("0x_");
+        rest = rest[i+2..].trim_start_matches(|c: char| c.is_ascii_hexdigit());
+    }
+    res.push_str(rest);
+    res
+}
+
+// `command` is the program to debug, with arguments.
+fn run_batch(dir: &Path, extra_args: &[&str], script: &str, command: &[&Path]) -> String {
+    run_batch_with_status(dir, extra_args, script, command, true)
+}
+
+// Batch mode exits with an error status if any command failed; `expect_success` = false is for scripts that test error messages.
+fn run_batch_with_status(dir: &Path, extra_args: &[&str], script: &str, command: &[&Path], expect_success: bool) -> String {
+    let script_path = dir.join("script.nnd");
+    std::fs::write(&script_path, script).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_nnd")).current_dir(dir).env("HOME", dir).args(extra_args).arg("--batch").arg(&script_path).args(command).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert_eq!(output.status.success(), expect_success, "stdout: {}\nstderr: {}", stdout, String::from_utf8_lossy(&output.stderr));
+    stdout
+}
+
+// With --non-stop, stepping one thread must not make the other running threads skip their breakpoints.
+#[test]
+fn non_stop_step_with_breakpoint_in_other_thread() {
+    let dir = test_dir("batch_non_stop_step");
+    let exe = match compile(&dir, "non_stop_step.c", "prog", &["-g", "-O0", "-pthread"]) {
+        Some(x) => x,
+        None => return };
+    let stdout = run_batch(&dir, &["--non-stop"], "break non_stop_step.c:19\nbreak hit_in_other_thread\nrun\nnext\nthreads\n", &[&exe]);
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.iter().any(|l| l.contains("stopped: breakpoint") && l.contains("non_stop_step.c:19")), "{}", stdout);
+    assert!(lines.iter().any(|l| l.contains("stopped: step") && l.contains("non_stop_step.c:20")), "{}", stdout);
+    // `threads` output: the stepped thread and the other thread, stopped at its breakpoint during the step.
+    assert!(lines.iter().any(|l| l.starts_with("* ") && l.ends_with(" step")), "{}", stdout);
+    assert!(lines.iter().any(|l| l.starts_with("  ") && l.ends_with(" breakpoint")), "{}", stdout);
+}
+
+// With --follow-fork=both, the child loads a library at a different address than the parent. The breakpoint must be hit and symbolized in both processes.
+#[test]
+fn follow_fork_both_with_different_library_addresses() {
+    let dir = test_dir("batch_fork_dlopen");
+    let (Some(exe), Some(liba), Some(libb)) = (
+        compile(&dir, "fork_dlopen/main.c", "prog", &["-g", "-O0", "-ldl"]),
+        compile(&dir, "fork_dlopen/liba.c", "liba.so", &["-g", "-O0", "-shared", "-fPIC"]),
+        compile(&dir, "fork_dlopen/libb.c", "libb.so", &["-g", "-O0", "-shared", "-fPIC"])) else { return };
+    let stdout = run_batch(&dir, &["--follow-fork", "both"], "break lib_fn\nrun\nbt\ncontinue\nbt\ncontinue\n", &[&exe, &liba, &libb]);
+
+    assert!(stdout.contains(" did fork, debugging child "), "{}", stdout);
+    // "thread 123 stopped: breakpoint in 0x7ffff7dcf0f9 lib_fn at .../liba.c:1:19", first in the child, then in the parent.
+    let hits: Vec<(&str, &str)> = stdout.lines().filter(|l| l.contains("stopped: breakpoint in ")).map(|l| {
+        let mut words = l.split(' ');
+        (words.nth(1).unwrap(), words.nth(3).unwrap())
+    }).collect();
+    assert_eq!(hits.len(), 2, "{}", stdout);
+    assert_ne!(hits[0].0, hits[1].0, "{}", stdout);
+    assert_ne!(hits[0].1, hits[1].1, "expected liba.so at different addresses in the two processes: {}", stdout);
+    // Stack traces of both processes are symbolized.
+    assert_eq!(stdout.lines().filter(|l| l.trim_start().starts_with("#0 ") && l.contains(" lib_fn at ") && l.contains("liba.c:")).count(), 2, "{}", stdout);
+    assert_eq!(stdout.lines().filter(|l| l.trim_start().starts_with("#2 ") && l.contains(" main at ")).count(), 2, "{}", stdout);
+}
+
+#[test]
+fn function_calls_share_time_budget_and_need_other_threads_stopped() {
+    let dir = test_dir("batch_function_calls");
+    let exe = match compile(&dir, "function_calls.c", "prog", &["-g", "-O0", "-pthread"]) {
+        Some(x) => x,
+        None => return };
+    let script = "break function_calls.c:16 if twice(i) == 4\nrun\nprint i\nprint twice(21)\nprint {let s = 0; loop {s += twice(1)}; s}\n";
+
+    // Single thread: the condition calls the function.
+    let stdout = run_batch_with_status(&dir, &[], script, &[&exe], false);
+    assert!(stdout.lines().any(|l| l == "i = 2"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l == "twice(21) = 42"), "{}", stdout);
+    // A loop of calls runs out of the time budget shared by the whole expression.
+    assert!(stdout.lines().any(|l| l.ends_with("function calls took more than 1000 ms in total")), "{}", stdout);
+
+    // Another thread is running while the condition is evaluated, so the call is refused, and the error stops at the first hit.
+    let stdout = run_batch_with_status(&dir, &[], script, &[&exe, Path::new("1")], false);
+    assert!(stdout.lines().any(|l| l == "i = 0"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l == "twice(21) = 42"), "{}", stdout);
+}
+
+#[test]
+fn compound_assignment_evaluates_target_once() {
+    let dir = test_dir("batch_assign");
+    let exe = match compile(&dir, "assign.c", "prog", &["-g", "-O0"]) {
+        Some(x) => x,
+        None => return };
+    // The index expression has side effects: a debuggee function call, and a script function call.
+    let script = "break assign.c:8\nrun\nprint arr[next_index()] += 5\nprint counter\nprint n = 0\nprint fn idx() -> u64 {n += 1; 2}\nprint arr[idx()] *= 2\nprint n\nprint arr\n";
+    let stdout = run_batch(&dir, &[], script, &[&exe]);
+    assert!(stdout.lines().any(|l| l == "counter = 1"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l == "n = 1"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l == "arr = [15, 20, 60]"), "{}", stdout);
+}
+
+#[test]
+fn reads_after_assignment_see_new_value() {
+    let dir = test_dir("batch_assign_reread");
+    let exe = match compile(&dir, "assign.c", "prog", &["-g", "-O0"]) {
+        Some(x) => x,
+        None => return };
+    // arr[1] is read (and cached) before the write, then read again in the same expression, in the next command, and by the program itself.
+    let script = "break assign.c:8\nrun\nprint {let s = arr[0] + arr[1]; arr[1] = 7; s * 100 + arr[1]}\nprint arr[1]\nprint arr[0] = arr[1] + 33\ncontinue\n";
+    let stdout = run_batch(&dir, &[], script, &[&exe]);
+    assert!(stdout.lines().any(|l| l.ends_with(" = 3007")), "{}", stdout);
+    assert!(stdout.lines().any(|l| l == "arr[1] = 7"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.ends_with(" exited with status 40")), "{}", stdout);
+}
+
+#[test]
+fn split_dwarf() {
+    let dir = test_dir("batch_split_dwarf");
+    if !build_with_script(&dir, "split_dwarf/build.sh") {
+        return;
+    }
+    let script = "break shapes.c:6\nrun\nlocals\nprint r.max\nprint *r\nframe 1\nlocals\n";
+    // (Skip the first line, it has the thread id.)
+    let run = |variant: &str| -> Vec<String> {
+        let stdout = run_batch(&dir.join(variant), &[], script, &[&dir.join(variant).join("prog")]);
+        assert!(stdout.lines().next().is_some_and(|l| l.contains("stopped: breakpoint") && l.contains(" area at ") && l.ends_with("shapes.c:6:14")), "{}: {}", variant, stdout);
+        stdout.lines().skip(1).map(mask_addresses).collect()
+    };
+    let plain = run("plain");
+    for line in ["w = 3", "h = 4", "r.max = {x: 4, y: 6}"] {
+        assert!(plain.iter().any(|l| l == line), "{:?}", plain);
+    }
+    assert!(plain.iter().any(|l| l.starts_with("r = {min: {x: 1, y: 2}, max: {x: 4, y: 6}, name: ")), "{:?}", plain);
+    // Locals, struct fields and the other compilation unit's frame come out the same with the debug info in .dwo files or in a .dwp package.
+    for variant in ["dwo4", "dwo5", "dwp4"] {
+        assert_eq!(run(variant), plain, "{}", variant);
+    }
+}
+
+#[test]
+fn debug_types_section() {
+    let dir = test_dir("batch_debug_types");
+    if !build_with_script(&dir, "debug_types/build.sh") {
+        return;
+    }
+    // (Skip the first line, it has the thread id.)
+    let run = |variant: &str, script: &str| -> Vec<String> {
+        let stdout = run_batch(&dir.join(variant), &[], script, &[&dir.join(variant).join("prog")]);
+        assert!(stdout.lines().next().is_some_and(|l| l.contains("stopped: breakpoint")), "{}: {}", variant, stdout);
+        stdout.lines().skip(1).map(mask_addresses).collect()
+    };
+
+    // C: variable types are references to type units, and types are looked up by name for casts.
+    let script = "break shapes.c:6\nrun\nlocals\nprint *r\nprint typeof(*r)\nprint (r as *Point)[1]\nframe 1\nlocals\n";
+    let plain = run("c", script);
+    for line in ["w = 3", "*r = {min: {x: 1, y: 2}, max: {x: 4, y: 6}, color: 7}", "typeof(*r) = struct (20 bytes): Rect", "(r as *Point)[1] = {x: 4, y: 6}", "r = {min: {x: 1, y: 2}, max: {x: 4, y: 6}, color: 7}"] {
+        assert!(plain.iter().any(|l| l == line), "{:?}", plain);
+    }
+    for variant in ["c4", "c5"] {
+        assert_eq!(run(variant, script), plain, "{}", variant);
+    }
+
+    // C++: a class and template instantiations in a namespace.
+    let script = "break widget.cpp:5\nrun\nlocals\nprint *this\nprint typeof(this.box)\nprint (&this.counter as *`ns::Box<long int>`).value\nprint *(this as *ns::Widget)\nframe 1\nlocals\n";
+    let plain = run("cpp", script);
+    for line in ["sum = 24", "*this = {id: 3, box: {value: 1.5, tag: 10}, counter: {value: 20, tag: 11}}", "typeof(this.box) = struct (16 bytes): ns::Box<double>", "(&this.counter as *`ns::Box<long int>`).value = 20", "w = {id: 3, box: {value: 1.5, tag: 10}, counter: {value: 20, tag: 11}}"] {
+        assert!(plain.iter().any(|l| l == line), "{:?}", plain);
+    }
+    assert!(plain.iter().any(|l| l.starts_with("*(this as *ns::Widget) = {id: 3,")), "{:?}", plain);
+    for variant in ["cpp4", "cpp5"] {
+        assert_eq!(run(variant, script), plain, "{}", variant);
+    }
+}
+
+#[test]
+fn dwz_supplementary_file() {
+    let dir = test_dir("batch_dwz");
+    if !build_with_script(&dir, "dwz/build.sh") {
+        return;
+    }
+    // The variable's name and type, and the type looked up by name for the cast, all come from alt.debug.
+    let script = "break main.c:3\nrun\nprint g_pair\nprint typeof(g_pair)\nprint (&g_pair as *Pair).b\n";
+    // (The dwz variant is only built if dwz is installed.)
+    for variant in ["handmade", "dwz"] {
+        if variant != "handmade" && !dir.join(variant).join("alt.debug").exists() {
+            continue;
+        }
+        let stdout = run_batch(&dir.join(variant), &[], script, &[&dir.join(variant).join("prog")]);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert!(lines.first().is_some_and(|l| l.contains("stopped: breakpoint")), "{}: {}", variant, stdout);
+        assert_eq!(&lines[1..], &["g_pair = {a: 3, b: 4}", "typeof(g_pair) = struct (8 bytes): Pair", "(&g_pair as *Pair).b = 4"], "{}", variant);
+    }
+}
@@ -0,0 +1,125 @@
+// Drives `nnd --dap` the way an IDE would: initialize -> setBreakpoints -> launch -> configurationDone, then waits for the breakpoint to be hit.
+// Needs a C compiler to build the debuggee (testprogs/tiny.c); skipped if there's none.
+
+use std::{process::{Command, Stdio, Child, ChildStdin}, io::{Read, Write, BufRead, BufReader}, sync::mpsc, thread, time::Duration, path::{Path, PathBuf}};
+
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    messages: mpsc::Receiver<String>,
+    seq: usize,
+}
+impl Client {
+    fn start(home: &Path) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_nnd")).arg("--dap").env("HOME", home).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || loop {
+            let mut content_length = 0usize;
+            loop {
+                let mut line = String::new();
+                if stdout.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(n) = line.strip_prefix("Content-Length:") {
+                    content_length = n.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            stdout.read_exact(&mut body).unwrap();
+            if sender.send(String::from_utf8(body).unwrap()).is_err() {
+                return;
+            }
+        });
+        Self {child, stdin, messages, seq: 1}
+    }
+
+    fn send(&mut self, command: &str, arguments: &str) -> usize {
+        let seq = self.seq;
+        self.seq += 1;
+        let body = format!(r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#, seq, command, arguments);
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+        seq
+    }
+
+    // Skips other messages until one containing all of `needles` arrives.
+    fn expect(&mut self, needles: &[&str]) -> String {
+        loop {
+            let message = self.messages.recv_timeout(TIMEOUT).expect("timed out waiting for DAP message");
+            if needles.iter().all(|n| message.contains(n)) {
+                return message;
+            }
+            assert!(!message.contains(r#""success":false"#), "request failed: {}", message);
+        }
+    }
+
+    fn expect_response(&mut self, seq: usize, command: &str) -> String {
+        let m = self.expect(&[&format!(r#""request_seq":{},"#, seq), &format!(r#""command":"{}""#, command)]);
+        assert!(m.contains(r#""success":true"#), "{}", m);
+        m
+    }
+}
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn build_debuggee(dir: &Path) -> Option<PathBuf> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("testprogs/tiny.c");
+    let exe = dir.join("tiny");
+    match Command::new("cc").args(["-g", "-O1", "-o"]).arg(&exe).arg(&source).status() {
+        Ok(s) if s.success() => Some(exe),
+        _ => None,
+    }
+}
+
+#[test]
+fn breakpoint_hit() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dap_breakpoint_hit");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = match build_debuggee(&dir) {
+        Some(x) => x,
+        None => {
+            eprintln!("skipping: couldn't compile testprogs/tiny.c with cc");
+            return;
+        }
+    };
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("testprogs/tiny.c");
+
+    let mut client = Client::start(&dir);
+    let seq = client.send("initialize", r#"{"adapterID":"nnd","linesStartAt1":true,"columnsStartAt1":true}"#);
+    client.expect_response(seq, "initialize");
+    client.expect(&[r#""event":"initialized""#]);
+
+    // Breakpoints and configurationDone before launch are queued until the launch request.
+    let bp_seq = client.send("setBreakpoints", &format!(r#"{{"source":{{"path":"{}"}},"breakpoints":[{{"line":2}}]}}"#, source.display()));
+    let done_seq = client.send("configurationDone", "{}");
+    let seq = client.send("launch", &format!(r#"{{"program":"{}","stopOnEntry":false}}"#, exe.display()));
+    client.expect_response(seq, "launch");
+    let m = client.expect_response(bp_seq, "setBreakpoints");
+    assert!(m.contains(r#""verified":true"#), "{}", m);
+    client.expect_response(done_seq, "configurationDone");
+
+    let m = client.expect(&[r#""event":"stopped""#]);
+    assert!(m.contains(r#""reason":"breakpoint""#), "{}", m);
+    assert!(m.contains(r#""allThreadsStopped":true"#), "{}", m);
+    let tid: i64 = m.split(r#""threadId":"#).nth(1).unwrap().split(|c: char| !c.is_ascii_digit()).next().unwrap().parse().unwrap();
+
+    let seq = client.send("stackTrace", &format!(r#"{{"threadId":{}}}"#, tid));
+    let m = client.expect_response(seq, "stackTrace");
+    assert!(m.contains(r#""name":"g""#) && m.contains(r#""line":2,"#), "{}", m);
+
+    let seq = client.send("disconnect", "{}");
+    client.expect_response(seq, "disconnect");
+}
@@ -0,0 +1,110 @@
+# Generates a tiny aarch64 executable and a core dump of it, for tests/aarch64_core.rs, without needing an aarch64 machine or toolchain
+# (other than an assembler: t.o is `aarch64-linux-gnu-as t.s -o t.o`, checked in).
+# Links t.o into `exe` by hand (just .text, .eh_frame, and .symtab), then writes `core`: a thread stopped in leaf_fn (called from outer_fn,
+# called from _start), with the return address into _start signed with pointer authentication (nonzero high bits), and x19 saved on the stack.
+# The core's NT_FILE refers to `exe` by relative path, so the test runs nnd in this directory.
+# Usage: python3 mk.py
+import struct
+
+BASE = 0x400000
+
+def rd(fmt, b, off): return struct.unpack_from(fmt, b, off)
+
+def link(obj_path, out_path):
+    o = open(obj_path, 'rb').read()
+    shoff, = rd('<Q', o, 0x28); shnum, shstrndx = rd('<HH', o, 0x3c)
+    secs = []
+    for i in range(shnum):
+        name, typ, flags, addr, off, size, link_, info, align, entsize = rd('<IIQQQQIIQQ', o, shoff + i * 64)
+        secs.append(dict(name=name, type=typ, off=off, size=size, link=link_, info=info))
+    shstr = secs[shstrndx]
+    def nm(s): e = o.index(b'\0', shstr['off'] + s['name']); return o[shstr['off'] + s['name']:e].decode()
+    by = {nm(s): (i, s) for i, s in enumerate(secs)}
+    ti, text = by['.text']; ei, eh = by['.eh_frame']; si, symtab = by['.symtab']; stri, strtab = by['.strtab']
+    text_off = 0x100; text_addr = BASE + text_off
+    eh_off = (text_off + text['size'] + 7) & ~7; eh_addr = BASE + eh_off
+    tb = bytearray(o[text['off']:text['off'] + text['size']])
+    eb = bytearray(o[eh['off']:eh['off'] + eh['size']])
+    secaddr = {ti: text_addr, ei: eh_addr}
+    syms = []
+    for k in range(symtab['size'] // 24):
+        n, info, other, shndx, val, sz = rd('<IBBHQQ', o, symtab['off'] + k * 24)
+        syms.append([n, info, other, shndx, val, sz])
+    def symval(k): n, info, other, shndx, val, sz = syms[k]; return secaddr[shndx] + val
+    for rname, target, buf, baddr in [('.rela.text', ti, tb, text_addr), ('.rela.eh_frame', ei, eb, eh_addr)]:
+        _, r = by[rname]
+        for k in range(r['size'] // 24):
+            off, info, addend = rd('<QQq', o, r['off'] + k * 24)
+            sym, typ = info >> 32, info & 0xffffffff
+            S = symval(sym); P = baddr + off
+            if typ == 283:  # CALL26
+                ins, = rd('<I', buf, off); ins = (ins & 0xfc000000) | (((S + addend - P) >> 2) & 0x3ffffff); struct.pack_into('<I', buf, off, ins)
+            elif typ == 261:  # PREL32
+                struct.pack_into('<i', buf, off, S + addend - P)
+            else: raise Exception('reloc %d' % typ)
+    # output symtab: only FUNC symbols + null
+    strs = bytearray(b'\0'); osyms = [struct.pack('<IBBHQQ', 0, 0, 0, 0, 0, 0)]
+    for n, info, other, shndx, val, sz in syms:
+        if info & 0xf == 2:
+            e = o.index(b'\0', strtab['off'] + n); name = o[strtab['off'] + n:e]
+            osyms.append(struct.pack('<IBBHQQ', len(strs), info, other, 1, text_addr + val, sz)); strs += name + b'\0'
+    symb = b''.join(osyms)
+    shstrs = bytearray(b'\0'); names = {}
+    for s in ['.text', '.eh_frame', '.symtab', '.strtab', '.shstrtab']: names[s] = len(shstrs); shstrs += s.encode() + b'\0'
+    f = bytearray(text_off); f += tb
+    f += bytes(eh_off - len(f)); f += eb
+    load_end = len(f)
+    f += bytes((-len(f)) % 8); sym_off = len(f); f += symb
+    str_off = len(f); f += strs
+    shs_off = len(f); f += shstrs
+    f += bytes((-len(f)) % 8); sh_off = len(f)
+    shdrs = [bytes(64),
+             struct.pack('<IIQQQQIIQQ', names['.text'], 1, 6, text_addr, text_off, len(tb), 0, 0, 4, 0),
+             struct.pack('<IIQQQQIIQQ', names['.eh_frame'], 1, 2, eh_addr, eh_off, len(eb), 0, 0, 8, 0)]
+    strtab_idx = len(shdrs) + 1
+    shdrs += [struct.pack('<IIQQQQIIQQ', names['.symtab'], 2, 0, 0, sym_off, len(symb), strtab_idx, 1, 8, 24),
+              struct.pack('<IIQQQQIIQQ', names['.strtab'], 3, 0, 0, str_off, len(strs), 0, 0, 1, 0),
+              struct.pack('<IIQQQQIIQQ', names['.shstrtab'], 3, 0, 0, shs_off, len(shstrs), 0, 0, 1, 0)]
+    f += b''.join(shdrs)
+    ident = b'\x7fELF' + bytes([2, 1, 1, 0]) + bytes(8)
+    hdr = ident + struct.pack('<HHIQQQIHHHHHH', 2, 183, 1, text_addr, 64, sh_off, 0, 64, 56, 1, 64, len(shdrs), len(shdrs) - 1)
+    f[0:64] = hdr
+    f[64:120] = struct.pack('<IIQQQQQQ', 1, 5, 0, BASE, BASE, load_end, load_end, 0x1000)
+    open(out_path, 'wb').write(f)
+    return text_addr
+
+def note(name, typ, desc):
+    n = name + b'\0'
+    return struct.pack('<III', len(n), len(desc), typ) + n + bytes((-len(n)) % 4) + desc + bytes((-len(desc)) % 4)
+
+def core(out_path, exe_path, T):
+    STACK = 0x7ff0000
+    S0 = STACK + 0xf00
+    sp = S0 - 32
+    stack = bytearray(0x1000)
+    ra_start = T + 8
+    ra_start |= 0x0012_0000_0000_0000 # PAC signature
+    struct.pack_into('<QQQ', stack, sp - STACK, 0, ra_start, 7)
+    regs = [0] * 34
+    regs[19] = 42; regs[29] = sp; regs[30] = T + 0x20
+    regs[31] = sp; regs[32] = T + 0x30; regs[33] = 0x60000000
+    prstatus = struct.pack('<iiih2xQQiiii', 11, 0, 0, 11, 0, 0, 1234, 1, 1234, 1234) + bytes(64) + struct.pack('<34Q', *regs) + struct.pack('<i4x', 1)
+    assert len(prstatus) == 112 + 272 + 8
+    fn = exe_path.encode() + b'\0'
+    ntfile = struct.pack('<QQ', 1, 4096) + struct.pack('<QQQ', BASE, BASE + 0x1000, 0) + fn
+    notes = note(b'CORE', 1, prstatus) + note(b'CORE', 0x46494c45, ntfile)
+    phnum = 3
+    notes_off = 64 + 56 * phnum
+    stack_off = (notes_off + len(notes) + 0xfff) & ~0xfff
+    f = bytearray(stack_off) + stack
+    ident = b'\x7fELF' + bytes([2, 1, 1, 0]) + bytes(8)
+    f[0:64] = ident + struct.pack('<HHIQQQIHHHHHH', 4, 183, 1, 0, 64, 0, 0, 64, 56, phnum, 64, 0, 0)
+    ph = struct.pack('<IIQQQQQQ', 4, 0, notes_off, 0, 0, len(notes), 0, 4)
+    ph += struct.pack('<IIQQQQQQ', 1, 5, stack_off, BASE, 0, 0, 0x1000, 0x1000)
+    ph += struct.pack('<IIQQQQQQ', 1, 6, stack_off, STACK, 0, 0x1000, 0x1000, 0x1000)
+    f[64:64 + len(ph)] = ph
+    f[notes_off:notes_off + len(notes)] = notes
+    open(out_path, 'wb').write(f)
+
+T = link('t.o', 'exe')
+core('core', 'exe', T)
@@ -0,0 +1,40 @@
+    .text
+    .globl _start
+    .type _start,%function
+_start:
+    .cfi_startproc
+    .cfi_undefined x30
+    mov x29, #0
+    bl outer_fn
+    brk #0
+    .cfi_endproc
+    .size _start, .-_start
+
+    .globl outer_fn
+    .type outer_fn,%function
+outer_fn:
+    .cfi_startproc
+    stp x29, x30, [sp, #-32]!
+    .cfi_def_cfa_offset 32
+    .cfi_offset x29, -32
+    .cfi_offset x30, -24
+    mov x29, sp
+    str x19, [sp, #16]
+    .cfi_offset x19, -16
+    mov x19, #42
+    bl leaf_fn
+    ldr x19, [sp, #16]
+    ldp x29, x30, [sp], #32
+    ret
+    .cfi_endproc
+    .size outer_fn, .-outer_fn
+
+    .globl leaf_fn
+    .type leaf_fn,%function
+leaf_fn:
+    .cfi_startproc
+    mov x0, #0
+    ldr x1, [x0]
+    ret
+    .cfi_endproc
+    .size leaf_fn, .-leaf_fn